@@ -1,7 +1,11 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
   let files = &[
+    "protos/google/rpc/status.proto",
+    "protos/google/rpc/error_details.proto",
     "protos/heath_check.proto",
     "protos/auth.proto",
+    "protos/admin.proto",
+    "protos/authz.proto",
   ];
   let dirs = &["protos"];
 
@@ -13,10 +13,28 @@
 //   }
 // }
 
+pub mod google {
+  pub mod rpc {
+    tonic::include_proto!("google.rpc");
+  }
+}
+
 pub mod auth {
   tonic::include_proto!("heimdallr.auth");
 }
 
+pub mod admin {
+  pub mod v1 {
+    tonic::include_proto!("heimdallr.admin.v1");
+  }
+}
+
+pub mod authz {
+  pub mod v1 {
+    tonic::include_proto!("heimdallr.authz.v1");
+  }
+}
+
 /// Converts a Rust Duration to a Protobuf Duration.
 /// Taken from https://github.com/linkerd/linkerd2-proxy-api
 pub fn convert_duration(duration: std::time::Duration) -> prost_types::Duration {
@@ -0,0 +1,16 @@
+DROP INDEX idx_keys_active;
+DROP INDEX idx_keys_kid;
+
+ALTER TABLE keys
+  DROP COLUMN retired_at,
+  DROP COLUMN activated_at,
+  DROP COLUMN status,
+  DROP COLUMN public_key,
+  DROP COLUMN private_key,
+  DROP COLUMN algorithm,
+  DROP COLUMN kid,
+  ADD COLUMN "primary" uuid NOT NULL DEFAULT gen_random_uuid(),
+  ADD COLUMN "secondary" uuid NOT NULL DEFAULT gen_random_uuid();
+
+CREATE INDEX idx_keys_primary ON keys USING btree("primary");
+CREATE INDEX idx_keys_secondary ON keys USING btree("secondary");
@@ -0,0 +1,29 @@
+DROP INDEX idx_keys_primary;
+DROP INDEX idx_keys_secondary;
+
+-- Added as nullable first so the migration also runs on tables that already hold keys.
+ALTER TABLE keys
+  ADD COLUMN kid VARCHAR,
+  ADD COLUMN algorithm VARCHAR,
+  ADD COLUMN private_key TEXT,
+  ADD COLUMN public_key TEXT,
+  ADD COLUMN status VARCHAR NOT NULL DEFAULT 'staged',
+  ADD COLUMN activated_at TIMESTAMP WITHOUT TIME ZONE,
+  ADD COLUMN retired_at TIMESTAMP WITHOUT TIME ZONE;
+
+-- Keys of the old format are a pair of random ids rather than key pairs; nothing was ever signed
+-- with them, so there is nothing to carry over.
+DELETE FROM keys WHERE kid IS NULL;
+
+ALTER TABLE keys
+  DROP COLUMN "primary",
+  DROP COLUMN "secondary",
+  ALTER COLUMN kid SET NOT NULL,
+  ALTER COLUMN algorithm SET NOT NULL,
+  ALTER COLUMN private_key SET NOT NULL,
+  ALTER COLUMN public_key SET NOT NULL;
+
+CREATE UNIQUE INDEX idx_keys_kid ON keys USING btree(kid);
+
+-- Only a single key may be used for signing at any given time.
+CREATE UNIQUE INDEX idx_keys_active ON keys USING btree(status) WHERE status = 'active';
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS user_roles;
+DROP TABLE IF EXISTS roles;
+DROP TABLE IF EXISTS scopes;
+DROP TABLE IF EXISTS clients;
+DROP TABLE IF EXISTS users;
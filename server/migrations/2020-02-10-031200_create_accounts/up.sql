@@ -0,0 +1,61 @@
+CREATE TABLE users (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  username VARCHAR NOT NULL,
+  email VARCHAR,
+  password_hash VARCHAR NOT NULL,
+  disabled BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Usernames & emails are normalized to lowercase before they are stored.
+CREATE UNIQUE INDEX idx_users_username ON users USING btree(username);
+CREATE UNIQUE INDEX idx_users_email ON users USING btree(email);
+SELECT diesel_manage_updated_at('users');
+
+CREATE TABLE clients (
+  id VARCHAR PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  secret_hash VARCHAR,
+  redirect_uris TEXT[] NOT NULL DEFAULT '{}',
+  grant_types TEXT[] NOT NULL DEFAULT '{}',
+  scopes TEXT[] NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+SELECT diesel_manage_updated_at('clients');
+
+CREATE TABLE scopes (
+  name VARCHAR PRIMARY KEY,
+  description TEXT NOT NULL DEFAULT '',
+  -- Restricted scopes are only granted to users holding a role that lists them.
+  restricted BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+SELECT diesel_manage_updated_at('scopes');
+
+CREATE TABLE roles (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  description TEXT NOT NULL DEFAULT '',
+  scopes TEXT[] NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_roles_name ON roles USING btree(name);
+SELECT diesel_manage_updated_at('roles');
+
+CREATE TABLE user_roles (
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (user_id, role_id)
+);
+
+CREATE INDEX idx_user_roles_role_id ON user_roles USING btree(role_id);
+
+INSERT INTO scopes (name, description, restricted) VALUES ('admin', 'Full access to the admin API', TRUE);
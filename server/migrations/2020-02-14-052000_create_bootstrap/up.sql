@@ -0,0 +1,11 @@
+-- Single row table tracking whether the first administrator has been created.
+CREATE TABLE bootstrap (
+  id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+  token_hash VARCHAR,
+  token_created_at TIMESTAMP WITHOUT TIME ZONE,
+  completed_at TIMESTAMP WITHOUT TIME ZONE
+);
+
+-- Deployments that already have users are considered bootstrapped.
+INSERT INTO bootstrap (id, completed_at)
+  SELECT TRUE, CASE WHEN EXISTS (SELECT 1 FROM users) THEN NOW() END;
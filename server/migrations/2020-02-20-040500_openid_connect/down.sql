@@ -0,0 +1,11 @@
+DROP TABLE IF EXISTS refresh_tokens;
+
+ALTER TABLE users
+  DROP COLUMN phone_number_verified,
+  DROP COLUMN phone_number,
+  DROP COLUMN email_verified,
+  DROP COLUMN locale,
+  DROP COLUMN picture,
+  DROP COLUMN family_name,
+  DROP COLUMN given_name,
+  DROP COLUMN name;
@@ -0,0 +1,33 @@
+ALTER TABLE users
+  ADD COLUMN name VARCHAR,
+  ADD COLUMN given_name VARCHAR,
+  ADD COLUMN family_name VARCHAR,
+  ADD COLUMN picture VARCHAR,
+  ADD COLUMN locale VARCHAR,
+  ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD COLUMN phone_number VARCHAR,
+  ADD COLUMN phone_number_verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE refresh_tokens (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  token_hash VARCHAR NOT NULL,
+  client_id VARCHAR NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+  -- NULL for tokens issued to a client acting on its own behalf.
+  user_id uuid REFERENCES users(id) ON DELETE CASCADE,
+  scopes TEXT[] NOT NULL DEFAULT '{}',
+  auth_time TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  amr TEXT[] NOT NULL DEFAULT '{}',
+  expires_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  revoked_at TIMESTAMP WITHOUT TIME ZONE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_refresh_tokens_token_hash ON refresh_tokens USING btree(token_hash);
+CREATE INDEX idx_refresh_tokens_user_id_client_id ON refresh_tokens USING btree(user_id, client_id);
+
+INSERT INTO scopes (name, description) VALUES
+  ('openid', 'Sign in with OpenID Connect'),
+  ('profile', 'Your name, picture & locale'),
+  ('email', 'Your email address'),
+  ('phone', 'Your phone number')
+ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,18 @@
+CREATE TABLE authorization_codes (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  code_hash VARCHAR NOT NULL,
+  client_id VARCHAR NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  redirect_uri VARCHAR NOT NULL,
+  scopes TEXT[] NOT NULL DEFAULT '{}',
+  nonce VARCHAR,
+  -- PKCE (RFC 7636); only S256 is accepted.
+  code_challenge VARCHAR,
+  auth_time TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  amr TEXT[] NOT NULL DEFAULT '{}',
+  expires_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  used_at TIMESTAMP WITHOUT TIME ZONE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_authorization_codes_code_hash ON authorization_codes USING btree(code_hash);
@@ -0,0 +1,4 @@
+-- Browser origins (scheme://host[:port]) allowed to call us cross-origin on behalf of the client.
+ALTER TABLE clients ADD COLUMN allowed_origins TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX idx_clients_allowed_origins ON clients USING gin(allowed_origins);
@@ -0,0 +1,2 @@
+DELETE FROM scopes WHERE name = 'account';
+DROP TABLE IF EXISTS consents;
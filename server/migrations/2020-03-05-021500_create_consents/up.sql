@@ -0,0 +1,15 @@
+-- Scopes a user has approved for a client, so they are only asked again for new ones.
+CREATE TABLE consents (
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  client_id VARCHAR NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+  scopes TEXT[] NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (user_id, client_id)
+);
+
+CREATE INDEX idx_consents_client_id ON consents USING btree(client_id);
+SELECT diesel_manage_updated_at('consents');
+
+INSERT INTO scopes (name, description) VALUES ('account', 'Manage your account, sign-in methods & approved applications')
+ON CONFLICT (name) DO NOTHING;
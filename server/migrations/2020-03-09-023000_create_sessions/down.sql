@@ -0,0 +1,9 @@
+ALTER TABLE clients
+  DROP COLUMN IF EXISTS backchannel_logout_uri,
+  DROP COLUMN IF EXISTS post_logout_redirect_uris;
+
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS session_id;
+ALTER TABLE authorization_codes DROP COLUMN IF EXISTS session_id;
+
+DROP TABLE IF EXISTS session_clients;
+DROP TABLE IF EXISTS sessions;
@@ -0,0 +1,30 @@
+-- Browser single sign-on sessions, shared by every client the user signs in to.
+CREATE TABLE sessions (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  auth_time TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  amr TEXT[] NOT NULL DEFAULT '{}',
+  user_agent VARCHAR,
+  expires_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  ended_at TIMESTAMP WITHOUT TIME ZONE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_sessions_user_id ON sessions USING btree(user_id);
+
+-- Clients that were issued tokens within a session & get notified when it ends.
+CREATE TABLE session_clients (
+  session_id uuid NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+  client_id VARCHAR NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (session_id, client_id)
+);
+
+ALTER TABLE authorization_codes ADD COLUMN session_id uuid REFERENCES sessions(id) ON DELETE SET NULL;
+
+ALTER TABLE refresh_tokens ADD COLUMN session_id uuid REFERENCES sessions(id) ON DELETE SET NULL;
+CREATE INDEX idx_refresh_tokens_session_id ON refresh_tokens USING btree(session_id);
+
+ALTER TABLE clients
+  ADD COLUMN post_logout_redirect_uris TEXT[] NOT NULL DEFAULT '{}',
+  ADD COLUMN backchannel_logout_uri VARCHAR;
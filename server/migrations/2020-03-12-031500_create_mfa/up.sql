@@ -0,0 +1,20 @@
+-- Authenticator app (TOTP) secrets; a secret only becomes a login factor once confirmed.
+CREATE TABLE totp_credentials (
+  user_id uuid PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+  secret VARCHAR NOT NULL,
+  confirmed_at TIMESTAMP WITHOUT TIME ZONE,
+  -- The last time step a code was accepted for, so codes cannot be replayed.
+  last_used_step BIGINT,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- One-time codes for when the authenticator is lost; only hashes are stored.
+CREATE TABLE recovery_codes (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  code_hash VARCHAR NOT NULL,
+  used_at TIMESTAMP WITHOUT TIME ZONE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_recovery_codes_user_id ON recovery_codes USING btree(user_id);
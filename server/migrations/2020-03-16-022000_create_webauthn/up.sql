@@ -0,0 +1,28 @@
+-- Passkeys & security keys registered by users.
+CREATE TABLE webauthn_credentials (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- Base64url encoded credential id chosen by the authenticator.
+  credential_id VARCHAR NOT NULL UNIQUE,
+  -- COSE encoded public key.
+  public_key BYTEA NOT NULL,
+  sign_count BIGINT NOT NULL DEFAULT 0,
+  attestation_format VARCHAR NOT NULL,
+  name VARCHAR NOT NULL,
+  last_used_at TIMESTAMP WITHOUT TIME ZONE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_webauthn_credentials_user_id ON webauthn_credentials USING btree(user_id);
+
+-- Outstanding registration & authentication ceremonies; each challenge can be answered once.
+CREATE TABLE webauthn_challenges (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  challenge VARCHAR NOT NULL,
+  ceremony VARCHAR NOT NULL,
+  user_id uuid REFERENCES users(id) ON DELETE CASCADE,
+  client_id VARCHAR REFERENCES clients(id) ON DELETE CASCADE,
+  user_verification VARCHAR NOT NULL,
+  expires_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,25 @@
+-- Single-use tokens mailed to users: email verification, password reset & magic-link login.
+CREATE TABLE email_tokens (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  token_hash VARCHAR NOT NULL UNIQUE,
+  -- `verify_email`, `password_reset` or `magic_link`.
+  purpose VARCHAR NOT NULL,
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- The address the token was sent to; verification only counts while it is still the user's.
+  email VARCHAR NOT NULL,
+  expires_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  used_at TIMESTAMP WITHOUT TIME ZONE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_email_tokens_user_id ON email_tokens USING btree(user_id);
+
+-- Messages sent (or asked for) per recipient, shared by every instance for rate limiting.
+CREATE TABLE mail_deliveries (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  recipient VARCHAR NOT NULL,
+  purpose VARCHAR NOT NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_mail_deliveries_recipient ON mail_deliveries USING btree(recipient, created_at);
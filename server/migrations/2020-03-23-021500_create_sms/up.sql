@@ -0,0 +1,22 @@
+-- Users who receive a code by SMS (or voice call) as their second factor, at their verified phone number.
+CREATE TABLE sms_factors (
+  user_id uuid PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- One-time codes sent to phones; only argon2 hashes are stored, and each code allows a few attempts.
+CREATE TABLE sms_codes (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- `verify_phone` or `mfa`.
+  purpose VARCHAR NOT NULL,
+  -- The number the code was sent to; it only counts while it is still the user's.
+  phone_number VARCHAR NOT NULL,
+  code_hash VARCHAR NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  expires_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  used_at TIMESTAMP WITHOUT TIME ZONE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_sms_codes_user_id ON sms_codes USING btree(user_id, purpose);
@@ -0,0 +1,12 @@
+-- Codes letting people sign up when registration is invitation-only; only hashes of the codes are
+-- stored. Accounts created with a code are given its roles.
+CREATE TABLE invitations (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  code_hash VARCHAR NOT NULL UNIQUE,
+  description TEXT NOT NULL DEFAULT '',
+  role_ids INTEGER[] NOT NULL DEFAULT '{}',
+  max_uses INTEGER NOT NULL DEFAULT 1,
+  uses INTEGER NOT NULL DEFAULT 0,
+  expires_at TIMESTAMP WITHOUT TIME ZONE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
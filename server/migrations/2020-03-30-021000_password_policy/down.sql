@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS previous_passwords;
+ALTER TABLE users DROP COLUMN IF EXISTS password_changed_at;
@@ -0,0 +1,12 @@
+-- Passwords expire a while after they were last changed, when the policy says so.
+ALTER TABLE users ADD COLUMN password_changed_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW();
+
+-- Hashes of the recent passwords of users, so they cannot pick them again.
+CREATE TABLE previous_passwords (
+  id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  password_hash VARCHAR NOT NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_previous_passwords_user_id ON previous_passwords USING btree(user_id, created_at);
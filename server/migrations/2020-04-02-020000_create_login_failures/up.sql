@@ -0,0 +1,9 @@
+-- Failed password logins per username & per client address, shared by every replica.
+CREATE TABLE login_failures (
+  key VARCHAR(255) PRIMARY KEY,
+  failures INTEGER NOT NULL DEFAULT 0,
+  last_failed_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW(),
+  locked_until TIMESTAMP WITHOUT TIME ZONE
+);
+
+CREATE INDEX idx_login_failures_last_failed_at ON login_failures USING btree(last_failed_at);
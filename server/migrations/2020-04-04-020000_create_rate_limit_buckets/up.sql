@@ -0,0 +1,10 @@
+-- Token buckets of the `postgres` rate limit backend, shared by every replica.
+CREATE TABLE rate_limit_buckets (
+  key VARCHAR(255) PRIMARY KEY,
+  tokens DOUBLE PRECISION NOT NULL,
+  -- Whether the last call got a token.
+  allowed BOOLEAN NOT NULL DEFAULT TRUE,
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_rate_limit_buckets_updated_at ON rate_limit_buckets USING btree(updated_at);
@@ -0,0 +1,5 @@
+DROP TABLE group_roles;
+DROP TABLE group_members;
+DROP TABLE groups;
+DROP TABLE role_permissions;
+DROP TABLE permissions;
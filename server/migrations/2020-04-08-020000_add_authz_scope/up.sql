@@ -0,0 +1,3 @@
+-- Lets services ask `heimdallr.authz.v1.Authz` for decisions; only users holding a role listing it
+-- may be granted it, while clients get it through client credentials once it is in their scopes.
+INSERT INTO scopes (name, description, restricted) VALUES ('authz', 'Ask for authorization decisions', TRUE);
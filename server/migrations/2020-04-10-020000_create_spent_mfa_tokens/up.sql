@@ -0,0 +1,7 @@
+-- MFA tokens that were redeemed, or burned by too many wrong codes; kept until they expire anyway.
+CREATE TABLE spent_mfa_tokens (
+  jti UUID PRIMARY KEY,
+  expires_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_spent_mfa_tokens_expires_at ON spent_mfa_tokens USING btree(expires_at);
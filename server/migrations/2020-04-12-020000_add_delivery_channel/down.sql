@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_mail_deliveries_recipient;
+ALTER TABLE mail_deliveries DROP COLUMN IF EXISTS channel;
+CREATE INDEX idx_mail_deliveries_recipient ON mail_deliveries USING btree(recipient, created_at);
@@ -0,0 +1,5 @@
+-- Texts & calls to a phone are throttled apart from emails, even when a recipient looks the same.
+ALTER TABLE mail_deliveries ADD COLUMN channel VARCHAR NOT NULL DEFAULT 'email';
+
+DROP INDEX idx_mail_deliveries_recipient;
+CREATE INDEX idx_mail_deliveries_recipient ON mail_deliveries USING btree(channel, recipient, created_at);
@@ -42,6 +42,264 @@ pub fn app() -> App<'static, 'static> {
             .arg(Arg::with_name("seed").long("seed").help("Seeds the database with test data"))
         )
     )
+    .subcommand(
+      SubCommand::with_name("bootstrap-admin")
+        .about("Creates the first administrator; only works once")
+        .version(crate_version!())
+        .arg(Arg::with_name("username").long("username").value_name("USERNAME").required(true).takes_value(true))
+        .arg(Arg::with_name("email").long("email").value_name("EMAIL").takes_value(true))
+        .arg(
+          Arg::with_name("password")
+            .long("password")
+            .value_name("PASSWORD")
+            .help("Password of the administrator; a random one is generated & printed when omitted")
+            .takes_value(true)
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("keys")
+        .about("signing key management")
+        .version(crate_version!())
+        .subcommand(
+          SubCommand::with_name("generate")
+            .about("Generates a new signing key")
+            .arg(
+              Arg::with_name("alg")
+                .long("alg")
+                .value_name("ALGORITHM")
+                .possible_values(&["ES256", "RS256", "EdDSA"])
+                .default_value("ES256")
+                .help("Signing algorithm of the new key")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("activate").long("activate").help("Immediately use the new key for signing"))
+        )
+        .subcommand(SubCommand::with_name("list").about("Lists every signing key"))
+        .subcommand(
+          SubCommand::with_name("import")
+            .about("Imports a PEM encoded private key")
+            .arg(Arg::with_name("pem").value_name("FILE").required(true).help("Path to the PEM file, or - to read from stdin"))
+            .arg(Arg::with_name("activate").long("activate").help("Immediately use the imported key for signing"))
+        )
+        .subcommand(
+          SubCommand::with_name("export-public")
+            .about("Prints the public keys that relying parties should trust")
+            .arg(Arg::with_name("kid").value_name("KID").help("Only export a single key"))
+            .arg(
+              Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&["jwks", "pem"])
+                .default_value("jwks")
+                .takes_value(true)
+            )
+        )
+        .subcommand(
+          SubCommand::with_name("activate")
+            .about("Makes a key the signing key")
+            .arg(Arg::with_name("kid").value_name("KID").required(true))
+        )
+        .subcommand(
+          SubCommand::with_name("retire")
+            .about("Retires a key; tokens signed by it are no longer accepted")
+            .arg(Arg::with_name("kid").value_name("KID").required(true))
+            .arg(Arg::with_name("force").long("force").help("Allow retiring the active signing key"))
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("argon2")
+        .about("password hashing")
+        .version(crate_version!())
+        .subcommand(
+          SubCommand::with_name("calibrate")
+            .about("Finds argon2 parameters that take a given time to hash a password on this machine")
+            .arg(
+              Arg::with_name("target-ms")
+                .long("target-ms")
+                .value_name("MILLISECONDS")
+                .default_value("250")
+                .help("Time hashing a password should take")
+                .takes_value(true)
+            )
+            .arg(
+              Arg::with_name("memory-cost")
+                .long("memory-cost")
+                .value_name("KIB")
+                .help("Memory per hash to start from; defaults to argon2.memory_cost")
+                .takes_value(true)
+            )
+            .arg(
+              Arg::with_name("parallelism")
+                .long("parallelism")
+                .value_name("LANES")
+                .help("Lanes per hash; defaults to argon2.parallelism")
+                .takes_value(true)
+            )
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("user")
+        .about("user management")
+        .version(crate_version!())
+        .subcommand(
+          SubCommand::with_name("import")
+            .about("Imports users along with the password hashes of another system")
+            .arg(Arg::with_name("file").value_name("FILE").required(true).help("Path to the export, or - to read from stdin"))
+            .arg(
+              Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&["csv", "jsonl"])
+                .required(true)
+                .help("CSV with a header row, or a JSON object per line")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("dry-run").long("dry-run").help("Only check the users, without creating them"))
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("rbac")
+        .about("roles, permissions & groups")
+        .version(crate_version!())
+        .subcommand(
+          SubCommand::with_name("permission")
+            .about("Manages permissions")
+            .subcommand(
+              SubCommand::with_name("create")
+                .arg(Arg::with_name("name").value_name("NAME").required(true).help("e.g. documents:write"))
+                .arg(Arg::with_name("description").long("description").value_name("TEXT").takes_value(true))
+            )
+            .subcommand(SubCommand::with_name("list"))
+            .subcommand(SubCommand::with_name("delete").arg(Arg::with_name("name").value_name("NAME").required(true)))
+        )
+        .subcommand(
+          SubCommand::with_name("role")
+            .about("Manages roles, the permissions they grant & the users holding them")
+            .subcommand(
+              SubCommand::with_name("create")
+                .arg(Arg::with_name("name").value_name("NAME").required(true))
+                .arg(Arg::with_name("description").long("description").value_name("TEXT").takes_value(true))
+                .arg(
+                  Arg::with_name("scope")
+                    .long("scope")
+                    .value_name("SCOPE")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Restricted scope holders of the role may be granted; may be repeated")
+                    .takes_value(true)
+                )
+            )
+            .subcommand(SubCommand::with_name("list"))
+            .subcommand(SubCommand::with_name("delete").arg(Arg::with_name("name").value_name("NAME").required(true)))
+            .subcommand(
+              SubCommand::with_name("grant")
+                .about("Grants a permission to a role")
+                .arg(Arg::with_name("role").value_name("ROLE").required(true))
+                .arg(Arg::with_name("permission").value_name("PERMISSION").required(true))
+            )
+            .subcommand(
+              SubCommand::with_name("revoke")
+                .about("Revokes a permission from a role")
+                .arg(Arg::with_name("role").value_name("ROLE").required(true))
+                .arg(Arg::with_name("permission").value_name("PERMISSION").required(true))
+            )
+            .subcommand(
+              SubCommand::with_name("assign")
+                .about("Assigns a role to a user")
+                .arg(Arg::with_name("role").value_name("ROLE").required(true))
+                .arg(Arg::with_name("username").value_name("USERNAME").required(true))
+            )
+            .subcommand(
+              SubCommand::with_name("unassign")
+                .about("Removes a role from a user")
+                .arg(Arg::with_name("role").value_name("ROLE").required(true))
+                .arg(Arg::with_name("username").value_name("USERNAME").required(true))
+            )
+        )
+        .subcommand(
+          SubCommand::with_name("group")
+            .about("Manages groups, their members & the roles they hold")
+            .subcommand(
+              SubCommand::with_name("create")
+                .arg(Arg::with_name("name").value_name("NAME").required(true))
+                .arg(Arg::with_name("description").long("description").value_name("TEXT").takes_value(true))
+            )
+            .subcommand(SubCommand::with_name("list"))
+            .subcommand(SubCommand::with_name("delete").arg(Arg::with_name("name").value_name("NAME").required(true)))
+            .subcommand(
+              SubCommand::with_name("add-member")
+                .arg(Arg::with_name("group").value_name("GROUP").required(true))
+                .arg(Arg::with_name("username").value_name("USERNAME").required(true))
+            )
+            .subcommand(
+              SubCommand::with_name("remove-member")
+                .arg(Arg::with_name("group").value_name("GROUP").required(true))
+                .arg(Arg::with_name("username").value_name("USERNAME").required(true))
+            )
+            .subcommand(
+              SubCommand::with_name("assign-role")
+                .arg(Arg::with_name("group").value_name("GROUP").required(true))
+                .arg(Arg::with_name("role").value_name("ROLE").required(true))
+            )
+            .subcommand(
+              SubCommand::with_name("unassign-role")
+                .arg(Arg::with_name("group").value_name("GROUP").required(true))
+                .arg(Arg::with_name("role").value_name("ROLE").required(true))
+            )
+        )
+        .subcommand(
+          SubCommand::with_name("show")
+            .about("Prints the groups, roles & permissions of a user")
+            .arg(Arg::with_name("username").value_name("USERNAME").required(true))
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("token")
+        .about("token debugging")
+        .version(crate_version!())
+        .subcommand(
+          SubCommand::with_name("issue")
+            .about("Issues a signed access token")
+            .arg(Arg::with_name("sub").long("sub").value_name("SUBJECT").required(true).takes_value(true))
+            .arg(
+              Arg::with_name("scope")
+                .long("scope")
+                .value_name("SCOPE")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Granted scope; may be repeated")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("aud").long("aud").value_name("AUDIENCE").takes_value(true))
+            .arg(
+              Arg::with_name("ttl")
+                .long("ttl")
+                .value_name("SECONDS")
+                .help("Lifetime of the token; defaults to jwt.access_token_ttl")
+                .takes_value(true)
+            )
+            .arg(
+              Arg::with_name("claim")
+                .long("claim")
+                .value_name("KEY=JSON")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Additional claim; the value is parsed as JSON and falls back to a string")
+                .takes_value(true)
+            )
+        )
+        .subcommand(
+          SubCommand::with_name("decode")
+            .about("Prints the header & claims of a token without verifying it")
+            .arg(Arg::with_name("jwt").value_name("JWT").required(true))
+        )
+        .subcommand(
+          SubCommand::with_name("verify")
+            .about("Fully validates a token against the key store")
+            .arg(Arg::with_name("jwt").value_name("JWT").required(true))
+            .arg(Arg::with_name("aud").long("aud").value_name("AUDIENCE").takes_value(true))
+        )
+    )
 }
 
 /// Different types of envor
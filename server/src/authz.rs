@@ -0,0 +1,268 @@
+//! Authorization decisions on the roles, permissions & groups of [`rbac`](crate::rbac): a subject
+//! may perform an action on a resource when one of the roles they hold grants a permission matching
+//! both.
+//!
+//! Permissions are named `<resource>:<action>`. A resource ending in `*` matches every resource
+//! starting with what comes before it, and an action of `*` matches every action, so
+//! `documents/*:read` allows reading `documents/42` & `documents/*:*` allows anything on it.
+//!
+//! Decisions are made on a snapshot of the grants of the subject, reused for up to
+//! `authz.cache_ttl_seconds`; that is as long as a change takes to apply to them.
+
+use chrono::{DateTime, Utc};
+use diesel::pg::PgConnection;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::db::models::{Group, Permission, Role, User};
+use crate::error::*;
+use crate::settings::Authz as AuthzSettings;
+
+/// A permission held by a subject, along with how they came to hold it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grant {
+  pub permission: String,
+  pub role: String,
+  /// The group the role is held through; `None` when it is assigned to the subject directly.
+  pub group: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Standing {
+  Active,
+  Disabled,
+  Unknown
+}
+
+/// The grants of a subject as read at one point in time.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+  standing: Standing,
+  grants: Vec<Grant>,
+  loaded_at: DateTime<Utc>
+}
+
+/// Whether a check passed, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+  pub allowed: bool,
+  /// The grant allowing access; `None` when denied.
+  pub grant: Option<Grant>,
+  pub reason: String,
+  /// When the grants the decision was made on were read.
+  pub evaluated_at: DateTime<Utc>
+}
+
+impl Snapshot {
+  /// Reads the grants of a subject; roles assigned directly come before those held through groups.
+  pub fn load(conn: &PgConnection, subject: Uuid) -> Result<Self, HeimdallrError> {
+    let loaded_at = Utc::now();
+
+    let user = match User::find(conn, subject)? {
+      Some(user) => user,
+      None       => return Ok(Snapshot { standing: Standing::Unknown, grants: Vec::new(), loaded_at })
+    };
+    if user.disabled {
+      return Ok(Snapshot { standing: Standing::Disabled, grants: Vec::new(), loaded_at });
+    }
+
+    let groups = Group::for_user(conn, user.id)?;
+    let mut held: Vec<(Role, Option<String>)> = Role::for_user(conn, user.id)?.into_iter().map(|role| (role, None)).collect();
+
+    for (group_id, role) in Role::for_groups(conn, &groups.iter().map(|group| group.id).collect::<Vec<_>>())? {
+      let group = groups.iter().find(|group| group.id == group_id).map(|group| group.name.clone());
+      held.push((role, group));
+    }
+
+    let permissions = Permission::for_roles(conn, &held.iter().map(|(role, _)| role.id).collect::<Vec<_>>())?;
+    let grants = held
+      .iter()
+      .flat_map(|(role, group)| {
+        permissions.iter().filter(move |(role_id, _)| *role_id == role.id).map(move |(_, permission)| Grant {
+          permission: permission.name.clone(),
+          role: role.name.clone(),
+          group: group.clone()
+        })
+      })
+      .collect();
+
+    Ok(Snapshot { standing: Standing::Active, grants, loaded_at })
+  }
+
+  /// Decides whether the subject may perform an action on a resource; the first matching grant is
+  /// the one given as the reason.
+  pub fn decide(&self, action: &str, resource: &str) -> Decision {
+    let (grant, reason) = match self.standing {
+      Standing::Unknown  => (None, "denied: the subject does not exist".to_owned()),
+      Standing::Disabled => (None, "denied: the subject is disabled".to_owned()),
+      Standing::Active   => match self.grants.iter().find(|grant| permits(&grant.permission, action, resource)) {
+        Some(grant) => (Some(grant.clone()), format!("allowed: {} grants {}", source(grant), grant.permission)),
+        None        => (None, format!("denied: no role of the subject grants {} on {}", action, resource))
+      }
+    };
+
+    Decision { allowed: grant.is_some(), grant, reason, evaluated_at: self.loaded_at }
+  }
+}
+
+/// Whether a permission allows an action on a resource.
+pub fn permits(permission: &str, action: &str, resource: &str) -> bool {
+  let split = match permission.rfind(':') {
+    Some(split) => split,
+    None        => return false
+  };
+  let (pattern, allowed_action) = (&permission[..split], &permission[split + 1..]);
+
+  let prefix = pattern.trim_end_matches('*');
+  let resource_matches = if prefix.len() < pattern.len() {
+    resource.starts_with(prefix)
+  } else {
+    pattern == resource
+  };
+
+  resource_matches && (allowed_action == "*" || allowed_action == action)
+}
+
+fn source(grant: &Grant) -> String {
+  match &grant.group {
+    Some(group) => format!("role {} (through group {})", grant.role, group),
+    None        => format!("role {}", grant.role)
+  }
+}
+
+/// Makes decisions, reusing the snapshots of recently checked subjects for up to a TTL.
+pub struct Authorizer {
+  ttl: Duration,
+  capacity: usize,
+  snapshots: Mutex<HashMap<Uuid, (Instant, Arc<Snapshot>)>>
+}
+
+impl Authorizer {
+  pub fn new(settings: &AuthzSettings) -> Self {
+    Authorizer {
+      ttl: settings.cache_ttl(),
+      capacity: settings.cache_capacity(),
+      snapshots: Mutex::new(HashMap::new())
+    }
+  }
+
+  /// The grants of a subject, read at most the TTL ago or, when `fully_consistent`, right now.
+  pub fn snapshot(&self, conn: &PgConnection, subject: Uuid, fully_consistent: bool) -> Result<Arc<Snapshot>, HeimdallrError> {
+    let now = Instant::now();
+
+    if !fully_consistent {
+      if let Some(snapshot) = self.cached_at(subject, now) {
+        return Ok(snapshot);
+      }
+    }
+
+    let snapshot = Arc::new(Snapshot::load(conn, subject)?);
+    self.store_at(subject, snapshot.clone(), now);
+    Ok(snapshot)
+  }
+
+  fn cached_at(&self, subject: Uuid, now: Instant) -> Option<Arc<Snapshot>> {
+    let snapshots = self.snapshots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    snapshots
+      .get(&subject)
+      .filter(|(loaded_at, _)| now.duration_since(*loaded_at) < self.ttl)
+      .map(|(_, snapshot)| snapshot.clone())
+  }
+
+  /// Keeps a snapshot, making room by forgetting expired ones and then the oldest.
+  fn store_at(&self, subject: Uuid, snapshot: Arc<Snapshot>, now: Instant) {
+    if self.ttl == Duration::from_secs(0) || self.capacity == 0 {
+      return;
+    }
+
+    let mut snapshots = self.snapshots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if snapshots.len() >= self.capacity && !snapshots.contains_key(&subject) {
+      let ttl = self.ttl;
+      snapshots.retain(|_, (loaded_at, _)| now.duration_since(*loaded_at) < ttl);
+
+      if snapshots.len() >= self.capacity {
+        let oldest = snapshots.iter().min_by_key(|(_, (loaded_at, _))| *loaded_at).map(|(subject, _)| *subject);
+        if let Some(oldest) = oldest {
+          snapshots.remove(&oldest);
+        }
+      }
+    }
+
+    snapshots.insert(subject, (now, snapshot));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn grant(permission: &str, role: &str, group: Option<&str>) -> Grant {
+    Grant { permission: permission.to_owned(), role: role.to_owned(), group: group.map(str::to_owned) }
+  }
+
+  fn snapshot(standing: Standing, grants: Vec<Grant>) -> Arc<Snapshot> {
+    Arc::new(Snapshot { standing, grants, loaded_at: Utc::now() })
+  }
+
+  #[test]
+  fn test_permits() {
+    assert!(permits("documents:read", "read", "documents"));
+    assert!(!permits("documents:read", "read", "documents/42"));
+    assert!(!permits("documents:read", "write", "documents"));
+    assert!(permits("documents/*:read", "read", "documents/42"));
+    assert!(permits("documents/*:*", "delete", "documents/42"));
+    assert!(!permits("documents/*:*", "delete", "invoices/42"));
+    assert!(permits("urn:docs:42:read", "read", "urn:docs:42"));
+    assert!(permits("*:*", "anything", "at/all"));
+    assert!(!permits("documents", "read", "documents"));
+  }
+
+  #[test]
+  fn test_decisions_explain_the_grant() {
+    let active = snapshot(Standing::Active, vec![
+      grant("documents/*:read", "reader", None),
+      grant("documents/*:*", "editor", Some("writers"))
+    ]);
+
+    let read = active.decide("read", "documents/42");
+    assert_eq!(read.grant, Some(grant("documents/*:read", "reader", None)));
+    assert_eq!(read.reason, "allowed: role reader grants documents/*:read");
+
+    let write = active.decide("write", "documents/42");
+    assert!(write.allowed);
+    assert_eq!(write.reason, "allowed: role editor (through group writers) grants documents/*:*");
+
+    let denied = active.decide("read", "invoices/1");
+    assert_eq!((denied.allowed, denied.grant), (false, None));
+    assert_eq!(denied.reason, "denied: no role of the subject grants read on invoices/1");
+
+    let disabled = snapshot(Standing::Disabled, vec![grant("*:*", "admin", None)]).decide("read", "documents/42");
+    assert_eq!((disabled.allowed, disabled.reason.as_str()), (false, "denied: the subject is disabled"));
+  }
+
+  #[test]
+  fn test_snapshots_are_reused_for_the_ttl() {
+    let authorizer = Authorizer::new(&AuthzSettings { cache_ttl_seconds: Some(10), cache_capacity: Some(2) });
+    let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let start = Instant::now();
+
+    authorizer.store_at(first, snapshot(Standing::Unknown, vec![]), start);
+    assert!(authorizer.cached_at(first, start + Duration::from_secs(9)).is_some());
+    assert!(authorizer.cached_at(first, start + Duration::from_secs(10)).is_none());
+
+    authorizer.store_at(second, snapshot(Standing::Unknown, vec![]), start + Duration::from_secs(1));
+    authorizer.store_at(third, snapshot(Standing::Unknown, vec![]), start + Duration::from_secs(2));
+    assert!(authorizer.cached_at(first, start + Duration::from_secs(2)).is_none());
+    assert!(authorizer.cached_at(second, start + Duration::from_secs(2)).is_some());
+    assert!(authorizer.cached_at(third, start + Duration::from_secs(2)).is_some());
+
+    let uncached = Authorizer::new(&AuthzSettings { cache_ttl_seconds: Some(0), ..Default::default() });
+    uncached.store_at(first, snapshot(Standing::Unknown, vec![]), start);
+    assert!(uncached.cached_at(first, start).is_none());
+  }
+}
@@ -1,8 +1,30 @@
 use heimdallr::prelude::*;
+use heimdallr::authz::Authorizer;
 use heimdallr::db::Database;
-use heimdallr::services::auth;
+use heimdallr::http;
+use heimdallr::jwt::{KeyStore, SharedKeyStore};
+use heimdallr::lockout::Lockout;
+use heimdallr::mail::Outbox;
+use heimdallr::password::{self, Hasher, Policy};
+use heimdallr::ratelimit::{self, RateLimitLayer, RateLimiter};
+use heimdallr::registration::Registrar;
+use heimdallr::services::{account, admin, auth, authz, bootstrap};
+use heimdallr::sms::SmsCodes;
+use heimdallr::tokens::TokenIssuer;
+use heimdallr::webauthn::RelyingParty;
 
 use tonic::transport::Server;
+use tower_layer::Layer;
+use std::time::Duration;
+
+/// How often signing keys are reloaded so rotations done elsewhere are picked up.
+const KEY_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often login failures that no longer hold anything off are forgotten.
+const LOGIN_FAILURE_PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How often rate limit buckets that have filled up again are forgotten.
+const RATE_LIMIT_PRUNE_INTERVAL: Duration = Duration::from_secs(300);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -14,19 +36,125 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
   // Safe to unwrap without exploding since the arg has a default value
   let settings = Settings::new(args.value_of("config").unwrap())?;
+  password::configure(Hasher::new(&settings.argon2.clone().unwrap_or_default())?)?;
 
   if let Some(cmd_args) = args.subcommand_matches("database") {
     commands::database::handle(&settings, &args, &cmd_args)?;
   }
+  else if let Some(cmd_args) = args.subcommand_matches("bootstrap-admin") {
+    commands::bootstrap::handle(&settings, &args, &cmd_args)?;
+  }
+  else if let Some(cmd_args) = args.subcommand_matches("keys") {
+    commands::keys::handle(&settings, &args, &cmd_args)?;
+  }
+  else if let Some(cmd_args) = args.subcommand_matches("argon2") {
+    commands::argon2::handle(&settings, &args, &cmd_args)?;
+  }
+  else if let Some(cmd_args) = args.subcommand_matches("token") {
+    commands::token::handle(&settings, &args, &cmd_args)?;
+  }
+  else if let Some(cmd_args) = args.subcommand_matches("user") {
+    commands::user::handle(&settings, &args, &cmd_args)?;
+  }
+  else if let Some(cmd_args) = args.subcommand_matches("rbac") {
+    commands::rbac::handle(&settings, &args, &cmd_args)?;
+  }
   else {
     let database = Database::create_pool(&settings.database)?;
-    let handler  = auth::AuthHandler::new(database);
+    let keys     = SharedKeyStore::new(KeyStore::load(&*database.pool.get()?)?);
+
+    tokio::spawn(reload_keys(database.clone(), keys.clone()));
+
+    let token_file = settings.bootstrap_token_file();
+    if let Some(token) = heimdallr::bootstrap::issue_token(&*database.pool.get()?, &token_file)? {
+      log::warn!("No administrator exists yet. Redeem this one-time bootstrap token with the Bootstrap RPC or run `heimdallr bootstrap-admin`:");
+      log::warn!("{}", token);
+      log::warn!("The token has also been written to {}", token_file.display());
+    }
+
+    let issuer    = TokenIssuer::new(keys.clone(), settings.jwt.clone());
+    let passkeys  = RelyingParty::new(&settings.webauthn.clone().unwrap_or_default(), &settings.jwt.issuer)?;
+    let outbox    = Outbox::new(&settings.mail.clone().unwrap_or_default(), &settings.jwt.issuer)?;
+    let sms       = SmsCodes::new(&settings.sms.clone().unwrap_or_default(), &settings.jwt.issuer)?;
+    let registrar = Registrar::new(&settings.registration.clone().unwrap_or_default())?;
+    let policy    = Policy::new(&settings.password_policy.clone().unwrap_or_default())?;
+    let lockout   = Lockout::new(&settings.lockout.clone().unwrap_or_default())?;
+    let handler   = auth::AuthHandler::new(database.clone(), issuer.clone(), passkeys.clone(), outbox.clone(), sms.clone(), registrar, policy.clone())
+      .with_lockout(lockout.clone());
+    let account   = account::AccountHandler::new(database.clone(), issuer.clone(), passkeys, outbox, sms, policy.clone());
+    let admin     = admin::AdminHandler::new(database.clone(), keys.clone(), policy.clone());
+    let authz     = authz::AuthzHandler::new(database.clone(), keys, Authorizer::new(&settings.authz.clone().unwrap_or_default()));
+    let bootstrap = bootstrap::BootstrapHandler::new(database.clone(), policy, token_file);
+
+    let rate_limit_settings = settings.rate_limits.clone().unwrap_or_default();
+    let limiter = RateLimiter::new(&rate_limit_settings, ratelimit::from_settings(&rate_limit_settings, &database)?, lockout.clone(), issuer)?;
+    let limits  = RateLimitLayer::new(limiter.clone());
+
+    tokio::spawn(prune_login_failures(database, lockout));
+    tokio::spawn(prune_rate_limits(limiter));
+
+    if let Some(listener) = &settings.http_listener {
+      let address = listener.address;
+      let context = http::HttpContext::new(handler.clone(), &limits, &settings.ui.clone().unwrap_or_default())?;
+
+      tokio::spawn(async move {
+        if let Err(err) = http::serve(address, context).await {
+          log::error!("HTTP listener failed: {}", err);
+          std::process::exit(1);
+        }
+      });
+    }
 
     Server::builder()
-      .add_service(handler.service())
+      .add_service(limits.layer(handler.service()))
+      .add_service(limits.layer(account.service()))
+      .add_service(limits.layer(admin.service(settings.jwt.validation())))
+      .add_service(limits.layer(authz.service(settings.jwt.validation())))
+      .add_service(limits.layer(bootstrap.service()))
       .serve(settings.grpc_listener.address)
       .await?;
   }
 
   Ok(())
 }
+
+/// Periodically refreshes the in-memory key store from the database.
+async fn reload_keys(database: Database, keys: SharedKeyStore) {
+  let mut interval = tokio::time::interval(KEY_RELOAD_INTERVAL);
+
+  loop {
+    interval.tick().await;
+
+    let result = database.pool.get().map_err(HeimdallrError::from).and_then(|conn| keys.reload(&conn));
+    if let Err(err) = result {
+      log::warn!("Unable to reload signing keys: {}", err);
+    }
+  }
+}
+
+/// Periodically forgets login failures that no longer hold anything off.
+async fn prune_login_failures(database: Database, lockout: Lockout) {
+  let mut interval = tokio::time::interval(LOGIN_FAILURE_PRUNE_INTERVAL);
+
+  loop {
+    interval.tick().await;
+
+    let result = database.pool.get().map_err(HeimdallrError::from).and_then(|conn| lockout.prune(&conn));
+    if let Err(err) = result {
+      log::warn!("Unable to prune login failures: {}", err);
+    }
+  }
+}
+
+/// Periodically forgets rate limit buckets that have filled up again.
+async fn prune_rate_limits(limiter: RateLimiter) {
+  let mut interval = tokio::time::interval(RATE_LIMIT_PRUNE_INTERVAL);
+
+  loop {
+    interval.tick().await;
+
+    if let Err(err) = limiter.prune() {
+      log::warn!("Unable to prune rate limit buckets: {}", err);
+    }
+  }
+}
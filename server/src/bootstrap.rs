@@ -0,0 +1,251 @@
+//! Creation of the first administrator, either via `heimdallr bootstrap-admin` or by redeeming
+//! the one-time token printed on first boot. Both stop working once an admin exists.
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use std::io::Write;
+use std::path::Path;
+
+use crate::crypto;
+use crate::db::bootstrap;
+use crate::db::models::*;
+use crate::error::*;
+use crate::password::{self, Policy};
+use crate::services::admin::ADMIN_SCOPE;
+
+/// Name of the role holding the admin scope.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Client id of the client created for the first administrator.
+pub const ADMIN_CLIENT_ID: &str = "heimdallr-admin";
+
+/// How long a bootstrap token printed on boot stays valid.
+const TOKEN_TTL_HOURS: i64 = 24;
+
+#[derive(Debug, Queryable)]
+struct BootstrapState {
+  id: bool,
+  token_hash: Option<String>,
+  token_created_at: Option<NaiveDateTime>,
+  completed_at: Option<NaiveDateTime>
+}
+
+/// Everything created for the first administrator. The secret is only ever shown once.
+#[derive(Debug)]
+pub struct AdminAccount {
+  pub user: User,
+  pub client_id: String,
+  pub client_secret: String
+}
+
+/// Whether the first administrator has already been created.
+pub fn is_complete(conn: &PgConnection) -> Result<bool, HeimdallrError> {
+  Ok(state(conn, false)?.completed_at.is_some())
+}
+
+/// Issues a one-time bootstrap token if the database still has no administrator.
+///
+/// The token is written to `token_file` (readable by the owner only) and returned so it can be
+/// logged. While a token issued earlier, e.g. by another replica, is still valid it is left alone
+/// and nothing is returned, so the token that replica printed keeps working.
+pub fn issue_token(conn: &PgConnection, token_file: &Path) -> Result<Option<String>, HeimdallrError> {
+  let token = conn.transaction::<_, HeimdallrError, _>(|| {
+    let state = state(conn, true)?;
+
+    if state.completed_at.is_some() {
+      return Ok(None);
+    }
+    if let (Some(_), Some(created_at)) = (&state.token_hash, state.token_created_at) {
+      if !is_expired(created_at) {
+        log::info!("A bootstrap token issued at {} UTC is still valid; see the token file of the replica that issued it", created_at.format("%Y-%m-%d %H:%M:%S"));
+        return Ok(None);
+      }
+    }
+
+    let token = crypto::random_token(32)?;
+
+    diesel::update(bootstrap::table)
+      .set((
+        bootstrap::token_hash.eq(Some(crypto::hash_token(&token)?)),
+        bootstrap::token_created_at.eq(Some(Utc::now().naive_utc()))
+      ))
+      .execute(conn)?;
+
+    Ok(Some(token))
+  })?;
+
+  if let Some(token) = &token {
+    write_token_file(token_file, token)?;
+  }
+  Ok(token)
+}
+
+/// Creates the first administrator after checking a bootstrap token issued on boot.
+pub fn redeem(conn: &PgConnection, policy: &Policy, token: &str, username: &str, email: Option<&str>, password: &str) -> Result<AdminAccount, HeimdallrError> {
+  conn.transaction(|| {
+    let state = state(conn, true)?;
+
+    let valid = match (&state.token_hash, state.token_created_at) {
+      (Some(hash), Some(created_at)) => {
+        !is_expired(created_at) && crypto::constant_time_eq(hash.as_bytes(), crypto::hash_token(token)?.as_bytes())
+      },
+      _ => false
+    };
+
+    if state.completed_at.is_some() || !valid {
+      return Err(HeimdallrError::BootstrapError("bootstrap token is invalid, expired or already used"));
+    }
+
+    create(conn, policy, username, email, password)
+  })
+}
+
+/// Creates the first administrator directly, e.g. from `heimdallr bootstrap-admin`.
+pub fn create_admin(conn: &PgConnection, policy: &Policy, username: &str, email: Option<&str>, password: &str) -> Result<AdminAccount, HeimdallrError> {
+  conn.transaction(|| {
+    if state(conn, true)?.completed_at.is_some() {
+      return Err(HeimdallrError::BootstrapError("an administrator has already been bootstrapped"));
+    }
+
+    create(conn, policy, username, email, password)
+  })
+}
+
+/// Removes a leftover token file once bootstrapping is done.
+pub fn remove_token_file(token_file: &Path) -> Result<(), HeimdallrError> {
+  match std::fs::remove_file(token_file) {
+    Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+    _ => Ok(())
+  }
+}
+
+/// Must run inside a transaction; permanently disables bootstrapping.
+fn create(conn: &PgConnection, policy: &Policy, username: &str, email: Option<&str>, password: &str) -> Result<AdminAccount, HeimdallrError> {
+  if username.trim().is_empty() || password.is_empty() {
+    return Err(HeimdallrError::BootstrapError("username and password are required"));
+  }
+
+  let admin_scopes = vec![ADMIN_SCOPE.to_owned()];
+
+  let role = match Role::find_by_name(conn, ADMIN_ROLE)? {
+    Some(role) => role,
+    None => Role::create(conn, &NewRole {
+      name: ADMIN_ROLE,
+      description: "Administrators of this Heimdallr deployment",
+      scopes: &admin_scopes
+    })?
+  };
+
+  let username = normalize(username);
+  let email    = email.map(normalize).filter(|email| !email.is_empty());
+
+  let identifiers: Vec<&str> = std::iter::once(username.as_str()).chain(email.as_deref()).collect();
+  let violations = policy.check(password, &identifiers)?;
+  if !violations.is_empty() {
+    return Err(HeimdallrError::PasswordRejected(violations));
+  }
+
+  let user = User::create(conn, &NewUser {
+    username: &username,
+    email: email.as_deref(),
+    password_hash: &password::hash(password)?
+  })?;
+  Role::assign(conn, user.id, role.id)?;
+
+  let client_secret = crypto::random_token(32)?;
+  let grant_types   = vec!["password".to_owned(), "client_credentials".to_owned(), "refresh_token".to_owned()];
+  let client = Client::create(conn, &NewClient {
+    id: ADMIN_CLIENT_ID,
+    name: "Heimdallr administration",
+    secret_hash: Some(&password::hash(&client_secret)?),
+    redirect_uris: &[],
+    grant_types: &grant_types,
+    scopes: &admin_scopes,
+    allowed_origins: &[],
+    post_logout_redirect_uris: &[],
+    backchannel_logout_uri: None
+  })?;
+
+  diesel::update(bootstrap::table)
+    .set((
+      bootstrap::token_hash.eq(None::<String>),
+      bootstrap::completed_at.eq(Some(Utc::now().naive_utc()))
+    ))
+    .execute(conn)?;
+
+  Ok(AdminAccount { user, client_id: client.id, client_secret })
+}
+
+fn is_expired(token_created_at: NaiveDateTime) -> bool {
+  token_created_at + Duration::hours(TOKEN_TTL_HOURS) <= Utc::now().naive_utc()
+}
+
+fn state(conn: &PgConnection, for_update: bool) -> Result<BootstrapState, HeimdallrError> {
+  let query = bootstrap::table.find(true);
+
+  let state = if for_update {
+    query.for_update().first(conn)
+  }
+  else {
+    query.first(conn)
+  };
+
+  Ok(state?)
+}
+
+#[cfg(unix)]
+fn write_token_file(path: &Path, token: &str) -> Result<(), HeimdallrError> {
+  use std::os::unix::fs::OpenOptionsExt;
+
+  // Recreate the file so the restrictive mode applies even if an old one was lying around.
+  remove_token_file(path)?;
+  let mut file = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+  writeln!(file, "{}", token)?;
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_token_file(path: &Path, token: &str) -> Result<(), HeimdallrError> {
+  remove_token_file(path)?;
+  let mut file = std::fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+  writeln!(file, "{}", token)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::test_helpers;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_issue_token_keeps_a_valid_token() {
+    let conn       = test_helpers::connection();
+    let token_file = std::env::temp_dir().join(format!("heimdallr-bootstrap-{}", uuid::Uuid::new_v4()));
+
+    let first = issue_token(&conn, &token_file).unwrap().expect("a token is issued without an administrator");
+    assert_eq!(issue_token(&conn, &token_file).unwrap(), None);
+    assert_eq!(std::fs::read_to_string(&token_file).unwrap().trim(), first);
+
+    diesel::update(bootstrap::table)
+      .set(bootstrap::token_created_at.eq(Some(Utc::now().naive_utc() - Duration::hours(TOKEN_TTL_HOURS))))
+      .execute(&conn)
+      .unwrap();
+
+    let second = issue_token(&conn, &token_file).unwrap().expect("an expired token is replaced");
+    assert!(second != first);
+    remove_token_file(&token_file).unwrap();
+  }
+
+  #[test]
+  fn test_create_admin_follows_the_password_policy() {
+    let conn   = test_helpers::connection();
+    let policy = Policy::new(&Default::default()).unwrap();
+
+    match create_admin(&conn, &policy, "root", None, "short") {
+      Err(HeimdallrError::PasswordRejected(violations)) => assert_eq!(violations[0].as_str(), "too_short"),
+      other                                             => panic!("expected a rejected password, got {:?}", other)
+    }
+    assert!(!is_complete(&conn).unwrap());
+  }
+}
@@ -1 +1,7 @@
+pub mod argon2;
+pub mod bootstrap;
 pub mod database;
+pub mod keys;
+pub mod rbac;
+pub mod token;
+pub mod user;
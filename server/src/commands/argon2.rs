@@ -0,0 +1,80 @@
+use crate::error::*;
+use crate::password::Hasher;
+use crate::settings::{Argon2, Settings};
+
+use clap::ArgMatches;
+use std::time::{Duration, Instant};
+
+/// Hashes timed per measurement; the average evens out noise.
+const SAMPLES: u32 = 3;
+
+pub fn handle(settings: &Settings, _args: &ArgMatches, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  match cmd_args.subcommand() {
+    ("calibrate", Some(matches)) => calibrate(settings, matches),
+    _ => {
+      println!("{}", cmd_args.usage());
+      Ok(())
+    }
+  }
+}
+
+/// Finds the argon2 parameters that take about `--target-ms` to hash a password on this machine,
+/// & prints them as a config section.
+///
+/// Memory is what makes guessing on GPUs expensive, so it is kept as configured (or given) & only
+/// halved when a single pass is already too slow; the time cost then fills the rest of the target.
+fn calibrate(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let base   = settings.argon2.clone().unwrap_or_default();
+  let target = Duration::from_millis(number(cmd_args, "target-ms", 250)?.into());
+
+  let parallelism     = number(cmd_args, "parallelism", base.parallelism())?;
+  let mut memory_cost = number(cmd_args, "memory-cost", base.memory_cost())?;
+
+  let mut one_pass = measure(&base, memory_cost, 1, parallelism)?;
+  while one_pass > target && memory_cost / 2 >= 8 * parallelism {
+    memory_cost /= 2;
+    one_pass = measure(&base, memory_cost, 1, parallelism)?;
+  }
+
+  let mut time_cost = ((target.as_secs_f64() / one_pass.as_secs_f64()) as u32).max(1);
+  let mut elapsed   = measure(&base, memory_cost, time_cost, parallelism)?;
+  while elapsed > target && time_cost > 1 {
+    time_cost -= 1;
+    elapsed = measure(&base, memory_cost, time_cost, parallelism)?;
+  }
+
+  println!("{} m={} KiB t={} p={}: {} ms", base.variant(), memory_cost, time_cost, parallelism, elapsed.as_millis());
+  println!();
+  println!("argon2:");
+  println!("  variant: {}", base.variant());
+  println!("  memory_cost: {}", memory_cost);
+  println!("  time_cost: {}", time_cost);
+  println!("  parallelism: {}", parallelism);
+  Ok(())
+}
+
+/// Average time to hash a password with the given costs.
+fn measure(base: &Argon2, memory_cost: u32, time_cost: u32, parallelism: u32) -> Result<Duration, HeimdallrError> {
+  let hasher = Hasher::new(&Argon2 {
+    memory_cost: Some(memory_cost),
+    time_cost: Some(time_cost),
+    parallelism: Some(parallelism),
+    ..base.clone()
+  })?;
+
+  let start = Instant::now();
+  for _ in 0..SAMPLES {
+    hasher.hash("correct horse battery staple")?;
+  }
+
+  Ok(start.elapsed() / SAMPLES)
+}
+
+fn number(cmd_args: &ArgMatches, name: &str, default: u32) -> Result<u32, HeimdallrError> {
+  match cmd_args.value_of(name) {
+    Some(value) => value.parse().ok().filter(|&value| value > 0).ok_or_else(|| {
+      HeimdallrError::ConfigError(config::ConfigError::Message(format!("--{} must be a positive number", name)))
+    }),
+    None => Ok(default)
+  }
+}
@@ -0,0 +1,36 @@
+use crate::bootstrap;
+use crate::crypto;
+use crate::db::establish_connection;
+use crate::error::*;
+use crate::password::Policy;
+use crate::settings::Settings;
+
+use clap::ArgMatches;
+
+/// Creates the first administrator together with an admin client.
+pub fn handle(settings: &Settings, _args: &ArgMatches, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let conn = establish_connection(&settings.database)?;
+
+  // Safe to unwrap since the arg is required
+  let username = cmd_args.value_of("username").unwrap();
+  let email    = cmd_args.value_of("email");
+
+  let generated = match cmd_args.value_of("password") {
+    Some(_) => None,
+    None    => Some(crypto::random_token(18)?)
+  };
+  let password = cmd_args.value_of("password").or(generated.as_deref()).unwrap_or_default();
+
+  let policy  = Policy::new(&settings.password_policy.clone().unwrap_or_default())?;
+  let account = bootstrap::create_admin(&conn, &policy, username, email, password)?;
+  bootstrap::remove_token_file(&settings.bootstrap_token_file())?;
+
+  println!("Created administrator `{}` ({})", account.user.username, account.user.id);
+  if let Some(password) = generated {
+    println!("Password:      {}", password);
+  }
+  println!("Client id:     {}", account.client_id);
+  println!("Client secret: {}", account.client_secret);
+  println!("\nThese credentials will not be shown again. Bootstrapping is now disabled.");
+  Ok(())
+}
@@ -0,0 +1,147 @@
+use crate::db::{establish_connection, models::SigningKey};
+use crate::error::*;
+use crate::jwt::{self, Algorithm, KeyPair};
+use crate::settings::Settings;
+
+use clap::ArgMatches;
+use std::io::Read;
+
+pub fn handle(settings: &Settings, _args: &ArgMatches, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  match cmd_args.subcommand() {
+    ("generate", Some(matches))      => generate(settings, matches),
+    ("list", Some(_))                => list(settings),
+    ("import", Some(matches))        => import(settings, matches),
+    ("export-public", Some(matches)) => export_public(settings, matches),
+    ("activate", Some(matches))      => activate(settings, matches),
+    ("retire", Some(matches))        => retire(settings, matches),
+    _ => {
+      println!("{}", cmd_args.usage());
+      Ok(())
+    }
+  }
+}
+
+/// Generates a new signing key and stores it as staged (or active with `--activate`).
+fn generate(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  // Safe to unwrap since the arg has a default value
+  let algorithm: Algorithm = cmd_args.value_of("alg").unwrap().parse()?;
+  let pair = KeyPair::generate(algorithm)?;
+  store(settings, &pair, cmd_args.is_present("activate"))
+}
+
+/// Imports an existing PEM encoded private key, e.g. one exported from a previous auth server.
+fn import(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  // Safe to unwrap since the arg is required
+  let path = cmd_args.value_of("pem").unwrap();
+  let mut pem = Vec::new();
+
+  if path == "-" {
+    std::io::stdin().read_to_end(&mut pem)?;
+  }
+  else {
+    pem = std::fs::read(path)?;
+  }
+
+  let pair = KeyPair::import(&pem)?;
+  store(settings, &pair, cmd_args.is_present("activate"))
+}
+
+fn store(settings: &Settings, pair: &KeyPair, activate: bool) -> Result<(), HeimdallrError> {
+  let conn = establish_connection(&settings.database)?;
+
+  if SigningKey::find_by_kid(&conn, &pair.kid)?.is_some() {
+    return Err(HeimdallrError::KeyError(format!("key `{}` already exists", pair.kid)));
+  }
+
+  let mut key = SigningKey::insert(&conn, pair)?;
+  if activate {
+    key = SigningKey::activate(&conn, &key.kid)?;
+  }
+
+  println!("{} {} {}", key.kid, key.algorithm, key.status);
+  Ok(())
+}
+
+/// Prints every key along with its lifecycle timestamps.
+fn list(settings: &Settings) -> Result<(), HeimdallrError> {
+  let conn = establish_connection(&settings.database)?;
+  let keys = SigningKey::all(&conn)?;
+
+  println!("{:<44} {:<6} {:<8} {:<20} {:<20} {:<20}", "KID", "ALG", "STATUS", "CREATED", "ACTIVATED", "RETIRED");
+  for key in keys {
+    println!(
+      "{:<44} {:<6} {:<8} {:<20} {:<20} {:<20}",
+      key.kid,
+      key.algorithm,
+      key.status,
+      format_time(Some(key.created_at)),
+      format_time(key.activated_at),
+      format_time(key.retired_at)
+    );
+  }
+
+  Ok(())
+}
+
+/// Prints the public half of every non-retired key (or a single key) as a JWKS document or PEM.
+fn export_public(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let conn = establish_connection(&settings.database)?;
+
+  let keys = match cmd_args.value_of("kid") {
+    Some(kid) => vec![find(&conn, kid)?],
+    None      => SigningKey::verifiable(&conn)?
+  };
+
+  if cmd_args.value_of("format") == Some("pem") {
+    for key in keys {
+      println!("# {} ({})", key.kid, key.algorithm);
+      print!("{}", key.public_key);
+    }
+  }
+  else {
+    let jwks = keys
+      .iter()
+      .map(|key| jwt::public_jwk_json(&key.kid, key.algorithm()?, &key.public_key))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let document = serde_json::json!({ "keys": jwks });
+    println!("{}", serde_json::to_string_pretty(&document).map_err(|err| HeimdallrError::KeyError(err.to_string()))?);
+  }
+
+  Ok(())
+}
+
+/// Makes the given key the signing key.
+fn activate(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let conn = establish_connection(&settings.database)?;
+  // Safe to unwrap since the arg is required
+  let key = SigningKey::activate(&conn, cmd_args.value_of("kid").unwrap())?;
+  println!("{} {} {}", key.kid, key.algorithm, key.status);
+  Ok(())
+}
+
+/// Retires the given key, refusing to retire the signing key unless forced.
+fn retire(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let conn = establish_connection(&settings.database)?;
+  // Safe to unwrap since the arg is required
+  let kid = cmd_args.value_of("kid").unwrap();
+
+  if find(&conn, kid)?.is_active() && !cmd_args.is_present("force") {
+    return Err(HeimdallrError::KeyError(format!(
+      "key `{}` is the active signing key; activate another key first or pass --force",
+      kid
+    )));
+  }
+
+  let key = SigningKey::retire(&conn, kid)?;
+  println!("{} {} {}", key.kid, key.algorithm, key.status);
+  Ok(())
+}
+
+fn find(conn: &diesel::PgConnection, kid: &str) -> Result<SigningKey, HeimdallrError> {
+  SigningKey::find_by_kid(conn, kid)?.ok_or_else(|| HeimdallrError::KeyError(format!("no key with kid `{}`", kid)))
+}
+
+fn format_time(time: Option<chrono::NaiveDateTime>) -> String {
+  time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".to_owned())
+}
@@ -0,0 +1,199 @@
+use crate::db::{establish_connection, models::*};
+use crate::error::*;
+use crate::rbac::{self, Entitlements};
+use crate::settings::Settings;
+
+use clap::ArgMatches;
+use diesel::pg::PgConnection;
+
+pub fn handle(settings: &Settings, _args: &ArgMatches, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let conn = establish_connection(&settings.database)?;
+
+  match cmd_args.subcommand() {
+    ("permission", Some(matches)) => permission(&conn, matches),
+    ("role", Some(matches))       => role(&conn, matches),
+    ("group", Some(matches))      => group(&conn, matches),
+    ("show", Some(matches))       => show(&conn, matches),
+    _ => {
+      println!("{}", cmd_args.usage());
+      Ok(())
+    }
+  }
+}
+
+fn permission(conn: &PgConnection, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  match cmd_args.subcommand() {
+    ("create", Some(matches)) => {
+      let name = value(matches, "name");
+      if !rbac::is_valid_permission_name(name) {
+        return Err(HeimdallrError::RbacError("permission names look like `<resource>:<action>`, without whitespace".to_owned()));
+      }
+
+      let permission = Permission::create(conn, &NewPermission { name, description: matches.value_of("description").unwrap_or_default() })?;
+      println!("Created permission {}", permission.name);
+    },
+    ("list", Some(_)) => {
+      println!("{:<32} DESCRIPTION", "NAME");
+      for permission in Permission::all(conn)? {
+        println!("{:<32} {}", permission.name, permission.description);
+      }
+    },
+    ("delete", Some(matches)) => {
+      let permission = find_permission(conn, value(matches, "name"))?;
+      Permission::delete(conn, permission.id)?;
+      println!("Deleted permission {}", permission.name);
+    },
+    _ => println!("{}", cmd_args.usage())
+  }
+
+  Ok(())
+}
+
+fn role(conn: &PgConnection, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  match cmd_args.subcommand() {
+    ("create", Some(matches)) => {
+      let scopes: Vec<String> = matches.values_of("scope").map(|values| values.map(str::to_owned).collect()).unwrap_or_default();
+      let role = Role::create(conn, &NewRole {
+        name: value(matches, "name"),
+        description: matches.value_of("description").unwrap_or_default(),
+        scopes: &scopes
+      })?;
+      println!("Created role {}", role.name);
+    },
+    ("list", Some(_)) => {
+      let roles  = Role::all(conn)?;
+      let grants = Permission::for_roles(conn, &roles.iter().map(|role| role.id).collect::<Vec<_>>())?;
+
+      println!("{:<24} {:<32} PERMISSIONS", "NAME", "SCOPES");
+      for role in roles {
+        let permissions: Vec<&str> = grants.iter().filter(|(id, _)| *id == role.id).map(|(_, permission)| permission.name.as_str()).collect();
+        println!("{:<24} {:<32} {}", role.name, role.scopes.join(" "), permissions.join(" "));
+      }
+    },
+    ("delete", Some(matches)) => {
+      let role = find_role(conn, value(matches, "name"))?;
+      Role::delete(conn, role.id)?;
+      println!("Deleted role {}", role.name);
+    },
+    ("grant", Some(matches)) => {
+      let role       = find_role(conn, value(matches, "role"))?;
+      let permission = find_permission(conn, value(matches, "permission"))?;
+      Permission::grant(conn, role.id, permission.id)?;
+      println!("Granted {} to role {}", permission.name, role.name);
+    },
+    ("revoke", Some(matches)) => {
+      let role       = find_role(conn, value(matches, "role"))?;
+      let permission = find_permission(conn, value(matches, "permission"))?;
+      if !Permission::revoke(conn, role.id, permission.id)? {
+        return Err(HeimdallrError::RbacError(format!("role `{}` is not granted `{}`", role.name, permission.name)));
+      }
+      println!("Revoked {} from role {}", permission.name, role.name);
+    },
+    ("assign", Some(matches)) => {
+      let role = find_role(conn, value(matches, "role"))?;
+      let user = find_user(conn, value(matches, "username"))?;
+      Role::assign(conn, user.id, role.id)?;
+      println!("Assigned role {} to {}", role.name, user.username);
+    },
+    ("unassign", Some(matches)) => {
+      let role = find_role(conn, value(matches, "role"))?;
+      let user = find_user(conn, value(matches, "username"))?;
+      if !Role::unassign(conn, user.id, role.id)? {
+        return Err(HeimdallrError::RbacError(format!("role `{}` is not assigned to `{}`", role.name, user.username)));
+      }
+      println!("Unassigned role {} from {}", role.name, user.username);
+    },
+    _ => println!("{}", cmd_args.usage())
+  }
+
+  Ok(())
+}
+
+fn group(conn: &PgConnection, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  match cmd_args.subcommand() {
+    ("create", Some(matches)) => {
+      let group = Group::create(conn, &NewGroup { name: value(matches, "name"), description: matches.value_of("description").unwrap_or_default() })?;
+      println!("Created group {}", group.name);
+    },
+    ("list", Some(_)) => {
+      let groups = Group::all(conn)?;
+      let roles  = Role::for_groups(conn, &groups.iter().map(|group| group.id).collect::<Vec<_>>())?;
+
+      println!("{:<24} ROLES", "NAME");
+      for group in groups {
+        let names: Vec<&str> = roles.iter().filter(|(id, _)| *id == group.id).map(|(_, role)| role.name.as_str()).collect();
+        println!("{:<24} {}", group.name, names.join(" "));
+      }
+    },
+    ("delete", Some(matches)) => {
+      let group = find_group(conn, value(matches, "name"))?;
+      Group::delete(conn, group.id)?;
+      println!("Deleted group {}", group.name);
+    },
+    ("add-member", Some(matches)) => {
+      let group = find_group(conn, value(matches, "group"))?;
+      let user  = find_user(conn, value(matches, "username"))?;
+      Group::add_member(conn, group.id, user.id)?;
+      println!("Added {} to group {}", user.username, group.name);
+    },
+    ("remove-member", Some(matches)) => {
+      let group = find_group(conn, value(matches, "group"))?;
+      let user  = find_user(conn, value(matches, "username"))?;
+      if !Group::remove_member(conn, group.id, user.id)? {
+        return Err(HeimdallrError::RbacError(format!("`{}` does not belong to group `{}`", user.username, group.name)));
+      }
+      println!("Removed {} from group {}", user.username, group.name);
+    },
+    ("assign-role", Some(matches)) => {
+      let group = find_group(conn, value(matches, "group"))?;
+      let role  = find_role(conn, value(matches, "role"))?;
+      Group::assign_role(conn, group.id, role.id)?;
+      println!("Assigned role {} to group {}", role.name, group.name);
+    },
+    ("unassign-role", Some(matches)) => {
+      let group = find_group(conn, value(matches, "group"))?;
+      let role  = find_role(conn, value(matches, "role"))?;
+      if !Group::unassign_role(conn, group.id, role.id)? {
+        return Err(HeimdallrError::RbacError(format!("role `{}` is not assigned to group `{}`", role.name, group.name)));
+      }
+      println!("Unassigned role {} from group {}", role.name, group.name);
+    },
+    _ => println!("{}", cmd_args.usage())
+  }
+
+  Ok(())
+}
+
+/// Prints the groups of a user along with every role & permission they hold through them.
+fn show(conn: &PgConnection, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let user         = find_user(conn, value(cmd_args, "username"))?;
+  let groups       = Group::for_user(conn, user.id)?;
+  let entitlements = Entitlements::load(conn, user.id)?;
+
+  println!("user:        {}", user.username);
+  println!("groups:      {}", groups.into_iter().map(|group| group.name).collect::<Vec<_>>().join(" "));
+  println!("roles:       {}", entitlements.roles.join(" "));
+  println!("permissions: {}", entitlements.permissions.join(" "));
+  Ok(())
+}
+
+/// Safe to unwrap since every positional arg is required.
+fn value<'a>(matches: &'a ArgMatches, name: &str) -> &'a str {
+  matches.value_of(name).unwrap()
+}
+
+fn find_permission(conn: &PgConnection, name: &str) -> Result<Permission, HeimdallrError> {
+  Permission::find_by_name(conn, name)?.ok_or_else(|| HeimdallrError::RbacError(format!("no permission named `{}`", name)))
+}
+
+fn find_role(conn: &PgConnection, name: &str) -> Result<Role, HeimdallrError> {
+  Role::find_by_name(conn, name)?.ok_or_else(|| HeimdallrError::RbacError(format!("no role named `{}`", name)))
+}
+
+fn find_group(conn: &PgConnection, name: &str) -> Result<Group, HeimdallrError> {
+  Group::find_by_name(conn, name)?.ok_or_else(|| HeimdallrError::RbacError(format!("no group named `{}`", name)))
+}
+
+fn find_user(conn: &PgConnection, username: &str) -> Result<User, HeimdallrError> {
+  User::find_by_username(conn, &normalize(username))?.ok_or_else(|| HeimdallrError::RbacError(format!("no user named `{}`", username)))
+}
@@ -0,0 +1,105 @@
+use crate::db::establish_connection;
+use crate::error::*;
+use crate::jwt::{decode_unverified, JwtClaims, JwtClaimsBuilder, KeyStore};
+use crate::settings::Settings;
+
+use clap::ArgMatches;
+use serde::Deserialize;
+
+pub fn handle(settings: &Settings, _args: &ArgMatches, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  match cmd_args.subcommand() {
+    ("issue", Some(matches))  => issue(settings, matches),
+    ("decode", Some(matches)) => decode(matches),
+    ("verify", Some(matches)) => verify(settings, matches),
+    _ => {
+      println!("{}", cmd_args.usage());
+      Ok(())
+    }
+  }
+}
+
+/// Issues an access token signed with the active key.
+fn issue(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let conn  = establish_connection(&settings.database)?;
+  let store = KeyStore::load(&conn)?;
+
+  let ttl = match cmd_args.value_of("ttl") {
+    Some(value) => chrono::Duration::seconds(value.parse().map_err(|_| HeimdallrError::JwtError("--ttl must be a number of seconds"))?),
+    None        => settings.jwt.access_token_ttl()
+  };
+
+  let mut builder = JwtClaimsBuilder::new();
+  builder
+    .issuer(settings.jwt.issuer.as_str())
+    // Safe to unwrap since the arg is required
+    .subject(cmd_args.value_of("sub").unwrap())
+    .jwt_id(uuid::Uuid::new_v4().to_string())
+    .expires_in(ttl);
+
+  if let Some(audience) = cmd_args.value_of("aud") {
+    builder.audience(audience);
+  }
+
+  if let Some(scopes) = cmd_args.values_of("scope") {
+    builder.add_claim("scope", serde_json::Value::String(scopes.collect::<Vec<_>>().join(" ")));
+  }
+
+  for claim in cmd_args.values_of("claim").into_iter().flatten() {
+    let mut parts = claim.splitn(2, '=');
+    let (key, raw) = match (parts.next(), parts.next()) {
+      (Some(key), Some(raw)) if !key.is_empty() => (key, raw),
+      _ => return Err(HeimdallrError::JwtError("--claim must be in the form KEY=VALUE"))
+    };
+
+    let value = serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.to_owned()));
+    builder.add_claim(key.to_owned(), value);
+  }
+
+  println!("{}", store.sign(&builder.build()?)?);
+  Ok(())
+}
+
+/// Pretty prints a token without verifying its signature.
+fn decode(cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  // Safe to unwrap since the arg is required
+  let (header, claims) = decode_unverified(cmd_args.value_of("jwt").unwrap())?;
+  print_token(&header, &claims)
+}
+
+/// Validates a token against the key store & the configured issuer, explaining any failure.
+fn verify(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  let conn  = establish_connection(&settings.database)?;
+  let store = KeyStore::load(&conn)?;
+  // Safe to unwrap since the arg is required
+  let token = cmd_args.value_of("jwt").unwrap();
+
+  let mut validation = settings.jwt.validation();
+  validation.audience = cmd_args.value_of("aud").map(|aud| aud.to_owned());
+
+  // A failure is reported once, by the error it returns.
+  let claims      = store.verify(token, &validation)?;
+  let (header, _) = decode_unverified(token)?;
+
+  println!("Token is valid\n");
+  print_token(&header, &claims)
+}
+
+fn print_token(header: &serde_json::Value, claims: &serde_json::Value) -> Result<(), HeimdallrError> {
+  let claims = JwtClaims::deserialize(claims)?;
+
+  println!("Header:\n{}\n", to_pretty(header)?);
+  println!("Claims:\n{}\n", to_pretty(&claims)?);
+  println!("Issued at:  {}", format_timestamp(claims.iat));
+  println!("Not before: {}", format_timestamp(claims.nbf));
+  println!("Expires:    {}", format_timestamp(claims.exp));
+  Ok(())
+}
+
+fn to_pretty<T: serde::Serialize>(value: &T) -> Result<String, HeimdallrError> {
+  Ok(serde_json::to_string_pretty(value)?)
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+  use chrono::TimeZone;
+  chrono::Utc.timestamp(timestamp, 0).to_rfc3339()
+}
@@ -0,0 +1,136 @@
+use crate::db::{establish_connection, models::*};
+use crate::error::*;
+use crate::password;
+use crate::registration;
+use crate::settings::Settings;
+
+use clap::ArgMatches;
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Read};
+
+pub fn handle(settings: &Settings, _args: &ArgMatches, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  match cmd_args.subcommand() {
+    ("import", Some(matches)) => import(settings, matches),
+    _ => {
+      println!("{}", cmd_args.usage());
+      Ok(())
+    }
+  }
+}
+
+/// A user exported from another system, as a CSV row (with a header naming the columns) or a JSON
+/// object per line.
+#[derive(Debug, Deserialize)]
+struct ImportedUser {
+  username: String,
+  email: Option<String>,
+  email_verified: Option<bool>,
+  /// The hash as stored by the other system; see `password::algorithm` for the ones understood.
+  password_hash: String,
+  name: Option<String>,
+  given_name: Option<String>,
+  family_name: Option<String>,
+  locale: Option<String>,
+  phone_number: Option<String>
+}
+
+/// Creates users with the password hashes of the system they come from, so they keep logging in
+/// with their passwords; the hashes are replaced with argon2 ones on their first login.
+///
+/// Every user is created on its own: rows that can't be imported are reported & skipped.
+fn import(settings: &Settings, cmd_args: &ArgMatches) -> Result<(), HeimdallrError> {
+  // Safe to unwrap since the args are required
+  let path    = cmd_args.value_of("file").unwrap();
+  let format  = cmd_args.value_of("format").unwrap();
+  let dry_run = cmd_args.is_present("dry-run");
+
+  let input: Box<dyn Read> = if path == "-" { Box::new(std::io::stdin()) } else { Box::new(std::fs::File::open(path)?) };
+  let records = match format {
+    "csv" => read_csv(input),
+    _     => read_jsonl(input)?
+  };
+
+  let conn = establish_connection(&settings.database)?;
+  let (mut imported, mut skipped) = (0, 0);
+
+  for (line, record) in records {
+    match record.and_then(|user| import_user(&conn, &user, dry_run)) {
+      Ok(()) => imported += 1,
+      Err(reason) => {
+        skipped += 1;
+        eprintln!("Line {}: {}", line, reason);
+      }
+    }
+  }
+
+  println!("{} {} users, skipped {}", if dry_run { "Checked" } else { "Imported" }, imported, skipped);
+  Ok(())
+}
+
+/// A user read from the input with its line number, or why the line could not be read.
+type Record = (usize, Result<ImportedUser, String>);
+
+/// Rows along with their line numbers; the header is line 1.
+fn read_csv(input: Box<dyn Read>) -> Vec<Record> {
+  csv::Reader::from_reader(input)
+    .into_deserialize()
+    .enumerate()
+    .map(|(index, record)| (index + 2, record.map_err(|err| err.to_string())))
+    .collect()
+}
+
+/// Objects along with their line numbers; blank lines are skipped.
+fn read_jsonl(input: Box<dyn Read>) -> Result<Vec<Record>, HeimdallrError> {
+  let mut records = Vec::new();
+
+  for (index, line) in BufReader::new(input).lines().enumerate() {
+    let line = line?;
+    if !line.trim().is_empty() {
+      records.push((index + 1, serde_json::from_str(&line).map_err(|err| err.to_string())));
+    }
+  }
+
+  Ok(records)
+}
+
+fn import_user(conn: &PgConnection, user: &ImportedUser, dry_run: bool) -> Result<(), String> {
+  let username = normalize(&user.username);
+  let email    = user.email.as_deref().map(normalize).filter(|email| !email.is_empty());
+
+  registration::validate_username(&username)?;
+  if let Some(email) = &email {
+    registration::validate_email(email)?;
+  }
+
+  let hash = user.password_hash.trim();
+  if password::algorithm(hash).is_none() {
+    return Err("password_hash is not an argon2, bcrypt, scrypt, PBKDF2 or SHA-crypt hash".to_owned());
+  }
+
+  let exists = User::find_by_username(conn, &username).map_err(|err| err.to_string())?.is_some();
+  if exists {
+    return Err(format!("a user named `{}` already exists", username));
+  }
+  if dry_run {
+    return Ok(());
+  }
+
+  let create = || -> Result<(), HeimdallrError> {
+    let created = User::create(conn, &NewUser { username: &username, email: email.as_deref(), password_hash: hash })?;
+    let changes = UserChanges {
+      email_verified: user.email_verified.filter(|_| email.is_some()),
+      name: user.name.clone().map(Some),
+      given_name: user.given_name.clone().map(Some),
+      family_name: user.family_name.clone().map(Some),
+      locale: user.locale.clone().map(Some),
+      phone_number: user.phone_number.clone().map(Some),
+      ..Default::default()
+    };
+
+    User::update(conn, created.id, &changes)?;
+    Ok(())
+  };
+
+  conn.transaction(create).map_err(|err| err.to_string())
+}
@@ -0,0 +1,45 @@
+use openssl::hash::{hash, MessageDigest};
+use openssl::memcmp;
+
+use crate::error::*;
+
+/// Generates a URL safe random token from the given number of random bytes.
+pub fn random_token(bytes: usize) -> Result<String, HeimdallrError> {
+  let mut buf = vec![0u8; bytes];
+  openssl::rand::rand_bytes(&mut buf)?;
+  Ok(base64::encode_config(&buf, base64::URL_SAFE_NO_PAD))
+}
+
+/// Hashes a high entropy token (refresh tokens, codes, ...) for storage.
+///
+/// Unlike passwords these do not need a slow hash since they are never guessable.
+pub fn hash_token(token: &str) -> Result<String, HeimdallrError> {
+  let digest = hash(MessageDigest::sha256(), token.as_bytes())?;
+  Ok(base64::encode_config(digest, base64::URL_SAFE_NO_PAD))
+}
+
+/// Compares two byte strings in constant time.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && memcmp::eq(a, b)
+}
+
+/// PKCE `S256` code challenge of a code verifier (RFC 7636 §4.2).
+pub fn pkce_challenge(verifier: &str) -> Result<String, HeimdallrError> {
+  let digest = hash(MessageDigest::sha256(), verifier.as_bytes())?;
+  Ok(base64::encode_config(digest, base64::URL_SAFE_NO_PAD))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_pkce_challenge() -> Result<(), HeimdallrError> {
+    // RFC 7636 Appendix B.
+    let challenge = pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk")?;
+    assert_eq!(challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    Ok(())
+  }
+}
@@ -1,6 +1,8 @@
 mod schema;
 pub use schema::*;
 
+pub mod models;
+
 #[cfg(test)]
 pub(crate) mod test_helpers;
 
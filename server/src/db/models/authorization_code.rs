@@ -0,0 +1,68 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::crypto;
+use crate::db::authorization_codes;
+use crate::error::*;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "authorization_codes"]
+pub struct AuthorizationCode {
+  pub id: Uuid,
+  pub code_hash: String,
+  pub client_id: String,
+  pub user_id: Uuid,
+  pub redirect_uri: String,
+  pub scopes: Vec<String>,
+  pub nonce: Option<String>,
+  pub code_challenge: Option<String>,
+  pub auth_time: NaiveDateTime,
+  pub amr: Vec<String>,
+  pub expires_at: NaiveDateTime,
+  pub used_at: Option<NaiveDateTime>,
+  pub created_at: NaiveDateTime,
+  pub session_id: Option<Uuid>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "authorization_codes"]
+pub struct NewAuthorizationCode<'a> {
+  pub code_hash: &'a str,
+  pub client_id: &'a str,
+  pub user_id: Uuid,
+  pub redirect_uri: &'a str,
+  pub scopes: &'a [String],
+  pub nonce: Option<&'a str>,
+  pub code_challenge: Option<&'a str>,
+  pub auth_time: NaiveDateTime,
+  pub amr: &'a [String],
+  pub expires_at: NaiveDateTime,
+  pub session_id: Option<Uuid>
+}
+
+impl AuthorizationCode {
+  pub fn is_expired(&self) -> bool {
+    self.expires_at <= Utc::now().naive_utc()
+  }
+
+  pub fn create(conn: &PgConnection, new_code: &NewAuthorizationCode) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(authorization_codes::table).values(new_code).get_result(conn)?)
+  }
+
+  /// Looks up a code by its plain text value.
+  pub fn find_by_code(conn: &PgConnection, code: &str) -> Result<Option<Self>, HeimdallrError> {
+    let hash = crypto::hash_token(code)?;
+    Ok(authorization_codes::table.filter(authorization_codes::code_hash.eq(hash)).first(conn).optional()?)
+  }
+
+  /// Marks the code as used, returning `false` if it already was; codes are strictly single use.
+  pub fn redeem(&self, conn: &PgConnection) -> Result<bool, HeimdallrError> {
+    let updated = diesel::update(self)
+      .filter(authorization_codes::used_at.is_null())
+      .set(authorization_codes::used_at.eq(Some(Utc::now().naive_utc())))
+      .execute(conn)?;
+
+    Ok(updated > 0)
+  }
+}
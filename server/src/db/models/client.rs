@@ -0,0 +1,160 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::clients;
+use crate::error::*;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "clients"]
+pub struct Client {
+  pub id: String,
+  pub name: String,
+  pub secret_hash: Option<String>,
+  pub redirect_uris: Vec<String>,
+  pub grant_types: Vec<String>,
+  pub scopes: Vec<String>,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime,
+  pub allowed_origins: Vec<String>,
+  pub post_logout_redirect_uris: Vec<String>,
+  /// Receives logout tokens when a session the client took part in ends (OpenID Connect Back-Channel Logout).
+  pub backchannel_logout_uri: Option<String>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "clients"]
+pub struct NewClient<'a> {
+  pub id: &'a str,
+  pub name: &'a str,
+  pub secret_hash: Option<&'a str>,
+  pub redirect_uris: &'a [String],
+  pub grant_types: &'a [String],
+  pub scopes: &'a [String],
+  pub allowed_origins: &'a [String],
+  pub post_logout_redirect_uris: &'a [String],
+  pub backchannel_logout_uri: Option<&'a str>
+}
+
+/// Partial update of a client; `None` leaves the column untouched.
+#[derive(Debug, Default, AsChangeset)]
+#[table_name = "clients"]
+pub struct ClientChanges {
+  pub name: Option<String>,
+  pub secret_hash: Option<Option<String>>,
+  pub redirect_uris: Option<Vec<String>>,
+  pub grant_types: Option<Vec<String>>,
+  pub scopes: Option<Vec<String>>,
+  pub allowed_origins: Option<Vec<String>>,
+  pub post_logout_redirect_uris: Option<Vec<String>>,
+  pub backchannel_logout_uri: Option<Option<String>>
+}
+
+impl ClientChanges {
+  pub fn is_empty(&self) -> bool {
+    self.name.is_none() && self.secret_hash.is_none() && self.redirect_uris.is_none() && self.grant_types.is_none() && self.scopes.is_none()
+      && self.allowed_origins.is_none() && self.post_logout_redirect_uris.is_none() && self.backchannel_logout_uri.is_none()
+  }
+}
+
+impl Client {
+  /// Confidential clients have a secret they must authenticate with.
+  pub fn is_confidential(&self) -> bool {
+    self.secret_hash.is_some()
+  }
+
+  pub fn allows_grant_type(&self, grant_type: &str) -> bool {
+    self.grant_types.iter().any(|value| value == grant_type)
+  }
+
+  pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+    self.redirect_uris.iter().any(|value| value == redirect_uri)
+  }
+
+  pub fn allows_post_logout_redirect_uri(&self, uri: &str) -> bool {
+    self.post_logout_redirect_uris.iter().any(|value| value == uri)
+  }
+
+  pub fn allows_origin(&self, origin: &str) -> bool {
+    self.allowed_origins.iter().any(|value| value == origin)
+  }
+
+  /// Checks the client secret; public clients never match.
+  pub fn verify_secret(&self, secret: &str) -> bool {
+    match &self.secret_hash {
+      Some(hash) => crate::password::verify(hash, secret),
+      None       => false
+    }
+  }
+
+  pub fn create(conn: &PgConnection, new_client: &NewClient) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(clients::table).values(new_client).get_result(conn)?)
+  }
+
+  pub fn find(conn: &PgConnection, id: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(clients::table.find(id).first(conn).optional()?)
+  }
+
+  /// Whether any client allows cross-origin requests from a browser origin.
+  pub fn origin_registered(conn: &PgConnection, origin: &str) -> Result<bool, HeimdallrError> {
+    let query = clients::table.filter(clients::allowed_origins.contains(vec![origin.to_owned()]));
+    Ok(diesel::select(diesel::dsl::exists(query)).get_result(conn)?)
+  }
+
+  pub fn list(conn: &PgConnection, limit: i64, offset: i64) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(clients::table.order(clients::id.asc()).limit(limit).offset(offset).load(conn)?)
+  }
+
+  pub fn update(conn: &PgConnection, id: &str, changes: &ClientChanges) -> Result<Option<Self>, HeimdallrError> {
+    if changes.is_empty() {
+      return Self::find(conn, id);
+    }
+
+    Ok(diesel::update(clients::table.find(id)).set(changes).get_result(conn).optional()?)
+  }
+
+  /// Deletes a client, returning whether it existed.
+  pub fn delete(conn: &PgConnection, id: &str) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(clients::table.find(id)).execute(conn)? > 0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::test_helpers;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_round_trip() -> Result<(), HeimdallrError> {
+    let conn    = test_helpers::connection();
+    let origins = vec!["https://app.example.com".to_owned()];
+
+    Client::create(&conn, &NewClient {
+      id: "app",
+      name: "App",
+      secret_hash: None,
+      redirect_uris: &["https://app.example.com/callback".to_owned()],
+      grant_types: &["authorization_code".to_owned()],
+      scopes: &["openid".to_owned()],
+      allowed_origins: &origins,
+      post_logout_redirect_uris: &[],
+      backchannel_logout_uri: None
+    })?;
+
+    let client = Client::find(&conn, "app")?.unwrap();
+    assert!(!client.is_confidential());
+    assert!(client.allows_redirect_uri("https://app.example.com/callback"));
+    assert!(Client::origin_registered(&conn, "https://app.example.com")?);
+    assert!(!Client::origin_registered(&conn, "https://evil.example.com")?);
+
+    let changes = ClientChanges { name: Some("Renamed".to_owned()), secret_hash: Some(Some("hash".to_owned())), ..Default::default() };
+    let updated = Client::update(&conn, "app", &changes)?.unwrap();
+    assert_eq!(updated.name, "Renamed");
+    assert!(updated.is_confidential());
+    assert!(Client::update(&conn, "missing", &changes)?.is_none());
+
+    assert!(Client::delete(&conn, "app")?);
+    assert!(Client::find(&conn, "app")?.is_none());
+    Ok(())
+  }
+}
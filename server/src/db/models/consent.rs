@@ -0,0 +1,114 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::{clients, consents};
+use crate::error::*;
+
+/// Scopes a user has approved for a client.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "consents"]
+#[primary_key(user_id, client_id)]
+pub struct Consent {
+  pub user_id: Uuid,
+  pub client_id: String,
+  pub scopes: Vec<String>,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "consents"]
+struct NewConsent<'a> {
+  user_id: Uuid,
+  client_id: &'a str,
+  scopes: &'a [String]
+}
+
+impl Consent {
+  /// Whether every one of `scopes` has already been approved.
+  pub fn covers(&self, scopes: &[String]) -> bool {
+    scopes.iter().all(|scope| self.scopes.contains(scope))
+  }
+
+  pub fn find(conn: &PgConnection, user_id: Uuid, client_id: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(consents::table.find((user_id, client_id)).first(conn).optional()?)
+  }
+
+  /// The consents of a user, along with the name of each client.
+  pub fn for_user(conn: &PgConnection, user_id: Uuid) -> Result<Vec<(Self, String)>, HeimdallrError> {
+    Ok(
+      consents::table
+        .inner_join(clients::table)
+        .filter(consents::user_id.eq(user_id))
+        .order(clients::name.asc())
+        .select((consents::all_columns, clients::name))
+        .load(conn)?
+    )
+  }
+
+  /// Records newly approved scopes, keeping the ones approved before.
+  ///
+  /// The scopes are merged by the upsert itself, so two approvals racing each other both count.
+  pub fn grant(conn: &PgConnection, user_id: Uuid, client_id: &str, scopes: &[String]) -> Result<Self, HeimdallrError> {
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Array, Text};
+
+    Ok(
+      diesel::insert_into(consents::table)
+        .values(&NewConsent { user_id, client_id, scopes })
+        .on_conflict((consents::user_id, consents::client_id))
+        .do_update()
+        .set(consents::scopes.eq(sql::<Array<Text>>("array(SELECT DISTINCT unnest(consents.scopes || EXCLUDED.scopes))")))
+        .get_result(conn)?
+    )
+  }
+
+  /// Forgets a consent, returning whether there was one.
+  pub fn revoke(conn: &PgConnection, user_id: Uuid, client_id: &str) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(consents::table.find((user_id, client_id))).execute(conn)? > 0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::models::{Client, NewClient, NewUser, User};
+  use crate::db::test_helpers;
+  use pretty_assertions::assert_eq;
+
+  fn scopes(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| (*value).to_owned()).collect()
+  }
+
+  #[test]
+  fn test_grant_merges_and_revoke_forgets() -> Result<(), HeimdallrError> {
+    let conn = test_helpers::connection();
+    let user = User::create(&conn, &NewUser { username: "takara", email: None, password_hash: "hash" })?;
+    Client::create(&conn, &NewClient {
+      id: "app",
+      name: "App",
+      secret_hash: None,
+      redirect_uris: &[],
+      grant_types: &[],
+      scopes: &[],
+      allowed_origins: &[],
+      post_logout_redirect_uris: &[],
+      backchannel_logout_uri: None
+    })?;
+
+    Consent::grant(&conn, user.id, "app", &scopes(&["openid", "profile"]))?;
+    let consent = Consent::grant(&conn, user.id, "app", &scopes(&["profile", "email"]))?;
+
+    let mut approved = consent.scopes.clone();
+    approved.sort();
+    assert_eq!(approved, scopes(&["email", "openid", "profile"]));
+    assert!(consent.covers(&scopes(&["openid", "email"])));
+    assert!(!consent.covers(&scopes(&["phone"])));
+
+    assert!(Consent::revoke(&conn, user.id, "app")?);
+    assert!(Consent::find(&conn, user.id, "app")?.is_none());
+    assert!(!Consent::revoke(&conn, user.id, "app")?);
+    Ok(())
+  }
+}
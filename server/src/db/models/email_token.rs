@@ -0,0 +1,78 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::crypto;
+use crate::db::email_tokens;
+use crate::error::*;
+
+/// A single-use token mailed to a user; only its hash is stored.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "email_tokens"]
+pub struct EmailToken {
+  pub id: Uuid,
+  pub token_hash: String,
+  /// `verify_email`, `password_reset` or `magic_link`.
+  pub purpose: String,
+  pub user_id: Uuid,
+  /// The address the token was sent to.
+  pub email: String,
+  pub expires_at: NaiveDateTime,
+  pub used_at: Option<NaiveDateTime>,
+  pub created_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "email_tokens"]
+pub struct NewEmailToken<'a> {
+  pub token_hash: &'a str,
+  pub purpose: &'a str,
+  pub user_id: Uuid,
+  pub email: &'a str,
+  pub expires_at: NaiveDateTime
+}
+
+impl EmailToken {
+  pub fn create(conn: &PgConnection, new_token: &NewEmailToken) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(email_tokens::table).values(new_token).get_result(conn)?)
+  }
+
+  /// Uses up a token, returning it unless it is unknown, meant for something else, expired or was
+  /// already used.
+  pub fn redeem(conn: &PgConnection, token: &str, purpose: &str) -> Result<Option<Self>, HeimdallrError> {
+    let now = Utc::now().naive_utc();
+
+    Ok(
+      diesel::update(
+        email_tokens::table
+          .filter(email_tokens::token_hash.eq(crypto::hash_token(token)?))
+          .filter(email_tokens::purpose.eq(purpose))
+          .filter(email_tokens::used_at.is_null())
+          .filter(email_tokens::expires_at.gt(now))
+      )
+      .set(email_tokens::used_at.eq(Some(now)))
+      .get_result(conn)
+      .optional()?
+    )
+  }
+
+  /// Invalidates the outstanding tokens of a user for a purpose, e.g. every reset link once the
+  /// password was changed.
+  pub fn revoke_all(conn: &PgConnection, user_id: Uuid, purpose: &str) -> Result<usize, HeimdallrError> {
+    Ok(
+      diesel::update(
+        email_tokens::table
+          .filter(email_tokens::user_id.eq(user_id))
+          .filter(email_tokens::purpose.eq(purpose))
+          .filter(email_tokens::used_at.is_null())
+      )
+      .set(email_tokens::used_at.eq(Some(Utc::now().naive_utc())))
+      .execute(conn)?
+    )
+  }
+
+  /// Forgets tokens that can no longer be used.
+  pub fn delete_expired(conn: &PgConnection) -> Result<usize, HeimdallrError> {
+    Ok(diesel::delete(email_tokens::table.filter(email_tokens::expires_at.lt(Utc::now().naive_utc()))).execute(conn)?)
+  }
+}
@@ -0,0 +1,137 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::{group_members, group_roles, groups, users};
+use crate::error::*;
+use super::User;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "groups"]
+pub struct Group {
+  pub id: i32,
+  pub name: String,
+  pub description: String,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "groups"]
+pub struct NewGroup<'a> {
+  pub name: &'a str,
+  pub description: &'a str
+}
+
+/// Partial update of a group; `None` leaves the column untouched.
+#[derive(Debug, Default, AsChangeset)]
+#[table_name = "groups"]
+pub struct GroupChanges {
+  pub name: Option<String>,
+  pub description: Option<String>
+}
+
+impl GroupChanges {
+  pub fn is_empty(&self) -> bool {
+    self.name.is_none() && self.description.is_none()
+  }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "group_members"]
+struct NewGroupMember {
+  group_id: i32,
+  user_id: Uuid
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "group_roles"]
+struct NewGroupRole {
+  group_id: i32,
+  role_id: i32
+}
+
+impl Group {
+  pub fn create(conn: &PgConnection, new_group: &NewGroup) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(groups::table).values(new_group).get_result(conn)?)
+  }
+
+  pub fn find(conn: &PgConnection, id: i32) -> Result<Option<Self>, HeimdallrError> {
+    Ok(groups::table.find(id).first(conn).optional()?)
+  }
+
+  pub fn find_by_name(conn: &PgConnection, name: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(groups::table.filter(groups::name.eq(name)).first(conn).optional()?)
+  }
+
+  pub fn all(conn: &PgConnection) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(groups::table.order(groups::name.asc()).load(conn)?)
+  }
+
+  /// Every group a user belongs to.
+  pub fn for_user(conn: &PgConnection, user_id: Uuid) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(
+      groups::table
+        .inner_join(group_members::table)
+        .filter(group_members::user_id.eq(user_id))
+        .select(groups::all_columns)
+        .order(groups::name.asc())
+        .load(conn)?
+    )
+  }
+
+  /// The users belonging to a group, by username.
+  pub fn members(conn: &PgConnection, id: i32) -> Result<Vec<User>, HeimdallrError> {
+    Ok(
+      users::table
+        .inner_join(group_members::table)
+        .filter(group_members::group_id.eq(id))
+        .select(users::all_columns)
+        .order(users::username.asc())
+        .load(conn)?
+    )
+  }
+
+  pub fn update(conn: &PgConnection, id: i32, changes: &GroupChanges) -> Result<Option<Self>, HeimdallrError> {
+    if changes.is_empty() {
+      return Self::find(conn, id);
+    }
+
+    Ok(diesel::update(groups::table.find(id)).set(changes).get_result(conn).optional()?)
+  }
+
+  /// Deletes a group, returning whether it existed; its members keep their own roles.
+  pub fn delete(conn: &PgConnection, id: i32) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(groups::table.find(id)).execute(conn)? > 0)
+  }
+
+  /// Adds a user to a group; adding them twice is a no-op.
+  pub fn add_member(conn: &PgConnection, id: i32, user_id: Uuid) -> Result<(), HeimdallrError> {
+    diesel::insert_into(group_members::table)
+      .values(&NewGroupMember { group_id: id, user_id })
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    Ok(())
+  }
+
+  /// Removes a user from a group, returning whether they belonged to it.
+  pub fn remove_member(conn: &PgConnection, id: i32, user_id: Uuid) -> Result<bool, HeimdallrError> {
+    let target = group_members::table.filter(group_members::group_id.eq(id)).filter(group_members::user_id.eq(user_id));
+    Ok(diesel::delete(target).execute(conn)? > 0)
+  }
+
+  /// Assigns a role to every member of a group; assigning it twice is a no-op.
+  pub fn assign_role(conn: &PgConnection, id: i32, role_id: i32) -> Result<(), HeimdallrError> {
+    diesel::insert_into(group_roles::table)
+      .values(&NewGroupRole { group_id: id, role_id })
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    Ok(())
+  }
+
+  /// Removes a role from a group, returning whether it was assigned.
+  pub fn unassign_role(conn: &PgConnection, id: i32, role_id: i32) -> Result<bool, HeimdallrError> {
+    let target = group_roles::table.filter(group_roles::group_id.eq(id)).filter(group_roles::role_id.eq(role_id));
+    Ok(diesel::delete(target).execute(conn)? > 0)
+  }
+}
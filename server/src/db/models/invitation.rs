@@ -0,0 +1,65 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::crypto;
+use crate::db::invitations;
+use crate::error::*;
+
+/// A code letting people sign up when registration is invitation-only; only its hash is stored.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "invitations"]
+pub struct Invitation {
+  pub id: Uuid,
+  pub code_hash: String,
+  pub description: String,
+  /// Roles given to every account created with the code.
+  pub role_ids: Vec<i32>,
+  pub max_uses: i32,
+  pub uses: i32,
+  pub expires_at: Option<NaiveDateTime>,
+  pub created_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "invitations"]
+pub struct NewInvitation<'a> {
+  pub code_hash: &'a str,
+  pub description: &'a str,
+  pub role_ids: &'a [i32],
+  pub max_uses: i32,
+  pub expires_at: Option<NaiveDateTime>
+}
+
+impl Invitation {
+  pub fn create(conn: &PgConnection, new_invitation: &NewInvitation) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(invitations::table).values(new_invitation).get_result(conn)?)
+  }
+
+  pub fn list(conn: &PgConnection, limit: i64, offset: i64) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(invitations::table.order(invitations::created_at.desc()).limit(limit).offset(offset).load(conn)?)
+  }
+
+  /// Uses up one use of a code, returning its invitation unless the code is unknown, expired or
+  /// used up.
+  pub fn redeem(conn: &PgConnection, code: &str) -> Result<Option<Self>, HeimdallrError> {
+    let now = Utc::now().naive_utc();
+
+    Ok(
+      diesel::update(
+        invitations::table
+          .filter(invitations::code_hash.eq(crypto::hash_token(code)?))
+          .filter(invitations::uses.lt(invitations::max_uses))
+          .filter(invitations::expires_at.is_null().or(invitations::expires_at.gt(now)))
+      )
+      .set(invitations::uses.eq(invitations::uses + 1))
+      .get_result(conn)
+      .optional()?
+    )
+  }
+
+  /// Deletes an invitation, returning whether it existed; accounts created with it are kept.
+  pub fn delete(conn: &PgConnection, id: Uuid) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(invitations::table.find(id)).execute(conn)? > 0)
+  }
+}
@@ -0,0 +1,147 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::db::keys;
+use crate::error::*;
+use crate::jwt::{Algorithm, KeyPair};
+
+/// Lifecycle of a signing key.
+///
+/// Staged keys are published so relying parties can pick them up ahead of time, the active
+/// key signs every new token, and retired keys are neither published nor trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStatus {
+  Staged,
+  Active,
+  Retired
+}
+
+impl KeyStatus {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      KeyStatus::Staged  => "staged",
+      KeyStatus::Active  => "active",
+      KeyStatus::Retired => "retired"
+    }
+  }
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "keys"]
+pub struct SigningKey {
+  pub id: i32,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime,
+  pub kid: String,
+  pub algorithm: String,
+  pub private_key: String,
+  pub public_key: String,
+  pub status: String,
+  pub activated_at: Option<NaiveDateTime>,
+  pub retired_at: Option<NaiveDateTime>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "keys"]
+pub struct NewSigningKey<'a> {
+  pub kid: &'a str,
+  pub algorithm: &'a str,
+  pub private_key: &'a str,
+  pub public_key: &'a str,
+  pub status: &'a str
+}
+
+impl SigningKey {
+  /// Parses the stored algorithm name.
+  pub fn algorithm(&self) -> Result<Algorithm, HeimdallrError> {
+    self.algorithm.parse()
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.status == KeyStatus::Active.as_str()
+  }
+
+  pub fn is_retired(&self) -> bool {
+    self.status == KeyStatus::Retired.as_str()
+  }
+
+  /// Stores a key pair as a staged key.
+  pub fn insert(conn: &PgConnection, pair: &KeyPair) -> Result<Self, HeimdallrError> {
+    let algorithm = pair.algorithm.as_str();
+    let new_key = NewSigningKey {
+      kid: &pair.kid,
+      algorithm,
+      private_key: &pair.private_pem,
+      public_key: &pair.public_pem,
+      status: KeyStatus::Staged.as_str()
+    };
+
+    Ok(diesel::insert_into(keys::table).values(&new_key).get_result(conn)?)
+  }
+
+  /// Lists every key, newest first.
+  pub fn all(conn: &PgConnection) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(keys::table.order(keys::created_at.desc()).load(conn)?)
+  }
+
+  /// Lists every key that may still be used to verify tokens.
+  pub fn verifiable(conn: &PgConnection) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(
+      keys::table
+        .filter(keys::status.ne(KeyStatus::Retired.as_str()))
+        .order(keys::created_at.desc())
+        .load(conn)?
+    )
+  }
+
+  pub fn find_by_kid(conn: &PgConnection, kid: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(keys::table.filter(keys::kid.eq(kid)).first(conn).optional()?)
+  }
+
+  /// Fetches the key currently used for signing, if any.
+  pub fn active(conn: &PgConnection) -> Result<Option<Self>, HeimdallrError> {
+    Ok(keys::table.filter(keys::status.eq(KeyStatus::Active.as_str())).first(conn).optional()?)
+  }
+
+  /// Promotes a key to be the signing key.
+  ///
+  /// The previously active key is demoted back to staged so tokens it already signed keep verifying.
+  pub fn activate(conn: &PgConnection, kid: &str) -> Result<Self, HeimdallrError> {
+    conn.transaction(|| {
+      let key = Self::find_by_kid(conn, kid)?
+        .ok_or_else(|| HeimdallrError::KeyError(format!("no key with kid `{}`", kid)))?;
+
+      if key.is_retired() {
+        return Err(HeimdallrError::KeyError(format!("key `{}` has been retired and cannot be activated", kid)));
+      }
+
+      diesel::update(keys::table.filter(keys::status.eq(KeyStatus::Active.as_str())))
+        .set(keys::status.eq(KeyStatus::Staged.as_str()))
+        .execute(conn)?;
+
+      Ok(
+        diesel::update(&key)
+          .set((
+            keys::status.eq(KeyStatus::Active.as_str()),
+            keys::activated_at.eq(Some(Utc::now().naive_utc()))
+          ))
+          .get_result(conn)?
+      )
+    })
+  }
+
+  /// Retires a key; tokens it signed are no longer accepted.
+  pub fn retire(conn: &PgConnection, kid: &str) -> Result<Self, HeimdallrError> {
+    let key = Self::find_by_kid(conn, kid)?
+      .ok_or_else(|| HeimdallrError::KeyError(format!("no key with kid `{}`", kid)))?;
+
+    Ok(
+      diesel::update(&key)
+        .set((
+          keys::status.eq(KeyStatus::Retired.as_str()),
+          keys::retired_at.eq(Some(Utc::now().naive_utc()))
+        ))
+        .get_result(conn)?
+    )
+  }
+}
@@ -0,0 +1,74 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::db::login_failures;
+use crate::error::*;
+
+/// Failed logins counted under a key, `user:<username>`, `ip:<address>` or `mfa:<user id>` for
+/// wrong codes in the two-factor step.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "login_failures"]
+#[primary_key(key)]
+pub struct LoginFailure {
+  pub key: String,
+  /// Failures since the counter was last reset.
+  pub failures: i32,
+  pub last_failed_at: NaiveDateTime,
+  /// No attempts are accepted under the key before then.
+  pub locked_until: Option<NaiveDateTime>
+}
+
+impl LoginFailure {
+  /// The counters of the given keys that exist.
+  pub fn find_all(conn: &PgConnection, keys: &[String]) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(login_failures::table.filter(login_failures::key.eq_any(keys)).load(conn)?)
+  }
+
+  /// Counts a failure under a key; failures older than `forget_before` are forgotten first.
+  ///
+  /// The row is locked while counting so concurrent failures, on any replica, are all counted.
+  /// `lock_for` decides, from the new count, how long the key is locked for.
+  pub fn record<F>(conn: &PgConnection, key: &str, forget_before: NaiveDateTime, lock_for: F) -> Result<Self, HeimdallrError>
+    where F: Fn(i32) -> Option<Duration>
+  {
+    conn.transaction(|| {
+      diesel::insert_into(login_failures::table)
+        .values(login_failures::key.eq(key))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+      let current: Self = login_failures::table.find(key).for_update().first(conn)?;
+      let failures      = if current.last_failed_at < forget_before { 1 } else { current.failures + 1 };
+      let now           = Utc::now().naive_utc();
+
+      Ok(
+        diesel::update(&current)
+          .set((
+            login_failures::failures.eq(failures),
+            login_failures::last_failed_at.eq(now),
+            login_failures::locked_until.eq(lock_for(failures).map(|duration| now + duration))
+          ))
+          .get_result(conn)?
+      )
+    })
+  }
+
+  /// Resets the counter of a key, returning whether there was one.
+  pub fn clear(conn: &PgConnection, key: &str) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(login_failures::table.find(key)).execute(conn)? > 0)
+  }
+
+  /// Forgets counters without failures since `before` that aren't locked.
+  pub fn delete_stale(conn: &PgConnection, before: NaiveDateTime) -> Result<usize, HeimdallrError> {
+    let now = Utc::now().naive_utc();
+
+    Ok(
+      diesel::delete(
+        login_failures::table
+          .filter(login_failures::last_failed_at.lt(before))
+          .filter(login_failures::locked_until.is_null().or(login_failures::locked_until.lt(now)))
+      )
+      .execute(conn)?
+    )
+  }
+}
@@ -0,0 +1,104 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::mail_deliveries;
+use crate::error::*;
+
+/// A message sent, or asked for, recorded to rate limit what a single recipient receives on a
+/// channel: an email address or a phone number.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "mail_deliveries"]
+pub struct MailDelivery {
+  pub id: Uuid,
+  pub recipient: String,
+  pub purpose: String,
+  pub created_at: NaiveDateTime,
+  pub channel: String
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "mail_deliveries"]
+struct NewMailDelivery<'a> {
+  channel: &'a str,
+  recipient: &'a str,
+  purpose: &'a str
+}
+
+impl MailDelivery {
+  /// `channel` of emails.
+  pub const EMAIL: &'static str = "email";
+
+  /// `channel` of texts & calls to a phone number, which count together.
+  pub const PHONE: &'static str = "phone";
+
+  pub fn record(conn: &PgConnection, channel: &str, recipient: &str, purpose: &str) -> Result<(), HeimdallrError> {
+    diesel::insert_into(mail_deliveries::table).values(&NewMailDelivery { channel, recipient, purpose }).execute(conn)?;
+    Ok(())
+  }
+
+  /// How many messages a recipient got on a channel since a moment, whatever they were for.
+  pub fn count_since(conn: &PgConnection, channel: &str, recipient: &str, since: NaiveDateTime) -> Result<i64, HeimdallrError> {
+    Ok(
+      mail_deliveries::table
+        .filter(mail_deliveries::channel.eq(channel))
+        .filter(mail_deliveries::recipient.eq(recipient))
+        .filter(mail_deliveries::created_at.gt(since))
+        .count()
+        .get_result(conn)?
+    )
+  }
+
+  /// The oldest message a recipient got on a channel since a moment, which is when they can get
+  /// another one.
+  pub fn oldest_since(conn: &PgConnection, channel: &str, recipient: &str, since: NaiveDateTime) -> Result<Option<NaiveDateTime>, HeimdallrError> {
+    Ok(
+      mail_deliveries::table
+        .select(mail_deliveries::created_at)
+        .filter(mail_deliveries::channel.eq(channel))
+        .filter(mail_deliveries::recipient.eq(recipient))
+        .filter(mail_deliveries::created_at.gt(since))
+        .order(mail_deliveries::created_at.asc())
+        .first(conn)
+        .optional()?
+    )
+  }
+
+  /// Counts a message towards the hourly limit of its recipient on a channel, unless the limit is
+  /// reached, in which case the wait until they can get another one is returned.
+  pub fn throttle(conn: &PgConnection, channel: &str, recipient: &str, purpose: &str, max_per_hour: i64) -> Result<Option<Duration>, HeimdallrError> {
+    let window = Duration::hours(1);
+    let now    = Utc::now().naive_utc();
+
+    Self::delete_before(conn, now - window)?;
+
+    if Self::count_since(conn, channel, recipient, now - window)? >= max_per_hour {
+      let oldest = Self::oldest_since(conn, channel, recipient, now - window)?.unwrap_or(now);
+      return Ok(Some(oldest + window - now));
+    }
+
+    Self::record(conn, channel, recipient, purpose)?;
+    Ok(None)
+  }
+
+  /// Forgets deliveries older than any rate limit window.
+  pub fn delete_before(conn: &PgConnection, before: NaiveDateTime) -> Result<usize, HeimdallrError> {
+    Ok(diesel::delete(mail_deliveries::table.filter(mail_deliveries::created_at.lt(before))).execute(conn)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::test_helpers;
+
+  #[test]
+  fn test_channels_are_throttled_apart() -> Result<(), HeimdallrError> {
+    let conn = test_helpers::connection();
+
+    assert!(MailDelivery::throttle(&conn, MailDelivery::PHONE, "+15550100", "mfa", 1)?.is_none());
+    assert!(MailDelivery::throttle(&conn, MailDelivery::PHONE, "+15550100", "verify_phone", 1)?.is_some());
+    assert!(MailDelivery::throttle(&conn, MailDelivery::EMAIL, "+15550100", "magic_link", 1)?.is_none());
+    Ok(())
+  }
+}
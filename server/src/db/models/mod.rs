@@ -0,0 +1,71 @@
+mod authorization_code;
+pub use authorization_code::*;
+
+mod client;
+pub use client::*;
+
+mod consent;
+pub use consent::*;
+
+mod email_token;
+pub use email_token::*;
+
+mod group;
+pub use group::*;
+
+mod invitation;
+pub use invitation::*;
+
+mod key;
+pub use key::*;
+
+mod login_failure;
+pub use login_failure::*;
+
+mod mail_delivery;
+pub use mail_delivery::*;
+
+mod permission;
+pub use permission::*;
+
+mod previous_password;
+pub use previous_password::*;
+
+mod rate_limit_bucket;
+pub use rate_limit_bucket::*;
+
+mod recovery_code;
+pub use recovery_code::*;
+
+mod refresh_token;
+pub use refresh_token::*;
+
+mod role;
+pub use role::*;
+
+mod scope;
+pub use scope::*;
+
+mod session;
+pub use session::*;
+
+mod sms_code;
+pub use sms_code::*;
+
+mod sms_factor;
+pub use sms_factor::*;
+
+mod spent_mfa_token;
+pub use spent_mfa_token::*;
+
+mod totp_credential;
+pub use totp_credential::*;
+
+mod user;
+pub use user::*;
+
+mod webauthn_challenge;
+pub use webauthn_challenge::*;
+
+mod webauthn_credential;
+pub use webauthn_credential::*;
@@ -0,0 +1,81 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::{permissions, role_permissions};
+use crate::error::*;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "permissions"]
+pub struct Permission {
+  pub id: i32,
+  pub name: String,
+  pub description: String,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "permissions"]
+pub struct NewPermission<'a> {
+  pub name: &'a str,
+  pub description: &'a str
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "role_permissions"]
+struct NewRolePermission {
+  role_id: i32,
+  permission_id: i32
+}
+
+impl Permission {
+  pub fn create(conn: &PgConnection, new_permission: &NewPermission) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(permissions::table).values(new_permission).get_result(conn)?)
+  }
+
+  pub fn find(conn: &PgConnection, id: i32) -> Result<Option<Self>, HeimdallrError> {
+    Ok(permissions::table.find(id).first(conn).optional()?)
+  }
+
+  pub fn find_by_name(conn: &PgConnection, name: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(permissions::table.filter(permissions::name.eq(name)).first(conn).optional()?)
+  }
+
+  pub fn all(conn: &PgConnection) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(permissions::table.order(permissions::name.asc()).load(conn)?)
+  }
+
+  /// Every permission granted to any of the roles, paired with the id of the role granting it.
+  pub fn for_roles(conn: &PgConnection, role_ids: &[i32]) -> Result<Vec<(i32, Self)>, HeimdallrError> {
+    Ok(
+      role_permissions::table
+        .inner_join(permissions::table)
+        .filter(role_permissions::role_id.eq_any(role_ids))
+        .select((role_permissions::role_id, permissions::all_columns))
+        .order((permissions::name.asc(), role_permissions::role_id.asc()))
+        .load(conn)?
+    )
+  }
+
+  /// Deletes a permission, returning whether it existed.
+  pub fn delete(conn: &PgConnection, id: i32) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(permissions::table.find(id)).execute(conn)? > 0)
+  }
+
+  /// Grants a permission to a role; granting it twice is a no-op.
+  pub fn grant(conn: &PgConnection, role_id: i32, permission_id: i32) -> Result<(), HeimdallrError> {
+    diesel::insert_into(role_permissions::table)
+      .values(&NewRolePermission { role_id, permission_id })
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    Ok(())
+  }
+
+  /// Revokes a permission from a role, returning whether it was granted.
+  pub fn revoke(conn: &PgConnection, role_id: i32, permission_id: i32) -> Result<bool, HeimdallrError> {
+    let target = role_permissions::table
+      .filter(role_permissions::role_id.eq(role_id))
+      .filter(role_permissions::permission_id.eq(permission_id));
+    Ok(diesel::delete(target).execute(conn)? > 0)
+  }
+}
@@ -0,0 +1,60 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::previous_passwords;
+use crate::error::*;
+
+/// The hash of a password a user had before, kept so they cannot pick it again.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "previous_passwords"]
+pub struct PreviousPassword {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub password_hash: String,
+  pub created_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "previous_passwords"]
+struct NewPreviousPassword<'a> {
+  user_id: Uuid,
+  password_hash: &'a str
+}
+
+impl PreviousPassword {
+  pub fn record(conn: &PgConnection, user_id: Uuid, password_hash: &str) -> Result<(), HeimdallrError> {
+    diesel::insert_into(previous_passwords::table)
+      .values(&NewPreviousPassword { user_id, password_hash })
+      .execute(conn)?;
+    Ok(())
+  }
+
+  /// Hashes of the most recent previous passwords of a user, newest first.
+  pub fn recent(conn: &PgConnection, user_id: Uuid, limit: i64) -> Result<Vec<String>, HeimdallrError> {
+    Ok(
+      previous_passwords::table
+        .filter(previous_passwords::user_id.eq(user_id))
+        .order(previous_passwords::created_at.desc())
+        .limit(limit)
+        .select(previous_passwords::password_hash)
+        .load(conn)?
+    )
+  }
+
+  /// Forgets all but the `keep` most recent previous passwords of a user.
+  pub fn prune(conn: &PgConnection, user_id: Uuid, keep: i64) -> Result<usize, HeimdallrError> {
+    let kept: Vec<Uuid> = previous_passwords::table
+      .filter(previous_passwords::user_id.eq(user_id))
+      .order(previous_passwords::created_at.desc())
+      .limit(keep)
+      .select(previous_passwords::id)
+      .load(conn)?;
+
+    let stale = previous_passwords::table
+      .filter(previous_passwords::user_id.eq(user_id))
+      .filter(previous_passwords::id.ne_all(kept));
+
+    Ok(diesel::delete(stale).execute(conn)?)
+  }
+}
@@ -0,0 +1,53 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Double, Varchar};
+
+use crate::db::rate_limit_buckets;
+use crate::error::*;
+
+/// A token bucket of the `postgres` rate limit backend.
+#[derive(Debug, Clone, Queryable, QueryableByName, Identifiable)]
+#[table_name = "rate_limit_buckets"]
+#[primary_key(key)]
+pub struct RateLimitBucket {
+  pub key: String,
+  /// Tokens left as of `updated_at`.
+  pub tokens: f64,
+  /// Whether the last call got a token.
+  pub allowed: bool,
+  pub updated_at: NaiveDateTime
+}
+
+/// Refills the bucket for the time since its last call & takes a token if there is one, in one
+/// statement so concurrent calls from every replica are all counted. New buckets start out full.
+const TAKE: &str = "
+  INSERT INTO rate_limit_buckets AS bucket (key, tokens, allowed, updated_at)
+  VALUES ($1, $2 - 1, TRUE, NOW())
+  ON CONFLICT (key) DO UPDATE SET
+    tokens = CASE
+      WHEN LEAST($2, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::FLOAT8 * $3) >= 1
+      THEN LEAST($2, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::FLOAT8 * $3) - 1
+      ELSE LEAST($2, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::FLOAT8 * $3)
+    END,
+    allowed = LEAST($2, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::FLOAT8 * $3) >= 1,
+    updated_at = NOW()
+  RETURNING *";
+
+impl RateLimitBucket {
+  /// Takes a token from a bucket holding up to `capacity`, refilled with `per_second` tokens a
+  /// second; `allowed` on the result tells whether there was one.
+  pub fn take(conn: &PgConnection, key: &str, capacity: f64, per_second: f64) -> Result<Self, HeimdallrError> {
+    Ok(
+      diesel::sql_query(TAKE)
+        .bind::<Varchar, _>(key)
+        .bind::<Double, _>(capacity)
+        .bind::<Double, _>(per_second)
+        .get_result(conn)?
+    )
+  }
+
+  /// Forgets buckets without calls since `before`.
+  pub fn delete_stale(conn: &PgConnection, before: NaiveDateTime) -> Result<usize, HeimdallrError> {
+    Ok(diesel::delete(rate_limit_buckets::table.filter(rate_limit_buckets::updated_at.lt(before))).execute(conn)?)
+  }
+}
@@ -0,0 +1,69 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::crypto;
+use crate::db::recovery_codes;
+use crate::error::*;
+
+/// A one-time code standing in for the authenticator app; only its hash is stored.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "recovery_codes"]
+pub struct RecoveryCode {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub code_hash: String,
+  pub used_at: Option<NaiveDateTime>,
+  pub created_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "recovery_codes"]
+struct NewRecoveryCode {
+  user_id: Uuid,
+  code_hash: String
+}
+
+impl RecoveryCode {
+  /// Replaces every recovery code of a user with new ones.
+  pub fn replace_all(conn: &PgConnection, user_id: Uuid, codes: &[String]) -> Result<(), HeimdallrError> {
+    let new_codes = codes.iter()
+      .map(|code| Ok(NewRecoveryCode { user_id, code_hash: crypto::hash_token(code)? }))
+      .collect::<Result<Vec<_>, HeimdallrError>>()?;
+
+    conn.transaction(|| {
+      RecoveryCode::delete_all(conn, user_id)?;
+      diesel::insert_into(recovery_codes::table).values(&new_codes).execute(conn)?;
+      Ok(())
+    })
+  }
+
+  /// Uses up a recovery code, returning `false` if it is unknown or was already used.
+  pub fn redeem(conn: &PgConnection, user_id: Uuid, code: &str) -> Result<bool, HeimdallrError> {
+    let redeemed = diesel::update(
+      recovery_codes::table
+        .filter(recovery_codes::user_id.eq(user_id))
+        .filter(recovery_codes::code_hash.eq(crypto::hash_token(code)?))
+        .filter(recovery_codes::used_at.is_null())
+    )
+    .set(recovery_codes::used_at.eq(Some(Utc::now().naive_utc())))
+    .execute(conn)?;
+
+    Ok(redeemed > 0)
+  }
+
+  /// How many unused codes a user has left.
+  pub fn remaining(conn: &PgConnection, user_id: Uuid) -> Result<i64, HeimdallrError> {
+    Ok(
+      recovery_codes::table
+        .filter(recovery_codes::user_id.eq(user_id))
+        .filter(recovery_codes::used_at.is_null())
+        .count()
+        .get_result(conn)?
+    )
+  }
+
+  pub fn delete_all(conn: &PgConnection, user_id: Uuid) -> Result<usize, HeimdallrError> {
+    Ok(diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user_id))).execute(conn)?)
+  }
+}
@@ -0,0 +1,126 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::crypto;
+use crate::db::refresh_tokens;
+use crate::error::*;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "refresh_tokens"]
+pub struct RefreshToken {
+  pub id: Uuid,
+  pub token_hash: String,
+  pub client_id: String,
+  pub user_id: Option<Uuid>,
+  pub scopes: Vec<String>,
+  pub auth_time: NaiveDateTime,
+  pub amr: Vec<String>,
+  pub expires_at: NaiveDateTime,
+  pub revoked_at: Option<NaiveDateTime>,
+  pub created_at: NaiveDateTime,
+  /// The SSO session the token was issued in; ending the session revokes it.
+  pub session_id: Option<Uuid>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "refresh_tokens"]
+pub struct NewRefreshToken<'a> {
+  pub token_hash: &'a str,
+  pub client_id: &'a str,
+  pub user_id: Option<Uuid>,
+  pub scopes: &'a [String],
+  pub auth_time: NaiveDateTime,
+  pub amr: &'a [String],
+  pub expires_at: NaiveDateTime,
+  pub session_id: Option<Uuid>
+}
+
+impl RefreshToken {
+  pub fn is_usable(&self) -> bool {
+    self.revoked_at.is_none() && self.expires_at > Utc::now().naive_utc()
+  }
+
+  pub fn create(conn: &PgConnection, new_token: &NewRefreshToken) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(refresh_tokens::table).values(new_token).get_result(conn)?)
+  }
+
+  /// Looks up a token by its plain text value.
+  pub fn find_by_token(conn: &PgConnection, token: &str) -> Result<Option<Self>, HeimdallrError> {
+    let hash = crypto::hash_token(token)?;
+    Ok(refresh_tokens::table.filter(refresh_tokens::token_hash.eq(hash)).first(conn).optional()?)
+  }
+
+  /// Revokes a single token, returning `false` if it already was.
+  pub fn revoke(&self, conn: &PgConnection) -> Result<bool, HeimdallrError> {
+    let updated = diesel::update(self)
+      .filter(refresh_tokens::revoked_at.is_null())
+      .set(refresh_tokens::revoked_at.eq(Some(Utc::now().naive_utc())))
+      .execute(conn)?;
+
+    Ok(updated > 0)
+  }
+
+  /// Revokes every token a client holds for a user, returning how many were revoked.
+  pub fn revoke_for_user_and_client(conn: &PgConnection, user_id: Uuid, client_id: &str) -> Result<usize, HeimdallrError> {
+    Ok(
+      diesel::update(
+        refresh_tokens::table
+          .filter(refresh_tokens::user_id.eq(user_id))
+          .filter(refresh_tokens::client_id.eq(client_id))
+          .filter(refresh_tokens::revoked_at.is_null())
+      )
+      .set(refresh_tokens::revoked_at.eq(Some(Utc::now().naive_utc())))
+      .execute(conn)?
+    )
+  }
+
+  /// Revokes every token of a user, e.g. once their password was reset.
+  pub fn revoke_for_user(conn: &PgConnection, user_id: Uuid) -> Result<usize, HeimdallrError> {
+    Ok(
+      diesel::update(refresh_tokens::table.filter(refresh_tokens::user_id.eq(user_id)).filter(refresh_tokens::revoked_at.is_null()))
+        .set(refresh_tokens::revoked_at.eq(Some(Utc::now().naive_utc())))
+        .execute(conn)?
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::models::{Client, NewClient};
+  use crate::db::test_helpers;
+
+  #[test]
+  fn test_revoke_reports_replays() -> Result<(), HeimdallrError> {
+    let conn = test_helpers::connection();
+    Client::create(&conn, &NewClient {
+      id: "app",
+      name: "App",
+      secret_hash: None,
+      redirect_uris: &[],
+      grant_types: &[],
+      scopes: &[],
+      allowed_origins: &[],
+      post_logout_redirect_uris: &[],
+      backchannel_logout_uri: None
+    })?;
+
+    let now   = Utc::now().naive_utc();
+    let token = RefreshToken::create(&conn, &NewRefreshToken {
+      token_hash: &crypto::hash_token("token")?,
+      client_id: "app",
+      user_id: None,
+      scopes: &[],
+      auth_time: now,
+      amr: &[],
+      expires_at: now + chrono::Duration::hours(1),
+      session_id: None
+    })?;
+
+    assert!(token.revoke(&conn)?);
+    assert!(!token.revoke(&conn)?);
+    assert!(!RefreshToken::find_by_token(&conn, "token")?.unwrap().is_usable());
+    Ok(())
+  }
+}
@@ -0,0 +1,159 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::{group_members, group_roles, roles, user_roles};
+use crate::error::*;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "roles"]
+pub struct Role {
+  pub id: i32,
+  pub name: String,
+  pub description: String,
+  pub scopes: Vec<String>,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "roles"]
+pub struct NewRole<'a> {
+  pub name: &'a str,
+  pub description: &'a str,
+  pub scopes: &'a [String]
+}
+
+/// Partial update of a role; `None` leaves the column untouched.
+#[derive(Debug, Default, AsChangeset)]
+#[table_name = "roles"]
+pub struct RoleChanges {
+  pub name: Option<String>,
+  pub description: Option<String>,
+  pub scopes: Option<Vec<String>>
+}
+
+impl RoleChanges {
+  pub fn is_empty(&self) -> bool {
+    self.name.is_none() && self.description.is_none() && self.scopes.is_none()
+  }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "user_roles"]
+struct NewUserRole {
+  user_id: Uuid,
+  role_id: i32
+}
+
+impl Role {
+  pub fn create(conn: &PgConnection, new_role: &NewRole) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(roles::table).values(new_role).get_result(conn)?)
+  }
+
+  pub fn find(conn: &PgConnection, id: i32) -> Result<Option<Self>, HeimdallrError> {
+    Ok(roles::table.find(id).first(conn).optional()?)
+  }
+
+  pub fn find_by_name(conn: &PgConnection, name: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(roles::table.filter(roles::name.eq(name)).first(conn).optional()?)
+  }
+
+  pub fn all(conn: &PgConnection) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(roles::table.order(roles::name.asc()).load(conn)?)
+  }
+
+  /// Every role directly assigned to a user.
+  pub fn for_user(conn: &PgConnection, user_id: Uuid) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(
+      roles::table
+        .inner_join(user_roles::table)
+        .filter(user_roles::user_id.eq(user_id))
+        .select(roles::all_columns)
+        .order(roles::name.asc())
+        .load(conn)?
+    )
+  }
+
+  /// Every role a user holds, whether assigned directly or through the groups they belong to.
+  pub fn effective_for_user(conn: &PgConnection, user_id: Uuid) -> Result<Vec<Self>, HeimdallrError> {
+    let direct = user_roles::table.filter(user_roles::user_id.eq(user_id)).select(user_roles::role_id);
+    let groups = group_members::table.filter(group_members::user_id.eq(user_id)).select(group_members::group_id);
+    let inherited = group_roles::table.filter(group_roles::group_id.eq_any(groups)).select(group_roles::role_id);
+
+    Ok(
+      roles::table
+        .filter(roles::id.eq_any(direct).or(roles::id.eq_any(inherited)))
+        .order(roles::name.asc())
+        .load(conn)?
+    )
+  }
+
+  /// Every role assigned to any of the groups, paired with the id of the group it is assigned to.
+  pub fn for_groups(conn: &PgConnection, group_ids: &[i32]) -> Result<Vec<(i32, Self)>, HeimdallrError> {
+    Ok(
+      group_roles::table
+        .inner_join(roles::table)
+        .filter(group_roles::group_id.eq_any(group_ids))
+        .select((group_roles::group_id, roles::all_columns))
+        .order((roles::name.asc(), group_roles::group_id.asc()))
+        .load(conn)?
+    )
+  }
+
+  pub fn update(conn: &PgConnection, id: i32, changes: &RoleChanges) -> Result<Option<Self>, HeimdallrError> {
+    if changes.is_empty() {
+      return Self::find(conn, id);
+    }
+
+    Ok(diesel::update(roles::table.find(id)).set(changes).get_result(conn).optional()?)
+  }
+
+  /// Deletes a role, returning whether it existed.
+  pub fn delete(conn: &PgConnection, id: i32) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(roles::table.find(id)).execute(conn)? > 0)
+  }
+
+  /// Assigns a role to a user; assigning it twice is a no-op.
+  pub fn assign(conn: &PgConnection, user_id: Uuid, role_id: i32) -> Result<(), HeimdallrError> {
+    diesel::insert_into(user_roles::table)
+      .values(&NewUserRole { user_id, role_id })
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    Ok(())
+  }
+
+  /// Removes a role from a user, returning whether it was assigned.
+  pub fn unassign(conn: &PgConnection, user_id: Uuid, role_id: i32) -> Result<bool, HeimdallrError> {
+    let target = user_roles::table.filter(user_roles::user_id.eq(user_id)).filter(user_roles::role_id.eq(role_id));
+    Ok(diesel::delete(target).execute(conn)? > 0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::models::{NewUser, User};
+  use crate::db::test_helpers;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_round_trip() -> Result<(), HeimdallrError> {
+    let conn = test_helpers::connection();
+    let user = User::create(&conn, &NewUser { username: "takara", email: None, password_hash: "hash" })?;
+    let role = Role::create(&conn, &NewRole { name: "auditor", description: "Reads reports", scopes: &["reports".to_owned()] })?;
+
+    Role::assign(&conn, user.id, role.id)?;
+    Role::assign(&conn, user.id, role.id)?;
+    assert_eq!(Role::for_user(&conn, user.id)?.into_iter().map(|role| role.name).collect::<Vec<_>>(), vec!["auditor"]);
+
+    let changes = RoleChanges { scopes: Some(vec![]), ..Default::default() };
+    assert_eq!(Role::update(&conn, role.id, &changes)?.unwrap().scopes, Vec::<String>::new());
+
+    assert!(Role::unassign(&conn, user.id, role.id)?);
+    assert!(!Role::unassign(&conn, user.id, role.id)?);
+    assert!(Role::delete(&conn, role.id)?);
+    assert!(Role::find_by_name(&conn, "auditor")?.is_none());
+    Ok(())
+  }
+}
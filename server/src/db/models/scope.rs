@@ -0,0 +1,75 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::scopes;
+use crate::error::*;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "scopes"]
+#[primary_key(name)]
+pub struct Scope {
+  pub name: String,
+  pub description: String,
+  pub restricted: bool,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "scopes"]
+pub struct NewScope<'a> {
+  pub name: &'a str,
+  pub description: &'a str,
+  pub restricted: bool
+}
+
+impl Scope {
+  /// Creates a scope or overwrites the description & restriction of an existing one.
+  pub fn upsert(conn: &PgConnection, scope: &NewScope) -> Result<Self, HeimdallrError> {
+    Ok(
+      diesel::insert_into(scopes::table)
+        .values(scope)
+        .on_conflict(scopes::name)
+        .do_update()
+        .set(scope)
+        .get_result(conn)?
+    )
+  }
+
+  pub fn all(conn: &PgConnection) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(scopes::table.order(scopes::name.asc()).load(conn)?)
+  }
+
+  /// Loads the given scopes; unknown names are silently skipped.
+  pub fn find_all(conn: &PgConnection, names: &[String]) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(scopes::table.filter(scopes::name.eq_any(names)).load(conn)?)
+  }
+
+  /// Deletes a scope, returning whether it existed.
+  pub fn delete(conn: &PgConnection, name: &str) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(scopes::table.find(name)).execute(conn)? > 0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::test_helpers;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_upsert_overwrites() -> Result<(), HeimdallrError> {
+    let conn = test_helpers::connection();
+
+    Scope::upsert(&conn, &NewScope { name: "reports", description: "Reports", restricted: false })?;
+    let scope = Scope::upsert(&conn, &NewScope { name: "reports", description: "Read reports", restricted: true })?;
+    assert_eq!((scope.description.as_str(), scope.restricted), ("Read reports", true));
+
+    let found = Scope::find_all(&conn, &["reports".to_owned(), "unknown".to_owned()])?;
+    assert_eq!(found.into_iter().map(|scope| scope.name).collect::<Vec<_>>(), vec!["reports"]);
+
+    assert!(Scope::delete(&conn, "reports")?);
+    assert!(!Scope::delete(&conn, "reports")?);
+    Ok(())
+  }
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::{clients, refresh_tokens, session_clients, sessions};
+use crate::error::*;
+use crate::oidc::Authentication;
+use super::Client;
+
+/// A browser single sign-on session; its id is the `sid` claim of the tokens issued within it.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "sessions"]
+pub struct Session {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub auth_time: NaiveDateTime,
+  pub amr: Vec<String>,
+  pub user_agent: Option<String>,
+  pub expires_at: NaiveDateTime,
+  pub ended_at: Option<NaiveDateTime>,
+  pub created_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "sessions"]
+pub struct NewSession<'a> {
+  pub user_id: Uuid,
+  pub auth_time: NaiveDateTime,
+  pub amr: &'a [String],
+  pub user_agent: Option<&'a str>,
+  pub expires_at: NaiveDateTime
+}
+
+impl Session {
+  pub fn is_active(&self) -> bool {
+    self.ended_at.is_none() && self.expires_at > Utc::now().naive_utc()
+  }
+
+  /// How the user authenticated when the session started.
+  pub fn authentication(&self) -> Authentication {
+    Authentication {
+      auth_time: DateTime::<Utc>::from_utc(self.auth_time, Utc),
+      amr: self.amr.clone(),
+      session_id: Some(self.id)
+    }
+  }
+
+  pub fn create(conn: &PgConnection, new_session: &NewSession) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(sessions::table).values(new_session).get_result(conn)?)
+  }
+
+  /// Looks up a session that has neither ended nor expired.
+  pub fn find_active(conn: &PgConnection, id: Uuid) -> Result<Option<Self>, HeimdallrError> {
+    let session: Option<Self> = sessions::table.find(id).first(conn).optional()?;
+    Ok(session.filter(Session::is_active))
+  }
+
+  /// Sessions of a user that have neither ended nor expired.
+  pub fn active_for_user(conn: &PgConnection, user_id: Uuid) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(
+      sessions::table
+        .filter(sessions::user_id.eq(user_id))
+        .filter(sessions::ended_at.is_null())
+        .filter(sessions::expires_at.gt(Utc::now().naive_utc()))
+        .load(conn)?
+    )
+  }
+
+  /// Remembers that a client was issued tokens within the session.
+  pub fn add_client(&self, conn: &PgConnection, client_id: &str) -> Result<(), HeimdallrError> {
+    diesel::insert_into(session_clients::table)
+      .values((session_clients::session_id.eq(self.id), session_clients::client_id.eq(client_id)))
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    Ok(())
+  }
+
+  /// Clients that were issued tokens within the session.
+  pub fn clients(&self, conn: &PgConnection) -> Result<Vec<Client>, HeimdallrError> {
+    Ok(
+      clients::table
+        .inner_join(session_clients::table)
+        .filter(session_clients::session_id.eq(self.id))
+        .select(clients::all_columns)
+        .load(conn)?
+    )
+  }
+
+  /// Ends the session & revokes the refresh tokens issued within it, returning `false` if it had
+  /// already ended.
+  pub fn end(&self, conn: &PgConnection) -> Result<bool, HeimdallrError> {
+    conn.transaction(|| {
+      let now = Utc::now().naive_utc();
+
+      let ended = diesel::update(self)
+        .filter(sessions::ended_at.is_null())
+        .set(sessions::ended_at.eq(Some(now)))
+        .execute(conn)?;
+
+      diesel::update(refresh_tokens::table.filter(refresh_tokens::session_id.eq(self.id)).filter(refresh_tokens::revoked_at.is_null()))
+        .set(refresh_tokens::revoked_at.eq(Some(now)))
+        .execute(conn)?;
+
+      Ok(ended > 0)
+    })
+  }
+}
@@ -0,0 +1,92 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::sms_codes;
+use crate::error::*;
+
+/// A one-time code sent to the phone of a user; only its hash is stored.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "sms_codes"]
+pub struct SmsCode {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  /// `verify_phone` or `mfa`.
+  pub purpose: String,
+  /// The number the code was sent to.
+  pub phone_number: String,
+  pub code_hash: String,
+  /// Failed guesses so far.
+  pub attempts: i32,
+  pub expires_at: NaiveDateTime,
+  pub used_at: Option<NaiveDateTime>,
+  pub created_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "sms_codes"]
+pub struct NewSmsCode<'a> {
+  pub user_id: Uuid,
+  pub purpose: &'a str,
+  pub phone_number: &'a str,
+  pub code_hash: &'a str,
+  pub expires_at: NaiveDateTime
+}
+
+impl SmsCode {
+  /// Stores a new code, which supersedes the outstanding ones of the user for the same purpose.
+  pub fn replace(conn: &PgConnection, new_code: &NewSmsCode) -> Result<Self, HeimdallrError> {
+    conn.transaction(|| {
+      diesel::update(
+        sms_codes::table
+          .filter(sms_codes::user_id.eq(new_code.user_id))
+          .filter(sms_codes::purpose.eq(new_code.purpose))
+          .filter(sms_codes::used_at.is_null())
+      )
+      .set(sms_codes::used_at.eq(Some(Utc::now().naive_utc())))
+      .execute(conn)?;
+
+      Ok(diesel::insert_into(sms_codes::table).values(new_code).get_result(conn)?)
+    })
+  }
+
+  /// The code a user was last sent for a purpose, unless it was used or has expired.
+  pub fn outstanding(conn: &PgConnection, user_id: Uuid, purpose: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(
+      sms_codes::table
+        .filter(sms_codes::user_id.eq(user_id))
+        .filter(sms_codes::purpose.eq(purpose))
+        .filter(sms_codes::used_at.is_null())
+        .filter(sms_codes::expires_at.gt(Utc::now().naive_utc()))
+        .order(sms_codes::created_at.desc())
+        .first(conn)
+        .optional()?
+    )
+  }
+
+  /// Counts a guess, returning `false` once the code has run out of attempts.
+  pub fn record_attempt(&self, conn: &PgConnection, max_attempts: i32) -> Result<bool, HeimdallrError> {
+    let updated = diesel::update(self)
+      .filter(sms_codes::used_at.is_null())
+      .filter(sms_codes::attempts.lt(max_attempts))
+      .set(sms_codes::attempts.eq(sms_codes::attempts + 1))
+      .execute(conn)?;
+
+    Ok(updated > 0)
+  }
+
+  /// Uses up the code, returning `false` if it was used concurrently.
+  pub fn redeem(&self, conn: &PgConnection) -> Result<bool, HeimdallrError> {
+    let updated = diesel::update(self)
+      .filter(sms_codes::used_at.is_null())
+      .set(sms_codes::used_at.eq(Some(Utc::now().naive_utc())))
+      .execute(conn)?;
+
+    Ok(updated > 0)
+  }
+
+  /// Forgets codes that can no longer be used.
+  pub fn delete_expired(conn: &PgConnection) -> Result<usize, HeimdallrError> {
+    Ok(diesel::delete(sms_codes::table.filter(sms_codes::expires_at.lt(Utc::now().naive_utc()))).execute(conn)?)
+  }
+}
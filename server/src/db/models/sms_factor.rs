@@ -0,0 +1,34 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::sms_factors;
+use crate::error::*;
+
+/// A user who gets a code on their verified phone number as their second factor.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "sms_factors"]
+#[primary_key(user_id)]
+pub struct SmsFactor {
+  pub user_id: Uuid,
+  pub created_at: NaiveDateTime
+}
+
+impl SmsFactor {
+  pub fn exists(conn: &PgConnection, user_id: Uuid) -> Result<bool, HeimdallrError> {
+    Ok(diesel::select(diesel::dsl::exists(sms_factors::table.find(user_id))).get_result(conn)?)
+  }
+
+  pub fn enable(conn: &PgConnection, user_id: Uuid) -> Result<(), HeimdallrError> {
+    diesel::insert_into(sms_factors::table)
+      .values(sms_factors::user_id.eq(user_id))
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    Ok(())
+  }
+
+  /// Turns the factor off, returning whether it was on.
+  pub fn delete(conn: &PgConnection, user_id: Uuid) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(sms_factors::table.find(user_id)).execute(conn)? > 0)
+  }
+}
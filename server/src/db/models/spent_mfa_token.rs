@@ -0,0 +1,62 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::spent_mfa_tokens;
+use crate::error::*;
+
+/// The `jti` of an MFA token that can no longer complete a login.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "spent_mfa_tokens"]
+#[primary_key(jti)]
+pub struct SpentMfaToken {
+  pub jti: Uuid,
+  /// When the token expires, after which it is rejected anyway & the row can go.
+  pub expires_at: NaiveDateTime
+}
+
+impl SpentMfaToken {
+  /// Records a token as spent, returning `false` if it already was.
+  pub fn spend(conn: &PgConnection, jti: Uuid, expires_at: NaiveDateTime) -> Result<bool, HeimdallrError> {
+    let inserted = diesel::insert_into(spent_mfa_tokens::table)
+      .values((spent_mfa_tokens::jti.eq(jti), spent_mfa_tokens::expires_at.eq(expires_at)))
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+
+    Ok(inserted > 0)
+  }
+
+  pub fn is_spent(conn: &PgConnection, jti: Uuid) -> Result<bool, HeimdallrError> {
+    Ok(diesel::select(diesel::dsl::exists(spent_mfa_tokens::table.find(jti))).get_result(conn)?)
+  }
+
+  /// Forgets tokens that have expired.
+  pub fn delete_expired(conn: &PgConnection) -> Result<usize, HeimdallrError> {
+    Ok(diesel::delete(spent_mfa_tokens::table.filter(spent_mfa_tokens::expires_at.lt(Utc::now().naive_utc()))).execute(conn)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::test_helpers;
+  use chrono::Duration;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_tokens_are_spent_once() -> Result<(), HeimdallrError> {
+    let conn    = test_helpers::connection();
+    let jti     = Uuid::new_v4();
+    let expired = Uuid::new_v4();
+
+    assert!(!SpentMfaToken::is_spent(&conn, jti)?);
+    assert!(SpentMfaToken::spend(&conn, jti, Utc::now().naive_utc() + Duration::minutes(5))?);
+    assert!(!SpentMfaToken::spend(&conn, jti, Utc::now().naive_utc() + Duration::minutes(5))?);
+    assert!(SpentMfaToken::is_spent(&conn, jti)?);
+
+    SpentMfaToken::spend(&conn, expired, Utc::now().naive_utc() - Duration::minutes(5))?;
+    assert_eq!(SpentMfaToken::delete_expired(&conn)?, 1);
+    assert!(SpentMfaToken::is_spent(&conn, jti)?);
+    Ok(())
+  }
+}
@@ -0,0 +1,71 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::totp_credentials;
+use crate::error::*;
+
+/// The authenticator app secret of a user.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "totp_credentials"]
+#[primary_key(user_id)]
+pub struct TotpCredential {
+  pub user_id: Uuid,
+  /// Base32 encoded, exactly as shown to the user during enrollment.
+  pub secret: String,
+  pub confirmed_at: Option<NaiveDateTime>,
+  pub last_used_step: Option<i64>,
+  pub created_at: NaiveDateTime
+}
+
+impl TotpCredential {
+  /// Only confirmed secrets are asked for when logging in.
+  pub fn is_confirmed(&self) -> bool {
+    self.confirmed_at.is_some()
+  }
+
+  pub fn find(conn: &PgConnection, user_id: Uuid) -> Result<Option<Self>, HeimdallrError> {
+    Ok(totp_credentials::table.find(user_id).first(conn).optional()?)
+  }
+
+  /// Starts an enrollment, replacing any unconfirmed secret from an earlier attempt.
+  pub fn enroll(conn: &PgConnection, user_id: Uuid, secret: &str) -> Result<Self, HeimdallrError> {
+    Ok(
+      diesel::insert_into(totp_credentials::table)
+        .values((totp_credentials::user_id.eq(user_id), totp_credentials::secret.eq(secret)))
+        .on_conflict(totp_credentials::user_id)
+        .do_update()
+        .set((
+          totp_credentials::secret.eq(secret),
+          totp_credentials::confirmed_at.eq(None::<NaiveDateTime>),
+          totp_credentials::last_used_step.eq(None::<i64>),
+          totp_credentials::created_at.eq(Utc::now().naive_utc())
+        ))
+        .get_result(conn)?
+    )
+  }
+
+  pub fn confirm(&self, conn: &PgConnection) -> Result<Self, HeimdallrError> {
+    Ok(
+      diesel::update(self)
+        .set(totp_credentials::confirmed_at.eq(Some(Utc::now().naive_utc())))
+        .get_result(conn)?
+    )
+  }
+
+  /// Records that the code of a time step was used, returning `false` if a code of this or a
+  /// later step was already accepted (i.e. the code is being replayed).
+  pub fn use_step(&self, conn: &PgConnection, step: i64) -> Result<bool, HeimdallrError> {
+    let updated = diesel::update(self)
+      .filter(totp_credentials::last_used_step.is_null().or(totp_credentials::last_used_step.lt(step)))
+      .set(totp_credentials::last_used_step.eq(Some(step)))
+      .execute(conn)?;
+
+    Ok(updated > 0)
+  }
+
+  /// Removes the secret of a user, returning whether there was one.
+  pub fn delete(conn: &PgConnection, user_id: Uuid) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(totp_credentials::table.find(user_id)).execute(conn)? > 0)
+  }
+}
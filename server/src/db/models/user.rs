@@ -0,0 +1,141 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::users;
+use crate::error::*;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "users"]
+pub struct User {
+  pub id: Uuid,
+  pub username: String,
+  pub email: Option<String>,
+  pub password_hash: String,
+  pub disabled: bool,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime,
+  pub name: Option<String>,
+  pub given_name: Option<String>,
+  pub family_name: Option<String>,
+  pub picture: Option<String>,
+  pub locale: Option<String>,
+  pub email_verified: bool,
+  pub phone_number: Option<String>,
+  pub phone_number_verified: bool,
+  pub password_changed_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "users"]
+pub struct NewUser<'a> {
+  pub username: &'a str,
+  pub email: Option<&'a str>,
+  pub password_hash: &'a str
+}
+
+/// Partial update of a user; `None` leaves the column untouched.
+#[derive(Debug, Default, AsChangeset)]
+#[table_name = "users"]
+pub struct UserChanges {
+  pub username: Option<String>,
+  pub email: Option<Option<String>>,
+  pub password_hash: Option<String>,
+  pub disabled: Option<bool>,
+  pub name: Option<Option<String>>,
+  pub given_name: Option<Option<String>>,
+  pub family_name: Option<Option<String>>,
+  pub picture: Option<Option<String>>,
+  pub locale: Option<Option<String>>,
+  pub email_verified: Option<bool>,
+  pub phone_number: Option<Option<String>>,
+  pub phone_number_verified: Option<bool>,
+  pub password_changed_at: Option<NaiveDateTime>
+}
+
+impl UserChanges {
+  /// Replaces the password hash, restarting the clock on password expiry.
+  pub fn password(password_hash: String) -> Self {
+    UserChanges {
+      password_hash: Some(password_hash),
+      password_changed_at: Some(Utc::now().naive_utc()),
+      ..Default::default()
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.username.is_none() && self.email.is_none() && self.password_hash.is_none() && self.disabled.is_none()
+      && self.name.is_none() && self.given_name.is_none() && self.family_name.is_none() && self.picture.is_none()
+      && self.locale.is_none() && self.email_verified.is_none() && self.phone_number.is_none() && self.phone_number_verified.is_none()
+      && self.password_changed_at.is_none()
+  }
+}
+
+/// Usernames & emails are compared case insensitively, so they are stored lowercased.
+pub fn normalize(value: &str) -> String {
+  value.trim().to_lowercase()
+}
+
+impl User {
+  pub fn create(conn: &PgConnection, new_user: &NewUser) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(users::table).values(new_user).get_result(conn)?)
+  }
+
+  pub fn find(conn: &PgConnection, id: Uuid) -> Result<Option<Self>, HeimdallrError> {
+    Ok(users::table.find(id).first(conn).optional()?)
+  }
+
+  pub fn find_by_username(conn: &PgConnection, username: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(users::table.filter(users::username.eq(normalize(username))).first(conn).optional()?)
+  }
+
+  pub fn find_by_email(conn: &PgConnection, email: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(users::table.filter(users::email.eq(normalize(email))).first(conn).optional()?)
+  }
+
+  pub fn list(conn: &PgConnection, limit: i64, offset: i64) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(users::table.order(users::username.asc()).limit(limit).offset(offset).load(conn)?)
+  }
+
+  pub fn count(conn: &PgConnection) -> Result<i64, HeimdallrError> {
+    Ok(users::table.count().get_result(conn)?)
+  }
+
+  pub fn update(conn: &PgConnection, id: Uuid, changes: &UserChanges) -> Result<Option<Self>, HeimdallrError> {
+    if changes.is_empty() {
+      return Self::find(conn, id);
+    }
+
+    Ok(diesel::update(users::table.find(id)).set(changes).get_result(conn).optional()?)
+  }
+
+  /// Deletes a user, returning whether it existed.
+  pub fn delete(conn: &PgConnection, id: Uuid) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(users::table.find(id)).execute(conn)? > 0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::test_helpers;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_round_trip() -> Result<(), HeimdallrError> {
+    let conn = test_helpers::connection();
+    let user = User::create(&conn, &NewUser { username: "takara", email: Some("takara@example.com"), password_hash: "hash" })?;
+
+    assert_eq!(User::find_by_username(&conn, " Takara ")?.map(|found| found.id), Some(user.id));
+    assert_eq!(User::find_by_email(&conn, "TAKARA@example.com")?.map(|found| found.id), Some(user.id));
+
+    let changes = UserChanges { email: Some(None), disabled: Some(true), ..Default::default() };
+    let updated = User::update(&conn, user.id, &changes)?.unwrap();
+    assert_eq!((updated.email, updated.disabled), (None, true));
+
+    assert!(User::delete(&conn, user.id)?);
+    assert!(User::find(&conn, user.id)?.is_none());
+    assert!(!User::delete(&conn, user.id)?);
+    Ok(())
+  }
+}
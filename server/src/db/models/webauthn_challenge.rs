@@ -0,0 +1,59 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::webauthn_challenges;
+use crate::error::*;
+
+/// An outstanding WebAuthn ceremony.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "webauthn_challenges"]
+pub struct WebauthnChallenge {
+  pub id: Uuid,
+  /// Base64url encoded, exactly as the client data echoes it back.
+  pub challenge: String,
+  /// `registration` or `authentication`.
+  pub ceremony: String,
+  /// The user the ceremony is for; unknown for passwordless logins with discoverable credentials.
+  pub user_id: Option<Uuid>,
+  /// The client a login ceremony was started by.
+  pub client_id: Option<String>,
+  pub user_verification: String,
+  pub expires_at: NaiveDateTime,
+  pub created_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "webauthn_challenges"]
+pub struct NewWebauthnChallenge<'a> {
+  pub challenge: &'a str,
+  pub ceremony: &'a str,
+  pub user_id: Option<Uuid>,
+  pub client_id: Option<&'a str>,
+  pub user_verification: &'a str,
+  pub expires_at: NaiveDateTime
+}
+
+impl WebauthnChallenge {
+  pub fn is_expired(&self) -> bool {
+    self.expires_at <= Utc::now().naive_utc()
+  }
+
+  pub fn create(conn: &PgConnection, new_challenge: &NewWebauthnChallenge) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(webauthn_challenges::table).values(new_challenge).get_result(conn)?)
+  }
+
+  /// Removes a challenge & returns it, so that every challenge is answered at most once.
+  pub fn take(conn: &PgConnection, id: Uuid, ceremony: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(
+      diesel::delete(webauthn_challenges::table.find(id).filter(webauthn_challenges::ceremony.eq(ceremony)))
+        .get_result(conn)
+        .optional()?
+    )
+  }
+
+  /// Forgets ceremonies that were never completed.
+  pub fn delete_expired(conn: &PgConnection) -> Result<usize, HeimdallrError> {
+    Ok(diesel::delete(webauthn_challenges::table.filter(webauthn_challenges::expires_at.lt(Utc::now().naive_utc()))).execute(conn)?)
+  }
+}
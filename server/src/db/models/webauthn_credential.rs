@@ -0,0 +1,87 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::webauthn_credentials;
+use crate::error::*;
+
+/// A passkey or security key registered by a user.
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[table_name = "webauthn_credentials"]
+pub struct WebauthnCredential {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  /// Base64url encoded, as it appears in `allowCredentials` & assertions.
+  pub credential_id: String,
+  /// COSE encoded public key.
+  pub public_key: Vec<u8>,
+  pub sign_count: i64,
+  pub attestation_format: String,
+  pub name: String,
+  pub last_used_at: Option<NaiveDateTime>,
+  pub created_at: NaiveDateTime
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "webauthn_credentials"]
+pub struct NewWebauthnCredential<'a> {
+  pub user_id: Uuid,
+  pub credential_id: &'a str,
+  pub public_key: &'a [u8],
+  pub sign_count: i64,
+  pub attestation_format: &'a str,
+  pub name: &'a str
+}
+
+impl WebauthnCredential {
+  pub fn create(conn: &PgConnection, new_credential: &NewWebauthnCredential) -> Result<Self, HeimdallrError> {
+    Ok(diesel::insert_into(webauthn_credentials::table).values(new_credential).get_result(conn)?)
+  }
+
+  pub fn find_by_credential_id(conn: &PgConnection, credential_id: &str) -> Result<Option<Self>, HeimdallrError> {
+    Ok(webauthn_credentials::table.filter(webauthn_credentials::credential_id.eq(credential_id)).first(conn).optional()?)
+  }
+
+  pub fn for_user(conn: &PgConnection, user_id: Uuid) -> Result<Vec<Self>, HeimdallrError> {
+    Ok(
+      webauthn_credentials::table
+        .filter(webauthn_credentials::user_id.eq(user_id))
+        .order(webauthn_credentials::created_at.asc())
+        .load(conn)?
+    )
+  }
+
+  pub fn exists_for_user(conn: &PgConnection, user_id: Uuid) -> Result<bool, HeimdallrError> {
+    use diesel::dsl::exists;
+    Ok(diesel::select(exists(webauthn_credentials::table.filter(webauthn_credentials::user_id.eq(user_id)))).get_result(conn)?)
+  }
+
+  /// Records a successful assertion, returning `false` if another assertion with the same or a
+  /// higher signature counter got there first.
+  pub fn record_use(&self, conn: &PgConnection, sign_count: i64) -> Result<bool, HeimdallrError> {
+    let mut update = diesel::update(self)
+      .set((
+        webauthn_credentials::sign_count.eq(sign_count),
+        webauthn_credentials::last_used_at.eq(Some(Utc::now().naive_utc()))
+      ))
+      .into_boxed();
+
+    // Authenticators that do not implement a counter always report 0.
+    if sign_count > 0 {
+      update = update.filter(webauthn_credentials::sign_count.lt(sign_count));
+    }
+
+    let updated = update.execute(conn)?;
+
+    Ok(updated > 0)
+  }
+
+  /// Removes a credential of a user, returning whether there was one.
+  pub fn delete(conn: &PgConnection, user_id: Uuid, id: Uuid) -> Result<bool, HeimdallrError> {
+    Ok(diesel::delete(webauthn_credentials::table.find(id).filter(webauthn_credentials::user_id.eq(user_id))).execute(conn)? > 0)
+  }
+
+  pub fn delete_all(conn: &PgConnection, user_id: Uuid) -> Result<usize, HeimdallrError> {
+    Ok(diesel::delete(webauthn_credentials::table.filter(webauthn_credentials::user_id.eq(user_id))).execute(conn)?)
+  }
+}
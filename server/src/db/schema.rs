@@ -1,39 +1,1489 @@
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `authorization_codes` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    authorization_codes (id) {
+        /// The `id` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `code_hash` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_hash -> Varchar,
+        /// The `client_id` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        client_id -> Varchar,
+        /// The `user_id` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `redirect_uri` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        redirect_uri -> Varchar,
+        /// The `scopes` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        scopes -> Array<Text>,
+        /// The `nonce` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        nonce -> Nullable<Varchar>,
+        /// The `code_challenge` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_challenge -> Nullable<Varchar>,
+        /// The `auth_time` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        auth_time -> Timestamp,
+        /// The `amr` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        amr -> Array<Text>,
+        /// The `expires_at` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+        /// The `used_at` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        used_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `session_id` column of the `authorization_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        session_id -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `bootstrap` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    bootstrap (id) {
+        /// The `id` column of the `bootstrap` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Bool,
+        /// The `token_hash` column of the `bootstrap` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_hash -> Nullable<Varchar>,
+        /// The `token_created_at` column of the `bootstrap` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_created_at -> Nullable<Timestamp>,
+        /// The `completed_at` column of the `bootstrap` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        completed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `clients` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    clients (id) {
+        /// The `id` column of the `clients` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Varchar,
+        /// The `name` column of the `clients` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `secret_hash` column of the `clients` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        secret_hash -> Nullable<Varchar>,
+        /// The `redirect_uris` column of the `clients` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        redirect_uris -> Array<Text>,
+        /// The `grant_types` column of the `clients` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        grant_types -> Array<Text>,
+        /// The `scopes` column of the `clients` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        scopes -> Array<Text>,
+        /// The `created_at` column of the `clients` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `clients` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `allowed_origins` column of the `clients` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        allowed_origins -> Array<Text>,
+        /// The `post_logout_redirect_uris` column of the `clients` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        post_logout_redirect_uris -> Array<Text>,
+        /// The `backchannel_logout_uri` column of the `clients` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        backchannel_logout_uri -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `consents` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    consents (user_id, client_id) {
+        /// The `user_id` column of the `consents` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `client_id` column of the `consents` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        client_id -> Varchar,
+        /// The `scopes` column of the `consents` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        scopes -> Array<Text>,
+        /// The `created_at` column of the `consents` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `consents` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `email_tokens` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    email_tokens (id) {
+        /// The `id` column of the `email_tokens` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `token_hash` column of the `email_tokens` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_hash -> Varchar,
+        /// The `purpose` column of the `email_tokens` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        purpose -> Varchar,
+        /// The `user_id` column of the `email_tokens` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `email` column of the `email_tokens` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email -> Varchar,
+        /// The `expires_at` column of the `email_tokens` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+        /// The `used_at` column of the `email_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        used_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `email_tokens` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `group_members` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    group_members (group_id, user_id) {
+        /// The `group_id` column of the `group_members` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        group_id -> Int4,
+        /// The `user_id` column of the `group_members` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `created_at` column of the `group_members` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `group_roles` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    group_roles (group_id, role_id) {
+        /// The `group_id` column of the `group_roles` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        group_id -> Int4,
+        /// The `role_id` column of the `group_roles` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role_id -> Int4,
+        /// The `created_at` column of the `group_roles` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `groups` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    groups (id) {
+        /// The `id` column of the `groups` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `name` column of the `groups` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `description` column of the `groups` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Text,
+        /// The `created_at` column of the `groups` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `groups` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `invitations` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    invitations (id) {
+        /// The `id` column of the `invitations` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `code_hash` column of the `invitations` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_hash -> Varchar,
+        /// The `description` column of the `invitations` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Text,
+        /// The `role_ids` column of the `invitations` table.
+        ///
+        /// Its SQL type is `Array<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role_ids -> Array<Int4>,
+        /// The `max_uses` column of the `invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        max_uses -> Int4,
+        /// The `uses` column of the `invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        uses -> Int4,
+        /// The `expires_at` column of the `invitations` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `invitations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
 
     /// Representation of the `keys` table.
     ///
     /// (Automatically generated by Diesel.)
-    keys (id) {
-        /// The `id` column of the `keys` table.
+    keys (id) {
+        /// The `id` column of the `keys` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `created_at` column of the `keys` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `keys` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `kid` column of the `keys` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kid -> Varchar,
+        /// The `algorithm` column of the `keys` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        algorithm -> Varchar,
+        /// The `private_key` column of the `keys` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        private_key -> Text,
+        /// The `public_key` column of the `keys` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        public_key -> Text,
+        /// The `status` column of the `keys` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Varchar,
+        /// The `activated_at` column of the `keys` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        activated_at -> Nullable<Timestamp>,
+        /// The `retired_at` column of the `keys` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        retired_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `login_failures` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    login_failures (key) {
+        /// The `key` column of the `login_failures` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        key -> Varchar,
+        /// The `failures` column of the `login_failures` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        failures -> Int4,
+        /// The `last_failed_at` column of the `login_failures` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_failed_at -> Timestamp,
+        /// The `locked_until` column of the `login_failures` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locked_until -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `mail_deliveries` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    mail_deliveries (id) {
+        /// The `id` column of the `mail_deliveries` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `recipient` column of the `mail_deliveries` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        recipient -> Varchar,
+        /// The `purpose` column of the `mail_deliveries` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        purpose -> Varchar,
+        /// The `created_at` column of the `mail_deliveries` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `channel` column of the `mail_deliveries` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        channel -> Varchar,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `permissions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    permissions (id) {
+        /// The `id` column of the `permissions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `name` column of the `permissions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `description` column of the `permissions` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Text,
+        /// The `created_at` column of the `permissions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `permissions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `previous_passwords` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    previous_passwords (id) {
+        /// The `id` column of the `previous_passwords` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `user_id` column of the `previous_passwords` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `password_hash` column of the `previous_passwords` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        password_hash -> Varchar,
+        /// The `created_at` column of the `previous_passwords` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `rate_limit_buckets` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    rate_limit_buckets (key) {
+        /// The `key` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        key -> Varchar,
+        /// The `tokens` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Float8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tokens -> Float8,
+        /// The `allowed` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        allowed -> Bool,
+        /// The `updated_at` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `recovery_codes` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    recovery_codes (id) {
+        /// The `id` column of the `recovery_codes` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `user_id` column of the `recovery_codes` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `code_hash` column of the `recovery_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_hash -> Varchar,
+        /// The `used_at` column of the `recovery_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        used_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `recovery_codes` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `refresh_tokens` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    refresh_tokens (id) {
+        /// The `id` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `token_hash` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_hash -> Varchar,
+        /// The `client_id` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        client_id -> Varchar,
+        /// The `user_id` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Nullable<Uuid>,
+        /// The `scopes` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        scopes -> Array<Text>,
+        /// The `auth_time` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        auth_time -> Timestamp,
+        /// The `amr` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        amr -> Array<Text>,
+        /// The `expires_at` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+        /// The `revoked_at` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        revoked_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `session_id` column of the `refresh_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        session_id -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `role_permissions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    role_permissions (role_id, permission_id) {
+        /// The `role_id` column of the `role_permissions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role_id -> Int4,
+        /// The `permission_id` column of the `role_permissions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        permission_id -> Int4,
+        /// The `created_at` column of the `role_permissions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `roles` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    roles (id) {
+        /// The `id` column of the `roles` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `name` column of the `roles` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `description` column of the `roles` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Text,
+        /// The `scopes` column of the `roles` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        scopes -> Array<Text>,
+        /// The `created_at` column of the `roles` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `roles` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `scopes` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    scopes (name) {
+        /// The `name` column of the `scopes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `description` column of the `scopes` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Text,
+        /// The `restricted` column of the `scopes` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        restricted -> Bool,
+        /// The `created_at` column of the `scopes` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `scopes` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `session_clients` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    session_clients (session_id, client_id) {
+        /// The `session_id` column of the `session_clients` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        session_id -> Uuid,
+        /// The `client_id` column of the `session_clients` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        client_id -> Varchar,
+        /// The `created_at` column of the `session_clients` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `sessions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    sessions (id) {
+        /// The `id` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `user_id` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `auth_time` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        auth_time -> Timestamp,
+        /// The `amr` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        amr -> Array<Text>,
+        /// The `user_agent` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_agent -> Nullable<Varchar>,
+        /// The `expires_at` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+        /// The `ended_at` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        ended_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `sms_codes` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    sms_codes (id) {
+        /// The `id` column of the `sms_codes` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `user_id` column of the `sms_codes` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `purpose` column of the `sms_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        purpose -> Varchar,
+        /// The `phone_number` column of the `sms_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        phone_number -> Varchar,
+        /// The `code_hash` column of the `sms_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_hash -> Varchar,
+        /// The `attempts` column of the `sms_codes` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        id -> Int4,
-        /// The `primary` column of the `keys` table.
+        attempts -> Int4,
+        /// The `expires_at` column of the `sms_codes` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+        /// The `used_at` column of the `sms_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        used_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `sms_codes` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `sms_factors` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    sms_factors (user_id) {
+        /// The `user_id` column of the `sms_factors` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `created_at` column of the `sms_factors` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `spent_mfa_tokens` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    spent_mfa_tokens (jti) {
+        /// The `jti` column of the `spent_mfa_tokens` table.
         ///
         /// Its SQL type is `Uuid`.
         ///
         /// (Automatically generated by Diesel.)
-        primary -> Uuid,
-        /// The `secondary` column of the `keys` table.
+        jti -> Uuid,
+        /// The `expires_at` column of the `spent_mfa_tokens` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `totp_credentials` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    totp_credentials (user_id) {
+        /// The `user_id` column of the `totp_credentials` table.
         ///
         /// Its SQL type is `Uuid`.
         ///
         /// (Automatically generated by Diesel.)
-        secondary -> Uuid,
-        /// The `created_at` column of the `keys` table.
+        user_id -> Uuid,
+        /// The `secret` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        secret -> Varchar,
+        /// The `confirmed_at` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        confirmed_at -> Nullable<Timestamp>,
+        /// The `last_used_step` column of the `totp_credentials` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_used_step -> Nullable<Int8>,
+        /// The `created_at` column of the `totp_credentials` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
         created_at -> Timestamp,
-        /// The `updated_at` column of the `keys` table.
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `user_roles` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    user_roles (user_id, role_id) {
+        /// The `user_id` column of the `user_roles` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `role_id` column of the `user_roles` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role_id -> Int4,
+        /// The `created_at` column of the `user_roles` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `users` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    users (id) {
+        /// The `id` column of the `users` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `username` column of the `users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        username -> Varchar,
+        /// The `email` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email -> Nullable<Varchar>,
+        /// The `password_hash` column of the `users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        password_hash -> Varchar,
+        /// The `disabled` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        disabled -> Bool,
+        /// The `created_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `users` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
         updated_at -> Timestamp,
+        /// The `name` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Nullable<Varchar>,
+        /// The `given_name` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        given_name -> Nullable<Varchar>,
+        /// The `family_name` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        family_name -> Nullable<Varchar>,
+        /// The `picture` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        picture -> Nullable<Varchar>,
+        /// The `locale` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locale -> Nullable<Varchar>,
+        /// The `email_verified` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email_verified -> Bool,
+        /// The `phone_number` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        phone_number -> Nullable<Varchar>,
+        /// The `phone_number_verified` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        phone_number_verified -> Bool,
+        /// The `password_changed_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        password_changed_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `webauthn_challenges` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    webauthn_challenges (id) {
+        /// The `id` column of the `webauthn_challenges` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `challenge` column of the `webauthn_challenges` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        challenge -> Varchar,
+        /// The `ceremony` column of the `webauthn_challenges` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        ceremony -> Varchar,
+        /// The `user_id` column of the `webauthn_challenges` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Nullable<Uuid>,
+        /// The `client_id` column of the `webauthn_challenges` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        client_id -> Nullable<Varchar>,
+        /// The `user_verification` column of the `webauthn_challenges` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_verification -> Varchar,
+        /// The `expires_at` column of the `webauthn_challenges` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+        /// The `created_at` column of the `webauthn_challenges` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    /// Representation of the `webauthn_credentials` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    webauthn_credentials (id) {
+        /// The `id` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Uuid,
+        /// The `user_id` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `credential_id` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        credential_id -> Varchar,
+        /// The `public_key` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Bytea`.
+        ///
+        /// (Automatically generated by Diesel.)
+        public_key -> Bytea,
+        /// The `sign_count` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sign_count -> Int8,
+        /// The `attestation_format` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        attestation_format -> Varchar,
+        /// The `name` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `last_used_at` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_used_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `webauthn_credentials` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
     }
 }
+
+joinable!(authorization_codes -> clients (client_id));
+joinable!(authorization_codes -> sessions (session_id));
+joinable!(authorization_codes -> users (user_id));
+joinable!(consents -> clients (client_id));
+joinable!(consents -> users (user_id));
+joinable!(email_tokens -> users (user_id));
+joinable!(group_members -> groups (group_id));
+joinable!(group_members -> users (user_id));
+joinable!(group_roles -> groups (group_id));
+joinable!(group_roles -> roles (role_id));
+joinable!(previous_passwords -> users (user_id));
+joinable!(recovery_codes -> users (user_id));
+joinable!(refresh_tokens -> clients (client_id));
+joinable!(refresh_tokens -> sessions (session_id));
+joinable!(refresh_tokens -> users (user_id));
+joinable!(role_permissions -> permissions (permission_id));
+joinable!(role_permissions -> roles (role_id));
+joinable!(session_clients -> clients (client_id));
+joinable!(session_clients -> sessions (session_id));
+joinable!(sessions -> users (user_id));
+joinable!(sms_codes -> users (user_id));
+joinable!(sms_factors -> users (user_id));
+joinable!(totp_credentials -> users (user_id));
+joinable!(user_roles -> roles (role_id));
+joinable!(user_roles -> users (user_id));
+joinable!(webauthn_challenges -> clients (client_id));
+joinable!(webauthn_challenges -> users (user_id));
+joinable!(webauthn_credentials -> users (user_id));
+
+allow_tables_to_appear_in_same_query!(
+    authorization_codes,
+    bootstrap,
+    clients,
+    consents,
+    email_tokens,
+    group_members,
+    group_roles,
+    groups,
+    invitations,
+    keys,
+    login_failures,
+    mail_deliveries,
+    permissions,
+    previous_passwords,
+    rate_limit_buckets,
+    recovery_codes,
+    refresh_tokens,
+    role_permissions,
+    roles,
+    scopes,
+    session_clients,
+    sessions,
+    sms_codes,
+    sms_factors,
+    spent_mfa_tokens,
+    totp_credentials,
+    user_roles,
+    users,
+    webauthn_challenges,
+    webauthn_credentials,
+);
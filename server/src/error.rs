@@ -7,7 +7,21 @@ pub enum HeimdallrError {
   IOError(std::io::Error),
   DatabaseConnectionError(diesel::ConnectionError),
   R2D2Error(r2d2::Error),
-  JwtError(&'static str)
+  DatabaseError(diesel::result::Error),
+  OpenSSLError(openssl::error::ErrorStack),
+  PasswordHashError(argon2::Error),
+  JwtError(&'static str),
+  KeyError(String),
+  JsonError(serde_json::Error),
+  BootstrapError(&'static str),
+  PasswordRejected(Vec<crate::password::Violation>),
+  TokenError(crate::jwt::TokenError),
+  HttpError(hyper::Error),
+  HttpClientError(reqwest::Error),
+  TemplateError(String),
+  MailError(String),
+  SmsError(String),
+  RbacError(String)
 }
 
 impl Error for HeimdallrError {}
@@ -22,7 +36,24 @@ impl fmt::Display for HeimdallrError {
       IOError(err)                 => write!(f, "IO error ({})", err),
       DatabaseConnectionError(err) => write!(f, "Database connection error ({})", err),
       R2D2Error(err)               => write!(f, "Database error ({})", err),
-      JwtError(err)                => write!(f, "JWT Error ({})", err)
+      DatabaseError(err)           => write!(f, "Database query error ({})", err),
+      OpenSSLError(err)            => write!(f, "OpenSSL error ({})", err),
+      PasswordHashError(err)       => write!(f, "Password hash error ({})", err),
+      JwtError(err)                => write!(f, "JWT Error ({})", err),
+      KeyError(err)                => write!(f, "Signing key error ({})", err),
+      JsonError(err)               => write!(f, "JSON error ({})", err),
+      BootstrapError(err)          => write!(f, "Bootstrap error ({})", err),
+      PasswordRejected(violations) => {
+        let reasons: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        write!(f, "Password rejected ({})", reasons.join("; "))
+      },
+      TokenError(err)              => write!(f, "Invalid token ({})", err),
+      HttpError(err)               => write!(f, "HTTP error ({})", err),
+      HttpClientError(err)         => write!(f, "HTTP request error ({})", err),
+      TemplateError(err)           => write!(f, "Template error ({})", err),
+      MailError(err)               => write!(f, "Mail delivery error ({})", err),
+      SmsError(err)                => write!(f, "SMS delivery error ({})", err),
+      RbacError(err)               => write!(f, "Access control error ({})", err)
     }
   }
 }
@@ -56,3 +87,57 @@ impl From<r2d2::Error> for HeimdallrError {
     HeimdallrError::R2D2Error(err)
   }
 }
+
+impl From<diesel::result::Error> for HeimdallrError {
+  fn from(err: diesel::result::Error) -> HeimdallrError {
+    HeimdallrError::DatabaseError(err)
+  }
+}
+
+impl From<openssl::error::ErrorStack> for HeimdallrError {
+  fn from(err: openssl::error::ErrorStack) -> HeimdallrError {
+    HeimdallrError::OpenSSLError(err)
+  }
+}
+
+impl From<serde_json::Error> for HeimdallrError {
+  fn from(err: serde_json::Error) -> HeimdallrError {
+    HeimdallrError::JsonError(err)
+  }
+}
+
+impl From<crate::jwt::TokenError> for HeimdallrError {
+  fn from(err: crate::jwt::TokenError) -> HeimdallrError {
+    HeimdallrError::TokenError(err)
+  }
+}
+
+impl From<hyper::Error> for HeimdallrError {
+  fn from(err: hyper::Error) -> HeimdallrError {
+    HeimdallrError::HttpError(err)
+  }
+}
+
+impl From<reqwest::Error> for HeimdallrError {
+  fn from(err: reqwest::Error) -> HeimdallrError {
+    HeimdallrError::HttpClientError(err)
+  }
+}
+
+impl From<argon2::Error> for HeimdallrError {
+  fn from(err: argon2::Error) -> HeimdallrError {
+    HeimdallrError::PasswordHashError(err)
+  }
+}
+
+impl From<handlebars::TemplateError> for HeimdallrError {
+  fn from(err: handlebars::TemplateError) -> HeimdallrError {
+    HeimdallrError::TemplateError(err.to_string())
+  }
+}
+
+impl From<handlebars::RenderError> for HeimdallrError {
+  fn from(err: handlebars::RenderError) -> HeimdallrError {
+    HeimdallrError::TemplateError(err.to_string())
+  }
+}
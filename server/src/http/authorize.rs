@@ -0,0 +1,137 @@
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+use crate::db::models::Client;
+use crate::oidc::PKCE_METHOD;
+use crate::services::error::ApiError;
+use super::form::{self, Form};
+use super::{pages, HttpContext};
+
+/// A validated authorization request (RFC 6749 §4.1.1, OpenID Connect Core §3.1.2.1).
+#[derive(Debug, Clone)]
+pub struct AuthorizationRequest {
+  pub client: Client,
+  pub redirect_uri: String,
+  /// Requested scopes, all allowed for the client; empty asks for the client's defaults. Which
+  /// ones the user may grant is decided by [`resolve_scopes`](crate::services::auth::resolve_scopes).
+  pub scopes: Vec<String>,
+  pub state: Option<String>,
+  pub nonce: Option<String>,
+  pub code_challenge: Option<String>,
+  pub prompt: Option<String>
+}
+
+/// Why an authorization request was rejected.
+#[derive(Debug)]
+pub enum AuthorizeError {
+  /// The client or redirect URI cannot be trusted, so the user is told instead of the client.
+  Invalid(ApiError),
+  /// Reported back to the client through its redirect URI.
+  Redirect { redirect_uri: String, state: Option<String>, error: Box<ApiError> }
+}
+
+impl From<ApiError> for AuthorizeError {
+  fn from(err: ApiError) -> AuthorizeError {
+    AuthorizeError::Invalid(err)
+  }
+}
+
+/// `GET|POST /authorize`: checks the request, then asks the user to sign in or approve it.
+pub async fn handle(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (headers, form) = if request.method() == Method::POST {
+    let (parts, form) = form::read_form(request).await?;
+    (parts.headers, form)
+  }
+  else {
+    let (parts, _) = request.into_parts();
+    let form = Form::parse(parts.uri.query().unwrap_or_default().as_bytes())?;
+    (parts.headers, form)
+  };
+
+  match validate(context, &form) {
+    Ok(request) => pages::authorize(context, &headers, &form, request),
+    Err(err)    => pages::error_page(context, &headers, err)
+  }
+}
+
+/// Checks an authorization request, in the order that decides how errors can be reported.
+pub fn validate(context: &HttpContext, form: &Form) -> Result<AuthorizationRequest, AuthorizeError> {
+  let conn = context.auth.database().pool.get().map_err(ApiError::from)?;
+
+  let client_id = form.require("client_id")?;
+  let client = Client::find(&conn, client_id)
+    .map_err(ApiError::from)?
+    .ok_or_else(|| ApiError::invalid_field("client_id", "unknown client"))?;
+
+  let redirect_uri = match form.get("redirect_uri") {
+    "" if client.redirect_uris.len() == 1 => client.redirect_uris[0].clone(),
+    ""                                    => return Err(ApiError::invalid_field("redirect_uri", "redirect_uri is required").into()),
+    uri if client.allows_redirect_uri(uri) => uri.to_owned(),
+    _                                     => return Err(ApiError::invalid_field("redirect_uri", "redirect_uri is not registered for this client").into())
+  };
+
+  // From here on the client is known & errors go back to it.
+  let state     = form.optional("state");
+  let to_client = |error: ApiError| AuthorizeError::Redirect { redirect_uri: redirect_uri.clone(), state: state.clone(), error: Box::new(error) };
+
+  if form.get("response_type") != "code" {
+    return Err(to_client(ApiError::unsupported_response_type("only the `code` response type is supported")));
+  }
+
+  if !client.allows_grant_type("authorization_code") {
+    return Err(to_client(ApiError::unauthorized_client("client is not allowed to use the authorization_code grant")));
+  }
+
+  let scopes = form.list("scope");
+  if let Some(unknown) = scopes.iter().find(|scope| !client.scopes.contains(scope)) {
+    return Err(to_client(ApiError::invalid_scope(format!("scope `{}` is not allowed for this client", unknown))));
+  }
+
+  let code_challenge = form.optional("code_challenge");
+  match (&code_challenge, form.get("code_challenge_method")) {
+    (None, _) if !client.is_confidential() => {
+      return Err(to_client(ApiError::invalid_request("public clients must use PKCE")));
+    },
+    (Some(_), method) if method != PKCE_METHOD => {
+      return Err(to_client(ApiError::invalid_request(format!("code_challenge_method must be {}", PKCE_METHOD))));
+    },
+    _ => ()
+  }
+
+  Ok(AuthorizationRequest {
+    redirect_uri: redirect_uri.clone(),
+    state: state.clone(),
+    scopes,
+    nonce: form.optional("nonce"),
+    code_challenge,
+    prompt: form.optional("prompt"),
+    client
+  })
+}
+
+/// Sends the user agent back to the client with the given query parameters.
+pub fn redirect(redirect_uri: &str, params: &[(&str, &str)]) -> Result<Response<Body>, ApiError> {
+  let mut url = url::Url::parse(redirect_uri).map_err(|_| ApiError::invalid_field("redirect_uri", "redirect_uri is not a valid URL"))?;
+  url.query_pairs_mut().extend_pairs(params);
+
+  let location = HeaderValue::from_str(url.as_str()).map_err(|_| ApiError::invalid_field("redirect_uri", "redirect_uri is not a valid URL"))?;
+
+  let mut response = Response::new(Body::empty());
+  *response.status_mut() = StatusCode::FOUND;
+  response.headers_mut().insert(header::LOCATION, location);
+  Ok(super::no_store(response))
+}
+
+/// Reports a rejected request: to the user when the client is not trusted, otherwise to the client.
+pub(crate) fn error_response(err: AuthorizeError) -> Result<Response<Body>, ApiError> {
+  match err {
+    AuthorizeError::Invalid(err) => Err(err),
+    AuthorizeError::Redirect { redirect_uri, state, error } => {
+      let mut params = vec![("error", error.code.as_str()), ("error_description", error.description.as_str())];
+      if let Some(state) = &state {
+        params.push(("state", state.as_str()));
+      }
+      redirect(&redirect_uri, &params)
+    }
+  }
+}
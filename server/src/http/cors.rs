@@ -0,0 +1,60 @@
+//! CORS for the gRPC-Web routes, the only ones browsers call from other origins. A preflight is
+//! answered for any origin some client lists in its `allowed_origins`; the call itself has to come
+//! from an origin the calling client lists, see `grpc_web::check_origin`.
+
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+
+use crate::db::models::Client;
+use crate::services::{self, error::ApiError};
+use super::HttpContext;
+
+const ALLOWED_METHODS: &str = "POST, OPTIONS";
+const ALLOWED_HEADERS: &str = "authorization, content-type, x-grpc-web, x-user-agent, grpc-timeout";
+const EXPOSED_HEADERS: &str = "grpc-status, grpc-message, grpc-status-details-bin, www-authenticate";
+const MAX_AGE: &str = "86400";
+
+/// Answers a preflight request, as long as some client allows the origin.
+pub async fn preflight(context: &HttpContext, request: &Request<Body>) -> Result<Response<Body>, ApiError> {
+  let origin = match request.headers().get(header::ORIGIN) {
+    Some(origin) => origin.clone(),
+    None         => return Err(ApiError::invalid_request("preflight requests need an Origin"))
+  };
+
+  if !origin_registered(context, &origin).await? {
+    return Err(ApiError::access_denied("origin is not allowed"));
+  }
+
+  let mut response = Response::new(Body::empty());
+  *response.status_mut() = StatusCode::NO_CONTENT;
+
+  let headers = response.headers_mut();
+  headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static(ALLOWED_METHODS));
+  headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static(ALLOWED_HEADERS));
+  headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static(MAX_AGE));
+
+  Ok(allow(response, origin))
+}
+
+/// Whether any client allows cross-origin requests from an origin.
+pub async fn origin_registered(context: &HttpContext, origin: &HeaderValue) -> Result<bool, ApiError> {
+  let origin = match origin.to_str() {
+    Ok(origin) => origin.to_owned(),
+    Err(_)     => return Ok(false)
+  };
+
+  let database = context.auth.database();
+  services::blocking(move || {
+    let conn = database.pool.get()?;
+    Ok(Client::origin_registered(&conn, &origin)?)
+  }).await
+}
+
+/// Lets the browser hand the response to the calling origin.
+pub fn allow(mut response: Response<Body>, origin: HeaderValue) -> Response<Body> {
+  let headers = response.headers_mut();
+  headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+  headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static(EXPOSED_HEADERS));
+  headers.append(header::VARY, HeaderValue::from_static("Origin"));
+  response
+}
@@ -0,0 +1,26 @@
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+
+use crate::services::error::ApiError;
+use super::HttpContext;
+
+/// Both documents change rarely, but key rotations should still be picked up quickly.
+const CACHE_CONTROL: &str = "public, max-age=300";
+
+/// `GET /.well-known/openid-configuration` (OpenID Connect Discovery §4).
+pub fn configuration(context: &HttpContext) -> Result<Response<Body>, ApiError> {
+  let discovery = context.auth.discovery()?;
+  let body      = serde_json::to_value(&discovery).map_err(|_| ApiError::server_error())?;
+  Ok(cacheable(super::json(StatusCode::OK, &body)))
+}
+
+/// `GET /.well-known/jwks.json`: the public keys tokens can be verified with.
+pub fn jwks(context: &HttpContext) -> Response<Body> {
+  let jwks = context.auth.issuer().keys().read().jwks();
+  cacheable(super::json(StatusCode::OK, &jwks))
+}
+
+fn cacheable(mut response: Response<Body>) -> Response<Body> {
+  response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL));
+  response
+}
@@ -0,0 +1,188 @@
+//! Form-encoded parameters & client authentication as used by the OAuth 2.0 endpoints.
+
+use hyper::header::{self, HeaderMap};
+use hyper::http::request::Parts;
+use hyper::{Body, Request};
+use percent_encoding::percent_decode_str;
+use std::collections::HashMap;
+
+use crate::services::error::ApiError;
+
+/// OAuth requests are tiny; anything bigger than this is rejected unread.
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+
+/// Request parameters, where every parameter may appear at most once (RFC 6749 §3.1).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Form(HashMap<String, String>);
+
+impl Form {
+  pub fn parse(input: &[u8]) -> Result<Self, ApiError> {
+    let mut params = HashMap::new();
+
+    for (key, value) in url::form_urlencoded::parse(input) {
+      if params.insert(key.to_string(), value.into_owned()).is_some() {
+        return Err(ApiError::invalid_field(key.to_string(), format!("`{}` must not be repeated", key)));
+      }
+    }
+
+    Ok(Form(params))
+  }
+
+  /// The value of a parameter; empty when missing, which OAuth treats the same way.
+  pub fn get(&self, name: &str) -> &str {
+    self.0.get(name).map(String::as_str).unwrap_or_default()
+  }
+
+  pub fn require(&self, name: &str) -> Result<&str, ApiError> {
+    match self.get(name) {
+      ""    => Err(ApiError::invalid_field(name, format!("{} is required", name))),
+      value => Ok(value)
+    }
+  }
+
+  /// A space delimited list, e.g. `scope`.
+  pub fn list(&self, name: &str) -> Vec<String> {
+    self.get(name).split(' ').filter(|value| !value.is_empty()).map(str::to_owned).collect()
+  }
+
+  pub fn optional(&self, name: &str) -> Option<String> {
+    Some(self.get(name)).filter(|value| !value.is_empty()).map(str::to_owned)
+  }
+
+  /// Replaces a parameter; an empty value removes it, which `get` treats the same anyway.
+  pub fn set(&mut self, name: &str, value: String) {
+    if value.is_empty() {
+      self.0.remove(name);
+    } else {
+      self.0.insert(name.to_owned(), value);
+    }
+  }
+
+  /// Encodes the parameters again, e.g. to carry a request through the login page.
+  pub fn encode(&self) -> String {
+    let mut keys: Vec<&String> = self.0.keys().collect();
+    keys.sort();
+
+    let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+    for key in keys {
+      serializer.append_pair(key, &self.0[key]);
+    }
+    serializer.finish()
+  }
+}
+
+/// Reads a form-encoded request body.
+pub async fn read_form(request: Request<Body>) -> Result<(Parts, Form), ApiError> {
+  let (parts, body) = request.into_parts();
+
+  let content_type = parts.headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or_default();
+  if !content_type.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case(FORM_CONTENT_TYPE) {
+    return Err(ApiError::invalid_request(format!("the request body must be {}", FORM_CONTENT_TYPE)));
+  }
+
+  let length = parts.headers.get(header::CONTENT_LENGTH).and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+  if length.map(|length| length > MAX_BODY_BYTES).unwrap_or(false) {
+    return Err(ApiError::invalid_request("request body is too large"));
+  }
+
+  let bytes = hyper::body::to_bytes(body).await.map_err(|_| ApiError::invalid_request("unable to read request body"))?;
+  if bytes.len() as u64 > MAX_BODY_BYTES {
+    return Err(ApiError::invalid_request("request body is too large"));
+  }
+
+  Ok((parts, Form::parse(&bytes)?))
+}
+
+/// The credentials a client presented.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientAuth {
+  pub client_id: String,
+  pub client_secret: String,
+  /// Whether HTTP Basic was used, in which case failures carry a `WWW-Authenticate` challenge.
+  pub basic: bool
+}
+
+/// Extracts client credentials from HTTP Basic (RFC 6749 §2.3.1) or the request body.
+pub fn client_auth(headers: &HeaderMap, form: &Form) -> Result<ClientAuth, ApiError> {
+  let authorization = headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()).unwrap_or_default();
+
+  if authorization.len() > 6 && authorization[..6].eq_ignore_ascii_case("basic ") {
+    if !form.get("client_secret").is_empty() {
+      return Err(ApiError::invalid_request("clients must use only one authentication method"));
+    }
+
+    let decoded = base64::decode(authorization[6..].trim()).ok().and_then(|bytes| String::from_utf8(bytes).ok());
+    let (client_id, client_secret) = match decoded.as_ref().and_then(|credentials| split_once(credentials, ':')) {
+      Some((id, secret)) => (form_decode(id)?, form_decode(secret)?),
+      None               => return Err(ApiError::invalid_client("malformed basic credentials"))
+    };
+
+    if !form.get("client_id").is_empty() && form.get("client_id") != client_id {
+      return Err(ApiError::invalid_request("client_id does not match the basic credentials"));
+    }
+
+    return Ok(ClientAuth { client_id, client_secret, basic: true });
+  }
+
+  Ok(ClientAuth {
+    client_id: form.require("client_id")?.to_owned(),
+    client_secret: form.get("client_secret").to_owned(),
+    basic: false
+  })
+}
+
+fn split_once(value: &str, separator: char) -> Option<(&str, &str)> {
+  let index = value.find(separator)?;
+  Some((&value[..index], &value[index + 1..]))
+}
+
+/// Basic credentials are form-encoded before being base64 encoded.
+fn form_decode(value: &str) -> Result<String, ApiError> {
+  percent_decode_str(&value.replace('+', " "))
+    .decode_utf8()
+    .map(|value| value.into_owned())
+    .map_err(|_| ApiError::invalid_client("malformed basic credentials"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use hyper::header::HeaderValue;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_repeated_parameters_are_rejected() {
+    assert!(Form::parse(b"scope=a&scope=b").is_err());
+    assert_eq!(Form::parse(b"scope=openid+email").unwrap().list("scope"), vec!["openid", "email"]);
+  }
+
+  #[test]
+  fn test_encode_round_trips() {
+    let form = Form::parse(b"state=a%26b&client_id=spa&scope=openid+email").unwrap();
+    assert_eq!(form.encode(), "client_id=spa&scope=openid+email&state=a%26b");
+    assert_eq!(Form::parse(form.encode().as_bytes()).unwrap(), form);
+  }
+
+  #[test]
+  fn test_basic_client_auth() {
+    let mut headers = HeaderMap::new();
+    let credentials = base64::encode("my%3Aclient:s3cr%2Bt");
+    headers.insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Basic {}", credentials)).unwrap());
+
+    let auth = client_auth(&headers, &Form::default()).unwrap();
+    assert_eq!(auth.client_id, "my:client");
+    assert_eq!(auth.client_secret, "s3cr+t");
+    assert!(auth.basic);
+  }
+
+  #[test]
+  fn test_form_client_auth() {
+    let form = Form::parse(b"client_id=spa").unwrap();
+    let auth = client_auth(&HeaderMap::new(), &form).unwrap();
+
+    assert_eq!(auth, ClientAuth { client_id: "spa".to_owned(), client_secret: String::new(), basic: false });
+  }
+}
@@ -0,0 +1,270 @@
+//! gRPC-Web (binary & text) for browser clients, translated in-process onto the `Login` service so
+//! no Envoy sidecar is needed.
+
+use bytes::{Bytes, BytesMut};
+use heimdallr_api::auth::{LoginRequest, SendMfaCodeRequest, StartPasskeyLoginRequest};
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::http::request::Parts;
+use hyper::{Body, Request, Response, StatusCode, Version};
+use prost::Message;
+use tonic::codegen::Service;
+use tonic::Status;
+
+use crate::db::models::Client;
+use crate::services::{self, error::ApiError};
+use super::{cors, HttpContext};
+
+/// Path prefix of every `heimdallr.auth.Login` method.
+pub const LOGIN_SERVICE: &str = "/heimdallr.auth.Login/";
+
+const GRPC_WEB: &str = "application/grpc-web";
+const GRPC_WEB_TEXT: &str = "application/grpc-web-text";
+
+/// Set on the frame carrying the trailers at the end of a gRPC-Web response body.
+const TRAILER_FLAG: u8 = 0x80;
+
+/// Set on frames carrying a compressed message.
+const COMPRESSED_FLAG: u8 = 0x01;
+
+/// Largest request body accepted, matching the default message size limit of gRPC.
+const MAX_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+pub fn is_grpc_web(request: &Request<Body>) -> bool {
+  content_type(request.headers()).starts_with(GRPC_WEB)
+}
+
+/// Forwards a gRPC-Web call to the `Login` service & encodes its response for the browser.
+pub async fn handle(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let text = content_type(request.headers()).starts_with(GRPC_WEB_TEXT);
+  let (parts, body) = request.into_parts();
+
+  let body = match read_body(&parts.headers, body).await {
+    Ok(body)    => body,
+    Err(status) => return Ok(status_response(text, &status))
+  };
+
+  match forward(context, parts, body, text).await {
+    Ok(response) => Ok(response),
+    Err(err)     => Ok(error_response(text, err))
+  }
+}
+
+/// Reads the request body, giving up as soon as it grows past [`MAX_BODY_BYTES`].
+async fn read_body(headers: &HeaderMap, mut body: Body) -> Result<Bytes, Status> {
+  let too_large = || Status::resource_exhausted(format!("request body is larger than {} bytes", MAX_BODY_BYTES));
+
+  let length = headers.get(header::CONTENT_LENGTH).and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+  if length.map(|length| length > MAX_BODY_BYTES).unwrap_or(false) {
+    return Err(too_large());
+  }
+
+  let mut bytes = BytesMut::new();
+  while let Some(chunk) = body.data().await {
+    let chunk = chunk.map_err(|_| Status::from(ApiError::invalid_request("unable to read request body")))?;
+    if bytes.len() + chunk.len() > MAX_BODY_BYTES {
+      return Err(too_large());
+    }
+    bytes.extend_from_slice(&chunk);
+  }
+
+  Ok(bytes.freeze())
+}
+
+async fn forward(context: &HttpContext, mut parts: Parts, body: Bytes, text: bool) -> Result<Response<Body>, ApiError> {
+  let body = if text {
+    Bytes::from(base64::decode(&body).map_err(|_| ApiError::invalid_request("malformed grpc-web-text body"))?)
+  }
+  else {
+    body
+  };
+
+  let message = single_message(&body)?;
+  let origin  = match parts.headers.get(header::ORIGIN) {
+    Some(origin) => Some(check_origin(context, parts.uri.path(), origin, message).await?),
+    None         => None
+  };
+
+  parts.version = Version::HTTP_2;
+  parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+  parts.headers.insert(header::TE, HeaderValue::from_static("trailers"));
+
+  let mut service = context.login.clone();
+  futures::future::poll_fn(|cx| service.poll_ready(cx)).await.map_err(|_| ApiError::server_error())?;
+  let response = service.call(Request::from_parts(parts, Body::from(body))).await.map_err(|_| ApiError::server_error())?;
+
+  let (mut parts, mut body) = response.into_parts();
+
+  let mut payload = BytesMut::new();
+  while let Some(chunk) = body.data().await {
+    payload.extend_from_slice(&chunk.map_err(|_| ApiError::server_error())?);
+  }
+  if let Some(trailers) = body.trailers().await.map_err(|_| ApiError::server_error())? {
+    payload.extend_from_slice(&trailer_frame(&trailers));
+  }
+
+  parts.version = Version::HTTP_11;
+  parts.headers.insert(header::CONTENT_TYPE, response_content_type(text));
+
+  let response = Response::from_parts(parts, Body::from(encode_body(text, &payload)));
+  Ok(match origin {
+    Some(origin) => cors::allow(response, origin),
+    None         => response
+  })
+}
+
+/// The message of a unary call, which has to be the whole body so that the message checked here
+/// is the one the service gets.
+fn single_message(body: &[u8]) -> Result<&[u8], ApiError> {
+  if body.len() < 5 {
+    return Err(ApiError::invalid_request("missing gRPC message frame"));
+  }
+  if body[0] & COMPRESSED_FLAG != 0 {
+    return Err(ApiError::invalid_request("compressed gRPC-Web messages are not supported"));
+  }
+
+  let length = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+  match body.len() - 5 {
+    rest if rest < length => Err(ApiError::invalid_request("truncated gRPC message frame")),
+    rest if rest > length => Err(ApiError::invalid_request("unary calls carry a single message")),
+    _                     => Ok(&body[5..])
+  }
+}
+
+/// The client a call acts for, if its request names one.
+fn client_id(path: &str, message: &[u8]) -> Result<Option<String>, ApiError> {
+  let malformed = |_| ApiError::invalid_request("malformed request message");
+
+  Ok(match &path[LOGIN_SERVICE.len()..] {
+    "Login"             => Some(LoginRequest::decode(message).map_err(malformed)?.client_id),
+    "StartPasskeyLogin" => Some(StartPasskeyLoginRequest::decode(message).map_err(malformed)?.client_id),
+    "SendMfaCode"       => Some(SendMfaCodeRequest::decode(message).map_err(malformed)?.client_id),
+    _                   => None
+  })
+}
+
+/// A browser may only call through a client that lists its origin. Calls naming no client, or a
+/// client that does not exist and will be refused with `invalid_client`, need an origin some
+/// client lists.
+async fn check_origin(context: &HttpContext, path: &str, origin: &HeaderValue, message: &[u8]) -> Result<HeaderValue, ApiError> {
+  let client = match client_id(path, message)? {
+    Some(client_id) => {
+      let database = context.auth.database();
+      services::blocking(move || {
+        let conn = database.pool.get()?;
+        Ok(Client::find(&conn, &client_id)?)
+      }).await?
+    },
+    None => None
+  };
+
+  let allowed = match (&client, origin.to_str()) {
+    (Some(client), Ok(value)) => client.allows_origin(value),
+    (Some(_), Err(_))         => false,
+    (None, _)                 => cors::origin_registered(context, origin).await?
+  };
+
+  if !allowed {
+    return Err(ApiError::access_denied("origin is not allowed for this client"));
+  }
+
+  Ok(origin.clone())
+}
+
+/// A trailers-only response reporting an error.
+fn status_response(text: bool, status: &Status) -> Response<Body> {
+  let mut response = trailers_only(text);
+  services::insert_status(response.headers_mut(), status);
+  response
+}
+
+/// A trailers-only response reporting an error with its details.
+fn error_response(text: bool, err: ApiError) -> Response<Body> {
+  let mut response = trailers_only(text);
+  services::insert_error(response.headers_mut(), err);
+  response
+}
+
+fn trailers_only(text: bool) -> Response<Body> {
+  let mut response = Response::new(Body::empty());
+  *response.status_mut() = StatusCode::OK;
+  response.headers_mut().insert(header::CONTENT_TYPE, response_content_type(text));
+  response
+}
+
+/// Trailers travel in the body, as a frame of HTTP/1 style header lines.
+fn trailer_frame(trailers: &HeaderMap) -> Vec<u8> {
+  let mut block = Vec::new();
+  for (name, value) in trailers {
+    block.extend_from_slice(name.as_str().as_bytes());
+    block.extend_from_slice(b": ");
+    block.extend_from_slice(value.as_bytes());
+    block.extend_from_slice(b"\r\n");
+  }
+
+  let mut frame = Vec::with_capacity(5 + block.len());
+  frame.push(TRAILER_FLAG);
+  frame.extend_from_slice(&(block.len() as u32).to_be_bytes());
+  frame.extend_from_slice(&block);
+  frame
+}
+
+fn encode_body(text: bool, payload: &[u8]) -> Vec<u8> {
+  if text { base64::encode(payload).into_bytes() } else { payload.to_vec() }
+}
+
+fn response_content_type(text: bool) -> HeaderValue {
+  HeaderValue::from_static(if text { "application/grpc-web-text+proto" } else { "application/grpc-web+proto" })
+}
+
+fn content_type(headers: &HeaderMap) -> &str {
+  headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_trailer_frame() {
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+
+    let frame = trailer_frame(&trailers);
+    assert_eq!(frame[0], TRAILER_FLAG);
+    assert_eq!(&frame[1..5], &[0, 0, 0, 16]);
+    assert_eq!(&frame[5..], b"grpc-status: 0\r\n");
+  }
+
+  #[test]
+  fn test_single_message() {
+    assert_eq!(single_message(b"\0\0\0\0\x02ab").unwrap(), b"ab");
+    assert!(single_message(b"\0\0\0\0\x02a").is_err());
+    assert!(single_message(b"\0\0\0\0\x02ab\0\0\0\0\x02cd").is_err());
+    assert!(single_message(b"\x01\0\0\0\x02ab").is_err());
+  }
+
+  #[test]
+  fn test_status_response_is_trailers_only() {
+    let response = status_response(true, &Status::permission_denied("100% nope"));
+
+    assert_eq!(response.headers()["content-type"], "application/grpc-web-text+proto");
+    assert_eq!(response.headers()["grpc-status"], "7");
+    assert_eq!(response.headers()["grpc-message"], "100%25 nope");
+  }
+
+  #[tokio::test]
+  async fn test_read_body_is_bounded() {
+    let headers = HeaderMap::new();
+    assert_eq!(read_body(&headers, Body::from("frame")).await.unwrap(), Bytes::from("frame"));
+
+    let oversized = read_body(&headers, Body::from(vec![0; MAX_BODY_BYTES + 1])).await.unwrap_err();
+    assert_eq!(oversized.code(), tonic::Code::ResourceExhausted);
+
+    let mut announced = HeaderMap::new();
+    announced.insert(header::CONTENT_LENGTH, HeaderValue::from(MAX_BODY_BYTES + 1));
+    assert_eq!(read_body(&announced, Body::empty()).await.unwrap_err().code(), tonic::Code::ResourceExhausted);
+  }
+}
@@ -0,0 +1,190 @@
+//! Plain HTTP front end for the OAuth 2.0 & OpenID Connect endpoints that clients expect to reach
+//! without gRPC. Handlers share their logic with the gRPC services.
+
+pub mod authorize;
+mod cors;
+mod discovery;
+pub mod form;
+mod grpc_web;
+mod oauth;
+mod pages;
+mod session;
+mod userinfo;
+
+use hyper::header::{self, HeaderValue};
+use hyper::http::request::Parts;
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower_layer::Layer;
+
+use crate::error::*;
+use crate::metrics;
+use crate::oidc;
+use heimdallr_api::auth::login_server::LoginServer;
+use crate::ratelimit::{RateLimitLayer, RateLimited};
+use crate::services::auth::AuthHandler;
+use crate::services::error::{ApiError, ErrorCode, ErrorDetails};
+use crate::settings::Ui;
+use pages::Templates;
+use session::Sessions;
+
+/// Everything the HTTP handlers need.
+pub struct HttpContext {
+  pub auth: AuthHandler,
+  /// The gRPC service gRPC-Web calls are forwarded to, behind the same rate limits as over gRPC.
+  pub login: RateLimited<ErrorDetails<LoginServer<AuthHandler>>>,
+  templates: Templates,
+  sessions: Sessions,
+  static_dir: Option<PathBuf>
+}
+
+impl HttpContext {
+  pub fn new(auth: AuthHandler, rate_limits: &RateLimitLayer, ui: &Ui) -> Result<Self, HeimdallrError> {
+    Ok(HttpContext {
+      login: rate_limits.layer(auth.clone().service()),
+      auth,
+      templates: Templates::load(ui.templates_dir.as_deref())?,
+      sessions: Sessions::new(ui)?,
+      static_dir: ui.static_dir.as_ref().map(PathBuf::from)
+    })
+  }
+}
+
+/// Serves the HTTP endpoints until the server fails.
+pub async fn serve(address: SocketAddr, context: HttpContext) -> Result<(), HeimdallrError> {
+  let context = Arc::new(context);
+
+  let make_service = make_service_fn(move |conn: &AddrStream| {
+    let context = context.clone();
+    let peer    = conn.remote_addr();
+
+    async move {
+      Ok::<_, Infallible>(service_fn(move |mut request: Request<Body>| {
+        // Handlers find the peer among the extensions, see `client_ip`.
+        request.extensions_mut().insert(peer);
+        route(context.clone(), request)
+      }))
+    }
+  });
+
+  log::info!("HTTP listener on {}", address);
+  Ok(Server::bind(&address).serve(make_service).await?)
+}
+
+async fn route(context: Arc<HttpContext>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+  let method = request.method().clone();
+  let path   = request.uri().path().to_owned();
+
+  let result = match (&method, path.as_str()) {
+    (&Method::POST, path) if path.starts_with(grpc_web::LOGIN_SERVICE) && grpc_web::is_grpc_web(&request) => {
+      grpc_web::handle(&context, request).await
+    },
+    (&Method::OPTIONS, path) if path.starts_with(grpc_web::LOGIN_SERVICE) => cors::preflight(&context, &request).await,
+    (&Method::GET, "/authorize") | (&Method::POST, "/authorize") => authorize::handle(&context, request).await,
+    (&Method::GET, "/login")       => pages::login_page(&context, request),
+    (&Method::POST, "/login")      => pages::login(&context, request).await,
+    (&Method::POST, "/login/mfa")  => pages::login_mfa(&context, request).await,
+    (&Method::POST, "/login/mfa/sms")         => pages::send_mfa_code(&context, request).await,
+    (&Method::POST, "/login/passkey")         => pages::login_passkey(&context, request).await,
+    (&Method::POST, "/login/passkey/options") => pages::passkey_options(&context, request).await,
+    (&Method::POST, "/login/magic-link")      => pages::send_magic_link(&context, request).await,
+    (&Method::GET, "/login/magic")            => pages::magic_link_page(&context, request),
+    (&Method::POST, "/login/magic")           => pages::login_magic(&context, request).await,
+    (&Method::GET, "/verify-email")           => pages::verify_email(&context, request),
+    (&Method::GET, "/password/forgot")        => pages::forgot_password_page(&context, request),
+    (&Method::POST, "/password/forgot")       => pages::forgot_password(&context, request).await,
+    (&Method::GET, "/password/reset")         => pages::reset_password_page(&context, request),
+    (&Method::POST, "/password/reset")        => pages::reset_password(&context, request).await,
+    (&Method::POST, "/consent")    => pages::consent(&context, request).await,
+    (&Method::GET, "/logout")      => pages::logout_page(&context, request),
+    (&Method::POST, "/logout")     => pages::logout(&context, request).await,
+    (&Method::GET, oidc::END_SESSION_PATH) | (&Method::POST, oidc::END_SESSION_PATH) => pages::end_session(&context, request).await,
+    (&Method::GET, path) if path.starts_with("/static/") => pages::asset(&context, &path["/static/".len()..]).await,
+    (&Method::POST, "/token")      => oauth::token(&context, request).await,
+    (&Method::POST, "/revoke")     => oauth::revoke(&context, request).await,
+    (&Method::POST, "/introspect") => oauth::introspect(&context, request).await,
+    (&Method::GET, "/userinfo") | (&Method::POST, "/userinfo") => userinfo::handle(&context, request).await,
+    (&Method::GET, oidc::DISCOVERY_PATH) => discovery::configuration(&context),
+    (&Method::GET, oidc::JWKS_PATH)      => Ok(discovery::jwks(&context)),
+    (&Method::GET, "/metrics")           => Ok(metrics_response()),
+    (_, "/authorize") | (_, "/token") | (_, "/revoke") | (_, "/introspect") | (_, "/userinfo") => Err(method_not_allowed()),
+    (_, "/login") | (_, "/login/mfa") | (_, "/login/passkey") | (_, "/login/passkey/options") | (_, "/consent") | (_, "/logout") | (_, oidc::END_SESSION_PATH) => Err(method_not_allowed()),
+    (_, "/login/mfa/sms") | (_, "/login/magic-link") | (_, "/login/magic") | (_, "/verify-email") | (_, "/password/forgot") | (_, "/password/reset") => Err(method_not_allowed()),
+    _ => Err(ApiError::not_found("no such endpoint"))
+  };
+
+  let response = result.unwrap_or_else(error_response);
+  log::debug!("{} {} -> {}", method, path, response.status());
+  Ok(response)
+}
+
+/// A JSON response.
+pub(crate) fn json(status: StatusCode, body: &serde_json::Value) -> Response<Body> {
+  let mut response = Response::new(Body::from(body.to_string()));
+  *response.status_mut() = status;
+  response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+  response
+}
+
+/// Marks a response carrying tokens or personal data as uncacheable.
+pub(crate) fn no_store(mut response: Response<Body>) -> Response<Body> {
+  response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+  response.headers_mut().insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+  response
+}
+
+/// The address a request comes from, behind the trusted proxies if any; see `Lockout::client_ip`.
+pub(crate) fn client_ip(context: &HttpContext, parts: &Parts) -> Option<IpAddr> {
+  let peer          = parts.extensions.get::<SocketAddr>().map(|addr| addr.ip());
+  let forwarded_for = parts.headers.get("x-forwarded-for").and_then(|value| value.to_str().ok());
+
+  context.auth.lockout().client_ip(peer, forwarded_for)
+}
+
+/// The bearer token from the `Authorization` header, if any.
+pub(crate) fn bearer_token(request: &Request<Body>) -> Option<&str> {
+  crate::services::parse_bearer(request.headers().get(header::AUTHORIZATION)?.to_str().ok()?)
+}
+
+/// Renders an error the way OAuth 2.0 clients expect (RFC 6749 §5.2, RFC 6750 §3).
+pub(crate) fn error_response(err: ApiError) -> Response<Body> {
+  let status = StatusCode::from_u16(err.code.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+  // Metadata, e.g. the `mfa_token` of an `mfa_required` error, is returned next to the error.
+  let mut body: serde_json::Map<String, serde_json::Value> = err.metadata.iter()
+    .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+    .collect();
+  body.insert("error".to_owned(), err.code.as_str().into());
+  body.insert("error_description".to_owned(), err.description.clone().into());
+
+  let mut response = no_store(json(status, &serde_json::Value::Object(body)));
+
+  if let ErrorCode::InvalidToken | ErrorCode::InsufficientScope = err.code {
+    let challenge = format!("Bearer error=\"{}\", error_description=\"{}\"", err.code, err.description.replace('"', "'"));
+    if let Ok(value) = HeaderValue::from_str(&challenge) {
+      response.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+    }
+  }
+
+  if let Some(value) = err.metadata.get("retry_after").and_then(|value| HeaderValue::from_str(value).ok()) {
+    response.headers_mut().insert(header::RETRY_AFTER, value);
+  }
+
+  response
+}
+
+/// `GET /metrics`, for Prometheus to scrape.
+fn metrics_response() -> Response<Body> {
+  let mut response = Response::new(Body::from(metrics::render()));
+  response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain; version=0.0.4"));
+  response
+}
+
+fn method_not_allowed() -> ApiError {
+  ApiError::invalid_request("method not allowed")
+}
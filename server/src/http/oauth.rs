@@ -0,0 +1,131 @@
+use chrono::Utc;
+use heimdallr_api::auth::{GrantType, LoginRequest, LoginResponse};
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use serde_json::json;
+
+use crate::services::{self, auth::grant_name};
+use crate::services::error::{ApiError, ErrorCode};
+use super::form::{self, ClientAuth};
+use super::HttpContext;
+
+const GRANT_TYPES: [GrantType; 6] = [
+  GrantType::Password,
+  GrantType::AuthorizationCode,
+  GrantType::ClientCredentials,
+  GrantType::RefreshToken,
+  GrantType::MfaOtp,
+  GrantType::Webauthn
+];
+
+/// `POST /token` (RFC 6749 §3.2); runs the same grants as the `Login` RPC.
+pub async fn token(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  let client = form::client_auth(&parts.headers, &form)?;
+
+  let name       = form.require("grant_type")?;
+  let grant_type = GRANT_TYPES.iter().copied().find(|grant_type| grant_name(*grant_type) == name)
+    .ok_or_else(|| ApiError::unsupported_grant_type(format!("the {} grant is not supported", name)))?;
+
+  let login = LoginRequest {
+    client_id: client.client_id.clone(),
+    client_secret: client.client_secret.clone(),
+    grant_type: grant_type as i32,
+    scope: form.list("scope"),
+    redirect_uri: form.get("redirect_uri").to_owned(),
+    code: form.get("code").to_owned(),
+    username: form.get("username").to_owned(),
+    password: form.get("password").to_owned(),
+    refresh_token: form.get("refresh_token").to_owned(),
+    nonce: form.get("nonce").to_owned(),
+    code_verifier: form.get("code_verifier").to_owned(),
+    mfa_token: form.get("mfa_token").to_owned(),
+    otp: form.get("otp").to_owned(),
+    webauthn_challenge_id: form.get("webauthn_challenge_id").to_owned(),
+    webauthn_credential: form.get("webauthn_credential").to_owned()
+  };
+
+  let auth      = context.auth.clone();
+  let client_ip = super::client_ip(context, &parts);
+  match services::blocking(move || auth.token(&login, client_ip)).await {
+    Ok(response) => Ok(super::no_store(super::json(StatusCode::OK, &token_json(response)))),
+    Err(err)     => Ok(client_error(&client, err))
+  }
+}
+
+/// `POST /revoke` (RFC 7009).
+pub async fn revoke(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  let client = form::client_auth(&parts.headers, &form)?;
+  let token  = form.require("token")?.to_owned();
+
+  // Client secrets are hashed, so checking them stays off the reactor.
+  let (auth, client_id, client_secret) = (context.auth.clone(), client.client_id.clone(), client.client_secret.clone());
+  match services::blocking(move || auth.revoke(&client_id, &client_secret, &token)).await {
+    Ok(())   => Ok(Response::new(Body::empty())),
+    Err(err) => Ok(client_error(&client, err))
+  }
+}
+
+/// `POST /introspect` (RFC 7662).
+pub async fn introspect(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  let client = form::client_auth(&parts.headers, &form)?;
+  let token  = form.require("token")?.to_owned();
+
+  // Client secrets are hashed, so checking them stays off the reactor.
+  let (auth, client_id, client_secret) = (context.auth.clone(), client.client_id.clone(), client.client_secret.clone());
+  match services::blocking(move || auth.introspect(&client_id, &client_secret, &token)).await {
+    Ok(body) => Ok(super::no_store(super::json(StatusCode::OK, &body))),
+    Err(err) => Ok(client_error(&client, err))
+  }
+}
+
+/// The token response of RFC 6749 §5.1; `expires_in` is relative there.
+fn token_json(response: LoginResponse) -> serde_json::Value {
+  let mut body = json!({
+    "access_token": response.access_token,
+    "token_type": response.token_type,
+    "scope": response.scope.join(" ")
+  });
+
+  if let Some(expires_at) = response.expires_in {
+    body["expires_in"] = json!((expires_at.seconds - Utc::now().timestamp()).max(0));
+  }
+  if !response.refresh_token.is_empty() {
+    body["refresh_token"] = json!(response.refresh_token);
+  }
+  if !response.id_token.is_empty() {
+    body["id_token"] = json!(response.id_token);
+  }
+
+  body
+}
+
+/// Clients that authenticated with HTTP Basic get a challenge back when that failed (RFC 6749 §5.2).
+fn client_error(client: &ClientAuth, err: ApiError) -> Response<Body> {
+  let basic        = client.basic && err.code == ErrorCode::InvalidClient;
+  let mut response = super::error_response(err);
+
+  if basic {
+    response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Basic realm=\"heimdallr\""));
+  }
+
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::oidc;
+
+  #[test]
+  fn test_advertised_grants_are_accepted() {
+    for name in oidc::GRANT_TYPES {
+      assert!(GRANT_TYPES.iter().any(|grant_type| grant_name(*grant_type) == *name), "{} is registrable but not accepted", name);
+    }
+    for name in oidc::STANDARD_GRANT_TYPES {
+      assert!(oidc::GRANT_TYPES.contains(name), "{} is advertised but not registrable", name);
+    }
+  }
+}
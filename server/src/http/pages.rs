@@ -0,0 +1,909 @@
+//! Server-rendered login, consent, logout & error pages.
+//!
+//! Every template can be replaced by a file of the same name in `ui.templates_dir`, and
+//! `ui.static_dir` takes precedence over the built-in stylesheet, so deployments can brand the
+//! pages without rebuilding.
+
+use diesel::pg::PgConnection;
+use handlebars::Handlebars;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::db::models::{Client, Consent, NewSession, Scope, Session, User};
+use crate::error::*;
+use crate::logout;
+use crate::mfa;
+use crate::oidc::Authentication;
+use crate::services::{self, auth::{resolve_scopes, Approval}};
+use crate::services::error::{ApiError, ErrorCode};
+use crate::sms::{self, Channel};
+use super::authorize::{self, AuthorizationRequest, AuthorizeError};
+use super::form::{self, Form};
+use super::session::CSRF_FIELD;
+use super::HttpContext;
+
+/// Built-in templates; `header` & `footer` are used as partials by the others.
+const TEMPLATES: &[(&str, &str)] = &[
+  ("header",          include_str!("../../templates/header.hbs")),
+  ("footer",          include_str!("../../templates/footer.hbs")),
+  ("login",           include_str!("../../templates/login.hbs")),
+  ("mfa",             include_str!("../../templates/mfa.hbs")),
+  ("consent",         include_str!("../../templates/consent.hbs")),
+  ("error",           include_str!("../../templates/error.hbs")),
+  ("logout",          include_str!("../../templates/logout.hbs")),
+  ("message",         include_str!("../../templates/message.hbs")),
+  ("forgot_password", include_str!("../../templates/forgot_password.hbs")),
+  ("reset_password",  include_str!("../../templates/reset_password.hbs")),
+  ("magic_link",      include_str!("../../templates/magic_link.hbs"))
+];
+
+const STYLESHEET: &str = include_str!("../../static/heimdallr.css");
+
+/// Runs the passkey forms of the login & two-factor pages.
+const WEBAUTHN_SCRIPT: &str = include_str!("../../static/webauthn.js");
+
+/// Pages must never be framed (clickjacking) and only load assets from the server itself.
+const CONTENT_SECURITY_POLICY: &str = "default-src 'self'; frame-ancestors 'none'";
+
+pub struct Templates {
+  registry: Handlebars<'static>
+}
+
+impl Templates {
+  /// Registers the built-in templates, replacing each one found in `dir`.
+  pub fn load(dir: Option<&str>) -> Result<Self, HeimdallrError> {
+    let mut registry = Handlebars::new();
+
+    for (name, source) in TEMPLATES {
+      let path = dir.map(|dir| Path::new(dir).join(format!("{}.hbs", name)));
+
+      match path {
+        Some(path) if path.is_file() => {
+          log::info!("Using template {}", path.display());
+          registry.register_template_string(name, std::fs::read_to_string(path)?)?;
+        },
+        _ => registry.register_template_string(name, source)?
+      }
+    }
+
+    Ok(Templates { registry })
+  }
+
+  pub fn render(&self, name: &str, data: &Value) -> Result<String, HeimdallrError> {
+    Ok(self.registry.render(name, data)?)
+  }
+}
+
+/// `GET /login`: shows who is signed in, or the login form.
+pub fn login_page(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let headers = request.headers();
+  let user    = current_session(context, headers)?.map(|(_, user)| user.username);
+
+  page(context, headers, StatusCode::OK, "login", json!({ "user": user }))
+}
+
+/// `POST /login`: checks the password & starts a session, then resumes the authorization request.
+pub async fn login(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let username  = form.get("username");
+  let auth      = context.auth.clone();
+  let client_ip = super::client_ip(context, &parts);
+  let (login, password) = (username.to_owned(), form.get("password").to_owned());
+
+  // Checking the password is slow on purpose, so it stays off the reactor.
+  let checked = services::blocking(move || {
+    let conn = auth.database().pool.get()?;
+    let user = auth.authenticate_user(&conn, &login, &password, client_ip)?;
+    auth.check_password_age(&user)?;
+    Ok(user)
+  });
+
+  let user = match checked.await {
+    Ok(user) => user,
+    Err(err) => {
+      let status = if err.code == ErrorCode::TooManyRequests { StatusCode::TOO_MANY_REQUESTS } else { StatusCode::UNAUTHORIZED };
+      return page(context, &parts.headers, status, "login", json!({
+        "error": err.description,
+        "username": username,
+        "request": form.get("request"),
+        "client_name": client_name(context, form.get("request"))
+      }));
+    }
+  };
+
+  let conn = context.auth.database().pool.get()?;
+  let auth = Authentication::new(&["pwd"]);
+
+  if mfa::is_enrolled(&conn, user.id)? {
+    let mfa_token = context.auth.issuer().mfa_token(&user, mfa_audience(context), &auth)?;
+    return mfa_page(context, &conn, &parts.headers, user.id, &mfa_token, form.get("request"), None);
+  }
+
+  sign_in(context, &conn, &parts.headers, &user, auth, form.get("request"))
+}
+
+/// `POST /login/passkey/options`: starts a passkey login, as a second factor when the form carries
+/// an `mfa_token`; called by `webauthn.js`.
+pub async fn passkey_options(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let conn    = context.auth.database().pool.get()?;
+  let user_id = match form.get("mfa_token") {
+    ""        => None,
+    mfa_token => Some(context.auth.pending_login(&conn, mfa_token, mfa_audience(context))?.user_id)
+  };
+
+  let (challenge_id, options) = context.auth.passkey_challenge(&conn, None, user_id, user_id.is_some())?;
+
+  Ok(super::no_store(super::json(StatusCode::OK, &json!({ "challenge_id": challenge_id.to_string(), "options": options }))))
+}
+
+/// `POST /login/passkey`: checks a passkey assertion, either instead of a password or as the
+/// second factor of a user whose password was accepted.
+pub async fn login_passkey(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let conn      = context.auth.database().pool.get()?;
+  let mfa_token = form.get("mfa_token");
+  let pending   = if mfa_token.is_empty() {
+    None
+  }
+  else {
+    match context.auth.pending_login(&conn, mfa_token, mfa_audience(context)) {
+      Ok(pending) => Some(pending),
+      Err(_)      => return expired_login(context, &parts.headers, &form)
+    }
+  };
+
+  let second_factor_of = pending.as_ref().map(|pending| pending.user_id);
+  let user = match context.auth.verify_passkey(&conn, form.get("challenge_id"), form.get("credential"), None, second_factor_of) {
+    Ok(user) => user,
+    Err(err) => {
+      log::info!("Passkey login failed: {}", err.description);
+      let error = Some("Your passkey could not be verified.");
+
+      return match second_factor_of {
+        Some(user_id) => mfa_page(context, &conn, &parts.headers, user_id, mfa_token, form.get("request"), error),
+        None => page(context, &parts.headers, StatusCode::UNAUTHORIZED, "login", json!({
+          "error": error,
+          "request": form.get("request"),
+          "client_name": client_name(context, form.get("request"))
+        }))
+      };
+    }
+  };
+
+  let auth = match pending {
+    Some(pending) => {
+      context.auth.redeem_mfa_token(&conn, &pending)?;
+
+      let mut auth = pending.auth;
+      auth.amr.push("hwk".to_owned());
+      auth
+    },
+    // A passkey that verified its user is something they have plus something they know or are.
+    None => Authentication::new(&["hwk", "mfa"])
+  };
+
+  sign_in(context, &conn, &parts.headers, &user, auth, form.get("request"))
+}
+
+/// `POST /login/mfa`: checks the second factor of a user whose password was accepted.
+pub async fn login_mfa(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let conn    = context.auth.database().pool.get()?;
+  let pending = match context.auth.pending_login(&conn, form.get("mfa_token"), mfa_audience(context)) {
+    Ok(pending) => pending,
+    Err(_)      => return expired_login(context, &parts.headers, &form)
+  };
+
+  let user = match User::find(&conn, pending.user_id)? {
+    Some(user) if !user.disabled => user,
+    _ => return Err(ApiError::access_denied("account is disabled"))
+  };
+
+  // Codes sent by SMS are checked against their hash, so the check stays off the reactor.
+  let (auth, otp) = (context.auth.clone(), form.get("otp").to_owned());
+  let (conn, pending, user, verified) = services::blocking(move || {
+    let verified = auth.verify_code(&conn, &pending, &user, &otp);
+    Ok((conn, pending, user, verified))
+  }).await?;
+
+  let error = match verified {
+    Ok(Some(method)) => {
+      let mut auth = pending.auth;
+      auth.amr.push(method.to_owned());
+      return sign_in(context, &conn, &parts.headers, &user, auth, form.get("request"));
+    },
+    Ok(None)                                           => "The code is invalid or has already been used.",
+    Err(err) if err.code == ErrorCode::TooManyRequests => "Too many wrong codes were entered, please try again later.",
+    Err(err)                                           => return Err(err)
+  };
+
+  mfa_page(context, &conn, &parts.headers, user.id, form.get("mfa_token"), form.get("request"), Some(error))
+}
+
+/// `POST /login/mfa/sms`: sends the code of the two-factor step by SMS, or by voice call when the
+/// form asks for `channel=voice`.
+pub async fn send_mfa_code(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let conn    = context.auth.database().pool.get()?;
+  let user_id = match context.auth.pending_login(&conn, form.get("mfa_token"), mfa_audience(context)) {
+    Ok(pending) => pending.user_id,
+    Err(_)      => return expired_login(context, &parts.headers, &form)
+  };
+
+  let user = match User::find(&conn, user_id)? {
+    Some(user) if !user.disabled => user,
+    _ => return Err(ApiError::access_denied("account is disabled"))
+  };
+
+  let channel = if form.get("channel") == "voice" { Channel::Voice } else { Channel::Sms };
+  let mut data = mfa_data(&conn, user.id, form.get("mfa_token"), form.get("request"))?;
+
+  // Codes are hashed before they are stored, so sending stays off the reactor.
+  let (auth, phone_number) = (context.auth.clone(), user.phone_number.clone());
+  let sent = services::blocking(move || Ok(auth.send_mfa_code(&conn, &user, channel))).await?;
+
+  let status = match sent {
+    Ok(()) => {
+      data["sent"] = json!(phone_number.as_deref().map(sms::mask));
+      StatusCode::OK
+    },
+    Err(err) => {
+      let (status, error) = match err.code {
+        ErrorCode::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too many codes were sent to your phone, please try again later."),
+        _                          => return Err(err)
+      };
+      data["error"] = json!(error);
+      status
+    }
+  };
+
+  page(context, &parts.headers, status, "mfa", data)
+}
+
+/// `POST /login/magic-link`: mails a sign-in link that resumes the authorization request.
+pub async fn send_magic_link(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let conn = context.auth.database().pool.get()?;
+  if let Err(err) = context.auth.send_magic_link(&conn, form.get("email"), form.get("request")) {
+    let (status, error) = mail_error(err)?;
+    return page(context, &parts.headers, status, "login", json!({
+      "error": error,
+      "request": form.get("request"),
+      "client_name": client_name(context, form.get("request"))
+    }));
+  }
+
+  page(context, &parts.headers, StatusCode::OK, "magic_link", json!({ "sent": true, "email": form.get("email") }))
+}
+
+/// `GET /login/magic`: the link of a sign-in email. Mail scanners follow links, so the token is
+/// only redeemed once the user confirms.
+pub fn magic_link_page(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let query = query(&request)?;
+  page(context, request.headers(), StatusCode::OK, "magic_link", json!({ "token": query.get("token"), "request": query.get("request") }))
+}
+
+/// `POST /login/magic`: redeems a sign-in link, asking for the second factor of users who set one up.
+pub async fn login_magic(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let conn = context.auth.database().pool.get()?;
+  let user = match context.auth.redeem_magic_link(&conn, form.get("token")) {
+    Ok(user) => user,
+    Err(err) => {
+      log::info!("Magic link login failed: {}", err.description);
+      return page(context, &parts.headers, StatusCode::UNAUTHORIZED, "login", json!({
+        "error": "The sign-in link is invalid, has expired or was already used.",
+        "request": form.get("request"),
+        "client_name": client_name(context, form.get("request"))
+      }));
+    }
+  };
+
+  let auth = Authentication::new(&["email"]);
+
+  if mfa::is_enrolled(&conn, user.id)? {
+    let mfa_token = context.auth.issuer().mfa_token(&user, mfa_audience(context), &auth)?;
+    return mfa_page(context, &conn, &parts.headers, user.id, &mfa_token, form.get("request"), None);
+  }
+
+  sign_in(context, &conn, &parts.headers, &user, auth, form.get("request"))
+}
+
+/// `GET /verify-email`: the link of a verification email.
+pub fn verify_email(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let query = query(&request)?;
+  let conn  = context.auth.database().pool.get()?;
+
+  match context.auth.verify_email(&conn, query.get("token")) {
+    Ok(_) => page(context, request.headers(), StatusCode::OK, "message", json!({
+      "title": "Email address verified",
+      "message": "Thank you, your email address has been verified."
+    })),
+    Err(err) => {
+      log::info!("Email verification failed: {}", err.description);
+      page(context, request.headers(), StatusCode::BAD_REQUEST, "message", json!({
+        "title": "Email address not verified",
+        "error": "The verification link is invalid, has expired or was already used."
+      }))
+    }
+  }
+}
+
+/// `GET /password/forgot`.
+pub fn forgot_password_page(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  page(context, request.headers(), StatusCode::OK, "forgot_password", json!({}))
+}
+
+/// `POST /password/forgot`: mails a reset link; whether the address is known is not revealed.
+pub async fn forgot_password(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let conn = context.auth.database().pool.get()?;
+  if let Err(err) = context.auth.request_password_reset(&conn, form.get("email")) {
+    let (status, error) = mail_error(err)?;
+    return page(context, &parts.headers, status, "forgot_password", json!({ "error": error, "email": form.get("email") }));
+  }
+
+  page(context, &parts.headers, StatusCode::OK, "forgot_password", json!({ "sent": true, "email": form.get("email") }))
+}
+
+/// `GET /password/reset`: the link of a reset email, asking for the new password.
+pub fn reset_password_page(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let query = query(&request)?;
+  page(context, request.headers(), StatusCode::OK, "reset_password", json!({ "token": query.get("token") }))
+}
+
+/// `POST /password/reset`: sets the new password, which signs the user out everywhere.
+pub async fn reset_password(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let retry = |error: &str| page(context, &parts.headers, StatusCode::BAD_REQUEST, "reset_password", json!({ "token": form.get("token"), "error": error }));
+
+  if form.get("new_password").is_empty() {
+    return retry("Please choose a new password.");
+  }
+  if form.get("new_password") != form.get("confirm_password") {
+    return retry("The passwords do not match.");
+  }
+
+  let auth = context.auth.clone();
+  let (token, new_password) = (form.get("token").to_owned(), form.get("new_password").to_owned());
+
+  // Hashing the new password is slow on purpose, so it stays off the reactor.
+  let reset = services::blocking(move || {
+    let conn = auth.database().pool.get()?;
+    auth.reset_password(&conn, &token, &new_password)
+  });
+
+  if let Err(err) = reset.await {
+    // The link stays usable when only the password was refused.
+    if err.metadata.contains_key("password_violations") {
+      let reasons: Vec<&str> = err.field_violations.iter().map(|(_, description)| description.as_str()).collect();
+      return retry(&format!("Please choose another password: {}.", reasons.join(", ")));
+    }
+
+    log::info!("Password reset failed: {}", err.description);
+    return page(context, &parts.headers, StatusCode::BAD_REQUEST, "message", json!({
+      "title": "Password not changed",
+      "error": "The reset link is invalid, has expired or was already used."
+    }));
+  }
+
+  let mut response = page(context, &parts.headers, StatusCode::OK, "message", json!({
+    "title": "Password changed",
+    "message": "Your password has been changed and you have been signed out everywhere."
+  }))?;
+  response.headers_mut().append(header::SET_COOKIE, context.sessions.end());
+  Ok(response)
+}
+
+/// Continues a valid authorization request: asks the user to sign in, or to approve scopes they
+/// have not approved for the client before.
+pub fn authorize(context: &HttpContext, headers: &HeaderMap, form: &Form, request: AuthorizationRequest) -> Result<Response<Body>, ApiError> {
+  let prompts: Vec<&str> = request.prompt.as_deref().unwrap_or_default().split(' ').collect();
+  let prompt = |value: &str| prompts.contains(&value);
+
+  let signed_in = if prompt("login") { None } else { current_session(context, headers)? };
+
+  let (session, user) = match signed_in {
+    Some(signed_in) => signed_in,
+    None if prompt("none") => {
+      return authorize::error_response(to_client(&request, ApiError::login_required("the user is not signed in")));
+    },
+    None => {
+      return page(context, headers, StatusCode::OK, "login", json!({
+        "request": form.encode(),
+        "client_name": request.client.name
+      }));
+    }
+  };
+
+  let conn = context.auth.database().pool.get()?;
+  let scopes = match resolve_scopes(&conn, &request.scopes, &request.client, Some(&user)) {
+    Ok(scopes) => scopes,
+    Err(err)   => return authorize::error_response(to_client(&request, err))
+  };
+
+  let consent = Consent::find(&conn, user.id, &request.client.id)?.filter(|_| !prompt("consent"));
+
+  if consent.as_ref().map(|consent| consent.covers(&scopes)).unwrap_or_else(|| scopes.is_empty()) {
+    return approve(context, &conn, &request, &user, &session, scopes);
+  }
+
+  let approved = consent.map(|consent| consent.scopes).unwrap_or_default();
+
+  if prompt("none") {
+    return authorize::error_response(to_client(&request, ApiError::consent_required("the user has to approve the request")));
+  }
+
+  let mut described = Scope::find_all(&conn, &scopes)?;
+  described.sort_by_key(|scope| scopes.iter().position(|name| *name == scope.name));
+
+  let (previously, requested): (Vec<Scope>, Vec<Scope>) = described.into_iter().partition(|scope| approved.contains(&scope.name));
+  let describe = |scopes: Vec<Scope>| scopes.into_iter().map(|scope| json!({ "name": scope.name, "description": scope.description })).collect::<Vec<_>>();
+
+  page(context, headers, StatusCode::OK, "consent", json!({
+    "client_name": request.client.name,
+    "user": user.username,
+    "scopes": describe(requested),
+    "approved": describe(previously),
+    "request": form.encode()
+  }))
+}
+
+/// `POST /consent`: issues an authorization code, or tells the client the user said no.
+pub async fn consent(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let original = Form::parse(form.get("request").as_bytes())?;
+  let request = match authorize::validate(context, &original) {
+    Ok(request) => request,
+    Err(err)    => return error_page(context, &parts.headers, err)
+  };
+
+  let signed_in = current_session(context, &parts.headers)?;
+
+  let (session, user) = match signed_in {
+    Some(signed_in) => signed_in,
+    None => {
+      return page(context, &parts.headers, StatusCode::OK, "login", json!({
+        "request": original.encode(),
+        "client_name": request.client.name
+      }));
+    }
+  };
+
+  if form.get("decision") != "allow" {
+    return authorize::error_response(to_client(&request, ApiError::access_denied("the user denied the request")));
+  }
+
+  let conn = context.auth.database().pool.get()?;
+  let scopes = match resolve_scopes(&conn, &request.scopes, &request.client, Some(&user)) {
+    Ok(scopes) => scopes,
+    Err(err)   => return authorize::error_response(to_client(&request, err))
+  };
+
+  Consent::grant(&conn, user.id, &request.client.id, &scopes)?;
+  approve(context, &conn, &request, &user, &session, scopes)
+}
+
+/// `GET /logout`: asks for confirmation, so a cross-site link cannot sign the user out.
+pub fn logout_page(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  confirm_logout(context, request.headers(), &Form::default())
+}
+
+/// `GET|POST /end_session`: logout initiated by a client (OpenID Connect RP-Initiated Logout 1.0).
+pub async fn end_session(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (headers, form) = if request.method() == Method::POST {
+    let (parts, form) = form::read_form(request).await?;
+    (parts.headers, form)
+  }
+  else {
+    let (parts, _) = request.into_parts();
+    let form = Form::parse(parts.uri.query().unwrap_or_default().as_bytes())?;
+    (parts.headers, form)
+  };
+
+  confirm_logout(context, &headers, &form)
+}
+
+/// `POST /logout`: ends the session, tells the clients that took part in it & returns to the
+/// client that asked for the logout, if any.
+pub async fn logout(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let (parts, form) = form::read_form(request).await?;
+  context.sessions.verify_csrf(&parts.headers, form.get(CSRF_FIELD))?;
+
+  let params = match logout_request(context, &Form::parse(form.get("request").as_bytes())?) {
+    Ok(params) => params,
+    Err(err)   => return error_page(context, &parts.headers, err.into())
+  };
+
+  if let Some((session, _)) = current_session(context, &parts.headers)? {
+    let conn = context.auth.database().pool.get()?;
+    logout::end_session(&conn, context.auth.issuer(), &session)?;
+  }
+
+  let mut response = match &params.post_logout_redirect_uri {
+    Some(uri) => post_logout_redirect(uri, params.state.as_deref())?,
+    None      => page(context, &parts.headers, StatusCode::OK, "logout", json!({}))?
+  };
+
+  response.headers_mut().append(header::SET_COOKIE, context.sessions.end());
+  Ok(response)
+}
+
+/// Shows an error to the user, or hands it back to the client when its redirect URI is known.
+pub fn error_page(context: &HttpContext, headers: &HeaderMap, err: AuthorizeError) -> Result<Response<Body>, ApiError> {
+  match err {
+    AuthorizeError::Invalid(err) => {
+      let status = StatusCode::from_u16(err.code.http_status()).unwrap_or(StatusCode::BAD_REQUEST);
+      page(context, headers, status, "error", json!({ "error": err.code.as_str(), "description": err.description }))
+    },
+    err => authorize::error_response(err)
+  }
+}
+
+/// `GET /static/<file>`.
+pub async fn asset(context: &HttpContext, name: &str) -> Result<Response<Body>, ApiError> {
+  let valid = !name.is_empty()
+    && !name.starts_with('.')
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+
+  if !valid {
+    return Err(ApiError::not_found("no such file"));
+  }
+
+  let custom = match &context.static_dir {
+    Some(dir) => tokio::fs::read(dir.join(name)).await.ok(),
+    None      => None
+  };
+
+  let body = match custom {
+    Some(body)                      => body,
+    None if name == "heimdallr.css" => STYLESHEET.as_bytes().to_vec(),
+    None if name == "webauthn.js"   => WEBAUTHN_SCRIPT.as_bytes().to_vec(),
+    None                            => return Err(ApiError::not_found("no such file"))
+  };
+
+  let mut response = Response::new(Body::from(body));
+  response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type(name)));
+  response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("public, max-age=3600"));
+  response.headers_mut().insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+  Ok(response)
+}
+
+/// Renders a page, adding the CSRF token its forms need.
+fn page(context: &HttpContext, headers: &HeaderMap, status: StatusCode, template: &str, mut data: Value) -> Result<Response<Body>, ApiError> {
+  let (token, cookie) = context.sessions.csrf_token(headers)?;
+  if let Value::Object(data) = &mut data {
+    data.insert(CSRF_FIELD.to_owned(), Value::String(token));
+  }
+
+  let mut response = Response::new(Body::from(context.templates.render(template, &data)?));
+  *response.status_mut() = status;
+
+  let response_headers = response.headers_mut();
+  response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8"));
+  response_headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(CONTENT_SECURITY_POLICY));
+  response_headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+  response_headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+  response_headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+  if let Some(cookie) = cookie {
+    response_headers.append(header::SET_COOKIE, cookie);
+  }
+
+  Ok(super::no_store(response))
+}
+
+/// Starts a session for a user who just authenticated, then resumes the authorization request.
+fn sign_in(context: &HttpContext, conn: &PgConnection, headers: &HeaderMap, user: &User, auth: Authentication, request: &str) -> Result<Response<Body>, ApiError> {
+  // Signing in again, e.g. for `prompt=login`, replaces the previous session.
+  if let Some((previous, _)) = current_session(context, headers)? {
+    logout::end_session(conn, context.auth.issuer(), &previous)?;
+  }
+
+  let expires_at = context.sessions.expires_at();
+  let session = Session::create(conn, &NewSession {
+    user_id: user.id,
+    auth_time: auth.auth_time.naive_utc(),
+    amr: &auth.amr,
+    user_agent: headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok()),
+    expires_at: expires_at.naive_utc()
+  })?;
+
+  let mut response = see_other(&resume_location(request)?)?;
+  response.headers_mut().append(header::SET_COOKIE, context.sessions.start(session.id, expires_at)?);
+  Ok(response)
+}
+
+/// Where a user who just signed in goes next. The authorization request no longer asks for
+/// `prompt=login`, which the user has just done, or it would send them back to the login page.
+fn resume_location(request: &str) -> Result<String, ApiError> {
+  if request.is_empty() {
+    return Ok("/login".to_owned());
+  }
+
+  let mut form = Form::parse(request.as_bytes())?;
+  let prompts: Vec<String> = form.list("prompt").into_iter().filter(|prompt| prompt != "login").collect();
+  form.set("prompt", prompts.join(" "));
+
+  Ok(format!("/authorize?{}", form.encode()))
+}
+
+/// MFA tokens of the login pages are addressed to the issuer itself rather than a client.
+fn mfa_audience(context: &HttpContext) -> &str {
+  &context.auth.issuer().settings().issuer
+}
+
+/// Asks for the second factor, offering the methods the user has set up; shown again with an
+/// error after a failed attempt.
+fn mfa_page(context: &HttpContext, conn: &PgConnection, headers: &HeaderMap, user_id: Uuid, mfa_token: &str, request: &str, error: Option<&str>) -> Result<Response<Body>, ApiError> {
+  let status   = if error.is_some() { StatusCode::UNAUTHORIZED } else { StatusCode::OK };
+  let mut data = mfa_data(conn, user_id, mfa_token, request)?;
+  data["error"] = json!(error);
+
+  page(context, headers, status, "mfa", data)
+}
+
+/// What the two-factor page shows for a user.
+fn mfa_data(conn: &PgConnection, user_id: Uuid, mfa_token: &str, request: &str) -> Result<Value, ApiError> {
+  let methods = mfa::methods(conn, user_id)?;
+  let has     = |method: &str| methods.contains(&method);
+
+  Ok(json!({
+    "code": has("otp") || has("sms"),
+    "otp": has("otp"),
+    "sms": has("sms"),
+    "passkey": has("webauthn"),
+    "mfa_token": mfa_token,
+    "request": request
+  }))
+}
+
+/// Starts over when the `mfa_token` of a two-factor page has expired.
+fn expired_login(context: &HttpContext, headers: &HeaderMap, form: &Form) -> Result<Response<Body>, ApiError> {
+  page(context, headers, StatusCode::UNAUTHORIZED, "login", json!({
+    "error": "Your sign in has expired, please try again.",
+    "request": form.get("request"),
+    "client_name": client_name(context, form.get("request"))
+  }))
+}
+
+/// Sends the user back to the client with an authorization code for the approved scopes.
+fn approve(context: &HttpContext, conn: &PgConnection, request: &AuthorizationRequest, user: &User, session: &Session, scopes: Vec<String>) -> Result<Response<Body>, ApiError> {
+  session.add_client(conn, &request.client.id)?;
+
+  let code = context.auth.authorization_code(conn, &Approval {
+    client: &request.client,
+    user,
+    redirect_uri: &request.redirect_uri,
+    scopes,
+    nonce: request.nonce.as_deref(),
+    code_challenge: request.code_challenge.as_deref(),
+    auth: session.authentication()
+  })?;
+
+  let mut params = vec![("code", code.as_str())];
+  if let Some(state) = &request.state {
+    params.push(("state", state.as_str()));
+  }
+  authorize::redirect(&request.redirect_uri, &params)
+}
+
+fn see_other(location: &str) -> Result<Response<Body>, ApiError> {
+  let location = HeaderValue::from_str(location).map_err(|_| ApiError::invalid_request("invalid redirect location"))?;
+
+  let mut response = Response::new(Body::empty());
+  *response.status_mut() = StatusCode::SEE_OTHER;
+  response.headers_mut().insert(header::LOCATION, location);
+  Ok(super::no_store(response))
+}
+
+/// The session the browser is signed in with & its user, unless the session has ended or the
+/// user has since been disabled or deleted.
+fn current_session(context: &HttpContext, headers: &HeaderMap) -> Result<Option<(Session, User)>, ApiError> {
+  let session_id = match context.sessions.current(headers) {
+    Some(session_id) => session_id,
+    None             => return Ok(None)
+  };
+
+  let conn = context.auth.database().pool.get()?;
+  let session = match Session::find_active(&conn, session_id)? {
+    Some(session) => session,
+    None          => return Ok(None)
+  };
+
+  Ok(User::find(&conn, session.user_id)?.filter(|user| !user.disabled).map(|user| (session, user)))
+}
+
+/// A validated logout request from a client.
+struct LogoutRequest {
+  client: Option<Client>,
+  post_logout_redirect_uri: Option<String>,
+  state: Option<String>
+}
+
+/// Checks the parameters of `/end_session`; all of them are optional.
+fn logout_request(context: &HttpContext, form: &Form) -> Result<LogoutRequest, ApiError> {
+  let hinted_client = match form.optional("id_token_hint") {
+    Some(hint) => {
+      let claims = context.auth.issuer().validate_id_token_hint(&hint)
+        .map_err(|_| ApiError::invalid_field("id_token_hint", "id_token_hint is not an ID token issued by this server"))?;
+      claims["aud"].as_str().map(str::to_owned)
+    },
+    None => None
+  };
+
+  let client_id = match (form.optional("client_id"), hinted_client) {
+    (Some(client_id), Some(hinted)) if client_id != hinted => {
+      return Err(ApiError::invalid_field("client_id", "client_id does not match the id_token_hint"));
+    },
+    (client_id, hinted) => client_id.or(hinted)
+  };
+
+  let client = match client_id {
+    Some(client_id) => {
+      let conn = context.auth.database().pool.get()?;
+      Some(Client::find(&conn, &client_id)?.ok_or_else(|| ApiError::invalid_field("client_id", "unknown client"))?)
+    },
+    None => None
+  };
+
+  let post_logout_redirect_uri = match (form.optional("post_logout_redirect_uri"), &client) {
+    (None, _) => None,
+    (Some(uri), Some(client)) if client.allows_post_logout_redirect_uri(&uri) => Some(uri),
+    (Some(_), _) => {
+      return Err(ApiError::invalid_field("post_logout_redirect_uri", "post_logout_redirect_uri is not registered for the client"));
+    }
+  };
+
+  Ok(LogoutRequest { client, post_logout_redirect_uri, state: form.optional("state") })
+}
+
+/// Asks a signed in user to confirm the logout; anyone else is sent straight back to the client.
+fn confirm_logout(context: &HttpContext, headers: &HeaderMap, form: &Form) -> Result<Response<Body>, ApiError> {
+  let params = match logout_request(context, form) {
+    Ok(params) => params,
+    Err(err)   => return error_page(context, headers, err.into())
+  };
+
+  match (current_session(context, headers)?, &params.post_logout_redirect_uri) {
+    (Some((_, user)), _) => page(context, headers, StatusCode::OK, "logout", json!({
+      "user": user.username,
+      "client_name": params.client.map(|client| client.name),
+      "request": form.encode()
+    })),
+    (None, Some(uri)) => post_logout_redirect(uri, params.state.as_deref()),
+    (None, None)      => page(context, headers, StatusCode::OK, "logout", json!({}))
+  }
+}
+
+fn post_logout_redirect(uri: &str, state: Option<&str>) -> Result<Response<Body>, ApiError> {
+  let mut url = url::Url::parse(uri).map_err(|_| ApiError::invalid_field("post_logout_redirect_uri", "post_logout_redirect_uri is not a valid URL"))?;
+  if let Some(state) = state {
+    url.query_pairs_mut().append_pair("state", state);
+  }
+
+  see_other(url.as_str())
+}
+
+/// The query string parameters of a request.
+fn query(request: &Request<Body>) -> Result<Form, ApiError> {
+  Form::parse(request.uri().query().unwrap_or_default().as_bytes())
+}
+
+/// The status & message shown when a mail could not be sent; other errors are passed on.
+fn mail_error(err: ApiError) -> Result<(StatusCode, String), ApiError> {
+  match err.code {
+    ErrorCode::TooManyRequests => {
+      let minutes = err.metadata.get("retry_after").and_then(|seconds| seconds.parse::<i64>().ok()).map_or(60, |seconds| (seconds + 59) / 60);
+      Ok((StatusCode::TOO_MANY_REQUESTS, format!("Too many emails were sent to this address, please try again in {} minutes.", minutes)))
+    },
+    ErrorCode::InvalidRequest => Ok((StatusCode::BAD_REQUEST, "Please enter your email address.".to_owned())),
+    _                         => Err(err)
+  }
+}
+
+/// Name of the client an encoded authorization request is for, shown on the login page.
+fn client_name(context: &HttpContext, request: &str) -> Option<String> {
+  let form = Form::parse(request.as_bytes()).ok()?;
+  authorize::validate(context, &form).ok().map(|request| request.client.name)
+}
+
+fn to_client(request: &AuthorizationRequest, error: ApiError) -> AuthorizeError {
+  AuthorizeError::Redirect { redirect_uri: request.redirect_uri.clone(), state: request.state.clone(), error: Box::new(error) }
+}
+
+fn content_type(name: &str) -> &'static str {
+  match name.rsplit('.').next().unwrap_or_default() {
+    "css"          => "text/css; charset=utf-8",
+    "js"           => "application/javascript; charset=utf-8",
+    "svg"          => "image/svg+xml",
+    "png"          => "image/png",
+    "jpg" | "jpeg" => "image/jpeg",
+    "ico"          => "image/x-icon",
+    "woff2"        => "font/woff2",
+    _              => "application/octet-stream"
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_built_in_templates_render() {
+    let templates = Templates::load(None).unwrap();
+
+    let html = templates.render("login", &json!({ "request": "client_id=a&state=<x>", "csrf_token": "t" })).unwrap();
+    assert!(html.contains("<title>Sign in · Heimdallr</title>"));
+    assert!(html.contains("client_id&#x3D;a&amp;state&#x3D;&lt;x&gt;"));
+
+    let html = templates.render("consent", &json!({ "client_name": "Demo", "scopes": [{ "name": "email" }] })).unwrap();
+    assert!(html.contains("Authorize Demo"));
+
+    let html = templates.render("mfa", &json!({ "mfa_token": "m", "csrf_token": "t", "code": true, "otp": true })).unwrap();
+    assert!(html.contains("name=\"mfa_token\" value=\"m\""));
+    assert!(!html.contains("data-passkey"));
+
+    let html = templates.render("mfa", &json!({ "mfa_token": "m", "csrf_token": "t", "passkey": true })).unwrap();
+    assert!(html.contains("action=\"/login/passkey\""));
+    assert!(!html.contains("name=\"otp\""));
+
+    let html = templates.render("mfa", &json!({ "mfa_token": "m", "csrf_token": "t", "code": true, "sms": true, "sent": "••• 0123" })).unwrap();
+    assert!(html.contains("action=\"/login/mfa/sms\""));
+    assert!(html.contains("name=\"otp\""));
+
+    let html = templates.render("magic_link", &json!({ "token": "k", "request": "r", "csrf_token": "t" })).unwrap();
+    assert!(html.contains("name=\"token\" value=\"k\""));
+
+    let html = templates.render("message", &json!({ "title": "Password changed" })).unwrap();
+    assert!(html.contains("<title>Password changed · Heimdallr</title>"));
+  }
+
+  #[test]
+  fn test_signing_in_satisfies_prompt_login() {
+    let request = Form::parse(b"client_id=spa&prompt=login+consent&state=s").unwrap().encode();
+
+    let location = resume_location(&request).unwrap();
+    let resumed  = Form::parse(location.trim_start_matches("/authorize?").as_bytes()).unwrap();
+    assert_eq!(resumed.list("prompt"), vec!["consent"]);
+    assert_eq!(resumed.get("state"), "s");
+
+    let location = resume_location("client_id=spa&prompt=login").unwrap();
+    assert_eq!(location, "/authorize?client_id=spa");
+    assert_eq!(resume_location(&location["/authorize?".len()..]).unwrap(), location);
+
+    assert_eq!(resume_location("").unwrap(), "/login");
+  }
+
+  #[test]
+  fn test_content_type() {
+    assert_eq!(content_type("heimdallr.css"), "text/css; charset=utf-8");
+    assert_eq!(content_type("logo.svg"), "image/svg+xml");
+    assert_eq!(content_type("README"), "application/octet-stream");
+  }
+}
@@ -0,0 +1,205 @@
+//! Login session cookies & CSRF protection for the HTML pages.
+//!
+//! The cookie only carries the signed id of a row in the `sessions` table, so sessions can be
+//! ended server side. CSRF tokens use the double submit pattern: a random value in a
+//! `SameSite=Strict` cookie that every form has to echo back.
+
+use chrono::{DateTime, Duration, Utc};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::crypto;
+use crate::error::*;
+use crate::services::error::ApiError;
+use crate::settings::Ui;
+
+pub const SESSION_COOKIE: &str = "heimdallr_session";
+pub const CSRF_COOKIE: &str = "heimdallr_csrf";
+
+/// Name of the hidden form field carrying the CSRF token.
+pub const CSRF_FIELD: &str = "csrf_token";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionCookie {
+  sid: Uuid,
+  exp: i64
+}
+
+/// Signs & checks session cookies.
+pub struct Sessions {
+  key: PKey<Private>,
+  ttl: Duration,
+  secure: bool
+}
+
+impl Sessions {
+  pub fn new(settings: &Ui) -> Result<Self, HeimdallrError> {
+    let secret = match &settings.session_secret {
+      Some(secret) => secret.as_bytes().to_vec(),
+      None => {
+        log::warn!("No ui.session_secret configured; login sessions will not survive a restart");
+        crypto::random_token(32)?.into_bytes()
+      }
+    };
+
+    Ok(Sessions {
+      key: PKey::hmac(&secret)?,
+      ttl: settings.session_ttl(),
+      secure: settings.secure_cookies()
+    })
+  }
+
+  /// When a session started now has to end.
+  pub fn expires_at(&self) -> DateTime<Utc> {
+    Utc::now() + self.ttl
+  }
+
+  /// The `Set-Cookie` value pointing the browser at a freshly started session.
+  pub fn start(&self, session_id: Uuid, expires_at: DateTime<Utc>) -> Result<HeaderValue, HeimdallrError> {
+    let cookie  = SessionCookie { sid: session_id, exp: expires_at.timestamp() };
+    let payload = serde_json::to_vec(&cookie).map_err(|_| HeimdallrError::JwtError("Unable to encode session"))?;
+    let payload = base64::encode_config(&payload, base64::URL_SAFE_NO_PAD);
+    let value   = format!("{}.{}", payload, self.sign(&payload)?);
+
+    Ok(self.cookie(SESSION_COOKIE, &value, Some(expires_at - Utc::now()), "Lax"))
+  }
+
+  /// The id of the session the request carries a valid, unexpired cookie for; whether the session
+  /// is still active has to be checked against the database.
+  pub fn current(&self, headers: &HeaderMap) -> Option<Uuid> {
+    let value = cookie(headers, SESSION_COOKIE)?;
+    let (payload, signature) = split_once(value, '.')?;
+
+    let expected = self.sign(payload).ok()?;
+    if !crypto::constant_time_eq(expected.as_bytes(), signature.as_bytes()) {
+      return None;
+    }
+
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+    let cookie: SessionCookie = serde_json::from_slice(&payload).ok()?;
+
+    Some(cookie.sid).filter(|_| cookie.exp > Utc::now().timestamp())
+  }
+
+  /// A `Set-Cookie` value removing the session.
+  pub fn end(&self) -> HeaderValue {
+    self.cookie(SESSION_COOKIE, "", Some(Duration::zero()), "Lax")
+  }
+
+  /// The CSRF token of the request, plus a `Set-Cookie` value when a new one had to be created.
+  pub fn csrf_token(&self, headers: &HeaderMap) -> Result<(String, Option<HeaderValue>), HeimdallrError> {
+    match cookie(headers, CSRF_COOKIE) {
+      Some(token) if !token.is_empty() => Ok((token.to_owned(), None)),
+      _ => {
+        let token  = crypto::random_token(32)?;
+        let cookie = self.cookie(CSRF_COOKIE, &token, None, "Strict");
+        Ok((token, Some(cookie)))
+      }
+    }
+  }
+
+  /// Checks that a submitted form echoes the CSRF cookie.
+  pub fn verify_csrf(&self, headers: &HeaderMap, submitted: &str) -> Result<(), ApiError> {
+    match cookie(headers, CSRF_COOKIE) {
+      Some(token) if !submitted.is_empty() && crypto::constant_time_eq(token.as_bytes(), submitted.as_bytes()) => Ok(()),
+      _ => Err(ApiError::access_denied("the form has expired, please try again"))
+    }
+  }
+
+  fn sign(&self, payload: &str) -> Result<String, HeimdallrError> {
+    let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+    signer.update(payload.as_bytes())?;
+    Ok(base64::encode_config(&signer.sign_to_vec()?, base64::URL_SAFE_NO_PAD))
+  }
+
+  fn cookie(&self, name: &str, value: &str, max_age: Option<Duration>, same_site: &str) -> HeaderValue {
+    let mut cookie = format!("{}={}; Path=/; HttpOnly; SameSite={}", name, value, same_site);
+    if let Some(max_age) = max_age {
+      cookie.push_str(&format!("; Max-Age={}", max_age.num_seconds()));
+    }
+    if self.secure {
+      cookie.push_str("; Secure");
+    }
+
+    // Names & values are base64url, which is always a valid header value.
+    HeaderValue::from_str(&cookie).expect("cookie is a valid header value")
+  }
+}
+
+/// Looks up a cookie sent by the browser.
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+  headers.get_all(header::COOKIE)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(';'))
+    .filter_map(|pair| split_once(pair.trim(), '='))
+    .find(|(key, _)| *key == name)
+    .map(|(_, value)| value)
+}
+
+fn split_once(value: &str, separator: char) -> Option<(&str, &str)> {
+  let index = value.find(separator)?;
+  Some((&value[..index], &value[index + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  fn sessions() -> Sessions {
+    Sessions::new(&Ui { session_secret: Some("correct horse battery staple".to_owned()), ..Default::default() }).unwrap()
+  }
+
+  fn request_with(cookie: &HeaderValue) -> HeaderMap {
+    let pair = cookie.to_str().unwrap().split(';').next().unwrap().to_owned();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::COOKIE, HeaderValue::from_str(&format!("theme=dark; {}", pair)).unwrap());
+    headers
+  }
+
+  #[test]
+  fn test_session_round_trip() {
+    let sessions   = sessions();
+    let session_id = Uuid::new_v4();
+    let cookie     = sessions.start(session_id, sessions.expires_at()).unwrap();
+
+    assert_eq!(sessions.current(&request_with(&cookie)), Some(session_id));
+    assert!(cookie.to_str().unwrap().contains("HttpOnly"));
+    assert!(cookie.to_str().unwrap().contains("Secure"));
+  }
+
+  #[test]
+  fn test_expired_session_is_rejected() {
+    let sessions = sessions();
+    let cookie   = sessions.start(Uuid::new_v4(), Utc::now() - Duration::minutes(1)).unwrap();
+
+    assert_eq!(sessions.current(&request_with(&cookie)), None);
+  }
+
+  #[test]
+  fn test_tampered_session_is_rejected() {
+    let sessions = sessions();
+    let cookie   = sessions.start(Uuid::new_v4(), sessions.expires_at()).unwrap();
+    let tampered = HeaderValue::from_str(&cookie.to_str().unwrap().replacen("heimdallr_session=e", "heimdallr_session=f", 1)).unwrap();
+
+    assert_eq!(sessions.current(&request_with(&tampered)), None);
+  }
+
+  #[test]
+  fn test_csrf_must_match_cookie() {
+    let sessions = sessions();
+    let (token, cookie) = sessions.csrf_token(&HeaderMap::new()).unwrap();
+    let headers = request_with(&cookie.unwrap());
+
+    assert!(sessions.verify_csrf(&headers, &token).is_ok());
+    assert!(sessions.verify_csrf(&headers, "forged").is_err());
+    assert!(sessions.verify_csrf(&HeaderMap::new(), &token).is_err());
+  }
+}
@@ -0,0 +1,34 @@
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+
+use crate::services::error::ApiError;
+use super::HttpContext;
+
+/// Content type of a signed UserInfo response.
+const JWT_CONTENT_TYPE: &str = "application/jwt";
+
+/// `GET|POST /userinfo` (OpenID Connect Core §5.3).
+///
+/// Answers with JSON, or with a signed JWT when the client sends `Accept: application/jwt`.
+pub async fn handle(context: &HttpContext, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+  let token = super::bearer_token(&request).ok_or_else(|| ApiError::invalid_token("missing bearer token"))?;
+  let info  = context.auth.userinfo(token)?;
+
+  if wants_jwt(&request) {
+    let mut response = Response::new(Body::from(context.auth.sign_userinfo(&info)?));
+    response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(JWT_CONTENT_TYPE));
+    Ok(super::no_store(response))
+  }
+  else {
+    Ok(super::no_store(super::json(StatusCode::OK, &serde_json::Value::Object(info.claims))))
+  }
+}
+
+fn wants_jwt(request: &Request<Body>) -> bool {
+  request.headers()
+    .get_all(header::ACCEPT)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .any(|value| value.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case(JWT_CONTENT_TYPE))
+}
@@ -6,6 +6,7 @@ use std::borrow::Cow;
 use crate::error::*;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct JwtClaims<'a> {
   // Time after which the JWT expires
   pub exp: i64,
@@ -37,7 +38,7 @@ pub struct JwtClaims<'a> {
   pub jti: Option<Cow<'a, str>>,
 
   #[serde(flatten)]
-  pub extra: HashMap<&'a str, serde_json::Value>
+  pub extra: HashMap<Cow<'a, str>, serde_json::Value>
 }
 
 impl<'a> JwtClaims<'a> {
@@ -101,6 +102,19 @@ impl<'a> JwtClaimsBuilder<'a> {
     self
   }
 
+  // Sets the JTI claim
+  pub fn jwt_id<J>(&mut self, value: J) -> &mut Self
+    where J: Into<Cow<'a, str>> {
+    self.jti = Some(value.into());
+    self
+  }
+
+  // Sets the IAT claim
+  pub fn issued_at<IAT: Into<i64>>(&mut self, issued_at: IAT) -> &mut Self {
+    self.iat = Some(issued_at.into());
+    self
+  }
+
   // Sets the EXP claim using a duration
   pub fn expires_in(&mut self, expires: Duration) -> &mut Self {
     let exp  = Utc::now() + expires;
@@ -127,11 +141,6 @@ impl<'a> JwtClaimsBuilder<'a> {
   }
 
   pub fn build(&self) -> Result<JwtClaims<'a>, HeimdallrError> {
-    // iss: None,
-    // sub: None,
-    // aud: None,
-    // jti: None,
-    // extra: HashMap::new()
     Ok(JwtClaims {
       exp: match self.exp {
         Some(value) => value,
@@ -139,8 +148,11 @@ impl<'a> JwtClaimsBuilder<'a> {
       },
       nbf: self.nbf.unwrap_or_else(|| Utc::now().timestamp()),
       iat: self.iat.unwrap_or_else(|| Utc::now().timestamp()),
-      // iss: self.iss.as_ref(),
-      ..Default::default()
+      iss: self.iss.clone(),
+      sub: self.sub.clone(),
+      aud: self.aud.clone(),
+      jti: self.jti.clone(),
+      extra: self.extra.iter().map(|(key, value)| (Cow::Owned(key.clone()), value.clone())).collect()
     })
   }
 }
@@ -204,4 +216,22 @@ mod tests {
     assert_eq!(claims.nbf, expected_nbf);
     Ok(())
   }
+
+  #[test]
+  fn test_builder_copies_registered_and_extra_claims() -> Result<(), HeimdallrError> {
+    let claims = JwtClaimsBuilder::default()
+      .issuer("https://heimdallr.example")
+      .subject("takara")
+      .audience("doge")
+      .jwt_id("42")
+      .add_claim("scope", serde_json::json!("read write"))
+      .build()?;
+
+    assert_eq!(claims.iss.as_ref().map(|v| v.as_ref()), Some("https://heimdallr.example"));
+    assert_eq!(claims.sub.as_ref().map(|v| v.as_ref()), Some("takara"));
+    assert_eq!(claims.aud.as_ref().map(|v| v.as_ref()), Some("doge"));
+    assert_eq!(claims.jti.as_ref().map(|v| v.as_ref()), Some("42"));
+    assert_eq!(claims.extra.get("scope"), Some(&serde_json::json!("read write")));
+    Ok(())
+  }
 }
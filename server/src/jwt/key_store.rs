@@ -0,0 +1,325 @@
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::errors::ErrorKind;
+use diesel::pg::PgConnection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use super::keys::{public_jwk_json, Algorithm};
+use crate::db::models::SigningKey;
+use crate::error::*;
+
+/// Reasons a token can be rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenError {
+  Malformed(String),
+  MissingKeyId,
+  UnknownKey(String),
+  AlgorithmMismatch,
+  InvalidSignature,
+  Expired,
+  NotYetValid,
+  InvalidIssuer,
+  InvalidAudience,
+  MissingClaim(String),
+  Other(String)
+}
+
+impl std::error::Error for TokenError {}
+
+impl fmt::Display for TokenError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    use TokenError::*;
+
+    match self {
+      Malformed(reason)   => write!(f, "token is malformed ({})", reason),
+      MissingKeyId        => write!(f, "token header has no `kid`"),
+      UnknownKey(kid)     => write!(f, "token was signed by unknown or retired key `{}`", kid),
+      AlgorithmMismatch   => write!(f, "token `alg` does not match the signing key"),
+      InvalidSignature    => write!(f, "signature does not match"),
+      Expired             => write!(f, "token has expired"),
+      NotYetValid         => write!(f, "token is not valid yet (nbf is in the future)"),
+      InvalidIssuer       => write!(f, "token was not issued by this server"),
+      InvalidAudience     => write!(f, "token audience does not match"),
+      MissingClaim(claim) => write!(f, "token is missing the required `{}` claim", claim),
+      Other(reason)       => write!(f, "{}", reason)
+    }
+  }
+}
+
+impl From<jsonwebtoken::errors::Error> for TokenError {
+  fn from(err: jsonwebtoken::errors::Error) -> TokenError {
+    match err.kind() {
+      ErrorKind::InvalidToken | ErrorKind::Base64(_) | ErrorKind::Json(_) | ErrorKind::Utf8(_) => TokenError::Malformed(err.to_string()),
+      ErrorKind::InvalidSignature        => TokenError::InvalidSignature,
+      ErrorKind::ExpiredSignature        => TokenError::Expired,
+      ErrorKind::ImmatureSignature       => TokenError::NotYetValid,
+      ErrorKind::InvalidIssuer           => TokenError::InvalidIssuer,
+      ErrorKind::InvalidAudience         => TokenError::InvalidAudience,
+      ErrorKind::InvalidAlgorithm        => TokenError::AlgorithmMismatch,
+      ErrorKind::MissingRequiredClaim(c) => TokenError::MissingClaim(c.clone()),
+      _                                  => TokenError::Other(err.to_string())
+    }
+  }
+}
+
+/// Expectations a token has to satisfy in addition to a valid signature.
+#[derive(Debug, Clone, Default)]
+pub struct TokenValidation {
+  pub issuer: Option<String>,
+  pub audience: Option<String>,
+  pub leeway: u64,
+  /// Accepts tokens past their `exp`, e.g. ID tokens passed back as a hint.
+  pub allow_expired: bool
+}
+
+struct SigningEntry {
+  kid: String,
+  algorithm: Algorithm,
+  key: EncodingKey
+}
+
+struct VerifyingEntry {
+  algorithm: Algorithm,
+  key: DecodingKey
+}
+
+/// In-memory view of the `keys` table used to sign and verify tokens.
+pub struct KeyStore {
+  signing: Option<SigningEntry>,
+  verifying: HashMap<String, VerifyingEntry>,
+  jwks: Vec<serde_json::Value>
+}
+
+impl KeyStore {
+  /// Loads the active key & every key that can still be used for verification.
+  pub fn load(conn: &PgConnection) -> Result<Self, HeimdallrError> {
+    let mut signing   = None;
+    let mut verifying = HashMap::new();
+    let mut jwks      = Vec::new();
+
+    for key in SigningKey::verifiable(conn)? {
+      let algorithm = key.algorithm()?;
+
+      if key.is_active() {
+        signing = Some(SigningEntry {
+          kid: key.kid.clone(),
+          algorithm,
+          key: encoding_key(algorithm, &key.private_key)?
+        });
+      }
+
+      verifying.insert(key.kid.clone(), VerifyingEntry {
+        algorithm,
+        key: decoding_key(algorithm, &key.public_key)?
+      });
+      jwks.push(public_jwk_json(&key.kid, algorithm, &key.public_key)?);
+    }
+
+    Ok(Self { signing, verifying, jwks })
+  }
+
+  /// JWK Set of every key tokens can currently be verified with.
+  pub fn jwks(&self) -> serde_json::Value {
+    serde_json::json!({ "keys": self.jwks })
+  }
+
+  /// Key id of the key new tokens are signed with.
+  pub fn signing_kid(&self) -> Option<&str> {
+    self.signing.as_ref().map(|entry| entry.kid.as_str())
+  }
+
+  /// Algorithm of the key new tokens are signed with.
+  pub fn signing_algorithm(&self) -> Option<Algorithm> {
+    self.signing.as_ref().map(|entry| entry.algorithm)
+  }
+
+  /// Signs a set of claims with the active key.
+  pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, HeimdallrError> {
+    self.sign_with_type(claims, "JWT")
+  }
+
+  /// Signs a set of claims with the active key, using a custom `typ` header.
+  pub fn sign_with_type<T: Serialize>(&self, claims: &T, typ: &str) -> Result<String, HeimdallrError> {
+    let entry = self.signing.as_ref().ok_or(HeimdallrError::JwtError("No active signing key; run `heimdallr keys generate --activate`"))?;
+
+    let mut header = Header::new(jwt_algorithm(entry.algorithm));
+    header.kid = Some(entry.kid.clone());
+    header.typ = Some(typ.to_owned());
+
+    jsonwebtoken::encode(&header, claims, &entry.key).map_err(|_| HeimdallrError::JwtError("Unable to sign token"))
+  }
+
+  /// Verifies the signature & registered claims of a token, returning the raw claim set.
+  pub fn verify(&self, token: &str, expected: &TokenValidation) -> Result<serde_json::Value, TokenError> {
+    let header = jsonwebtoken::decode_header(token)?;
+    let kid    = header.kid.ok_or(TokenError::MissingKeyId)?;
+    let entry  = self.verifying.get(&kid).ok_or_else(|| TokenError::UnknownKey(kid.clone()))?;
+
+    if header.alg != jwt_algorithm(entry.algorithm) {
+      return Err(TokenError::AlgorithmMismatch);
+    }
+
+    let mut validation = Validation::new(header.alg);
+    validation.leeway = expected.leeway;
+    validation.validate_exp = !expected.allow_expired;
+    if let Some(issuer) = &expected.issuer {
+      validation.set_issuer(&[issuer]);
+    }
+    if let Some(audience) = &expected.audience {
+      validation.set_audience(&[audience]);
+    }
+
+    let data = jsonwebtoken::decode::<serde_json::Value>(token, &entry.key, &validation)?;
+    Ok(data.claims)
+  }
+}
+
+/// Key store shared between services, refreshed from the database in the background.
+#[derive(Clone)]
+pub struct SharedKeyStore(Arc<RwLock<KeyStore>>);
+
+impl SharedKeyStore {
+  pub fn new(store: KeyStore) -> Self {
+    SharedKeyStore(Arc::new(RwLock::new(store)))
+  }
+
+  pub fn read(&self) -> RwLockReadGuard<'_, KeyStore> {
+    // A panic while holding the lock can only happen mid-assignment, so the data is still sound.
+    self.0.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+
+  /// Reloads every key from the database, e.g. after a rotation.
+  pub fn reload(&self, conn: &PgConnection) -> Result<(), HeimdallrError> {
+    let store = KeyStore::load(conn)?;
+    *self.0.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = store;
+    Ok(())
+  }
+}
+
+/// Splits a token into its header & claims without checking the signature.
+pub fn decode_unverified(token: &str) -> Result<(serde_json::Value, serde_json::Value), TokenError> {
+  let mut parts = token.split('.');
+
+  let (header, claims) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+    (Some(header), Some(claims), Some(_), None) => (header, claims),
+    _ => return Err(TokenError::Malformed("expected three dot separated segments".to_owned()))
+  };
+
+  Ok((decode_segment(header)?, decode_segment(claims)?))
+}
+
+fn decode_segment(segment: &str) -> Result<serde_json::Value, TokenError> {
+  let bytes = base64::decode_config(segment, base64::URL_SAFE_NO_PAD).map_err(|err| TokenError::Malformed(err.to_string()))?;
+  serde_json::from_slice(&bytes).map_err(|err| TokenError::Malformed(err.to_string()))
+}
+
+fn jwt_algorithm(algorithm: Algorithm) -> jsonwebtoken::Algorithm {
+  match algorithm {
+    Algorithm::ES256 => jsonwebtoken::Algorithm::ES256,
+    Algorithm::RS256 => jsonwebtoken::Algorithm::RS256,
+    Algorithm::EdDSA => jsonwebtoken::Algorithm::EdDSA
+  }
+}
+
+fn encoding_key(algorithm: Algorithm, pem: &str) -> Result<EncodingKey, HeimdallrError> {
+  let key = match algorithm {
+    Algorithm::ES256 => EncodingKey::from_ec_pem(pem.as_bytes()),
+    Algorithm::RS256 => EncodingKey::from_rsa_pem(pem.as_bytes()),
+    Algorithm::EdDSA => EncodingKey::from_ed_pem(pem.as_bytes())
+  };
+  key.map_err(|err| HeimdallrError::KeyError(err.to_string()))
+}
+
+fn decoding_key(algorithm: Algorithm, pem: &str) -> Result<DecodingKey, HeimdallrError> {
+  let key = match algorithm {
+    Algorithm::ES256 => DecodingKey::from_ec_pem(pem.as_bytes()),
+    Algorithm::RS256 => DecodingKey::from_rsa_pem(pem.as_bytes()),
+    Algorithm::EdDSA => DecodingKey::from_ed_pem(pem.as_bytes())
+  };
+  key.map_err(|err| HeimdallrError::KeyError(err.to_string()))
+}
+
+/// A store signing with a single key pair, without a database.
+#[cfg(test)]
+impl KeyStore {
+  pub(crate) fn for_pair(pair: &super::KeyPair) -> Self {
+    let mut verifying = HashMap::new();
+    verifying.insert(pair.kid.clone(), VerifyingEntry {
+      algorithm: pair.algorithm,
+      key: decoding_key(pair.algorithm, &pair.public_pem).unwrap()
+    });
+
+    KeyStore {
+      signing: Some(SigningEntry {
+        kid: pair.kid.clone(),
+        algorithm: pair.algorithm,
+        key: encoding_key(pair.algorithm, &pair.private_pem).unwrap()
+      }),
+      verifying,
+      jwks: vec![public_jwk_json(&pair.kid, pair.algorithm, &pair.public_pem).unwrap()]
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::jwt::{JwtClaimsBuilder, KeyPair};
+
+  use chrono::Duration;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_jwks_lists_verifying_keys() -> Result<(), HeimdallrError> {
+    let pair = KeyPair::generate(Algorithm::ES256)?;
+    let jwks = KeyStore::for_pair(&pair).jwks();
+
+    assert_eq!(jwks["keys"][0]["kid"], pair.kid.as_str());
+    assert_eq!(jwks["keys"][0]["alg"], "ES256");
+    Ok(())
+  }
+
+  #[test]
+  fn test_sign_and_verify() -> Result<(), HeimdallrError> {
+    for algorithm in &[Algorithm::ES256, Algorithm::RS256, Algorithm::EdDSA] {
+      let store  = KeyStore::for_pair(&KeyPair::generate(*algorithm)?);
+      let claims = JwtClaimsBuilder::new().issuer("heimdallr").subject("takara").expires_in(Duration::minutes(5)).build()?;
+      let token  = store.sign(&claims)?;
+
+      let expected = TokenValidation { issuer: Some("heimdallr".to_owned()), ..Default::default() };
+      let verified = store.verify(&token, &expected).unwrap();
+      assert_eq!(verified["sub"], "takara");
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_verify_reports_reason() -> Result<(), HeimdallrError> {
+    let store = KeyStore::for_pair(&KeyPair::generate(Algorithm::ES256)?);
+
+    let expired = store.sign(&JwtClaimsBuilder::new().expires_in(Duration::minutes(-5)).build()?)?;
+    assert_eq!(store.verify(&expired, &TokenValidation::default()), Err(TokenError::Expired));
+
+    let foreign = KeyStore::for_pair(&KeyPair::generate(Algorithm::ES256)?).sign(&JwtClaimsBuilder::new().expires_in(Duration::minutes(5)).build()?)?;
+    assert!(matches!(store.verify(&foreign, &TokenValidation::default()), Err(TokenError::UnknownKey(_))));
+
+    let wrong_issuer = store.sign(&JwtClaimsBuilder::new().issuer("mallory").expires_in(Duration::minutes(5)).build()?)?;
+    let expected = TokenValidation { issuer: Some("heimdallr".to_owned()), ..Default::default() };
+    assert_eq!(store.verify(&wrong_issuer, &expected), Err(TokenError::InvalidIssuer));
+    Ok(())
+  }
+
+  #[test]
+  fn test_decode_unverified() -> Result<(), HeimdallrError> {
+    let store = KeyStore::for_pair(&KeyPair::generate(Algorithm::EdDSA)?);
+    let token = store.sign(&JwtClaimsBuilder::new().subject("takara").expires_in(Duration::minutes(5)).build()?)?;
+
+    let (header, claims) = decode_unverified(&token).unwrap();
+    assert_eq!(header["alg"], "EdDSA");
+    assert_eq!(claims["sub"], "takara");
+    assert!(decode_unverified("not-a-token").is_err());
+    Ok(())
+  }
+}
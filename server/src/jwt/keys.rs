@@ -0,0 +1,224 @@
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{HasPublic, Id, PKey, PKeyRef, Private, Public};
+use openssl::rsa::Rsa;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::*;
+
+/// Minimum modulus size accepted for RSA signing keys.
+const MIN_RSA_BITS: u32 = 2048;
+
+/// Signing algorithms supported for issued tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+  ES256,
+  RS256,
+  EdDSA
+}
+
+impl Algorithm {
+  /// Every algorithm keys can be generated or imported for.
+  pub const ALL: [Algorithm; 3] = [Algorithm::ES256, Algorithm::RS256, Algorithm::EdDSA];
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Algorithm::ES256 => "ES256",
+      Algorithm::RS256 => "RS256",
+      Algorithm::EdDSA => "EdDSA"
+    }
+  }
+}
+
+impl fmt::Display for Algorithm {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for Algorithm {
+  type Err = HeimdallrError;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "ES256" => Ok(Algorithm::ES256),
+      "RS256" => Ok(Algorithm::RS256),
+      "EdDSA" => Ok(Algorithm::EdDSA),
+      other   => Err(HeimdallrError::KeyError(format!("unsupported algorithm `{}`", other)))
+    }
+  }
+}
+
+/// PEM encoded key material for a single signing key.
+#[derive(Debug, Clone)]
+pub struct KeyPair {
+  pub kid: String,
+  pub algorithm: Algorithm,
+  pub private_pem: String,
+  pub public_pem: String
+}
+
+impl KeyPair {
+  /// Generates a brand new key pair for the given algorithm.
+  pub fn generate(algorithm: Algorithm) -> Result<Self, HeimdallrError> {
+    let pkey = match algorithm {
+      Algorithm::ES256 => {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        PKey::from_ec_key(EcKey::generate(&group)?)?
+      },
+      Algorithm::RS256 => PKey::from_rsa(Rsa::generate(MIN_RSA_BITS)?)?,
+      Algorithm::EdDSA => PKey::generate_ed25519()?
+    };
+
+    Self::from_pkey(algorithm, &pkey)
+  }
+
+  /// Imports an existing private key in either PKCS#8 or traditional PEM format.
+  ///
+  /// The algorithm is inferred from the key type; EC keys must use the P-256 curve.
+  pub fn import(pem: &[u8]) -> Result<Self, HeimdallrError> {
+    let pkey = PKey::private_key_from_pem(pem)?;
+    let algorithm = detect_algorithm(&pkey)?;
+    Self::from_pkey(algorithm, &pkey)
+  }
+
+  fn from_pkey(algorithm: Algorithm, pkey: &PKey<Private>) -> Result<Self, HeimdallrError> {
+    Ok(KeyPair {
+      kid: thumbprint(pkey)?,
+      algorithm,
+      private_pem: pem_to_string(pkey.private_key_to_pem_pkcs8()?)?,
+      public_pem: pem_to_string(pkey.public_key_to_pem()?)?
+    })
+  }
+}
+
+/// Figures out which signing algorithm a key can be used with.
+pub fn detect_algorithm<T: HasPublic>(pkey: &PKeyRef<T>) -> Result<Algorithm, HeimdallrError> {
+  match pkey.id() {
+    Id::RSA => {
+      if pkey.bits() < MIN_RSA_BITS {
+        return Err(HeimdallrError::KeyError(format!("RSA keys must be at least {} bits", MIN_RSA_BITS)));
+      }
+      Ok(Algorithm::RS256)
+    },
+    Id::EC => match pkey.ec_key()?.group().curve_name() {
+      Some(Nid::X9_62_PRIME256V1) => Ok(Algorithm::ES256),
+      _ => Err(HeimdallrError::KeyError("only P-256 elliptic curve keys are supported".to_owned()))
+    },
+    Id::ED25519 => Ok(Algorithm::EdDSA),
+    _ => Err(HeimdallrError::KeyError("unsupported key type".to_owned()))
+  }
+}
+
+/// Loads a PEM encoded public key.
+pub fn public_key_from_pem(pem: &str) -> Result<PKey<Public>, HeimdallrError> {
+  Ok(PKey::public_key_from_pem(pem.as_bytes())?)
+}
+
+/// Builds the public JSON Web Key members (RFC 7517) for a key, excluding `kid`, `alg` & `use`.
+pub fn public_jwk<T: HasPublic>(pkey: &PKeyRef<T>) -> Result<BTreeMap<&'static str, String>, HeimdallrError> {
+  let mut jwk = BTreeMap::new();
+
+  match pkey.id() {
+    Id::RSA => {
+      let rsa = pkey.rsa()?;
+      jwk.insert("kty", "RSA".to_owned());
+      jwk.insert("n", b64(&rsa.n().to_vec()));
+      jwk.insert("e", b64(&rsa.e().to_vec()));
+    },
+    Id::EC => {
+      let ec = pkey.ec_key()?;
+      let mut ctx = BigNumContext::new()?;
+      let mut x = BigNum::new()?;
+      let mut y = BigNum::new()?;
+      ec.public_key().affine_coordinates_gfp(ec.group(), &mut x, &mut y, &mut ctx)?;
+
+      jwk.insert("kty", "EC".to_owned());
+      jwk.insert("crv", "P-256".to_owned());
+      jwk.insert("x", b64(&x.to_vec_padded(32)?));
+      jwk.insert("y", b64(&y.to_vec_padded(32)?));
+    },
+    Id::ED25519 => {
+      jwk.insert("kty", "OKP".to_owned());
+      jwk.insert("crv", "Ed25519".to_owned());
+      jwk.insert("x", b64(&pkey.raw_public_key()?));
+    },
+    _ => return Err(HeimdallrError::KeyError("unsupported key type".to_owned()))
+  }
+
+  Ok(jwk)
+}
+
+/// Builds a complete public JWK, suitable for publishing in a JWKS document.
+pub fn public_jwk_json(kid: &str, algorithm: Algorithm, public_pem: &str) -> Result<serde_json::Value, HeimdallrError> {
+  let pkey = public_key_from_pem(public_pem)?;
+  let mut jwk = json!(public_jwk(&pkey)?);
+  jwk["kid"] = json!(kid);
+  jwk["alg"] = json!(algorithm.as_str());
+  jwk["use"] = json!("sig");
+  Ok(jwk)
+}
+
+/// Computes the RFC 7638 JWK thumbprint, which is used as the key id.
+pub fn thumbprint<T: HasPublic>(pkey: &PKeyRef<T>) -> Result<String, HeimdallrError> {
+  // The required members are already in lexicographic order thanks to the BTreeMap,
+  // and serde_json emits them without any insignificant whitespace.
+  let canonical = serde_json::to_string(&public_jwk(pkey)?).map_err(|err| HeimdallrError::KeyError(err.to_string()))?;
+  Ok(b64(&hash(MessageDigest::sha256(), canonical.as_bytes())?))
+}
+
+fn b64(bytes: &[u8]) -> String {
+  base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn pem_to_string(pem: Vec<u8>) -> Result<String, HeimdallrError> {
+  String::from_utf8(pem).map_err(|err| HeimdallrError::KeyError(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_generate_and_import_round_trip() -> Result<(), HeimdallrError> {
+    for algorithm in &[Algorithm::ES256, Algorithm::RS256, Algorithm::EdDSA] {
+      let generated = KeyPair::generate(*algorithm)?;
+      let imported  = KeyPair::import(generated.private_pem.as_bytes())?;
+
+      assert_eq!(imported.algorithm, *algorithm);
+      assert_eq!(imported.kid, generated.kid);
+      assert_eq!(imported.public_pem, generated.public_pem);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_rejects_small_rsa_keys() -> Result<(), HeimdallrError> {
+    let pkey = PKey::from_rsa(Rsa::generate(1024)?)?;
+    let pem  = pkey.private_key_to_pem_pkcs8()?;
+    assert!(KeyPair::import(&pem).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_rejects_other_curves() -> Result<(), HeimdallrError> {
+    let group = EcGroup::from_curve_name(Nid::SECP384R1)?;
+    let pkey  = PKey::from_ec_key(EcKey::generate(&group)?)?;
+    let pem   = pkey.private_key_to_pem_pkcs8()?;
+    assert!(KeyPair::import(&pem).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_algorithm_from_str() {
+    assert_eq!("EdDSA".parse::<Algorithm>().unwrap(), Algorithm::EdDSA);
+    assert!("HS256".parse::<Algorithm>().is_err());
+  }
+}
@@ -3,6 +3,12 @@
 mod claims;
 pub use claims::*;
 
+mod keys;
+pub use keys::*;
+
+mod key_store;
+pub use key_store::*;
+
 #[derive(Debug)]
 pub enum JwtType {
   AccessToken,
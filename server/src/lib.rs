@@ -8,13 +8,30 @@ extern crate clap;
 extern crate diesel;
 
 pub mod app;
+pub mod authz;
+pub mod bootstrap;
 pub mod commands;
+pub mod crypto;
 pub mod db;
 pub mod error;
+pub mod http;
 pub mod logging;
+pub mod logout;
+pub mod mail;
+pub mod metrics;
+pub mod mfa;
+pub mod oidc;
 pub mod jwt;
+pub mod lockout;
+pub mod password;
+pub mod ratelimit;
+pub mod rbac;
+pub mod registration;
 pub mod services;
 pub mod settings;
+pub mod sms;
+pub mod tokens;
+pub mod webauthn;
 
 pub mod prelude {
   pub use crate::app::*;
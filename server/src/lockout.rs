@@ -0,0 +1,300 @@
+//! Brute-force protection of password logins: failures are counted per username & per client
+//! address, every failure past a few free ones makes the next attempt wait twice as long, and too
+//! many for a username lock the account until it cools down or an administrator unlocks it.
+//!
+//! Wrong codes in the two-factor step are counted per user the same way.
+//!
+//! The counters live in the database so every replica sees the same ones.
+
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use crate::db::models::{normalize, LoginFailure, User};
+use crate::error::*;
+use crate::settings::Lockout as LockoutSettings;
+
+/// Longest key the `login_failures` table holds.
+const MAX_KEY_LENGTH: usize = 255;
+
+/// A network of addresses, e.g. `10.0.0.0/8`; a single address is a network of one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Network {
+  address: IpAddr,
+  prefix: u8
+}
+
+impl Network {
+  fn parse(value: &str) -> Option<Self> {
+    let mut parts = value.trim().splitn(2, '/');
+    let address: IpAddr = parts.next()?.parse().ok()?;
+    let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+
+    let prefix = match parts.next() {
+      Some(prefix) => prefix.parse().ok().filter(|&prefix| prefix <= max_prefix)?,
+      None         => max_prefix
+    };
+
+    Some(Network { address, prefix })
+  }
+
+  fn contains(&self, address: IpAddr) -> bool {
+    match (self.address, address) {
+      (IpAddr::V4(network), IpAddr::V4(address)) => {
+        let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+        u32::from(network) & mask == u32::from(address) & mask
+      },
+      (IpAddr::V6(network), IpAddr::V6(address)) => {
+        let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+        u128::from(network) & mask == u128::from(address) & mask
+      },
+      _ => false
+    }
+  }
+}
+
+/// The lockout settings of a deployment, checked on boot.
+#[derive(Debug, Clone)]
+pub struct Lockout {
+  user_free_attempts: i32,
+  user_lockout_attempts: i32,
+  lockout_duration: Duration,
+  ip_free_attempts: i32,
+  base_delay: Duration,
+  max_delay: Duration,
+  reset_after: Duration,
+  trusted_proxies: Vec<Network>
+}
+
+impl Default for Lockout {
+  fn default() -> Self {
+    Lockout::new(&LockoutSettings::default()).expect("the default lockout settings are valid")
+  }
+}
+
+impl Lockout {
+  pub fn new(settings: &LockoutSettings) -> Result<Self, HeimdallrError> {
+    let invalid = |message: String| HeimdallrError::ConfigError(config::ConfigError::Message(format!("lockout.{}", message)));
+
+    let trusted_proxies = settings.trusted_proxies
+      .iter()
+      .map(|proxy| Network::parse(proxy).ok_or_else(|| invalid(format!("trusted_proxies `{}` is not an address or CIDR range", proxy))))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    if settings.user_lockout_attempts() <= settings.user_free_attempts() {
+      return Err(invalid("user_lockout_attempts must be more than user_free_attempts".to_owned()));
+    }
+    if settings.base_delay() <= Duration::zero() || settings.max_delay() < settings.base_delay() {
+      return Err(invalid("base_delay_seconds must be positive and at most max_delay_seconds".to_owned()));
+    }
+
+    Ok(Lockout {
+      user_free_attempts: settings.user_free_attempts() as i32,
+      user_lockout_attempts: settings.user_lockout_attempts() as i32,
+      lockout_duration: settings.lockout_duration(),
+      ip_free_attempts: settings.ip_free_attempts() as i32,
+      base_delay: settings.base_delay(),
+      max_delay: settings.max_delay(),
+      reset_after: settings.reset_after(),
+      trusted_proxies
+    })
+  }
+
+  /// The address a request comes from: its peer, unless that is a trusted proxy, in which case
+  /// `X-Forwarded-For` is followed from the right up to the first address that isn't one.
+  pub fn client_ip(&self, peer: Option<IpAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+    let mut address = peer?;
+
+    if let Some(forwarded_for) = forwarded_for {
+      for hop in forwarded_for.rsplit(',') {
+        if !self.is_trusted(address) {
+          break;
+        }
+        match hop.trim().parse() {
+          Ok(hop) => address = hop,
+          Err(_)  => break
+        }
+      }
+    }
+
+    Some(address)
+  }
+
+  /// How long until logins as a username, or from an address, are accepted again; `None` when they
+  /// are accepted now.
+  pub fn retry_after(&self, conn: &PgConnection, username: &str, ip: Option<IpAddr>) -> Result<Option<Duration>, HeimdallrError> {
+    locked_for(conn, &keys(username, ip))
+  }
+
+  /// How long until second factor codes of a user are accepted again; `None` when they are now.
+  pub fn mfa_retry_after(&self, conn: &PgConnection, user_id: Uuid) -> Result<Option<Duration>, HeimdallrError> {
+    locked_for(conn, &[mfa_key(user_id)])
+  }
+
+  /// Counts a failed login as a username from an address, which holds off the next attempts.
+  ///
+  /// Unknown usernames are counted too, so guessing reveals nothing about which ones exist.
+  pub fn record_failure(&self, conn: &PgConnection, username: &str, ip: Option<IpAddr>) -> Result<(), HeimdallrError> {
+    let forget_before = Utc::now().naive_utc() - self.reset_after;
+
+    let user = LoginFailure::record(conn, &user_key(username), forget_before, |failures| self.user_delay(failures))?;
+    if user.failures == self.user_lockout_attempts {
+      log::warn!("Locked logins as `{}` for {} minutes after {} failures", normalize(username), self.lockout_duration.num_minutes(), user.failures);
+    }
+
+    if let Some(ip) = ip {
+      LoginFailure::record(conn, &ip_key(ip), forget_before, |failures| self.ip_delay(failures))?;
+    }
+
+    Ok(())
+  }
+
+  /// Resets the failures of a username after a successful login.
+  ///
+  /// The address keeps its failures: one account that can log in must not clear the way for
+  /// guessing the passwords of others.
+  pub fn record_success(&self, conn: &PgConnection, username: &str) -> Result<(), HeimdallrError> {
+    LoginFailure::clear(conn, &user_key(username))?;
+    Ok(())
+  }
+
+  /// Counts a wrong code in the two-factor step, returning how many there were in a row.
+  pub fn record_mfa_failure(&self, conn: &PgConnection, user_id: Uuid) -> Result<i32, HeimdallrError> {
+    let forget_before = Utc::now().naive_utc() - self.reset_after;
+    Ok(LoginFailure::record(conn, &mfa_key(user_id), forget_before, |failures| self.user_delay(failures))?.failures)
+  }
+
+  /// Resets the wrong codes of a user after the two-factor step succeeded.
+  pub fn record_mfa_success(&self, conn: &PgConnection, user_id: Uuid) -> Result<(), HeimdallrError> {
+    LoginFailure::clear(conn, &mfa_key(user_id))?;
+    Ok(())
+  }
+
+  /// Forgets failures that no longer hold anything off.
+  pub fn prune(&self, conn: &PgConnection) -> Result<usize, HeimdallrError> {
+    LoginFailure::delete_stale(conn, Utc::now().naive_utc() - self.reset_after)
+  }
+
+  fn user_delay(&self, failures: i32) -> Option<Duration> {
+    if failures >= self.user_lockout_attempts {
+      Some(self.lockout_duration)
+    } else {
+      backoff(failures, self.user_free_attempts, self.base_delay, self.max_delay)
+    }
+  }
+
+  fn ip_delay(&self, failures: i32) -> Option<Duration> {
+    backoff(failures, self.ip_free_attempts, self.base_delay, self.max_delay)
+  }
+
+  fn is_trusted(&self, address: IpAddr) -> bool {
+    self.trusted_proxies.iter().any(|network| network.contains(address))
+  }
+}
+
+/// How long the longest lock on any of the keys still holds.
+fn locked_for(conn: &PgConnection, keys: &[String]) -> Result<Option<Duration>, HeimdallrError> {
+  let now = Utc::now().naive_utc();
+
+  Ok(
+    LoginFailure::find_all(conn, keys)?
+      .into_iter()
+      .filter_map(|failure| failure.locked_until)
+      .max()
+      .map(|locked_until| locked_until - now)
+      .filter(|wait| *wait > Duration::zero())
+  )
+}
+
+/// Unlocks a user locked out by failed logins or wrong codes, returning whether they had any
+/// failures.
+pub fn unlock(conn: &PgConnection, user: &User) -> Result<bool, HeimdallrError> {
+  let logins = LoginFailure::clear(conn, &user_key(&user.username))?;
+  let codes  = LoginFailure::clear(conn, &mfa_key(user.id))?;
+  Ok(logins || codes)
+}
+
+/// The wait after a number of failures: none for the free ones, then `base` doubling with every
+/// further failure, up to `max`.
+fn backoff(failures: i32, free: i32, base: Duration, max: Duration) -> Option<Duration> {
+  if failures <= free {
+    return None;
+  }
+
+  let doublings = (failures - free - 1).min(32) as u32;
+  let seconds   = base.num_seconds().saturating_mul(1i64 << doublings);
+  Some(Duration::seconds(seconds).min(max))
+}
+
+fn keys(username: &str, ip: Option<IpAddr>) -> Vec<String> {
+  let mut keys = vec![user_key(username)];
+  keys.extend(ip.map(ip_key));
+  keys
+}
+
+fn user_key(username: &str) -> String {
+  format!("user:{}", normalize(username)).chars().take(MAX_KEY_LENGTH).collect()
+}
+
+fn mfa_key(user_id: Uuid) -> String {
+  format!("mfa:{}", user_id)
+}
+
+/// IPv4 addresses are counted one by one; IPv6 ones by /64, the smallest network usually handed
+/// out to a single customer.
+pub fn ip_key(ip: IpAddr) -> String {
+  match ip {
+    IpAddr::V4(address) => format!("ip:{}", address),
+    IpAddr::V6(address) => {
+      let network = std::net::Ipv6Addr::from(u128::from(address) & !(u128::MAX >> 64));
+      format!("ip:{}/64", network)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn lockout(trusted_proxies: &[&str]) -> Lockout {
+    Lockout::new(&LockoutSettings {
+      trusted_proxies: trusted_proxies.iter().map(|proxy| proxy.to_string()).collect(),
+      ..Default::default()
+    }).unwrap()
+  }
+
+  #[test]
+  fn test_backoff_doubles_up_to_the_lockout() {
+    let lockout = lockout(&[]);
+    let delays: Vec<Option<i64>> = (4..=10).map(|failures| lockout.user_delay(failures).map(|delay| delay.num_seconds())).collect();
+
+    assert_eq!(delays, vec![None, None, Some(1), Some(2), Some(4), Some(8), Some(16)]);
+    assert_eq!(lockout.user_delay(19), Some(Duration::seconds(900)));
+    assert_eq!(lockout.user_delay(20), Some(Duration::minutes(60)));
+    assert_eq!(lockout.ip_delay(20), None);
+    assert_eq!(lockout.ip_delay(1000), Some(Duration::seconds(900)));
+  }
+
+  #[test]
+  fn test_client_ip_only_trusts_configured_proxies() {
+    let ip = |value: &str| value.parse::<IpAddr>().unwrap();
+    let behind_proxies = lockout(&["10.0.0.0/8", "2001:db8::1"]);
+
+    assert_eq!(lockout(&[]).client_ip(Some(ip("10.0.0.1")), Some("203.0.113.9")), Some(ip("10.0.0.1")));
+    assert_eq!(behind_proxies.client_ip(Some(ip("10.0.0.1")), Some("198.51.100.7, 203.0.113.9, 10.1.2.3")), Some(ip("203.0.113.9")));
+    assert_eq!(behind_proxies.client_ip(Some(ip("2001:db8::1")), Some("203.0.113.9")), Some(ip("203.0.113.9")));
+    assert_eq!(behind_proxies.client_ip(Some(ip("192.0.2.1")), Some("203.0.113.9")), Some(ip("192.0.2.1")));
+    assert_eq!(behind_proxies.client_ip(Some(ip("10.0.0.1")), Some("garbage")), Some(ip("10.0.0.1")));
+    assert_eq!(behind_proxies.client_ip(None, Some("203.0.113.9")), None);
+  }
+
+  #[test]
+  fn test_keys() {
+    assert_eq!(keys(" Alice ", Some("2001:db8:1:2:3:4:5:6".parse().unwrap())), vec!["user:alice", "ip:2001:db8:1:2::/64"]);
+    assert_eq!(keys("alice", Some("192.0.2.1".parse().unwrap())), vec!["user:alice", "ip:192.0.2.1"]);
+    assert_eq!(user_key(&"a".repeat(300)).len(), MAX_KEY_LENGTH);
+    assert_eq!(mfa_key(Uuid::nil()), "mfa:00000000-0000-0000-0000-000000000000");
+  }
+}
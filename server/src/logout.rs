@@ -0,0 +1,132 @@
+//! Ending single sign-on sessions & telling the clients that took part in them
+//! (OpenID Connect Back-Channel Logout 1.0).
+
+use diesel::pg::PgConnection;
+use lazy_static::lazy_static;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::db::models::{RefreshToken, Session};
+use crate::error::*;
+use crate::tokens::TokenIssuer;
+
+/// Clients have to answer logout requests quickly; slow ones are simply not waited for.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+lazy_static! {
+  static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::builder()
+    .timeout(REQUEST_TIMEOUT)
+    .redirect(reqwest::redirect::Policy::none())
+    .build()
+    .expect("unable to build the back-channel logout HTTP client");
+}
+
+/// Ends a session, revoking its refresh tokens, and sends a logout token to every client with a
+/// back-channel logout URI that was issued tokens within it. Notifications run in the background.
+pub fn end_session(conn: &PgConnection, issuer: &TokenIssuer, session: &Session) -> Result<(), HeimdallrError> {
+  if !session.end(conn)? {
+    return Ok(());
+  }
+
+  for client in session.clients(conn)? {
+    let uri = match &client.backchannel_logout_uri {
+      Some(uri) => uri.clone(),
+      None      => continue
+    };
+
+    let token     = issuer.logout_token(&client.id, &session.user_id.to_string(), &session.id.to_string())?;
+    let client_id = client.id;
+
+    tokio::spawn(async move {
+      match notify(&uri, &token).await {
+        Ok(())   => log::debug!("Sent back-channel logout to {}", client_id),
+        Err(err) => log::warn!("Back-channel logout of client {} failed: {}", client_id, err)
+      }
+    });
+  }
+
+  Ok(())
+}
+
+/// Ends every session of a user & revokes all their refresh tokens, e.g. once their password was
+/// reset.
+pub fn end_all_sessions(conn: &PgConnection, issuer: &TokenIssuer, user_id: Uuid) -> Result<(), HeimdallrError> {
+  for session in Session::active_for_user(conn, user_id)? {
+    end_session(conn, issuer, &session)?;
+  }
+
+  RefreshToken::revoke_for_user(conn, user_id)?;
+  Ok(())
+}
+
+/// POSTs a logout token to a client's back-channel logout URI.
+pub async fn notify(uri: &str, logout_token: &str) -> Result<(), HeimdallrError> {
+  HTTP_CLIENT
+    .post(uri)
+    .form(&[("logout_token", logout_token)])
+    .send()
+    .await?
+    .error_for_status()?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use hyper::service::{make_service_fn, service_fn};
+  use hyper::{Body, Request, Response, Server};
+  use pretty_assertions::assert_eq;
+  use std::convert::Infallible;
+  use std::sync::{Arc, Mutex};
+
+  #[tokio::test]
+  async fn test_notify_posts_the_logout_token() {
+    let received = Arc::new(Mutex::new(None));
+    let recorder = received.clone();
+
+    // Stands in for a client's back-channel logout endpoint.
+    let make_service = make_service_fn(move |_| {
+      let recorder = recorder.clone();
+      async move {
+        Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+          let recorder = recorder.clone();
+          async move {
+            let content_type = request.headers()[hyper::header::CONTENT_TYPE].to_str().unwrap().to_owned();
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            *recorder.lock().unwrap() = Some((content_type, String::from_utf8(body.to_vec()).unwrap()));
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+          }
+        }))
+      }
+    });
+
+    let server  = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let address = server.local_addr();
+    tokio::spawn(server);
+
+    notify(&format!("http://{}/logout", address), "header.claims.signature").await.unwrap();
+
+    let (content_type, body) = received.lock().unwrap().take().unwrap();
+    assert_eq!(content_type, "application/x-www-form-urlencoded");
+    assert_eq!(body, "logout_token=header.claims.signature");
+  }
+
+  #[tokio::test]
+  async fn test_notify_fails_on_error_status() {
+    let make_service = make_service_fn(|_| async {
+      Ok::<_, Infallible>(service_fn(|_: Request<Body>| async {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = hyper::StatusCode::BAD_REQUEST;
+        Ok::<_, Infallible>(response)
+      }))
+    });
+
+    let server  = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let address = server.local_addr();
+    tokio::spawn(server);
+
+    assert!(notify(&format!("http://{}/logout", address), "token").await.is_err());
+  }
+}
@@ -0,0 +1,128 @@
+//! Outgoing email. A [`Mailer`] delivers messages, which the [`Outbox`] renders from localizable
+//! templates for the account flows: email verification, password reset & magic-link login.
+//!
+//! `mail.transport` picks the mailer: `smtp`, `file` (one `.eml` file per message, handy during
+//! development) or `log`.
+
+mod outbox;
+mod sink;
+mod smtp;
+mod templates;
+
+pub use outbox::{Delivery, Outbox, MAGIC_LINK, PASSWORD_RESET, VERIFY_EMAIL};
+pub use sink::{FileMailer, LogMailer};
+pub use smtp::SmtpMailer;
+pub use templates::MailTemplates;
+
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::settings::Mail as MailSettings;
+
+/// A rendered message for a single recipient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+  pub to: String,
+  pub subject: String,
+  /// Plain text.
+  pub body: String
+}
+
+pub trait Mailer: Send + Sync {
+  /// Hands a message over for delivery.
+  fn send(&self, message: &Message) -> Result<(), HeimdallrError>;
+}
+
+/// The mailer configured by `mail.transport`.
+pub fn from_settings(settings: &MailSettings) -> Result<Arc<dyn Mailer>, HeimdallrError> {
+  match settings.transport.as_deref().unwrap_or("log") {
+    "smtp" => {
+      let smtp = settings.smtp.as_ref().ok_or_else(|| config_error("mail.smtp is required for the smtp transport"))?;
+      Ok(Arc::new(SmtpMailer::new(smtp, &settings.from())?))
+    },
+    "file" => {
+      let directory = settings.directory.as_ref().ok_or_else(|| config_error("mail.directory is required for the file transport"))?;
+      Ok(Arc::new(FileMailer::new(directory, &settings.from())))
+    },
+    "log" => Ok(Arc::new(LogMailer)),
+    other => Err(config_error(&format!("mail.transport `{}` must be smtp, file or log", other)))
+  }
+}
+
+fn config_error(message: &str) -> HeimdallrError {
+  HeimdallrError::ConfigError(config::ConfigError::Message(message.to_owned()))
+}
+
+/// The address of a mailbox such as `Heimdallr <no-reply@example.com>`.
+pub fn address(mailbox: &str) -> &str {
+  match (mailbox.rfind('<'), mailbox.rfind('>')) {
+    (Some(start), Some(end)) if start < end => mailbox[start + 1..end].trim(),
+    _ => mailbox.trim()
+  }
+}
+
+/// Renders a message as RFC 5322 text with CRLF line endings, ready for SMTP `DATA` or an `.eml`
+/// file. The body is base64 encoded, which keeps any text 7-bit clean & free of long lines.
+pub fn format(from: &str, message: &Message, date: DateTime<Utc>, message_id: &str) -> String {
+  let body = base64::encode(message.body.replace("\r\n", "\n").replace('\n', "\r\n").as_bytes());
+  let lines: Vec<&str> = body.as_bytes().chunks(76).map(|chunk| std::str::from_utf8(chunk).unwrap_or_default()).collect();
+
+  let mut text = String::new();
+  text.push_str(&format!("From: {}\r\n", header_value(from)));
+  text.push_str(&format!("To: {}\r\n", header_value(&message.to)));
+  text.push_str(&format!("Subject: {}\r\n", encode_header(&header_value(&message.subject))));
+  text.push_str(&format!("Date: {}\r\n", date.to_rfc2822()));
+  text.push_str(&format!("Message-ID: <{}>\r\n", message_id));
+  text.push_str("MIME-Version: 1.0\r\n");
+  text.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+  text.push_str("Content-Transfer-Encoding: base64\r\n");
+  text.push_str("\r\n");
+  text.push_str(&lines.join("\r\n"));
+  text.push_str("\r\n");
+  text
+}
+
+/// Header values must not break out of their line.
+fn header_value(value: &str) -> String {
+  value.chars().filter(|c| *c != '\r' && *c != '\n').collect()
+}
+
+/// RFC 2047 encoded-word for headers that are not plain ASCII.
+fn encode_header(value: &str) -> String {
+  if value.is_ascii() {
+    value.to_owned()
+  }
+  else {
+    format!("=?utf-8?B?{}?=", base64::encode(value.as_bytes()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_address() {
+    assert_eq!(address("Heimdallr <no-reply@example.com>"), "no-reply@example.com");
+    assert_eq!(address(" alice@example.com "), "alice@example.com");
+  }
+
+  #[test]
+  fn test_format() {
+    let message = Message {
+      to: "alice@example.com\r\nBcc: mallory@example.com".to_owned(),
+      subject: "Vérifiez votre adresse".to_owned(),
+      body: "Hello\nWorld".to_owned()
+    };
+
+    let text = format("Heimdallr <no-reply@example.com>", &message, DateTime::parse_from_rfc3339("2020-03-19T12:00:00Z").unwrap().with_timezone(&Utc), "1@example.com");
+
+    assert!(text.starts_with("From: Heimdallr <no-reply@example.com>\r\nTo: alice@example.comBcc: mallory@example.com\r\n"));
+    assert!(text.contains("Subject: =?utf-8?B?VsOpcmlmaWV6IHZvdHJlIGFkcmVzc2U=?=\r\n"));
+    assert!(text.contains("Date: Thu, 19 Mar 2020 12:00:00 +0000\r\n"));
+    assert!(text.ends_with("\r\n\r\nSGVsbG8NCldvcmxk\r\n"));
+  }
+}
@@ -0,0 +1,172 @@
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use serde_json::json;
+use std::sync::Arc;
+use url::form_urlencoded;
+
+use crate::crypto;
+use crate::db::models::{normalize, EmailToken, MailDelivery, NewEmailToken, User};
+use crate::error::*;
+use crate::settings::Mail as MailSettings;
+use super::{Mailer, MailTemplates, Message};
+
+/// `purpose` of email verification tokens, and the name of their template.
+pub const VERIFY_EMAIL: &str = "verify_email";
+
+/// `purpose` of password reset tokens, and the name of their template.
+pub const PASSWORD_RESET: &str = "password_reset";
+
+/// `purpose` of sign-in link tokens, and the name of their template.
+pub const MAGIC_LINK: &str = "magic_link";
+
+/// What became of a message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+  /// Queued for delivery, or silently dropped when there is no matching account.
+  Accepted,
+  /// The recipient got too many messages lately; they can get another one after this long.
+  Throttled(Duration)
+}
+
+/// Sends the messages of the account flows, each carrying a single-use link back to the server.
+///
+/// Messages are delivered in the background, so neither slow mail servers nor the difference
+/// between known & unknown addresses show in response times.
+#[derive(Clone)]
+pub struct Outbox {
+  mailer: Arc<dyn Mailer>,
+  templates: Arc<MailTemplates>,
+  settings: MailSettings,
+  base_url: String
+}
+
+impl Outbox {
+  /// Links point at the HTTP listener, i.e. the issuer.
+  pub fn new(settings: &MailSettings, issuer: &str) -> Result<Self, HeimdallrError> {
+    Ok(Outbox {
+      mailer: super::from_settings(settings)?,
+      templates: Arc::new(MailTemplates::load(settings.templates_dir.as_deref(), &settings.default_locale())?),
+      settings: settings.clone(),
+      base_url: issuer.trim_end_matches('/').to_owned()
+    })
+  }
+
+  /// Sends a link confirming the current email address of a user.
+  pub fn send_verification(&self, conn: &PgConnection, user: &User) -> Result<Delivery, HeimdallrError> {
+    let email = match &user.email {
+      Some(email) => email,
+      None        => return Ok(Delivery::Accepted)
+    };
+
+    if let Some(retry_after) = self.throttle(conn, email, VERIFY_EMAIL)? {
+      return Ok(Delivery::Throttled(retry_after));
+    }
+
+    self.send_link(conn, user, email, VERIFY_EMAIL, &[])
+  }
+
+  /// Sends a password reset link to the owner of an address, without revealing whether there is one.
+  pub fn send_password_reset(&self, conn: &PgConnection, email: &str) -> Result<Delivery, HeimdallrError> {
+    let email = normalize(email);
+
+    if let Some(retry_after) = self.throttle(conn, &email, PASSWORD_RESET)? {
+      return Ok(Delivery::Throttled(retry_after));
+    }
+
+    match User::find_by_email(conn, &email)? {
+      Some(user) if !user.disabled => self.send_link(conn, &user, &email, PASSWORD_RESET, &[]),
+      _ => Ok(Delivery::Accepted)
+    }
+  }
+
+  /// Sends a sign-in link resuming an authorization request to the owner of an address, without
+  /// revealing whether there is one.
+  pub fn send_magic_link(&self, conn: &PgConnection, email: &str, request: &str) -> Result<Delivery, HeimdallrError> {
+    let email = normalize(email);
+
+    if let Some(retry_after) = self.throttle(conn, &email, MAGIC_LINK)? {
+      return Ok(Delivery::Throttled(retry_after));
+    }
+
+    match User::find_by_email(conn, &email)? {
+      Some(user) if !user.disabled => self.send_link(conn, &user, &email, MAGIC_LINK, &[("request", request)]),
+      _ => Ok(Delivery::Accepted)
+    }
+  }
+
+  /// Counts a message towards the hourly limit of its recipient, unless the limit is reached.
+  ///
+  /// Requests for unknown addresses count too, so the limit does not give away which are known.
+  fn throttle(&self, conn: &PgConnection, recipient: &str, purpose: &str) -> Result<Option<Duration>, HeimdallrError> {
+    MailDelivery::throttle(conn, MailDelivery::EMAIL, recipient, purpose, self.settings.max_per_hour())
+  }
+
+  /// Issues a token for a purpose & mails the link redeeming it.
+  fn send_link(&self, conn: &PgConnection, user: &User, email: &str, purpose: &str, query: &[(&str, &str)]) -> Result<Delivery, HeimdallrError> {
+    let (ttl, path) = match purpose {
+      VERIFY_EMAIL   => (self.settings.verification_ttl(), "/verify-email"),
+      PASSWORD_RESET => (self.settings.password_reset_ttl(), "/password/reset"),
+      _              => (self.settings.magic_link_ttl(), "/login/magic")
+    };
+    let token = crypto::random_token(32)?;
+
+    EmailToken::delete_expired(conn)?;
+    EmailToken::create(conn, &NewEmailToken {
+      token_hash: &crypto::hash_token(&token)?,
+      purpose,
+      user_id: user.id,
+      email,
+      expires_at: (Utc::now() + ttl).naive_utc()
+    })?;
+
+    let query = form_urlencoded::Serializer::new(String::new())
+      .append_pair("token", &token)
+      .extend_pairs(query.iter().filter(|(_, value)| !value.is_empty()))
+      .finish();
+
+    let (subject, body) = self.templates.render(purpose, user.locale.as_deref(), &json!({
+      "name": user.name.as_deref().unwrap_or(&user.username),
+      "username": user.username,
+      "link": format!("{}{}?{}", self.base_url, path, query),
+      "expires_in": describe(ttl),
+      "expires_in_minutes": ttl.num_minutes()
+    }))?;
+
+    let message = Message { to: email.to_owned(), subject, body };
+    let mailer  = self.mailer.clone();
+
+    tokio::task::spawn_blocking(move || {
+      if let Err(err) = mailer.send(&message) {
+        log::error!("Unable to send mail to {}: {}", message.to, err);
+      }
+    });
+
+    Ok(Delivery::Accepted)
+  }
+}
+
+/// `24 hours`, `1 hour` or `15 minutes`.
+fn describe(ttl: Duration) -> String {
+  let plural = |count: i64, unit: &str| format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" });
+
+  if ttl.num_minutes() >= 60 && ttl.num_minutes() % 60 == 0 {
+    plural(ttl.num_hours(), "hour")
+  }
+  else {
+    plural(ttl.num_minutes(), "minute")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_describe() {
+    assert_eq!(describe(Duration::hours(24)), "24 hours");
+    assert_eq!(describe(Duration::hours(1)), "1 hour");
+    assert_eq!(describe(Duration::minutes(90)), "90 minutes");
+  }
+}
@@ -0,0 +1,44 @@
+//! Mailers that keep messages local instead of delivering them.
+
+use chrono::Utc;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::error::*;
+use super::{Mailer, Message};
+
+/// Writes every message to an `.eml` file, which mail clients can open.
+pub struct FileMailer {
+  directory: PathBuf,
+  from: String
+}
+
+impl FileMailer {
+  pub fn new(directory: &str, from: &str) -> Self {
+    FileMailer { directory: PathBuf::from(directory), from: from.to_owned() }
+  }
+}
+
+impl Mailer for FileMailer {
+  fn send(&self, message: &Message) -> Result<(), HeimdallrError> {
+    let now  = Utc::now();
+    let id   = Uuid::new_v4();
+    let path = self.directory.join(format!("{}-{}.eml", now.format("%Y%m%dT%H%M%S"), id));
+
+    std::fs::create_dir_all(&self.directory)?;
+    std::fs::write(&path, super::format(&self.from, message, now, &format!("{}@localhost", id)))?;
+
+    log::info!("Wrote mail to {} as {}", message.to, path.display());
+    Ok(())
+  }
+}
+
+/// Logs every message; links in them are live, so this is only fit for development.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+  fn send(&self, message: &Message) -> Result<(), HeimdallrError> {
+    log::info!("Mail to {}: {}\n{}", message.to, message.subject, message.body);
+    Ok(())
+  }
+}
@@ -0,0 +1,268 @@
+//! A minimal SMTP submission client (RFC 5321): one connection per message, STARTTLS or implicit
+//! TLS, and `AUTH PLAIN`.
+
+use chrono::Utc;
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::error::*;
+use crate::settings::Smtp as SmtpSettings;
+use super::{Mailer, Message};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Security {
+  None,
+  StartTls,
+  Tls
+}
+
+pub struct SmtpMailer {
+  host: String,
+  port: u16,
+  security: Security,
+  credentials: Option<(String, String)>,
+  from: String,
+  timeout: Duration
+}
+
+impl SmtpMailer {
+  pub fn new(settings: &SmtpSettings, from: &str) -> Result<Self, HeimdallrError> {
+    let security = match settings.security.as_deref().unwrap_or("starttls") {
+      "starttls" => Security::StartTls,
+      "tls"      => Security::Tls,
+      "none"     => Security::None,
+      other      => return Err(HeimdallrError::ConfigError(config::ConfigError::Message(format!("mail.smtp.security `{}` must be starttls, tls or none", other))))
+    };
+
+    let default_port = match security {
+      Security::Tls      => 465,
+      Security::StartTls => 587,
+      Security::None     => 25
+    };
+
+    Ok(SmtpMailer {
+      host: settings.host.clone(),
+      port: settings.port.unwrap_or(default_port),
+      security,
+      credentials: settings.username.clone().map(|username| (username, settings.password.clone().unwrap_or_default())),
+      from: from.to_owned(),
+      timeout: Duration::from_secs(settings.timeout.unwrap_or(30))
+    })
+  }
+
+  fn connect(&self) -> Result<TcpStream, HeimdallrError> {
+    let stream = TcpStream::connect((self.host.as_str(), self.port))?;
+    stream.set_read_timeout(Some(self.timeout))?;
+    stream.set_write_timeout(Some(self.timeout))?;
+    Ok(stream)
+  }
+
+  fn tls(&self, stream: TcpStream) -> Result<SslStream<TcpStream>, HeimdallrError> {
+    SslConnector::builder(SslMethod::tls())?
+      .build()
+      .connect(&self.host, stream)
+      .map_err(|err| HeimdallrError::MailError(format!("TLS handshake with {} failed ({})", self.host, err)))
+  }
+
+  /// Everything after the greeting (and TLS), up to `QUIT`.
+  fn transaction<S: Read + Write>(&self, session: &mut Session<S>, data: &str, to: &str) -> Result<(), HeimdallrError> {
+    if let Some((username, password)) = &self.credentials {
+      let token = base64::encode(format!("\0{}\0{}", username, password).as_bytes());
+      session.command(&format!("AUTH PLAIN {}", token), &[235])?;
+    }
+
+    session.command(&format!("MAIL FROM:<{}>", super::address(&self.from)), &[250])?;
+    session.command(&format!("RCPT TO:<{}>", super::address(to)), &[250, 251])?;
+    session.command("DATA", &[354])?;
+    session.command(&format!("{}.", dot_stuff(data)), &[250])?;
+    session.command("QUIT", &[221])?;
+    Ok(())
+  }
+}
+
+impl Mailer for SmtpMailer {
+  fn send(&self, message: &Message) -> Result<(), HeimdallrError> {
+    let domain = super::address(&self.from).rsplit('@').next().unwrap_or("localhost").to_owned();
+    let data   = super::format(&self.from, message, Utc::now(), &format!("{}@{}", Uuid::new_v4(), domain));
+    let stream = self.connect()?;
+
+    match self.security {
+      Security::Tls => {
+        let mut session = Session::new(self.tls(stream)?);
+        session.expect(&[220])?;
+        session.command(&format!("EHLO {}", domain), &[250])?;
+        self.transaction(&mut session, &data, &message.to)
+      },
+      Security::StartTls => {
+        let mut session = Session::new(stream);
+        session.expect(&[220])?;
+        let capabilities = session.command(&format!("EHLO {}", domain), &[250])?;
+        if !capabilities.iter().any(|line| line.eq_ignore_ascii_case("STARTTLS")) {
+          return Err(HeimdallrError::MailError(format!("{} does not offer STARTTLS", self.host)));
+        }
+        session.command("STARTTLS", &[220])?;
+
+        let mut session = Session::new(self.tls(session.into_inner())?);
+        session.command(&format!("EHLO {}", domain), &[250])?;
+        self.transaction(&mut session, &data, &message.to)
+      },
+      Security::None => {
+        let mut session = Session::new(stream);
+        session.expect(&[220])?;
+        session.command(&format!("EHLO {}", domain), &[250])?;
+        self.transaction(&mut session, &data, &message.to)
+      }
+    }
+  }
+}
+
+/// A connection to the server, speaking in commands & replies.
+struct Session<S: Read + Write> {
+  reader: BufReader<S>
+}
+
+impl<S: Read + Write> Session<S> {
+  fn new(stream: S) -> Self {
+    Session { reader: BufReader::new(stream) }
+  }
+
+  fn into_inner(self) -> S {
+    self.reader.into_inner()
+  }
+
+  /// Sends a command & checks the reply, returning its text lines.
+  fn command(&mut self, command: &str, expected: &[u16]) -> Result<Vec<String>, HeimdallrError> {
+    let stream = self.reader.get_mut();
+    stream.write_all(command.as_bytes())?;
+    stream.write_all(b"\r\n")?;
+    stream.flush()?;
+
+    self.expect(expected).map_err(|err| match command.split(' ').next() {
+      // Never log credentials.
+      Some("AUTH") => HeimdallrError::MailError(format!("authentication failed: {}", err)),
+      _            => err
+    })
+  }
+
+  /// Reads a (possibly multiline) reply, failing unless its code is one of those expected.
+  fn expect(&mut self, expected: &[u16]) -> Result<Vec<String>, HeimdallrError> {
+    let mut lines = Vec::new();
+
+    loop {
+      let mut line = String::new();
+      if self.reader.read_line(&mut line)? == 0 {
+        return Err(HeimdallrError::MailError("the server closed the connection".to_owned()));
+      }
+
+      let line = line.trim_end();
+      let code = line.get(..3).and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| HeimdallrError::MailError(format!("malformed reply `{}`", line)))?;
+      lines.push(line.get(4..).unwrap_or_default().to_owned());
+
+      if line.as_bytes().get(3) != Some(&b'-') {
+        if expected.contains(&code) {
+          return Ok(lines);
+        }
+        return Err(HeimdallrError::MailError(format!("server replied {} {}", code, lines.join(" "))));
+      }
+    }
+  }
+}
+
+/// Escapes lines starting with a dot, which would otherwise end `DATA` early (RFC 5321 §4.5.2).
+fn dot_stuff(data: &str) -> String {
+  let stuffed = data.replace("\r\n.", "\r\n..");
+  if stuffed.starts_with('.') { format!(".{}", stuffed) } else { stuffed }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+  use std::net::TcpListener;
+  use std::thread;
+
+  /// Plays the server side of a submission, returning the commands it received.
+  fn stand_in(listener: TcpListener) -> thread::JoinHandle<Vec<String>> {
+    thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut writer  = stream.try_clone().unwrap();
+      let mut reader  = BufReader::new(stream);
+      let mut received = Vec::new();
+      let mut in_data  = false;
+
+      writer.write_all(b"220 localhost ESMTP stand-in\r\n").unwrap();
+
+      loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 {
+          break;
+        }
+        let line = line.trim_end_matches("\r\n").to_owned();
+
+        if in_data {
+          if line == "." {
+            in_data = false;
+            writer.write_all(b"250 2.0.0 queued\r\n").unwrap();
+          }
+          received.push(line);
+          continue;
+        }
+
+        let reply: &[u8] = match line.split(' ').next().unwrap() {
+          "EHLO" => b"250-localhost\r\n250-AUTH PLAIN\r\n250 8BITMIME\r\n",
+          "AUTH" => b"235 2.7.0 authenticated\r\n",
+          "DATA" => {
+            in_data = true;
+            b"354 go ahead\r\n"
+          },
+          "QUIT" => b"221 bye\r\n",
+          _      => b"250 ok\r\n"
+        };
+        received.push(line);
+        writer.write_all(reply).unwrap();
+      }
+
+      received
+    })
+  }
+
+  #[test]
+  fn test_send_to_stand_in() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port     = listener.local_addr().unwrap().port();
+    let server   = stand_in(listener);
+
+    let settings = SmtpSettings {
+      host: "127.0.0.1".to_owned(),
+      port: Some(port),
+      security: Some("none".to_owned()),
+      username: Some("heimdallr".to_owned()),
+      password: Some("secret".to_owned()),
+      timeout: Some(5)
+    };
+    let mailer = SmtpMailer::new(&settings, "Heimdallr <no-reply@example.com>").unwrap();
+
+    mailer.send(&Message { to: "alice@example.com".to_owned(), subject: "Hello".to_owned(), body: "Hi".to_owned() }).unwrap();
+
+    let received = server.join().unwrap();
+    assert_eq!(&received[..5], &[
+      "EHLO example.com".to_owned(),
+      format!("AUTH PLAIN {}", base64::encode("\0heimdallr\0secret")),
+      "MAIL FROM:<no-reply@example.com>".to_owned(),
+      "RCPT TO:<alice@example.com>".to_owned(),
+      "DATA".to_owned()
+    ]);
+    assert!(received.contains(&"Subject: Hello".to_owned()));
+    assert_eq!(&received[received.len() - 2..], &[".".to_owned(), "QUIT".to_owned()]);
+  }
+
+  #[test]
+  fn test_dot_stuffing() {
+    assert_eq!(dot_stuff(".a\r\n.b\r\nc"), "..a\r\n..b\r\nc");
+  }
+}
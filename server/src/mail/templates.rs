@@ -0,0 +1,153 @@
+//! Localizable message templates.
+//!
+//! A template is a Handlebars file whose first line is the subject, followed by a blank line & the
+//! plain text body. Templates are looked up by locale, falling back from `pt-BR` to `pt`, then to
+//! the default locale & finally to the built-in English ones.
+
+use handlebars::Handlebars;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::error::*;
+
+/// Built-in templates, in English.
+const TEMPLATES: &[(&str, &str)] = &[
+  ("verify_email",   include_str!("../../templates/mail/en/verify_email.hbs")),
+  ("password_reset", include_str!("../../templates/mail/en/password_reset.hbs")),
+  ("magic_link",     include_str!("../../templates/mail/en/magic_link.hbs"))
+];
+
+const FALLBACK_LOCALE: &str = "en";
+
+pub struct MailTemplates {
+  registry: Handlebars<'static>,
+  default_locale: String
+}
+
+impl MailTemplates {
+  /// Registers the built-in templates, then every `<locale>/<name>.hbs` found in `dir`.
+  pub fn load(dir: Option<&str>, default_locale: &str) -> Result<Self, HeimdallrError> {
+    let mut registry = Handlebars::new();
+    // Messages are plain text; HTML escaping would mangle names & links.
+    registry.register_escape_fn(handlebars::no_escape);
+
+    for (name, source) in TEMPLATES {
+      registry.register_template_string(&key(FALLBACK_LOCALE, name), source)?;
+    }
+
+    if let Some(dir) = dir {
+      for locale in std::fs::read_dir(dir)? {
+        let locale = locale?.path();
+        if !locale.is_dir() {
+          continue;
+        }
+
+        for template in std::fs::read_dir(&locale)? {
+          let template = template?.path();
+          if let (Some(locale), Some(name)) = (file_name(&locale), template_name(&template)) {
+            log::info!("Using mail template {}", template.display());
+            registry.register_template_string(&key(&locale, &name), std::fs::read_to_string(&template)?)?;
+          }
+        }
+      }
+    }
+
+    Ok(MailTemplates { registry, default_locale: normalize_locale(default_locale) })
+  }
+
+  /// Renders a template in the best matching locale, returning the subject & the body.
+  pub fn render(&self, name: &str, locale: Option<&str>, data: &Value) -> Result<(String, String), HeimdallrError> {
+    let template = self.candidates(locale)
+      .into_iter()
+      .map(|locale| key(&locale, name))
+      .find(|key| self.registry.get_template(key).is_some())
+      .ok_or_else(|| HeimdallrError::TemplateError(format!("no mail template named `{}`", name)))?;
+
+    let rendered = self.registry.render(&template, data)?;
+    let mut parts = rendered.splitn(2, '\n');
+    let subject   = parts.next().unwrap_or_default().trim().to_owned();
+    let body      = parts.next().unwrap_or_default().trim_start_matches(['\r', '\n']).to_owned();
+
+    Ok((subject, body))
+  }
+
+  /// Locales to try, most specific first.
+  fn candidates(&self, locale: Option<&str>) -> Vec<String> {
+    let mut candidates = Vec::new();
+
+    if let Some(locale) = locale.map(normalize_locale) {
+      if let Some((language, _)) = split_region(&locale) {
+        candidates.push(locale.clone());
+        candidates.push(language.to_owned());
+      }
+      else {
+        candidates.push(locale);
+      }
+    }
+
+    candidates.push(self.default_locale.clone());
+    candidates.push(FALLBACK_LOCALE.to_owned());
+    candidates
+  }
+}
+
+fn key(locale: &str, name: &str) -> String {
+  format!("{}/{}", locale, name)
+}
+
+/// `pt_BR` & `PT-br` both become `pt-br`.
+fn normalize_locale(locale: &str) -> String {
+  locale.trim().replace('_', "-").to_lowercase()
+}
+
+fn split_region(locale: &str) -> Option<(&str, &str)> {
+  let index = locale.find('-')?;
+  Some((&locale[..index], &locale[index + 1..]))
+}
+
+fn file_name(path: &Path) -> Option<String> {
+  path.file_name().and_then(|name| name.to_str()).map(normalize_locale)
+}
+
+fn template_name(path: &Path) -> Option<String> {
+  if path.extension().and_then(|extension| extension.to_str()) != Some("hbs") {
+    return None;
+  }
+  path.file_stem().and_then(|stem| stem.to_str()).map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  #[test]
+  fn test_built_in_templates_render() {
+    let templates = MailTemplates::load(None, "en").unwrap();
+    let data = json!({ "name": "Alice & Bob", "link": "https://auth.example.com/verify-email?token=a&b", "expires_in": "24 hours" });
+
+    for (name, _) in TEMPLATES {
+      let (subject, body) = templates.render(name, Some("de-AT"), &data).unwrap();
+      assert!(!subject.is_empty());
+      assert!(body.contains("Alice & Bob"));
+    }
+
+    let (subject, body) = templates.render("verify_email", None, &data).unwrap();
+    assert_eq!(subject, "Verify your email address");
+    assert!(body.contains("https://auth.example.com/verify-email?token=a&b"));
+  }
+
+  #[test]
+  fn test_locale_fallback() {
+    let mut templates = MailTemplates::load(None, "fr").unwrap();
+    templates.registry.register_template_string("pt/verify_email", "Verifique o seu email\n\nOlá").unwrap();
+    templates.registry.register_template_string("fr/verify_email", "Vérifiez votre adresse\n\nBonjour").unwrap();
+
+    assert_eq!(templates.render("verify_email", Some("pt_BR"), &json!({})).unwrap().0, "Verifique o seu email");
+    assert_eq!(templates.render("verify_email", Some("de"), &json!({})).unwrap().0, "Vérifiez votre adresse");
+    assert_eq!(templates.render("password_reset", Some("fr"), &json!({})).unwrap().0, "Reset your password");
+    assert!(templates.render("unknown", None, &json!({})).is_err());
+  }
+}
@@ -0,0 +1,71 @@
+//! Counters of the events operators want to alert on, served in the Prometheus text format at
+//! `GET /metrics` of the HTTP listener.
+
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Calls turned away by a rate limit, by rule & what it counts per.
+pub const RATE_LIMITED: &str = "heimdallr_rate_limited_total";
+
+/// Authorization checks, by whether they were allowed.
+pub const AUTHZ_DECISIONS: &str = "heimdallr_authz_decisions_total";
+
+/// Every counter along with its help text; counters are listed even before they are incremented.
+const COUNTERS: &[(&str, &str)] = &[
+  (RATE_LIMITED, "Calls turned away by a rate limit."),
+  (AUTHZ_DECISIONS, "Authorization checks made.")
+];
+
+type Labels = Vec<(String, String)>;
+
+lazy_static! {
+  static ref VALUES: Mutex<BTreeMap<(&'static str, Labels), u64>> = Mutex::new(BTreeMap::new());
+}
+
+/// Adds one to a counter with the given labels.
+pub fn increment(name: &'static str, labels: &[(&str, &str)]) {
+  let labels = labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+  let mut values = VALUES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+  *values.entry((name, labels)).or_insert(0) += 1;
+}
+
+/// Every counter in the Prometheus text exposition format.
+pub fn render() -> String {
+  let values = VALUES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+  let mut text = String::new();
+
+  for (name, help) in COUNTERS {
+    text.push_str(&format!("# HELP {} {}\n# TYPE {} counter\n", name, help, name));
+
+    for ((_, labels), value) in values.iter().filter(|((counter, _), _)| counter == name) {
+      let labels: Vec<String> = labels.iter().map(|(key, value)| format!("{}=\"{}\"", key, escape(value))).collect();
+      text.push_str(&format!("{}{{{}}} {}\n", name, labels.join(","), value));
+    }
+  }
+
+  text
+}
+
+fn escape(value: &str) -> String {
+  value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_render() {
+    increment(RATE_LIMITED, &[("rule", "/test.Service/\"quoted\""), ("per", "ip")]);
+    increment(RATE_LIMITED, &[("rule", "/test.Service/\"quoted\""), ("per", "ip")]);
+
+    let text = render();
+    assert!(text.starts_with("# HELP heimdallr_rate_limited_total Calls turned away by a rate limit.\n# TYPE heimdallr_rate_limited_total counter\n"));
+
+    let line = text.lines().find(|line| line.contains("test.Service")).unwrap();
+    assert_eq!(line, "heimdallr_rate_limited_total{rule=\"/test.Service/\\\"quoted\\\"\",per=\"ip\"} 2");
+  }
+}
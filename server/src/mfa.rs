@@ -0,0 +1,238 @@
+//! Second factors: authenticator app codes (TOTP, RFC 6238), one-time recovery codes, passkeys
+//! (see [`crate::webauthn`]) & codes sent to a phone (see [`crate::sms`]).
+
+use base32::Alphabet;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use uuid::Uuid;
+
+use crate::crypto;
+use crate::db::models::{RecoveryCode, SmsFactor, TotpCredential, User, WebauthnCredential};
+use crate::error::*;
+
+/// Digits of a TOTP code.
+pub const TOTP_DIGITS: usize = 6;
+
+/// Seconds a TOTP code is valid for.
+pub const TOTP_PERIOD: i64 = 30;
+
+/// Codes of the neighbouring time steps are accepted too, to make up for clock drift.
+const TOTP_SKEW: i64 = 1;
+
+/// Number of recovery codes handed out at a time.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const BASE32: Alphabet = Alphabet::RFC4648 { padding: false };
+
+/// A new random TOTP secret, base32 encoded.
+pub fn generate_secret() -> Result<String, HeimdallrError> {
+  let mut secret = [0u8; 20];
+  openssl::rand::rand_bytes(&mut secret)?;
+  Ok(base32::encode(BASE32, &secret))
+}
+
+/// The `otpauth://` URI authenticator apps import secrets from (usually via a QR code).
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+  let label = format!("{}:{}", issuer, account);
+
+  format!(
+    "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+    utf8_percent_encode(&label, NON_ALPHANUMERIC),
+    secret,
+    utf8_percent_encode(issuer, NON_ALPHANUMERIC),
+    TOTP_DIGITS,
+    TOTP_PERIOD
+  )
+}
+
+/// The TOTP time step a moment falls into.
+pub fn time_step(at: DateTime<Utc>) -> i64 {
+  at.timestamp().div_euclid(TOTP_PERIOD)
+}
+
+/// The HOTP code of a counter (RFC 4226 §5.3).
+fn hotp(key: &[u8], counter: i64, digits: usize) -> Result<String, HeimdallrError> {
+  let key = PKey::hmac(key)?;
+  let mut signer = Signer::new(MessageDigest::sha1(), &key)?;
+  signer.update(&counter.to_be_bytes())?;
+  let hmac = signer.sign_to_vec()?;
+
+  let offset = (hmac[hmac.len() - 1] & 0x0f) as usize;
+  let binary = u32::from_be_bytes([hmac[offset] & 0x7f, hmac[offset + 1], hmac[offset + 2], hmac[offset + 3]]);
+
+  Ok(format!("{:0width$}", binary % 10u32.pow(digits as u32), width = digits))
+}
+
+/// The time step a code was generated for, if it is valid at the given moment.
+pub fn matching_step(secret: &str, code: &str, at: DateTime<Utc>) -> Result<Option<i64>, HeimdallrError> {
+  let key = base32::decode(BASE32, secret).ok_or_else(|| HeimdallrError::KeyError("TOTP secret is not valid base32".to_owned()))?;
+  let current = time_step(at);
+
+  for step in (current - TOTP_SKEW)..=(current + TOTP_SKEW) {
+    if crypto::constant_time_eq(hotp(&key, step, TOTP_DIGITS)?.as_bytes(), code.as_bytes()) {
+      return Ok(Some(step));
+    }
+  }
+
+  Ok(None)
+}
+
+/// Whether a code looks like it came from an authenticator app rather than being a recovery code.
+fn is_totp_code(code: &str) -> bool {
+  code.len() == TOTP_DIGITS && code.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Recovery codes are shown grouped (`abcde-fghij`) but accepted in any case & with any spacing.
+fn normalize_recovery_code(code: &str) -> String {
+  code.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+/// New random recovery codes, formatted for display.
+fn generate_recovery_codes() -> Result<Vec<String>, HeimdallrError> {
+  (0..RECOVERY_CODE_COUNT)
+    .map(|_| {
+      let mut bytes = [0u8; 8];
+      openssl::rand::rand_bytes(&mut bytes)?;
+      let code = base32::encode(BASE32, &bytes).to_lowercase();
+      Ok(format!("{}-{}", &code[..5], &code[5..10]))
+    })
+    .collect()
+}
+
+/// Whether a user has a confirmed authenticator app.
+pub fn has_totp(conn: &PgConnection, user_id: Uuid) -> Result<bool, HeimdallrError> {
+  Ok(TotpCredential::find(conn, user_id)?.map(|credential| credential.is_confirmed()).unwrap_or(false))
+}
+
+/// Whether a user gets codes on their phone, which only works while their number is verified.
+pub fn has_sms(conn: &PgConnection, user_id: Uuid) -> Result<bool, HeimdallrError> {
+  if !SmsFactor::exists(conn, user_id)? {
+    return Ok(false);
+  }
+
+  Ok(User::find(conn, user_id)?.map(|user| user.phone_number.is_some() && user.phone_number_verified).unwrap_or(false))
+}
+
+/// The second factors a user can log in with: `otp`, `webauthn` and/or `sms`.
+pub fn methods(conn: &PgConnection, user_id: Uuid) -> Result<Vec<&'static str>, HeimdallrError> {
+  let mut methods = Vec::new();
+  if has_totp(conn, user_id)? {
+    methods.push("otp");
+  }
+  if WebauthnCredential::exists_for_user(conn, user_id)? {
+    methods.push("webauthn");
+  }
+  if has_sms(conn, user_id)? {
+    methods.push("sms");
+  }
+  Ok(methods)
+}
+
+/// Whether logging in as a user requires a second factor.
+pub fn is_enrolled(conn: &PgConnection, user_id: Uuid) -> Result<bool, HeimdallrError> {
+  Ok(!methods(conn, user_id)?.is_empty())
+}
+
+/// Checks a code from the authenticator app; each code is only accepted once.
+pub fn verify_totp(conn: &PgConnection, credential: &TotpCredential, code: &str) -> Result<bool, HeimdallrError> {
+  match matching_step(&credential.secret, code.trim(), Utc::now())? {
+    Some(step) => credential.use_step(conn, step),
+    None       => Ok(false)
+  }
+}
+
+/// Checks the second factor of a user, either a code from their authenticator app or one of their
+/// recovery codes, which is used up.
+pub fn verify(conn: &PgConnection, user_id: Uuid, code: &str) -> Result<bool, HeimdallrError> {
+  let credential = match TotpCredential::find(conn, user_id)? {
+    Some(credential) if credential.is_confirmed() => credential,
+    _ => return Ok(false)
+  };
+
+  let code = code.trim();
+  if is_totp_code(code) {
+    verify_totp(conn, &credential, code)
+  }
+  else {
+    RecoveryCode::redeem(conn, user_id, &normalize_recovery_code(code))
+  }
+}
+
+/// Replaces the recovery codes of a user, returning the new ones for display.
+pub fn new_recovery_codes(conn: &PgConnection, user_id: Uuid) -> Result<Vec<String>, HeimdallrError> {
+  let codes = generate_recovery_codes()?;
+  let normalized: Vec<String> = codes.iter().map(|code| normalize_recovery_code(code)).collect();
+
+  RecoveryCode::replace_all(conn, user_id, &normalized)?;
+  Ok(codes)
+}
+
+/// Removes the authenticator app of a user, along with their recovery codes.
+pub fn disable_totp(conn: &PgConnection, user_id: Uuid) -> Result<(), HeimdallrError> {
+  conn.transaction(|| {
+    TotpCredential::delete(conn, user_id)?;
+    RecoveryCode::delete_all(conn, user_id)?;
+    Ok(())
+  })
+}
+
+/// Removes every second factor of a user.
+pub fn reset(conn: &PgConnection, user_id: Uuid) -> Result<(), HeimdallrError> {
+  conn.transaction(|| {
+    disable_totp(conn, user_id)?;
+    WebauthnCredential::delete_all(conn, user_id)?;
+    SmsFactor::delete(conn, user_id)?;
+    Ok(())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use chrono::TimeZone;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_hotp_matches_rfc_6238_vectors() -> Result<(), HeimdallrError> {
+    // RFC 6238 Appendix B, SHA-1.
+    let key = b"12345678901234567890";
+    assert_eq!(hotp(key, time_step(Utc.timestamp(59, 0)), 8)?, "94287082");
+    assert_eq!(hotp(key, time_step(Utc.timestamp(1_111_111_109, 0)), 8)?, "07081804");
+    assert_eq!(hotp(key, time_step(Utc.timestamp(2_000_000_000, 0)), 8)?, "69279037");
+    Ok(())
+  }
+
+  #[test]
+  fn test_codes_of_neighbouring_steps_match() -> Result<(), HeimdallrError> {
+    let secret = base32::encode(BASE32, b"12345678901234567890");
+    let at     = Utc.timestamp(1_111_111_109, 0);
+
+    assert_eq!(matching_step(&secret, "081804", at)?, Some(time_step(at)));
+    assert_eq!(matching_step(&secret, "081804", at + chrono::Duration::seconds(TOTP_PERIOD))?, Some(time_step(at)));
+    assert_eq!(matching_step(&secret, "081804", at + chrono::Duration::seconds(3 * TOTP_PERIOD))?, None);
+    Ok(())
+  }
+
+  #[test]
+  fn test_otpauth_uri() {
+    assert_eq!(
+      otpauth_uri("auth.example.com", "alice@example.com", "JBSWY3DPEHPK3PXP"),
+      "otpauth://totp/auth%2Eexample%2Ecom%3Aalice%40example%2Ecom?secret=JBSWY3DPEHPK3PXP&issuer=auth%2Eexample%2Ecom&algorithm=SHA1&digits=6&period=30"
+    );
+  }
+
+  #[test]
+  fn test_recovery_codes() -> Result<(), HeimdallrError> {
+    let codes = generate_recovery_codes()?;
+
+    assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+    assert!(codes.iter().all(|code| code.len() == 11 && !is_totp_code(code)));
+    assert_eq!(normalize_recovery_code(" ABCDE-fghij "), "abcdefghij");
+    Ok(())
+  }
+}
@@ -0,0 +1,253 @@
+use chrono::{DateTime, Utc};
+use openssl::hash::{hash, MessageDigest};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::db::models::User;
+use crate::error::*;
+use crate::jwt::Algorithm;
+
+/// Scope that turns an OAuth 2.0 request into an OpenID Connect request.
+pub const OPENID_SCOPE: &str = "openid";
+
+/// Scopes defined by OpenID Connect Core §5.4, in addition to `openid`.
+pub const STANDARD_SCOPES: &[&str] = &["profile", "email", "phone"];
+
+/// Authentication context level reported when only a single factor was used.
+pub const ACR_SINGLE_FACTOR: &str = "urn:heimdallr:acr:1fa";
+
+/// Authentication context level reported when more than one factor was used.
+pub const ACR_MULTI_FACTOR: &str = "urn:heimdallr:acr:2fa";
+
+/// Claims that can appear in ID tokens & UserInfo responses.
+pub const SUPPORTED_CLAIMS: &[&str] = &[
+  "sub", "iss", "aud", "exp", "iat", "auth_time", "nonce", "acr", "amr", "azp", "at_hash", "c_hash",
+  "name", "given_name", "family_name", "preferred_username", "picture", "locale", "updated_at",
+  "email", "email_verified", "phone_number", "phone_number_verified"
+];
+
+/// Grant types a client can be registered with; every one of them is accepted by `/token`.
+pub const GRANT_TYPES: &[&str] = &["password", "authorization_code", "client_credentials", "refresh_token", "webauthn"];
+
+/// The grant types of [`GRANT_TYPES`] defined by RFC 6749, which are the ones discovery advertises;
+/// `webauthn` is heimdallr's own.
+pub const STANDARD_GRANT_TYPES: &[&str] = &["password", "authorization_code", "client_credentials", "refresh_token"];
+
+/// The only PKCE code challenge method accepted.
+pub const PKCE_METHOD: &str = "S256";
+
+/// Path of the discovery document, relative to the issuer (OpenID Connect Discovery §4).
+pub const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+
+/// Path of the JWK Set, relative to the issuer.
+pub const JWKS_PATH: &str = "/.well-known/jwks.json";
+
+/// Path of the RP-initiated logout endpoint, relative to the issuer.
+pub const END_SESSION_PATH: &str = "/end_session";
+
+/// Event type identifying logout tokens (OpenID Connect Back-Channel Logout §2.4).
+pub const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+/// How & when the end-user authenticated.
+#[derive(Debug, Clone)]
+pub struct Authentication {
+  pub auth_time: DateTime<Utc>,
+  /// Authentication method references (RFC 8176), e.g. `pwd` or `otp`.
+  pub amr: Vec<String>,
+  /// The browser session the user authenticated in, reported as the `sid` claim.
+  pub session_id: Option<Uuid>
+}
+
+impl Authentication {
+  pub fn new(methods: &[&str]) -> Self {
+    Authentication {
+      auth_time: Utc::now(),
+      amr: methods.iter().map(|method| method.to_string()).collect(),
+      session_id: None
+    }
+  }
+
+  /// `acr` value matching the methods used.
+  pub fn acr(&self) -> &'static str {
+    if self.amr.len() > 1 || self.amr.iter().any(|method| method == "mfa") { ACR_MULTI_FACTOR } else { ACR_SINGLE_FACTOR }
+  }
+}
+
+/// OpenID Provider metadata (OpenID Connect Discovery §3).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Discovery {
+  pub issuer: String,
+  pub authorization_endpoint: String,
+  pub token_endpoint: String,
+  pub userinfo_endpoint: String,
+  pub jwks_uri: String,
+  pub revocation_endpoint: String,
+  pub introspection_endpoint: String,
+  pub end_session_endpoint: String,
+  pub scopes_supported: Vec<String>,
+  pub response_types_supported: Vec<String>,
+  pub response_modes_supported: Vec<String>,
+  pub grant_types_supported: Vec<String>,
+  pub subject_types_supported: Vec<String>,
+  pub id_token_signing_alg_values_supported: Vec<String>,
+  pub userinfo_signing_alg_values_supported: Vec<String>,
+  pub token_endpoint_auth_methods_supported: Vec<String>,
+  pub claims_supported: Vec<String>,
+  pub acr_values_supported: Vec<String>,
+  pub code_challenge_methods_supported: Vec<String>,
+  pub backchannel_logout_supported: bool,
+  pub backchannel_logout_session_supported: bool
+}
+
+impl Discovery {
+  /// Builds the metadata for an issuer; endpoints are served by the HTTP listener under the issuer URL.
+  pub fn new(issuer: &str, scopes: Vec<String>) -> Self {
+    let base      = issuer.trim_end_matches('/');
+    let endpoint  = |path: &str| format!("{}{}", base, path);
+    let owned     = |values: &[&str]| values.iter().map(|value| value.to_string()).collect::<Vec<_>>();
+    let algorithms: Vec<String> = Algorithm::ALL.iter().map(|algorithm| algorithm.to_string()).collect();
+
+    Discovery {
+      issuer: issuer.to_owned(),
+      authorization_endpoint: endpoint("/authorize"),
+      token_endpoint: endpoint("/token"),
+      userinfo_endpoint: endpoint("/userinfo"),
+      jwks_uri: endpoint(JWKS_PATH),
+      revocation_endpoint: endpoint("/revoke"),
+      introspection_endpoint: endpoint("/introspect"),
+      end_session_endpoint: endpoint(END_SESSION_PATH),
+      scopes_supported: scopes,
+      response_types_supported: owned(&["code"]),
+      response_modes_supported: owned(&["query"]),
+      grant_types_supported: owned(STANDARD_GRANT_TYPES),
+      subject_types_supported: owned(&["public"]),
+      id_token_signing_alg_values_supported: algorithms.clone(),
+      userinfo_signing_alg_values_supported: algorithms,
+      token_endpoint_auth_methods_supported: owned(&["client_secret_basic", "client_secret_post", "none"]),
+      claims_supported: owned(SUPPORTED_CLAIMS),
+      acr_values_supported: owned(&[ACR_SINGLE_FACTOR, ACR_MULTI_FACTOR]),
+      code_challenge_methods_supported: owned(&[PKCE_METHOD]),
+      backchannel_logout_supported: true,
+      backchannel_logout_session_supported: true
+    }
+  }
+}
+
+/// Whether the granted scopes make this an OpenID Connect request.
+pub fn is_openid(scopes: &[String]) -> bool {
+  scopes.iter().any(|scope| scope == OPENID_SCOPE)
+}
+
+/// Standard claims about a user, filtered by the granted scopes (OpenID Connect Core §5.4).
+pub fn standard_claims(user: &User, scopes: &[String]) -> Map<String, Value> {
+  let mut claims = Map::new();
+  let has = |name: &str| scopes.iter().any(|scope| scope == name);
+
+  claims.insert("sub".to_owned(), Value::String(user.id.to_string()));
+
+  if has("profile") {
+    insert_optional(&mut claims, "name", &user.name);
+    insert_optional(&mut claims, "given_name", &user.given_name);
+    insert_optional(&mut claims, "family_name", &user.family_name);
+    insert_optional(&mut claims, "picture", &user.picture);
+    insert_optional(&mut claims, "locale", &user.locale);
+    claims.insert("preferred_username".to_owned(), Value::String(user.username.clone()));
+    claims.insert("updated_at".to_owned(), Value::from(user.updated_at.timestamp()));
+  }
+
+  if has("email") {
+    if let Some(email) = &user.email {
+      claims.insert("email".to_owned(), Value::String(email.clone()));
+      claims.insert("email_verified".to_owned(), Value::Bool(user.email_verified));
+    }
+  }
+
+  if has("phone") {
+    if let Some(phone_number) = &user.phone_number {
+      claims.insert("phone_number".to_owned(), Value::String(phone_number.clone()));
+      claims.insert("phone_number_verified".to_owned(), Value::Bool(user.phone_number_verified));
+    }
+  }
+
+  claims
+}
+
+/// Computes `at_hash` / `c_hash`: the left half of the hash of the value, using the hash
+/// function of the signing algorithm (OpenID Connect Core §3.1.3.6 & §3.3.2.11).
+pub fn left_half_hash(algorithm: Algorithm, value: &str) -> Result<String, HeimdallrError> {
+  let digest = match algorithm {
+    Algorithm::ES256 | Algorithm::RS256 => MessageDigest::sha256(),
+    // Ed25519 signatures use SHA-512 internally.
+    Algorithm::EdDSA => MessageDigest::sha512()
+  };
+
+  let bytes = hash(digest, value.as_bytes())?;
+  Ok(base64::encode_config(&bytes[..bytes.len() / 2], base64::URL_SAFE_NO_PAD))
+}
+
+fn insert_optional(claims: &mut Map<String, Value>, name: &str, value: &Option<String>) {
+  if let Some(value) = value {
+    claims.insert(name.to_owned(), Value::String(value.clone()));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  fn user() -> User {
+    let now = Utc::now().naive_utc();
+
+    User {
+      id: uuid::Uuid::new_v4(),
+      username: "takara".to_owned(),
+      email: Some("takara@doge.com".to_owned()),
+      password_hash: String::new(),
+      disabled: false,
+      created_at: now,
+      updated_at: now,
+      name: Some("Takara Doge".to_owned()),
+      given_name: None,
+      family_name: None,
+      picture: None,
+      locale: None,
+      email_verified: true,
+      phone_number: Some("+15555550100".to_owned()),
+      phone_number_verified: false,
+      password_changed_at: now
+    }
+  }
+
+  #[test]
+  fn test_standard_claims_follow_scopes() {
+    let user   = user();
+    let claims = standard_claims(&user, &["openid".to_owned(), "email".to_owned()]);
+
+    assert_eq!(claims["email"], "takara@doge.com");
+    assert_eq!(claims["email_verified"], true);
+    assert!(claims.get("name").is_none());
+    assert!(claims.get("phone_number").is_none());
+  }
+
+  #[test]
+  fn test_discovery_endpoints_live_under_the_issuer() {
+    let discovery = Discovery::new("https://auth.doge.com/", vec!["openid".to_owned()]);
+
+    assert_eq!(discovery.issuer, "https://auth.doge.com/");
+    assert_eq!(discovery.token_endpoint, "https://auth.doge.com/token");
+    assert_eq!(discovery.jwks_uri, "https://auth.doge.com/.well-known/jwks.json");
+    assert_eq!(discovery.end_session_endpoint, "https://auth.doge.com/end_session");
+    assert!(discovery.id_token_signing_alg_values_supported.contains(&"RS256".to_owned()));
+  }
+
+  #[test]
+  fn test_left_half_hash() -> Result<(), HeimdallrError> {
+    let hash = left_half_hash(Algorithm::RS256, "jHkWEdUXMU1BwAsC4vtUsZwnNqhKbn8lB8bAaqVCUqo")?;
+    assert_eq!(hash, "DvmPnSwKALtYUWgxSj0OVg");
+    assert_eq!(left_half_hash(Algorithm::EdDSA, "jHkWEdUXMU1BwAsC4vtUsZwnNqhKbn8lB8bAaqVCUqo")?.len(), 43);
+    Ok(())
+  }
+}
@@ -0,0 +1,179 @@
+//! Offline lookups in a Pwned Passwords dump, which lists the SHA-1 hashes of passwords seen in
+//! data breaches along with how often each was seen.
+//!
+//! Dumps are read as downloaded, without importing them anywhere: either split by the first 5
+//! characters of the hashes, the k-anonymity prefixes of the range API, or as one sorted file.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+
+/// Characters of a hash the range files are named after.
+const PREFIX_LENGTH: usize = 5;
+
+#[derive(Debug, Clone)]
+pub enum BreachedPasswords {
+  /// A directory of `<PREFIX>.txt` files holding `SUFFIX:COUNT` lines, as served by the range API.
+  Ranges(PathBuf),
+  /// A single file of `HASH:COUNT` lines ordered by hash, which is binary searched.
+  Sorted(PathBuf)
+}
+
+impl BreachedPasswords {
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, HeimdallrError> {
+    let path     = path.as_ref();
+    let metadata = std::fs::metadata(path).map_err(|err| {
+      HeimdallrError::ConfigError(config::ConfigError::Message(format!("password_policy.breached_passwords `{}`: {}", path.display(), err)))
+    })?;
+
+    if metadata.is_dir() {
+      Ok(BreachedPasswords::Ranges(path.to_owned()))
+    }
+    else {
+      Ok(BreachedPasswords::Sorted(path.to_owned()))
+    }
+  }
+
+  /// How often a password was seen in breaches; 0 when it never was.
+  pub fn count(&self, password: &str) -> Result<u64, HeimdallrError> {
+    let hash = sha1_hex(password);
+
+    match self {
+      BreachedPasswords::Ranges(directory) => {
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+
+        match File::open(directory.join(format!("{}.txt", prefix))) {
+          Ok(file)                                               => find_in_range(file, suffix),
+          Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+          Err(err)                                               => Err(err.into())
+        }
+      },
+      BreachedPasswords::Sorted(path) => find_sorted(File::open(path)?, &hash)
+    }
+  }
+}
+
+/// Upper case hex SHA-1 of a password, the form the dumps use.
+fn sha1_hex(password: &str) -> String {
+  openssl::sha::sha1(password.as_bytes()).iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Splits a `HASH:COUNT` line; lines without a count count once.
+fn parse_line(line: &str) -> (String, u64) {
+  let mut parts = line.trim().splitn(2, ':');
+  let hash      = parts.next().unwrap_or_default().to_uppercase();
+  let count     = parts.next().and_then(|count| count.trim().parse().ok()).unwrap_or(1);
+  (hash, count)
+}
+
+/// Range files are small (a few hundred lines), so they are simply scanned.
+fn find_in_range(file: File, suffix: &str) -> Result<u64, HeimdallrError> {
+  for line in BufReader::new(file).lines() {
+    let (hash, count) = parse_line(&line?);
+    if hash == suffix {
+      return Ok(count);
+    }
+  }
+
+  Ok(0)
+}
+
+/// Binary searches the byte offsets of a sorted dump for the line of a hash.
+fn find_sorted(file: File, hash: &str) -> Result<u64, HeimdallrError> {
+  let mut reader = BufReader::new(file);
+  let (mut low, mut high) = (0, reader.seek(SeekFrom::End(0))?);
+  let mut line = String::new();
+
+  while low < high {
+    let middle = low + (high - low) / 2;
+
+    // Skip the rest of the line `middle` falls into, unless a line starts right there.
+    let mut start = middle;
+    if middle > 0 {
+      reader.seek(SeekFrom::Start(middle - 1))?;
+      line.clear();
+      start = middle - 1 + reader.read_line(&mut line)? as u64;
+    }
+    else {
+      reader.seek(SeekFrom::Start(0))?;
+    }
+
+    line.clear();
+    let length = reader.read_line(&mut line)? as u64;
+    if length == 0 {
+      high = middle;
+      continue;
+    }
+
+    let (candidate, count) = parse_line(&line);
+    match candidate.as_str().cmp(hash) {
+      std::cmp::Ordering::Equal   => return Ok(count),
+      std::cmp::Ordering::Less    => low = start + length,
+      std::cmp::Ordering::Greater => high = middle
+    }
+  }
+
+  Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::io::Write;
+
+  /// SHA-1 of `password`.
+  const PASSWORD: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+  fn scratch(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("heimdallr-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&path).unwrap();
+    path
+  }
+
+  #[test]
+  fn test_sha1_hex() {
+    assert_eq!(sha1_hex("password"), PASSWORD);
+  }
+
+  #[test]
+  fn test_range_files() -> Result<(), HeimdallrError> {
+    let directory = scratch("ranges");
+    let mut file  = File::create(directory.join("5BAA6.txt"))?;
+    writeln!(file, "003D68EB55068C33ACE09247EE4C639306B:3\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:3861493\r\n")?;
+
+    let breached = BreachedPasswords::open(&directory)?;
+    assert_eq!(breached.count("password")?, 3_861_493);
+    assert_eq!(breached.count("password1")?, 0);
+
+    std::fs::remove_dir_all(directory)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_sorted_file() -> Result<(), HeimdallrError> {
+    let directory = scratch("sorted");
+    let path      = directory.join("pwned-passwords-sha1-ordered-by-hash.txt");
+
+    let mut hashes: Vec<String> = (0..200).map(|n| sha1_hex(&format!("filler {}", n))).collect();
+    hashes.push(PASSWORD.to_owned());
+    hashes.sort();
+
+    let mut file = File::create(&path)?;
+    for (n, hash) in hashes.iter().enumerate() {
+      writeln!(file, "{}:{}", hash, n + 1)?;
+    }
+
+    let breached = BreachedPasswords::open(&path)?;
+    for (n, hash) in hashes.iter().enumerate() {
+      assert_eq!(find_sorted(File::open(&path)?, hash)?, n as u64 + 1);
+    }
+    assert!(breached.count("password")? > 0);
+    assert_eq!(breached.count("not in the list")?, 0);
+
+    std::fs::remove_dir_all(directory)?;
+    Ok(())
+  }
+}
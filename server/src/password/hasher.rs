@@ -0,0 +1,182 @@
+use argon2::{Config, ThreadMode, Variant, Version};
+
+use super::legacy;
+use crate::error::*;
+use crate::settings::Argon2 as Argon2Settings;
+
+/// Bytes of random salt in each hash.
+const SALT_LENGTH: usize = 16;
+
+/// Shortest pepper accepted; a guessable one adds nothing.
+const MIN_PEPPER_LENGTH: usize = 16;
+
+/// How a password compares to an encoded hash.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verification {
+  Invalid,
+  Valid,
+  /// The password matches, but the hash was made with other parameters, without the pepper or by
+  /// another system, and should be replaced.
+  Outdated
+}
+
+impl Verification {
+  pub fn is_match(self) -> bool {
+    self != Verification::Invalid
+  }
+}
+
+/// Hashes & verifies passwords with the argon2 parameters of the deployment.
+#[derive(Debug, Clone)]
+pub struct Hasher {
+  variant: Variant,
+  memory_cost: u32,
+  time_cost: u32,
+  parallelism: u32,
+  pepper: Vec<u8>
+}
+
+impl Default for Hasher {
+  fn default() -> Self {
+    Hasher::new(&Argon2Settings::default()).expect("the default argon2 parameters are valid")
+  }
+}
+
+impl Hasher {
+  pub fn new(settings: &Argon2Settings) -> Result<Self, HeimdallrError> {
+    let invalid = |message: &str| HeimdallrError::ConfigError(config::ConfigError::Message(format!("argon2.{}", message)));
+
+    let variant = match settings.variant() {
+      "argon2id" => Variant::Argon2id,
+      "argon2i"  => Variant::Argon2i,
+      "argon2d"  => Variant::Argon2d,
+      other      => return Err(invalid(&format!("variant `{}` must be argon2id, argon2i or argon2d", other)))
+    };
+
+    let parallelism = settings.parallelism();
+    if parallelism == 0 {
+      return Err(invalid("parallelism must be at least 1"));
+    }
+    if settings.time_cost() == 0 {
+      return Err(invalid("time_cost must be at least 1"));
+    }
+    if settings.memory_cost() < 8 * parallelism {
+      return Err(invalid("memory_cost must be at least 8 KiB per lane of parallelism"));
+    }
+
+    let pepper = settings.pepper.clone().unwrap_or_default().into_bytes();
+    if !pepper.is_empty() && pepper.len() < MIN_PEPPER_LENGTH {
+      return Err(invalid("pepper must be at least 16 bytes long"));
+    }
+
+    Ok(Hasher {
+      variant,
+      memory_cost: settings.memory_cost(),
+      time_cost: settings.time_cost(),
+      parallelism,
+      pepper
+    })
+  }
+
+  /// Hashes a password with a random salt.
+  pub fn hash(&self, password: &str) -> Result<String, HeimdallrError> {
+    let mut salt = [0u8; SALT_LENGTH];
+    openssl::rand::rand_bytes(&mut salt)?;
+
+    Ok(argon2::hash_encoded(password.as_bytes(), &salt, &self.config())?)
+  }
+
+  /// Checks a password against an encoded hash; malformed hashes never match.
+  pub fn verify(&self, encoded: &str, password: &str) -> Verification {
+    // Hashes imported from other systems.
+    if !encoded.starts_with("$argon2") {
+      return if legacy::verify(encoded, password) { Verification::Outdated } else { Verification::Invalid };
+    }
+
+    if argon2::verify_encoded_ext(encoded, password.as_bytes(), &self.pepper, &[]).unwrap_or(false) {
+      return if self.is_current(encoded) { Verification::Valid } else { Verification::Outdated };
+    }
+
+    // Hashes made before a pepper was configured.
+    if !self.pepper.is_empty() && argon2::verify_encoded(encoded, password.as_bytes()).unwrap_or(false) {
+      return Verification::Outdated;
+    }
+
+    Verification::Invalid
+  }
+
+  /// Whether a hash was made with the parameters new hashes get, e.g. `$argon2id$v=19$m=19456,t=2,p=1$...`.
+  fn is_current(&self, encoded: &str) -> bool {
+    let parts: Vec<&str> = encoded.split('$').collect();
+    let params = format!("m={},t={},p={}", self.memory_cost, self.time_cost, self.parallelism);
+
+    match parts.as_slice() {
+      ["", variant, version, encoded_params, _, _] => {
+        *variant == self.variant.as_lowercase_str() && *version == format!("v={}", Version::Version13.as_u32()) && *encoded_params == params
+      },
+      _ => false
+    }
+  }
+
+  fn config(&self) -> Config<'_> {
+    Config {
+      variant: self.variant,
+      version: Version::Version13,
+      mem_cost: self.memory_cost,
+      time_cost: self.time_cost,
+      lanes: self.parallelism,
+      thread_mode: if self.parallelism > 1 { ThreadMode::Parallel } else { ThreadMode::Sequential },
+      secret: &self.pepper,
+      ..Default::default()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn hasher(time_cost: u32, pepper: Option<&str>) -> Hasher {
+    Hasher::new(&Argon2Settings {
+      memory_cost: Some(64),
+      time_cost: Some(time_cost),
+      pepper: pepper.map(str::to_owned),
+      ..Default::default()
+    }).unwrap()
+  }
+
+  #[test]
+  fn test_older_parameters_are_outdated() -> Result<(), HeimdallrError> {
+    let encoded = hasher(1, None).hash("hunter2")?;
+
+    assert_eq!(hasher(1, None).verify(&encoded, "hunter2"), Verification::Valid);
+    assert_eq!(hasher(2, None).verify(&encoded, "hunter2"), Verification::Outdated);
+    assert_eq!(hasher(2, None).verify(&encoded, "hunter3"), Verification::Invalid);
+    assert_eq!(hasher(1, None).verify("not a hash", "hunter2"), Verification::Invalid);
+    Ok(())
+  }
+
+  #[test]
+  fn test_pepper() -> Result<(), HeimdallrError> {
+    let peppered = hasher(1, Some("0123456789abcdef"));
+    let encoded  = peppered.hash("hunter2")?;
+
+    assert_eq!(peppered.verify(&encoded, "hunter2"), Verification::Valid);
+    assert_eq!(hasher(1, None).verify(&encoded, "hunter2"), Verification::Invalid);
+    assert_eq!(hasher(1, Some("fedcba9876543210")).verify(&encoded, "hunter2"), Verification::Invalid);
+
+    // Hashes from before the pepper still match, until they are upgraded.
+    assert_eq!(peppered.verify(&hasher(1, None).hash("hunter2")?, "hunter2"), Verification::Outdated);
+    assert!(Hasher::new(&Argon2Settings { pepper: Some("short".to_owned()), ..Default::default() }).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_legacy_hashes_are_outdated() {
+    let encoded = "$pbkdf2-sha256$1000$c2FsdHNhbHRzYWx0c2FsdA$RilxBxnvGa3JIyaXwlUUKmvuPzxjHerJeqIuhiIvKNU";
+
+    assert_eq!(hasher(1, None).verify(encoded, "hunter2"), Verification::Outdated);
+    assert_eq!(hasher(1, None).verify(encoded, "hunter3"), Verification::Invalid);
+  }
+}
@@ -0,0 +1,264 @@
+//! Password hashes imported from other systems, in their usual modular crypt & PHC forms:
+//!
+//! * bcrypt: `$2b$12$<salt><hash>` (also `$2a$` & `$2y$`)
+//! * SHA-crypt: `$5$rounds=5000$<salt>$<hash>` & `$6$...`
+//! * PBKDF2: `$pbkdf2-sha256$<iterations>$<salt>$<hash>` (passlib, also `$pbkdf2$` for SHA-1 &
+//!   `$pbkdf2-sha512$`), `$pbkdf2-sha256$i=<iterations>,l=<length>$<salt>$<hash>` (PHC) &
+//!   `pbkdf2_sha256$<iterations>$<salt>$<hash>` (Django)
+//! * scrypt: `$scrypt$ln=<log2 n>,r=<r>,p=<p>$<salt>$<hash>`
+//!
+//! They are only ever verified; users get an argon2 hash the first time they log in.
+
+use openssl::hash::{self, MessageDigest};
+
+/// Largest scrypt cost accepted, 2²⁰; that takes a GiB of memory with the usual `r=8`.
+const MAX_SCRYPT_LOG_N: u32 = 20;
+
+const SHA_CRYPT_DEFAULT_ROUNDS: u32 = 5000;
+const SHA_CRYPT_MIN_ROUNDS: u32 = 1000;
+const SHA_CRYPT_MAX_ROUNDS: u32 = 999_999_999;
+const SHA_CRYPT_MAX_SALT: usize = 16;
+
+/// Characters of the base64 variant of crypt(3).
+const CRYPT_BASE64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Order the bytes of SHA-256 & SHA-512 digests are encoded in by SHA-crypt, three at a time.
+const SHA256_CRYPT_ORDER: &[(usize, usize, usize)] = &[
+  (0, 10, 20), (21, 1, 11), (12, 22, 2), (3, 13, 23), (24, 4, 14), (15, 25, 5), (6, 16, 26), (27, 7, 17),
+  (18, 28, 8), (9, 19, 29)
+];
+const SHA512_CRYPT_ORDER: &[(usize, usize, usize)] = &[
+  (0, 21, 42), (22, 43, 1), (44, 2, 23), (3, 24, 45), (25, 46, 4), (47, 5, 26), (6, 27, 48), (28, 49, 7),
+  (50, 8, 29), (9, 30, 51), (31, 52, 10), (53, 11, 32), (12, 33, 54), (34, 55, 13), (56, 14, 35), (15, 36, 57),
+  (37, 58, 16), (59, 17, 38), (18, 39, 60), (40, 61, 19), (62, 20, 41)
+];
+
+/// A parsed legacy hash.
+enum Hash<'a> {
+  Bcrypt(&'a str),
+  ShaCrypt { digest: MessageDigest, rounds: u32, salt: &'a str, hash: &'a str },
+  Pbkdf2 { digest: MessageDigest, iterations: usize, salt: Vec<u8>, hash: Vec<u8> },
+  Scrypt { log_n: u32, r: u32, p: u32, salt: Vec<u8>, hash: Vec<u8> }
+}
+
+/// Name of the algorithm of a legacy hash, or `None` when it is not one this module understands.
+pub fn algorithm(encoded: &str) -> Option<&'static str> {
+  parse(encoded).map(|hash| match hash {
+    Hash::Bcrypt(_)       => "bcrypt",
+    Hash::ShaCrypt { .. } => "sha-crypt",
+    Hash::Pbkdf2 { .. }   => "pbkdf2",
+    Hash::Scrypt { .. }   => "scrypt"
+  })
+}
+
+/// Checks a password against a legacy hash; unknown & malformed hashes never match.
+pub fn verify(encoded: &str, password: &str) -> bool {
+  let password = password.as_bytes();
+
+  match parse(encoded) {
+    Some(Hash::Bcrypt(encoded)) => bcrypt::verify(password, encoded).unwrap_or(false),
+    Some(Hash::ShaCrypt { digest, rounds, salt, hash }) => {
+      sha_crypt(digest, password, salt.as_bytes(), rounds).map(|computed| constant_time_eq(computed.as_bytes(), hash.as_bytes())).unwrap_or(false)
+    },
+    Some(Hash::Pbkdf2 { digest, iterations, salt, hash }) => {
+      let mut computed = vec![0u8; hash.len()];
+      openssl::pkcs5::pbkdf2_hmac(password, &salt, iterations, digest, &mut computed).is_ok() && constant_time_eq(&computed, &hash)
+    },
+    Some(Hash::Scrypt { log_n, r, p, salt, hash }) => {
+      let (n, r, p) = (1u64 << log_n, u64::from(r), u64::from(p));
+      // What OpenSSL needs for these costs, with some room to spare.
+      let max_memory = 128 * r * (n + p + 2) + (1 << 20);
+
+      let mut computed = vec![0u8; hash.len()];
+      openssl::pkcs5::scrypt(password, &salt, n, r, p, max_memory, &mut computed).is_ok() && constant_time_eq(&computed, &hash)
+    },
+    None => false
+  }
+}
+
+fn parse(encoded: &str) -> Option<Hash<'_>> {
+  let fields: Vec<&str> = encoded.split('$').collect();
+
+  match fields.as_slice() {
+    ["", "2a", cost, rest] | ["", "2b", cost, rest] | ["", "2y", cost, rest] if cost.len() == 2 && rest.len() == 53 => {
+      Some(Hash::Bcrypt(encoded))
+    },
+    ["", "5", rest @ ..] => parse_sha_crypt(MessageDigest::sha256(), rest),
+    ["", "6", rest @ ..] => parse_sha_crypt(MessageDigest::sha512(), rest),
+    ["", "pbkdf2", params, salt, hash]        => parse_pbkdf2(MessageDigest::sha1(), params, salt, hash),
+    ["", "pbkdf2-sha1", params, salt, hash]   => parse_pbkdf2(MessageDigest::sha1(), params, salt, hash),
+    ["", "pbkdf2-sha256", params, salt, hash] => parse_pbkdf2(MessageDigest::sha256(), params, salt, hash),
+    ["", "pbkdf2-sha512", params, salt, hash] => parse_pbkdf2(MessageDigest::sha512(), params, salt, hash),
+    ["pbkdf2_sha1", iterations, salt, hash]   => parse_django(MessageDigest::sha1(), iterations, salt, hash),
+    ["pbkdf2_sha256", iterations, salt, hash] => parse_django(MessageDigest::sha256(), iterations, salt, hash),
+    ["", "scrypt", params, salt, hash] => {
+      let log_n = param(params, "ln")?;
+      if log_n == 0 || log_n > MAX_SCRYPT_LOG_N {
+        return None;
+      }
+
+      Some(Hash::Scrypt { log_n, r: param(params, "r")?, p: param(params, "p")?, salt: decode_ab64(salt)?, hash: decode_ab64(hash)? })
+    },
+    _ => None
+  }
+}
+
+/// `[rounds=N, salt, hash]` or `[salt, hash]`.
+fn parse_sha_crypt<'a>(digest: MessageDigest, fields: &[&'a str]) -> Option<Hash<'a>> {
+  let (rounds, salt, hash) = match fields {
+    [rounds, salt, hash] if rounds.starts_with("rounds=") => (rounds["rounds=".len()..].parse().ok()?, *salt, *hash),
+    [salt, hash] => (SHA_CRYPT_DEFAULT_ROUNDS, *salt, *hash),
+    _            => return None
+  };
+
+  let salt = &salt[..salt.char_indices().nth(SHA_CRYPT_MAX_SALT).map_or(salt.len(), |(index, _)| index)];
+  Some(Hash::ShaCrypt { digest, rounds: rounds.clamp(SHA_CRYPT_MIN_ROUNDS, SHA_CRYPT_MAX_ROUNDS), salt, hash })
+}
+
+/// passlib puts the iterations on their own, PHC strings as `i=N,l=N`.
+fn parse_pbkdf2<'a>(digest: MessageDigest, params: &str, salt: &str, hash: &str) -> Option<Hash<'a>> {
+  let iterations = match params.parse() {
+    Ok(iterations) => iterations,
+    Err(_)         => param(params, "i")? as usize
+  };
+
+  pbkdf2(digest, iterations, decode_ab64(salt)?, decode_ab64(hash)?)
+}
+
+/// Django keeps the salt as text & pads the hash.
+fn parse_django<'a>(digest: MessageDigest, iterations: &str, salt: &str, hash: &str) -> Option<Hash<'a>> {
+  pbkdf2(digest, iterations.parse().ok()?, salt.as_bytes().to_vec(), base64::decode(hash).ok()?)
+}
+
+fn pbkdf2<'a>(digest: MessageDigest, iterations: usize, salt: Vec<u8>, hash: Vec<u8>) -> Option<Hash<'a>> {
+  if iterations == 0 || hash.is_empty() {
+    return None;
+  }
+
+  Some(Hash::Pbkdf2 { digest, iterations, salt, hash })
+}
+
+/// A numeric `name=value` parameter of a PHC string.
+fn param(params: &str, name: &str) -> Option<u32> {
+  params.split(',')
+    .find(|param| param.starts_with(name) && param[name.len()..].starts_with('='))
+    .and_then(|param| param[name.len() + 1..].parse().ok())
+}
+
+/// Base64 without padding, as PHC strings use it; passlib writes `.` instead of `+`.
+fn decode_ab64(value: &str) -> Option<Vec<u8>> {
+  base64::decode_config(value.replace('.', "+"), base64::STANDARD_NO_PAD).ok()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && openssl::memcmp::eq(a, b)
+}
+
+/// The encoded digest of SHA-crypt, as specified at https://www.akkadia.org/drepper/SHA-crypt.txt.
+fn sha_crypt(digest: MessageDigest, password: &[u8], salt: &[u8], rounds: u32) -> Option<String> {
+  let size = digest.size();
+  let sum  = |parts: &[&[u8]]| -> Option<Vec<u8>> {
+    let mut hasher = hash::Hasher::new(digest).ok()?;
+    for part in parts {
+      hasher.update(part).ok()?;
+    }
+    hasher.finish().ok().map(|digest| digest.to_vec())
+  };
+
+  let alternate = sum(&[password, salt, password])?;
+
+  let mut parts: Vec<&[u8]> = vec![password, salt];
+  let mut remaining = password.len();
+  while remaining > size {
+    parts.push(&alternate);
+    remaining -= size;
+  }
+  parts.push(&alternate[..remaining]);
+
+  let mut length = password.len();
+  while length > 0 {
+    parts.push(if length & 1 == 1 { &alternate } else { password });
+    length >>= 1;
+  }
+  let initial = sum(&parts)?;
+
+  let password_digest = sum(&vec![password; password.len()])?;
+  let salt_digest     = sum(&vec![salt; 16 + initial[0] as usize])?;
+  let p_sequence: Vec<u8> = password_digest.iter().cycle().take(password.len()).cloned().collect();
+  let s_sequence: Vec<u8> = salt_digest.iter().cycle().take(salt.len()).cloned().collect();
+
+  let mut current = initial;
+  for round in 0..rounds {
+    let mut parts: Vec<&[u8]> = Vec::with_capacity(4);
+    parts.push(if round % 2 == 1 { &p_sequence } else { &current });
+    if round % 3 != 0 {
+      parts.push(&s_sequence);
+    }
+    if round % 7 != 0 {
+      parts.push(&p_sequence);
+    }
+    parts.push(if round % 2 == 1 { &current } else { &p_sequence });
+    current = sum(&parts)?;
+  }
+
+  let (order, last) = if size == 32 {
+    (SHA256_CRYPT_ORDER, [(0, current[31], current[30], 3)])
+  }
+  else {
+    (SHA512_CRYPT_ORDER, [(0, 0, current[63], 2)])
+  };
+
+  let mut encoded = String::new();
+  let groups = order.iter().map(|&(a, b, c)| (current[a], current[b], current[c], 4)).chain(last.iter().cloned());
+  for (high, middle, low, length) in groups {
+    let mut word = (u32::from(high) << 16) | (u32::from(middle) << 8) | u32::from(low);
+    for _ in 0..length {
+      encoded.push(CRYPT_BASE64[(word & 0x3f) as usize] as char);
+      word >>= 6;
+    }
+  }
+
+  Some(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  const HASHES: &[(&str, &str)] = &[
+    ("bcrypt", "$2b$04$abcdefghijklmnopqrstuughE8Ev8uGFaUgY2cNEySvxngrb/Jzdm"),
+    ("sha-crypt", "$5$rounds=1000$saltsalt$f25dkPYTm952Io0nI9FgssYnbY.87HWdiFw89b8zOR8"),
+    ("sha-crypt", "$6$saltsalt$8iYtNHxjWRl.NF6oNZ5tF.iKFlQREaXBLlSmZKP6dy9l5z3vsooWNW0/GZ6Nej73/TFug6pIPSqbJoCT6dfnj."),
+    ("pbkdf2", "$pbkdf2$1000$c2FsdHNhbHRzYWx0c2FsdA$xMfBVkeH1sfGSLo3UHk2ISF8owc"),
+    ("pbkdf2", "$pbkdf2-sha256$1000$c2FsdHNhbHRzYWx0c2FsdA$RilxBxnvGa3JIyaXwlUUKmvuPzxjHerJeqIuhiIvKNU"),
+    ("pbkdf2", "$pbkdf2-sha512$i=1000,l=64$c2FsdHNhbHRzYWx0c2FsdA$uHBiiPdIHgrbiMhoB2V/9a0plYfCgvbrBRDDFg1YzClF408xFBHqUitf7Em2Y8G+RZ42qB6ca3nzr1ef3VtwjA"),
+    ("pbkdf2", "pbkdf2_sha256$1000$seasalt$aZOLUDnbVq4qfmIhIFCkAqvDNHspRzj9l43SgVe7GOM="),
+    ("scrypt", "$scrypt$ln=10,r=8,p=1$c2FsdHNhbHRzYWx0c2FsdA$v/uBvjpkrv4.RPlRbT7o/v0/ucpdIQIN3.rMqzxpxj4")
+  ];
+
+  #[test]
+  fn test_verify() {
+    for (name, encoded) in HASHES {
+      let password = if *name == "bcrypt" { "password" } else { "hunter2" };
+
+      assert_eq!(algorithm(encoded), Some(*name), "{}", encoded);
+      assert!(verify(encoded, password), "{} should match", encoded);
+      assert!(!verify(encoded, "hunter3"), "{} should not match", encoded);
+    }
+  }
+
+  #[test]
+  fn test_sha_crypt_reference_vectors() {
+    assert!(verify("$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5", "Hello world!"));
+    assert!(verify("$6$rounds=10000$saltstringsaltstring$OW1/O6BYHV6BcXZu8QVeXbDWra3Oeqh0sbHbbMCVNSnCM/UrjmM0Dp8vOuZeHBy/YTBmSK6H9qs/y3RnOaw5v.", "Hello world!"));
+  }
+
+  #[test]
+  fn test_unknown_hashes() {
+    assert_eq!(algorithm("$argon2id$v=19$m=4096,t=3,p=1$c2FsdA$aGFzaA"), None);
+    assert_eq!(algorithm("5f4dcc3b5aa765d61d8327deb882cf99"), None);
+    assert_eq!(algorithm("$scrypt$ln=40,r=8,p=1$c2FsdA$aGFzaA"), None);
+    assert!(!verify("$2b$04$short", "password"));
+  }
+}
@@ -0,0 +1,93 @@
+mod breached;
+mod hasher;
+mod legacy;
+mod policy;
+pub mod strength;
+
+pub use breached::BreachedPasswords;
+pub use hasher::{Hasher, Verification};
+pub use policy::{Policy, Violation};
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+use crate::crypto;
+use crate::error::*;
+
+lazy_static! {
+  static ref HASHER: RwLock<Hasher> = RwLock::new(Hasher::default());
+
+  /// A hash of a random password made by `HASHER`, checked in place of the hash of unknown users.
+  static ref DUMMY_HASH: RwLock<Option<String>> = RwLock::new(None);
+}
+
+/// Makes every later hash & verification use `hasher`; called once on boot.
+pub fn configure(hasher: Hasher) -> Result<(), HeimdallrError> {
+  let dummy = dummy_hash(&hasher)?;
+
+  *HASHER.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = hasher;
+  *DUMMY_HASH.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(dummy);
+  Ok(())
+}
+
+fn hasher() -> Hasher {
+  HASHER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+/// Hashes a password (or client secret) with argon2 & a random salt.
+///
+/// Hashing is slow on purpose; async code should call it on the blocking thread pool.
+pub fn hash(password: &str) -> Result<String, HeimdallrError> {
+  hasher().hash(password)
+}
+
+/// Name of the algorithm of an encoded hash that can be verified, e.g. `argon2` or `bcrypt`; `None`
+/// for anything else.
+pub fn algorithm(encoded: &str) -> Option<&'static str> {
+  if encoded.starts_with("$argon2") { Some("argon2") } else { legacy::algorithm(encoded) }
+}
+
+/// Checks a password against an encoded hash, telling whether the hash should be upgraded.
+pub fn check(encoded: &str, password: &str) -> Verification {
+  hasher().verify(encoded, password)
+}
+
+/// Checks a password against a hash that matches nothing, taking as long as checking the password
+/// of a user would; logins as unknown usernames must not stand out by answering sooner.
+pub fn check_nothing(password: &str) -> Result<(), HeimdallrError> {
+  let dummy = DUMMY_HASH.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+  let dummy = match dummy {
+    Some(dummy) => dummy,
+    None        => {
+      let dummy = dummy_hash(&hasher())?;
+      *DUMMY_HASH.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(dummy.clone());
+      dummy
+    }
+  };
+
+  check(&dummy, password);
+  Ok(())
+}
+
+/// Checks a password against an encoded hash; malformed hashes never match.
+pub fn verify(encoded: &str, password: &str) -> bool {
+  check(encoded, password).is_match()
+}
+
+fn dummy_hash(hasher: &Hasher) -> Result<String, HeimdallrError> {
+  hasher.hash(&crypto::random_token(32)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_check_nothing_uses_a_current_hash() {
+    check_nothing("hunter2").unwrap();
+
+    let dummy = DUMMY_HASH.read().unwrap().clone().unwrap();
+    assert_eq!(check(&dummy, "hunter2"), Verification::Invalid);
+    assert!(dummy.starts_with("$argon2id$"));
+  }
+}
@@ -0,0 +1,205 @@
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use std::fmt;
+
+use super::{strength, BreachedPasswords};
+use crate::db::models::{PreviousPassword, User};
+use crate::error::*;
+use crate::settings::PasswordPolicy as PolicySettings;
+
+/// Longest password accepted; hashing is slow on purpose, so huge ones would tie up the server.
+pub const MAX_LENGTH: usize = 1024;
+
+/// Identifiers shorter than this turn up in passwords by chance too often to be refused.
+const MIN_IDENTIFIER_LENGTH: usize = 4;
+
+/// A rule a password breaks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+  TooShort { min_length: usize },
+  TooLong { max_length: usize },
+  TooWeak { score: u8, min_strength: u8 },
+  ContainsIdentifier,
+  Reused { history: usize },
+  Breached { count: u64 }
+}
+
+impl Violation {
+  /// Name of the rule, reported in the `password_violations` metadata of errors.
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Violation::TooShort { .. }    => "too_short",
+      Violation::TooLong { .. }     => "too_long",
+      Violation::TooWeak { .. }     => "too_weak",
+      Violation::ContainsIdentifier => "contains_identifier",
+      Violation::Reused { .. }      => "reused",
+      Violation::Breached { .. }    => "breached"
+    }
+  }
+}
+
+impl fmt::Display for Violation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Violation::TooShort { min_length }         => write!(f, "password must be at least {} characters long", min_length),
+      Violation::TooLong { max_length }          => write!(f, "password must be at most {} characters long", max_length),
+      Violation::TooWeak { score, min_strength } => write!(f, "password is too easy to guess (strength {} of 4, at least {} is required)", score, min_strength),
+      Violation::ContainsIdentifier              => write!(f, "password must not contain the username or email address"),
+      Violation::Reused { history: 1 }           => write!(f, "password must differ from the current one"),
+      Violation::Reused { history }              => write!(f, "password must differ from the last {} passwords", history),
+      Violation::Breached { count }              => write!(f, "password has appeared in data breaches {} times", count)
+    }
+  }
+}
+
+/// The rules new passwords have to follow, and how long they last.
+#[derive(Debug, Clone)]
+pub struct Policy {
+  min_length: usize,
+  min_strength: u8,
+  history: usize,
+  max_age: Option<Duration>,
+  breached: Option<BreachedPasswords>
+}
+
+impl Default for Policy {
+  fn default() -> Self {
+    Policy::new(&PolicySettings::default()).expect("the default password policy is valid")
+  }
+}
+
+impl Policy {
+  pub fn new(settings: &PolicySettings) -> Result<Self, HeimdallrError> {
+    if settings.min_strength() > 4 {
+      return Err(HeimdallrError::ConfigError(config::ConfigError::Message("password_policy.min_strength must be between 0 and 4".to_owned())));
+    }
+
+    let breached = match &settings.breached_passwords {
+      Some(path) => Some(BreachedPasswords::open(path)?),
+      None       => None
+    };
+
+    Ok(Policy {
+      min_length: settings.min_length().min(MAX_LENGTH),
+      min_strength: settings.min_strength(),
+      history: settings.history(),
+      max_age: settings.max_age(),
+      breached
+    })
+  }
+
+  /// Checks a password for an account whose username, email address & such are `identifiers`,
+  /// returning every rule it breaks.
+  pub fn check(&self, password: &str, identifiers: &[&str]) -> Result<Vec<Violation>, HeimdallrError> {
+    let length = password.chars().count();
+    if length > MAX_LENGTH {
+      return Ok(vec![Violation::TooLong { max_length: MAX_LENGTH }]);
+    }
+
+    let mut violations = Vec::new();
+
+    if length < self.min_length {
+      violations.push(Violation::TooShort { min_length: self.min_length });
+    }
+
+    let identifiers = expand_identifiers(identifiers);
+    let lowercase   = password.to_lowercase();
+    if identifiers.iter().any(|identifier| lowercase.contains(identifier.as_str())) {
+      violations.push(Violation::ContainsIdentifier);
+    }
+
+    if self.min_strength > 0 {
+      let inputs: Vec<&str> = identifiers.iter().map(String::as_str).collect();
+      let score = strength::score(password, &inputs);
+
+      if score < self.min_strength {
+        violations.push(Violation::TooWeak { score, min_strength: self.min_strength });
+      }
+    }
+
+    if let Some(breached) = &self.breached {
+      let count = breached.count(password)?;
+      if count > 0 {
+        violations.push(Violation::Breached { count });
+      }
+    }
+
+    Ok(violations)
+  }
+
+  /// Checks a new password of an existing user, which also has to differ from their recent ones.
+  pub fn check_change(&self, conn: &PgConnection, user: &User, password: &str) -> Result<Vec<Violation>, HeimdallrError> {
+    let identifiers = [Some(user.username.as_str()), user.email.as_deref(), user.name.as_deref()];
+    let identifiers: Vec<&str> = identifiers.iter().flatten().cloned().collect();
+    let mut violations = self.check(password, &identifiers)?;
+
+    if self.history > 0 && !violations.contains(&Violation::TooLong { max_length: MAX_LENGTH }) {
+      let previous = PreviousPassword::recent(conn, user.id, self.history as i64 - 1)?;
+      let reused   = std::iter::once(&user.password_hash).chain(previous.iter()).any(|hash| super::verify(hash, password));
+
+      if reused {
+        violations.push(Violation::Reused { history: self.history });
+      }
+    }
+
+    Ok(violations)
+  }
+
+  /// Keeps the current password hash of a user around before it is replaced, for as long as the
+  /// history needs it.
+  pub fn remember(&self, conn: &PgConnection, user: &User) -> Result<(), HeimdallrError> {
+    let keep = self.history.saturating_sub(1) as i64;
+
+    if keep > 0 {
+      PreviousPassword::record(conn, user.id, &user.password_hash)?;
+    }
+
+    PreviousPassword::prune(conn, user.id, keep)?;
+    Ok(())
+  }
+
+  /// Whether the password of a user is older than the policy allows.
+  pub fn is_expired(&self, user: &User) -> bool {
+    match self.max_age {
+      Some(max_age) => user.password_changed_at + max_age < Utc::now().naive_utc(),
+      None          => false
+    }
+  }
+}
+
+/// Lowercased identifiers worth refusing, along with the mailbox part of email addresses.
+fn expand_identifiers(identifiers: &[&str]) -> Vec<String> {
+  identifiers.iter()
+    .flat_map(|identifier| {
+      let identifier = identifier.trim().to_lowercase();
+      let mailbox    = identifier.split('@').next().filter(|_| identifier.contains('@')).map(str::to_owned);
+      std::iter::once(identifier).chain(mailbox)
+    })
+    .filter(|identifier| identifier.chars().count() >= MIN_IDENTIFIER_LENGTH)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_check_reports_every_violation() -> Result<(), HeimdallrError> {
+    let policy = Policy::default();
+
+    assert_eq!(policy.check("x7Kq9pWm2", &["jane", "jane@example.com"])?, vec![]);
+    assert_eq!(policy.check("jane", &["jane"])?, vec![
+      Violation::TooShort { min_length: 8 },
+      Violation::ContainsIdentifier,
+      Violation::TooWeak { score: 0, min_strength: 2 }
+    ]);
+    assert_eq!(policy.check(&"x".repeat(MAX_LENGTH + 1), &[])?, vec![Violation::TooLong { max_length: MAX_LENGTH }]);
+    Ok(())
+  }
+
+  #[test]
+  fn test_identifiers_include_the_mailbox() {
+    assert_eq!(expand_identifiers(&["Jane.Doe@example.com", "al"]), vec!["jane.doe@example.com", "jane.doe"]);
+  }
+}
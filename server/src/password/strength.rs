@@ -0,0 +1,183 @@
+//! A password strength estimate in the spirit of zxcvbn: the password is split into common words,
+//! keyboard walks, sequences & repeats, which attackers try first, and the remaining characters,
+//! and the bits of entropy of each part are added up.
+//!
+//! The estimate is scored from 0 (guessed within about a thousand tries) to 4 (more than ten
+//! billion tries), with the thresholds of zxcvbn.
+
+/// Passwords & words found at the top of leaked password lists, most common first.
+const COMMON: &[&str] = &[
+  "password", "123456", "12345678", "qwerty", "123456789", "12345", "1234", "111111", "1234567",
+  "dragon", "123123", "baseball", "abc123", "football", "monkey", "letmein", "696969", "shadow",
+  "master", "666666", "qwertyuiop", "123321", "mustang", "1234567890", "michael", "654321", "superman",
+  "1qaz2wsx", "7777777", "121212", "000000", "qazwsx", "123qwe", "killer", "trustno1", "jordan",
+  "jennifer", "zxcvbnm", "asdfgh", "hunter", "buster", "soccer", "harley", "batman", "andrew",
+  "tigger", "sunshine", "iloveyou", "fuckme", "2000", "charlie", "robert", "thomas", "hockey",
+  "ranger", "daniel", "starwars", "klaster", "112233", "george", "computer", "michelle", "jessica",
+  "pepper", "1111", "zxcvbn", "555555", "11111111", "131313", "freedom", "777777", "pass", "maggie",
+  "159753", "aaaaaa", "ginger", "princess", "joshua", "cheese", "amanda", "summer", "love", "ashley",
+  "nicole", "chelsea", "biteme", "matthew", "access", "yankees", "987654321", "dallas", "austin",
+  "thunder", "taylor", "matrix", "mobilemail", "admin", "welcome", "login", "secret", "winter",
+  "spring", "autumn", "monday", "passw0rd", "qwerty123", "changeme", "default", "hello", "guest",
+  "root", "test", "abcdef", "abcd1234", "asdf", "asdfghjkl", "zaq12wsx", "azerty", "solo", "flower",
+  "shalom", "lovely", "whatever", "donald", "orange", "banana", "apple", "cookie", "heimdallr"
+];
+
+/// Keyboard rows; walking along one is barely harder to guess than repeating a key.
+const KEYBOARD_ROWS: &[&str] = &["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./"];
+
+/// Shortest word worth looking for; shorter matches are as likely to be chance.
+const MIN_WORD_LENGTH: usize = 4;
+
+/// Bits of entropy needed for each score above 0: 10³, 10⁶, 10⁸ & 10¹⁰ guesses.
+const THRESHOLDS: [f64; 4] = [9.97, 19.93, 26.58, 33.22];
+
+/// Scores a password from 0 to 4; `user_inputs` (username, email, name, ...) count as common words.
+pub fn score(password: &str, user_inputs: &[&str]) -> u8 {
+  let bits = entropy(password, user_inputs);
+  THRESHOLDS.iter().filter(|&&threshold| bits >= threshold).count() as u8
+}
+
+/// Bits of entropy of a password, as far as the patterns above can tell.
+pub fn entropy(password: &str, user_inputs: &[&str]) -> f64 {
+  let chars: Vec<char> = password.chars().collect();
+  let folded: Vec<char> = chars.iter().map(|&c| unleet(fold(c))).collect();
+
+  // User inputs are the first thing a targeted attack tries.
+  let words: Vec<Word> = user_inputs.iter().map(|input| Word::new(input, 0))
+    .chain(COMMON.iter().enumerate().map(|(rank, word)| Word::new(word, rank)))
+    .filter(|word| word.folded.len() >= MIN_WORD_LENGTH)
+    .collect();
+
+  let mut bits = 0.0;
+  let mut position = 0;
+
+  while position < chars.len() {
+    if let Some(word) = longest_word(&folded[position..], &words) {
+      let part = &chars[position..position + word.folded.len()];
+      bits += ((word.rank + 2) as f64).log2();
+
+      // Capitals & substituted letters only take a few more guesses.
+      if part.iter().any(|c| c.is_uppercase()) {
+        bits += 1.0;
+      }
+      if part.iter().map(|&c| fold(c)).ne(word.original.iter().cloned()) {
+        bits += 1.0;
+      }
+
+      position += part.len();
+      continue;
+    }
+
+    bits += if position > 0 && predictable(chars[position - 1], chars[position]) { 1.0 } else { cardinality(chars[position]).log2() };
+    position += 1;
+  }
+
+  bits
+}
+
+/// A common word or user input; words are compared with the substitutions undone.
+struct Word {
+  original: Vec<char>,
+  folded: Vec<char>,
+  rank: usize
+}
+
+impl Word {
+  fn new(word: &str, rank: usize) -> Self {
+    let original: Vec<char> = word.chars().map(fold).collect();
+    let folded = original.iter().map(|&c| unleet(c)).collect();
+    Word { original, folded, rank }
+  }
+}
+
+/// The longest, then most common, word `chars` start with.
+fn longest_word<'a>(chars: &[char], words: &'a [Word]) -> Option<&'a Word> {
+  words.iter()
+    .filter(|word| chars.starts_with(&word.folded))
+    .max_by_key(|word| (word.folded.len(), std::cmp::Reverse(word.rank)))
+}
+
+/// Whether a character follows from the previous one: a repeat, the next one in a sequence such as
+/// `abc` or `321`, or a neighbouring key.
+fn predictable(previous: char, current: char) -> bool {
+  let (previous, current) = (fold(previous), fold(current));
+
+  if previous == current || (previous as i64 - current as i64).abs() == 1 {
+    return true;
+  }
+
+  KEYBOARD_ROWS.iter().any(|row| {
+    let keys: Vec<char> = row.chars().collect();
+    keys.windows(2).any(|pair| (pair[0] == previous && pair[1] == current) || (pair[1] == previous && pair[0] == current))
+  })
+}
+
+/// Characters an attacker has to try for a position holding this one.
+fn cardinality(c: char) -> f64 {
+  if c.is_ascii_lowercase() || c.is_ascii_uppercase() {
+    26.0
+  }
+  else if c.is_ascii_digit() {
+    10.0
+  }
+  else if c.is_ascii() {
+    33.0
+  }
+  else {
+    // Any of a large alphabet, but attackers rarely bother with them.
+    100.0
+  }
+}
+
+fn fold(c: char) -> char {
+  c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Undoes the usual substitutions, `p@$$w0rd` for `password`.
+fn unleet(c: char) -> char {
+  match c {
+    '4' | '@' => 'a',
+    '8'       => 'b',
+    '3'       => 'e',
+    '1' | '!' => 'i',
+    '0'       => 'o',
+    '5' | '$' => 's',
+    '7'       => 't',
+    other     => other
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_common_passwords_score_zero() {
+    assert_eq!(score("password", &[]), 0);
+    assert_eq!(score("123456789", &[]), 0);
+    assert_eq!(score("P@ssw0rd", &[]), 0);
+    assert_eq!(score("qwertyuiop", &[]), 0);
+  }
+
+  #[test]
+  fn test_patterns_score_low() {
+    assert!(score("aaaaaaaaaaaa", &[]) <= 1);
+    assert!(score("abcdefghijkl", &[]) <= 1);
+    assert!(score("asdfghjkl;'", &[]) <= 1);
+    assert!(score("password123!", &[]) <= 1);
+  }
+
+  #[test]
+  fn test_user_inputs_count_as_words() {
+    assert!(score("janedoe1987", &["janedoe"]) < score("janedoe1987", &[]));
+  }
+
+  #[test]
+  fn test_random_passwords_score_high() {
+    assert_eq!(score("correct horse battery staple", &[]), 4);
+    assert_eq!(score("Tr0ub4dor&3", &[]), 4);
+    assert_eq!(score("x7Kq9pWm", &[]), 4);
+  }
+}
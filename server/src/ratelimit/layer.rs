@@ -0,0 +1,157 @@
+use futures::future::BoxFuture;
+use hyper::header::{self, HeaderValue};
+use hyper::{Request, Response};
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::Service;
+use tonic::transport::NamedService;
+use tower_layer::Layer;
+
+use crate::services::{self, error::ApiError};
+use super::{Caller, RateLimiter};
+
+/// Puts the rate limits in front of a tonic service.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+  limiter: RateLimiter
+}
+
+impl RateLimitLayer {
+  pub fn new(limiter: RateLimiter) -> Self {
+    RateLimitLayer { limiter }
+  }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+  type Service = RateLimited<S>;
+
+  fn layer(&self, inner: S) -> Self::Service {
+    RateLimited { inner, limiter: self.limiter.clone() }
+  }
+}
+
+/// A tonic service whose calls are turned away with `RESOURCE_EXHAUSTED` once over a limit.
+#[derive(Clone)]
+pub struct RateLimited<S> {
+  inner: S,
+  limiter: RateLimiter
+}
+
+impl<S: NamedService> NamedService for RateLimited<S> {
+  const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> Service<Request<B>> for RateLimited<S>
+  where S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+        B: Send + 'static
+{
+  type Response = Response<BoxBody>;
+  type Error = S::Error;
+  type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, mut request: Request<B>) -> Self::Future {
+    let peer = take_peer(&mut request);
+
+    if self.limiter.is_empty() {
+      return Box::pin(services::PEER.scope(peer, self.inner.call(request)));
+    }
+
+    let limiter = self.limiter.clone();
+    let method  = request.uri().path().to_owned();
+    let headers = request.headers().clone();
+
+    // The service polled ready handles the call; its clone takes its place for the next one.
+    let clone     = self.inner.clone();
+    let mut inner = std::mem::replace(&mut self.inner, clone);
+
+    Box::pin(services::PEER.scope(peer, async move {
+      // Access tokens are verified & the postgres backend queries the database, so checks stay off
+      // the reactor.
+      let checked = services::blocking(move || {
+        let caller = Caller::from_headers(&headers, peer.map(|addr| addr.ip()), &limiter);
+        limiter.check(&method, &caller)
+      });
+
+      match checked.await {
+        Ok(())   => inner.call(request).await,
+        Err(err) => Ok(rejection(err))
+      }
+    }))
+  }
+}
+
+/// The address of the peer of a call: the HTTP front end puts it among the extensions of gRPC-Web
+/// calls, tonic keeps it where only `tonic::Request::remote_addr` can read it.
+///
+/// Reading it moves the extensions into a `tonic::Request`, so handlers get it from
+/// [`services::PEER`] instead.
+fn take_peer<B>(request: &mut Request<B>) -> Option<SocketAddr> {
+  if let Some(peer) = request.extensions().get::<SocketAddr>() {
+    return Some(*peer);
+  }
+
+  let mut probe = Request::new(());
+  *probe.extensions_mut() = std::mem::take(request.extensions_mut());
+  tonic::Request::from_http(probe).remote_addr()
+}
+
+/// A trailers-only response turning a call away.
+fn rejection(err: ApiError) -> Response<BoxBody> {
+  let mut response = Response::new(BoxBody::empty());
+
+  let headers = response.headers_mut();
+  headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+  services::insert_error(headers, err);
+
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use heimdallr_api::admin::v1::bootstrap_client::BootstrapClient;
+  use heimdallr_api::admin::v1::bootstrap_server::{Bootstrap, BootstrapServer};
+  use heimdallr_api::admin::v1::{BootstrapRequest, BootstrapResponse};
+  use pretty_assertions::assert_eq;
+  use tokio::net::TcpListener;
+  use tonic::{Code, Status};
+
+  /// Answers with the address the call came from.
+  struct Echo;
+
+  #[tonic::async_trait]
+  impl Bootstrap for Echo {
+    async fn bootstrap(&self, request: tonic::Request<BootstrapRequest>) -> Result<tonic::Response<BootstrapResponse>, Status> {
+      let peer = services::peer_addr(&request).map(|addr| addr.ip().to_string()).unwrap_or_default();
+      Ok(tonic::Response::new(BootstrapResponse { user: None, client_id: peer, client_secret: String::new() }))
+    }
+  }
+
+  #[tokio::test]
+  async fn test_limits_per_ip_over_tcp() {
+    let limiter      = super::super::tests::limiter(&[("*", "ip", 1, 1)]);
+    let mut listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap();
+    let addr         = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+      tonic::transport::Server::builder()
+        .add_service(RateLimitLayer::new(limiter).layer(BootstrapServer::new(Echo)))
+        .serve_with_incoming(listener.incoming())
+        .await
+    });
+
+    let mut client = BootstrapClient::connect(format!("http://{}", addr)).await.unwrap();
+
+    let response = client.bootstrap(BootstrapRequest::default()).await.unwrap();
+    assert_eq!(response.into_inner().client_id, "127.0.0.1");
+
+    let err = client.bootstrap(BootstrapRequest::default()).await.unwrap_err();
+    assert_eq!(err.code(), Code::ResourceExhausted);
+  }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::*;
+use super::{refill, wait_for_token, Backend, Limit};
+
+struct Bucket {
+  tokens: f64,
+  updated_at: Instant,
+  limit: Limit
+}
+
+/// Buckets in the memory of this replica; lost on restart.
+#[derive(Default)]
+pub struct MemoryBackend {
+  buckets: Mutex<HashMap<String, Bucket>>
+}
+
+impl MemoryBackend {
+  fn take_at(&self, key: &str, limit: Limit, now: Instant) -> Option<Duration> {
+    let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let bucket = buckets.entry(key.to_owned()).or_insert(Bucket { tokens: limit.capacity, updated_at: now, limit });
+
+    bucket.tokens     = refill(bucket.tokens, (now - bucket.updated_at).as_secs_f64(), limit);
+    bucket.updated_at = now;
+    bucket.limit      = limit;
+
+    if bucket.tokens >= 1.0 {
+      bucket.tokens -= 1.0;
+      None
+    }
+    else {
+      Some(wait_for_token(bucket.tokens, limit))
+    }
+  }
+}
+
+impl Backend for MemoryBackend {
+  fn take(&self, key: &str, limit: Limit) -> Result<Option<Duration>, HeimdallrError> {
+    Ok(self.take_at(key, limit, Instant::now()))
+  }
+
+  fn prune(&self) -> Result<usize, HeimdallrError> {
+    let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let now    = Instant::now();
+    let before = buckets.len();
+
+    buckets.retain(|_, bucket| refill(bucket.tokens, (now - bucket.updated_at).as_secs_f64(), bucket.limit) < bucket.limit.capacity);
+    Ok(before - buckets.len())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_buckets_refill_over_time() {
+    let backend = MemoryBackend::default();
+    let limit   = Limit { capacity: 2.0, per_second: 0.5 };
+    let start   = Instant::now();
+
+    assert_eq!(backend.take_at("key", limit, start), None);
+    assert_eq!(backend.take_at("key", limit, start), None);
+    assert_eq!(backend.take_at("key", limit, start), Some(Duration::from_secs(2)));
+    assert_eq!(backend.take_at("other", limit, start), None);
+
+    assert_eq!(backend.take_at("key", limit, start + Duration::from_secs(1)), Some(Duration::from_secs(1)));
+    assert_eq!(backend.take_at("key", limit, start + Duration::from_secs(2)), None);
+  }
+}
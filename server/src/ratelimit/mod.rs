@@ -0,0 +1,320 @@
+//! Token-bucket rate limits on RPCs. [`RateLimitLayer`] wraps the tonic services; every call has to
+//! get a token from the bucket of each rule matching its method, keyed by the client address, the
+//! client id of a valid access token or the method itself. Buckets live in a [`Backend`].
+//!
+//! `rate_limits.backend` picks the backend: `memory`, each replica limiting on its own, or
+//! `postgres`, sharing the buckets through the database.
+
+mod layer;
+mod memory;
+mod postgres;
+
+pub use layer::{RateLimitLayer, RateLimited};
+pub use memory::MemoryBackend;
+pub use postgres::PostgresBackend;
+
+use hyper::HeaderMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::Database;
+use crate::error::*;
+use crate::lockout::{self, Lockout};
+use crate::services::error::ApiError;
+use crate::settings::{RateLimitRule, RateLimits as RateLimitSettings};
+use crate::tokens::TokenIssuer;
+
+/// Longest bucket key.
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Size & refill rate of a bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+  pub capacity: f64,
+  pub per_second: f64
+}
+
+pub trait Backend: Send + Sync {
+  /// Takes a token from the bucket under `key`, which starts out full; returns how long until one
+  /// is available when the bucket is empty.
+  fn take(&self, key: &str, limit: Limit) -> Result<Option<Duration>, HeimdallrError>;
+
+  /// Forgets buckets that have filled up again, returning how many.
+  fn prune(&self) -> Result<usize, HeimdallrError>;
+}
+
+/// The backend configured by `rate_limits.backend`.
+pub fn from_settings(settings: &RateLimitSettings, database: &Database) -> Result<Arc<dyn Backend>, HeimdallrError> {
+  match settings.backend() {
+    "memory"   => Ok(Arc::new(MemoryBackend::default())),
+    "postgres" => Ok(Arc::new(PostgresBackend::new(database.clone()))),
+    other      => Err(config_error(&format!("rate_limits.backend `{}` must be memory or postgres", other)))
+  }
+}
+
+fn config_error(message: &str) -> HeimdallrError {
+  HeimdallrError::ConfigError(config::ConfigError::Message(message.to_owned()))
+}
+
+/// What the calls of a rule are counted by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Per {
+  Ip,
+  ClientId,
+  Method
+}
+
+impl Per {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "ip"        => Some(Per::Ip),
+      "client_id" => Some(Per::ClientId),
+      "method"    => Some(Per::Method),
+      _           => None
+    }
+  }
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Per::Ip       => "ip",
+      Per::ClientId => "client_id",
+      Per::Method   => "method"
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+  method: String,
+  per: Per,
+  limit: Limit
+}
+
+impl Rule {
+  fn new(settings: &RateLimitRule) -> Result<Self, HeimdallrError> {
+    let per = Per::parse(&settings.per)
+      .ok_or_else(|| config_error(&format!("rate_limits.rules per `{}` must be ip, client_id or method", settings.per)))?;
+
+    if !(settings.method == "*" || settings.method.starts_with('/')) {
+      return Err(config_error(&format!("rate_limits.rules method `{}` must be `*` or start with `/`", settings.method)));
+    }
+    if settings.burst == 0 || settings.per_minute == 0 {
+      return Err(config_error("rate_limits.rules burst & per_minute must be at least 1"));
+    }
+
+    Ok(Rule {
+      method: settings.method.clone(),
+      per,
+      limit: Limit { capacity: f64::from(settings.burst), per_second: f64::from(settings.per_minute) / 60.0 }
+    })
+  }
+
+  /// `*` matches every method, `/package.Service/*` every method of a service.
+  fn matches(&self, method: &str) -> bool {
+    if self.method == "*" {
+      true
+    }
+    else if self.method.ends_with("/*") {
+      method.starts_with(&self.method[..self.method.len() - 1])
+    }
+    else {
+      method == self.method
+    }
+  }
+}
+
+/// Who a call comes from, as far as the rules care.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Caller {
+  pub ip: Option<IpAddr>,
+  pub client_id: Option<String>
+}
+
+impl Caller {
+  /// The client id is the `client_id` claim of a bearer token this server issued & that is still
+  /// valid, so a caller can't spend the capacity of another client by claiming to be it. Calls
+  /// without one aren't counted by `client_id` rules; `ip` rules are the ones that hold off abuse.
+  pub fn from_headers(headers: &HeaderMap, peer: Option<IpAddr>, limiter: &RateLimiter) -> Self {
+    let header    = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let client_id = || {
+      let token = crate::services::parse_bearer(header("authorization")?)?;
+      limiter.issuer.validate(token).ok()?["client_id"].as_str().map(str::to_owned)
+    };
+
+    Caller {
+      ip: limiter.lockout.client_ip(peer, header("x-forwarded-for")),
+      // Checking the signature is only worth it when a rule counts by client.
+      client_id: if limiter.counts_clients { client_id() } else { None }
+    }
+  }
+}
+
+/// The rules of a deployment & the backend keeping their buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+  rules: Arc<Vec<Rule>>,
+  backend: Arc<dyn Backend>,
+  /// For its trusted proxies.
+  lockout: Lockout,
+  /// Validates the access tokens `client_id` rules count by.
+  issuer: TokenIssuer,
+  counts_clients: bool
+}
+
+impl RateLimiter {
+  pub fn new(settings: &RateLimitSettings, backend: Arc<dyn Backend>, lockout: Lockout, issuer: TokenIssuer) -> Result<Self, HeimdallrError> {
+    let rules = settings.rules.iter().map(Rule::new).collect::<Result<Vec<_>, _>>()?;
+    let counts_clients = rules.iter().any(|rule| rule.per == Per::ClientId);
+
+    Ok(RateLimiter { rules: Arc::new(rules), backend, lockout, issuer, counts_clients })
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rules.is_empty()
+  }
+
+  /// Takes a token for a call from the bucket of every matching rule, turning it away with the
+  /// longest wait when any of them is empty.
+  ///
+  /// Rules whose key the call doesn't carry, e.g. `client_id` ones for anonymous calls, don't apply.
+  /// Backend failures let calls through: an outage of the limiter shouldn't take the server down.
+  pub fn check(&self, method: &str, caller: &Caller) -> Result<(), ApiError> {
+    let mut wait: Option<(Duration, &Rule)> = None;
+
+    for rule in self.rules.iter().filter(|rule| rule.matches(method)) {
+      let value = match rule.per {
+        Per::Ip       => caller.ip.map(lockout::ip_key),
+        Per::ClientId => caller.client_id.clone(),
+        Per::Method   => Some(method.to_owned())
+      };
+      let value = match value {
+        Some(value) => value,
+        None        => continue
+      };
+
+      let key: String = format!("{}|{}:{}", rule.method, rule.per.as_str(), value).chars().take(MAX_KEY_LENGTH).collect();
+      match self.backend.take(&key, rule.limit) {
+        Ok(Some(rule_wait)) if wait.map(|(longest, _)| rule_wait > longest).unwrap_or(true) => wait = Some((rule_wait, rule)),
+        Ok(_)    => (),
+        Err(err) => log::warn!("Unable to check rate limit `{}`: {}", key, err)
+      }
+    }
+
+    match wait {
+      Some((wait, rule)) => {
+        log::info!("Rate limit of {} per {} exceeded by {} ({:?}, client {:?})", rule.method, rule.per.as_str(), method, caller.ip, caller.client_id);
+        crate::metrics::increment(crate::metrics::RATE_LIMITED, &[("rule", &rule.method), ("per", rule.per.as_str())]);
+
+        Err(ApiError::too_many_requests("rate limit exceeded, try again later", wait.as_secs_f64().ceil() as i64))
+      },
+      None => Ok(())
+    }
+  }
+
+  /// Forgets buckets that have filled up again.
+  pub fn prune(&self) -> Result<usize, HeimdallrError> {
+    self.backend.prune()
+  }
+}
+
+/// Tokens in a bucket after `elapsed` seconds of refilling, & the wait for the next one once it is
+/// empty; shared by the backends.
+fn refill(tokens: f64, elapsed: f64, limit: Limit) -> f64 {
+  (tokens + elapsed.max(0.0) * limit.per_second).min(limit.capacity)
+}
+
+fn wait_for_token(tokens: f64, limit: Limit) -> Duration {
+  Duration::from_secs_f64(((1.0 - tokens) / limit.per_second).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::settings::{Jwt as JwtSettings, Lockout as LockoutSettings};
+  use pretty_assertions::assert_eq;
+
+  fn issuer() -> TokenIssuer {
+    TokenIssuer::ephemeral(JwtSettings::for_issuer("https://heimdallr.test"))
+  }
+
+  pub(super) fn limiter(rules: &[(&str, &str, u32, u32)]) -> RateLimiter {
+    let settings = RateLimitSettings {
+      backend: None,
+      rules: rules.iter().map(|&(method, per, burst, per_minute)| RateLimitRule {
+        method: method.to_owned(),
+        per: per.to_owned(),
+        burst,
+        per_minute
+      }).collect()
+    };
+
+    RateLimiter::new(&settings, Arc::new(MemoryBackend::default()), Lockout::new(&LockoutSettings::default()).unwrap(), issuer()).unwrap()
+  }
+
+  #[test]
+  fn test_rule_matching() {
+    let rule = |method: &str| Rule {
+      method: method.to_owned(),
+      per: Per::Method,
+      limit: Limit { capacity: 1.0, per_second: 1.0 }
+    };
+
+    assert!(rule("*").matches("/heimdallr.auth.Login/Login"));
+    assert!(rule("/heimdallr.auth.Login/*").matches("/heimdallr.auth.Login/Login"));
+    assert!(!rule("/heimdallr.auth.Login/*").matches("/heimdallr.admin.v1.Admin/GetUser"));
+    assert!(rule("/heimdallr.auth.Login/Login").matches("/heimdallr.auth.Login/Login"));
+    assert!(!rule("/heimdallr.auth.Login/Login").matches("/heimdallr.auth.Login/LoginAgain"));
+  }
+
+  #[test]
+  fn test_every_matching_rule_applies() {
+    let limiter = limiter(&[("/heimdallr.auth.Login/*", "ip", 2, 1), ("*", "client_id", 3, 1)]);
+    let caller  = Caller { ip: Some("192.0.2.1".parse().unwrap()), client_id: Some("app".to_owned()) };
+    let other   = Caller { ip: Some("192.0.2.2".parse().unwrap()), client_id: Some("app".to_owned()) };
+
+    assert!(limiter.check("/heimdallr.auth.Login/Login", &caller).is_ok());
+    assert!(limiter.check("/heimdallr.auth.Login/Login", &caller).is_ok());
+
+    let err = limiter.check("/heimdallr.auth.Login/Login", &caller).unwrap_err();
+    assert_eq!(err.metadata["retry_after"], "60");
+
+    // Another address still has its own tokens, but the client has used up its three.
+    assert!(limiter.check("/heimdallr.auth.Login/Login", &other).is_err());
+    assert!(limiter.check("/heimdallr.auth.Login/Login", &Caller::default()).is_ok());
+  }
+
+  #[test]
+  fn test_invalid_rules_are_refused() {
+    let settings = |method: &str, per: &str, burst: u32| RateLimitSettings {
+      backend: None,
+      rules: vec![RateLimitRule { method: method.to_owned(), per: per.to_owned(), burst, per_minute: 10 }]
+    };
+    let new = |settings: &RateLimitSettings| RateLimiter::new(settings, Arc::new(MemoryBackend::default()), Lockout::default(), issuer());
+
+    assert!(new(&settings("*", "ip", 5)).is_ok());
+    assert!(new(&settings("*", "user", 5)).is_err());
+    assert!(new(&settings("Login", "ip", 5)).is_err());
+    assert!(new(&settings("*", "ip", 0)).is_err());
+  }
+
+  #[test]
+  fn test_client_id_only_from_valid_tokens() {
+    let limiter = limiter(&[("*", "client_id", 3, 1)]);
+    let caller  = |authorization: String| {
+      let mut headers = HeaderMap::new();
+      headers.insert("authorization", authorization.parse().unwrap());
+      headers.insert("x-client-id", "other".parse().unwrap());
+      Caller::from_headers(&headers, None, &limiter).client_id
+    };
+
+    let token = limiter.issuer.access_token("someone", "app", &[], None).unwrap().token;
+    assert_eq!(caller(format!("Bearer {}", token)), Some("app".to_owned()));
+
+    let claims = base64::encode_config(br#"{"sub":"someone","client_id":"app"}"#, base64::URL_SAFE_NO_PAD);
+    assert_eq!(caller(format!("Bearer header.{}.signature", claims)), None);
+
+    let foreign = issuer().access_token("someone", "app", &[], None).unwrap().token;
+    assert_eq!(caller(format!("Bearer {}", foreign)), None);
+  }
+}
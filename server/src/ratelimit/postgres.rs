@@ -0,0 +1,34 @@
+use chrono::Utc;
+use std::time::Duration;
+
+use crate::db::{Database, models::RateLimitBucket};
+use crate::error::*;
+use super::{wait_for_token, Backend, Limit};
+
+/// Buckets untouched for this long are forgotten; every sensible rule has refilled by then.
+const STALE_AFTER_HOURS: i64 = 24;
+
+/// Buckets in the `rate_limit_buckets` table, shared by every replica.
+pub struct PostgresBackend {
+  db: Database
+}
+
+impl PostgresBackend {
+  pub fn new(db: Database) -> Self {
+    PostgresBackend { db }
+  }
+}
+
+impl Backend for PostgresBackend {
+  fn take(&self, key: &str, limit: Limit) -> Result<Option<Duration>, HeimdallrError> {
+    let conn   = self.db.pool.get()?;
+    let bucket = RateLimitBucket::take(&conn, key, limit.capacity, limit.per_second)?;
+
+    Ok(if bucket.allowed { None } else { Some(wait_for_token(bucket.tokens, limit)) })
+  }
+
+  fn prune(&self) -> Result<usize, HeimdallrError> {
+    let conn = self.db.pool.get()?;
+    RateLimitBucket::delete_stale(&conn, Utc::now().naive_utc() - chrono::Duration::hours(STALE_AFTER_HOURS))
+  }
+}
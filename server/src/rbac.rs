@@ -0,0 +1,84 @@
+//! Role based access control: users hold roles, either directly or through the groups they belong
+//! to, and every role bundles a set of permissions.
+
+use diesel::pg::PgConnection;
+use uuid::Uuid;
+
+use crate::db::models::{Permission, Role};
+use crate::error::*;
+
+/// What a user is entitled to, as carried by their access tokens.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entitlements {
+  /// Names of the roles the user holds, sorted.
+  pub roles: Vec<String>,
+  /// Names of the permissions granted by any of those roles, sorted & without duplicates.
+  pub permissions: Vec<String>
+}
+
+impl Entitlements {
+  pub fn load(conn: &PgConnection, user_id: Uuid) -> Result<Self, HeimdallrError> {
+    let roles  = Role::effective_for_user(conn, user_id)?;
+    let grants = Permission::for_roles(conn, &roles.iter().map(|role| role.id).collect::<Vec<_>>())?;
+
+    Ok(Self::from_grants(&roles, grants.into_iter().map(|(_, permission)| permission)))
+  }
+
+  fn from_grants(roles: &[Role], permissions: impl Iterator<Item = Permission>) -> Self {
+    let mut roles: Vec<String> = roles.iter().map(|role| role.name.clone()).collect();
+    roles.sort();
+    roles.dedup();
+
+    let mut permissions: Vec<String> = permissions.map(|permission| permission.name).collect();
+    permissions.sort();
+    permissions.dedup();
+
+    Entitlements { roles, permissions }
+  }
+}
+
+/// Permission names end up in tokens & authorization checks, so they are single words shaped the
+/// way [`authz`](crate::authz) reads them: `<resource>:<action>`, like `documents:write`.
+pub fn is_valid_permission_name(name: &str) -> bool {
+  let mut parts = name.splitn(2, ':');
+  let resource  = parts.next().unwrap_or_default();
+  let action    = parts.next().unwrap_or_default();
+
+  !resource.is_empty() && !action.is_empty() && !action.contains(':') && !name.chars().any(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::Utc;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_permissions_granted_by_several_roles_are_listed_once() {
+    let now  = Utc::now().naive_utc();
+    let role = |id: i32, name: &str| Role { id, name: name.to_owned(), description: String::new(), scopes: Vec::new(), created_at: now, updated_at: now };
+    let permission = |id: i32, name: &str| Permission { id, name: name.to_owned(), description: String::new(), created_at: now, updated_at: now };
+
+    let entitlements = Entitlements::from_grants(
+      &[role(2, "editor"), role(1, "admin")],
+      vec![permission(1, "documents:write"), permission(2, "documents:read"), permission(1, "documents:write")].into_iter()
+    );
+
+    assert_eq!(entitlements, Entitlements {
+      roles: vec!["admin".to_owned(), "editor".to_owned()],
+      permissions: vec!["documents:read".to_owned(), "documents:write".to_owned()]
+    });
+  }
+
+  #[test]
+  fn test_permission_names_are_resource_and_action() {
+    assert!(is_valid_permission_name("documents:write"));
+    assert!(is_valid_permission_name("documents/*:*"));
+
+    assert!(!is_valid_permission_name("documents"));
+    assert!(!is_valid_permission_name(":write"));
+    assert!(!is_valid_permission_name("documents:"));
+    assert!(!is_valid_permission_name("documents:write:all"));
+    assert!(!is_valid_permission_name("documents: write"));
+  }
+}
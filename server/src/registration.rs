@@ -0,0 +1,152 @@
+//! Self-service sign-up: who may register (see [`Mode`]), invitation codes & the checks usernames
+//! and email addresses of new accounts go through.
+
+use base32::Alphabet;
+
+use crate::error::*;
+use crate::settings::Registration as RegistrationSettings;
+
+/// Random bytes in an invitation code; 15 make 24 base32 characters.
+const INVITATION_CODE_BYTES: usize = 15;
+
+const BASE32: Alphabet = Alphabet::RFC4648 { padding: false };
+
+const MAX_USERNAME_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+  /// Anyone can sign up; an invitation code is only needed to be given its roles.
+  Open,
+  /// Signing up takes an invitation code issued by an administrator.
+  InvitationOnly,
+  Disabled
+}
+
+impl Mode {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "open"            => Some(Mode::Open),
+      "invitation_only" => Some(Mode::InvitationOnly),
+      "disabled"        => Some(Mode::Disabled),
+      _                 => None
+    }
+  }
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Mode::Open           => "open",
+      Mode::InvitationOnly => "invitation_only",
+      Mode::Disabled       => "disabled"
+    }
+  }
+}
+
+/// The registration settings of a deployment, checked on boot.
+#[derive(Debug, Clone)]
+pub struct Registrar {
+  mode: Mode,
+  require_email: bool,
+  verify_email: bool
+}
+
+impl Registrar {
+  pub fn new(settings: &RegistrationSettings) -> Result<Self, HeimdallrError> {
+    let mode = match &settings.mode {
+      Some(value) => Mode::parse(value).ok_or_else(|| {
+        HeimdallrError::ConfigError(config::ConfigError::Message(format!("registration.mode `{}` must be open, invitation_only or disabled", value)))
+      })?,
+      None => Mode::Disabled
+    };
+
+    Ok(Registrar {
+      mode,
+      require_email: settings.require_email(),
+      verify_email: settings.verify_email()
+    })
+  }
+
+  pub fn mode(&self) -> Mode {
+    self.mode
+  }
+
+  pub fn require_email(&self) -> bool {
+    self.require_email
+  }
+
+  pub fn verify_email(&self) -> bool {
+    self.verify_email
+  }
+}
+
+/// A new random invitation code, formatted for display as `XXXX-XXXX-...`.
+pub fn generate_invitation_code() -> Result<String, HeimdallrError> {
+  let mut bytes = [0u8; INVITATION_CODE_BYTES];
+  openssl::rand::rand_bytes(&mut bytes)?;
+
+  let code = base32::encode(BASE32, &bytes);
+  let groups: Vec<&str> = (0..code.len()).step_by(4).map(|start| &code[start..(start + 4).min(code.len())]).collect();
+  Ok(groups.join("-"))
+}
+
+/// Invitation codes are typed in by people, so dashes, spaces & case are ignored.
+pub fn normalize_invitation_code(code: &str) -> String {
+  code.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_uppercase()
+}
+
+/// Usernames are letters, digits, `.`, `_`, `-` & `@`, so email addresses can serve as one.
+pub fn validate_username(username: &str) -> Result<(), &'static str> {
+  if username.is_empty() {
+    return Err("username is required");
+  }
+  if username.chars().count() > MAX_USERNAME_LENGTH {
+    return Err("username must be at most 64 characters long");
+  }
+  if !username.chars().all(|c| c.is_alphanumeric() || ".-_@".contains(c)) {
+    return Err("username can only contain letters, digits, `.`, `_`, `-` and `@`");
+  }
+
+  Ok(())
+}
+
+/// A loose check catching typos; whether the address works is up to email verification.
+pub fn validate_email(email: &str) -> Result<(), &'static str> {
+  let valid = match email.rfind('@') {
+    Some(at) => at > 0 && email[at + 1..].contains('.') && !email.ends_with('.') && !email.contains(char::is_whitespace),
+    None     => false
+  };
+
+  if valid { Ok(()) } else { Err("email is not a valid address") }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_invitation_codes_survive_retyping() -> Result<(), HeimdallrError> {
+    let code = generate_invitation_code()?;
+
+    assert_eq!(code.len(), 24 + 5);
+    assert_eq!(normalize_invitation_code(&code.to_lowercase().replace('-', " ")), code.replace('-', ""));
+    Ok(())
+  }
+
+  #[test]
+  fn test_validate_username() {
+    assert_eq!(validate_username("jane.doe"), Ok(()));
+    assert_eq!(validate_username("jane@example.com"), Ok(()));
+    assert_eq!(validate_username(""), Err("username is required"));
+    assert!(validate_username("jane doe").is_err());
+    assert!(validate_username(&"a".repeat(65)).is_err());
+  }
+
+  #[test]
+  fn test_validate_email() {
+    assert_eq!(validate_email("jane@example.com"), Ok(()));
+    assert!(validate_email("jane").is_err());
+    assert!(validate_email("@example.com").is_err());
+    assert!(validate_email("jane@localhost").is_err());
+    assert!(validate_email("jane doe@example.com").is_err());
+  }
+}
@@ -0,0 +1,394 @@
+use heimdallr_api::auth::{
+  account_server::{Account, AccountServer},
+  ChangePasswordRequest, Consent as ProtoConsent, DeletePasskeyRequest, DisableSmsMfaRequest, FinishPasskeyRegistrationRequest, ListConsentsResponse,
+  ListPasskeysResponse, Passkey, RecoveryCodes, RevokeConsentRequest, SendPhoneCodeRequest, TotpCodeRequest, TotpEnrollment,
+  VerifyPhoneRequest, WebauthnChallenge as ProtoWebauthnChallenge
+};
+use crate::db::{Database, models::*};
+use crate::logout;
+use crate::mail::Outbox;
+use crate::mfa;
+use crate::password::{self, Policy};
+use crate::sms::{self, SmsCodes};
+use crate::tokens::{scopes_of, TokenIssuer};
+use crate::webauthn::{self, RegistrationResponse, RelyingParty, UserVerification};
+use super::auth::password_changes;
+use super::error::{self, ApiError, ErrorDetails};
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use tonic::{metadata::MetadataMap, Request, Response, Status};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Scope an access token must carry to use the account API.
+pub const ACCOUNT_SCOPE: &str = "account";
+
+pub struct AccountHandler {
+  db: Arc<Database>,
+  issuer: TokenIssuer,
+  passkeys: RelyingParty,
+  outbox: Outbox,
+  sms: SmsCodes,
+  policy: Policy
+}
+
+impl AccountHandler {
+  pub fn new(db: Database, issuer: TokenIssuer, passkeys: RelyingParty, outbox: Outbox, sms: SmsCodes, policy: Policy) -> Self {
+    Self { db: Arc::new(db), issuer, passkeys, outbox, sms, policy }
+  }
+
+  pub fn service(self) -> ErrorDetails<AccountServer<Self>> {
+    error::with_details(AccountServer::new(self))
+  }
+
+  fn connection(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, ApiError> {
+    Ok(self.db.pool.get()?)
+  }
+
+  /// The user the access token of a request was issued for.
+  fn authenticate(&self, conn: &PgConnection, metadata: &MetadataMap) -> Result<User, ApiError> {
+    let token = super::bearer_token(metadata).ok_or_else(|| ApiError::invalid_token("missing bearer token"))?;
+    let claims = self.issuer.validate(token)
+      .map_err(|err| ApiError::invalid_token(format!("invalid access token: {}", err)))?;
+
+    if !scopes_of(&claims).contains(&ACCOUNT_SCOPE) {
+      return Err(ApiError::insufficient_scope("the `account` scope is required"));
+    }
+
+    // Client credentials tokens carry the client id as their subject.
+    let user_id = claims["sub"].as_str()
+      .and_then(|subject| Uuid::parse_str(subject).ok())
+      .ok_or_else(|| ApiError::invalid_token("access token was not issued for a user"))?;
+
+    match User::find(conn, user_id)? {
+      Some(user) if !user.disabled => Ok(user),
+      _ => Err(ApiError::invalid_token("the user no longer exists or is disabled"))
+    }
+  }
+
+  /// Changes to the second factors of a user need a current code, so a stolen access token alone
+  /// cannot take them over.
+  fn verify_second_factor(&self, conn: &PgConnection, user: &User, code: &str) -> Result<(), ApiError> {
+    if !mfa::has_totp(conn, user.id)? {
+      return Err(ApiError::failed_precondition("no authenticator app is set up"));
+    }
+
+    if mfa::verify(conn, user.id, code)? {
+      Ok(())
+    }
+    else {
+      Err(ApiError::invalid_field("code", "the code is invalid or has already been used"))
+    }
+  }
+
+  /// Name the authenticator app files the secret under: the host of the issuer.
+  fn totp_issuer(&self) -> String {
+    let issuer = &self.issuer.settings().issuer;
+    url::Url::parse(issuer).ok().and_then(|url| url.host_str().map(str::to_owned)).unwrap_or_else(|| issuer.clone())
+  }
+}
+
+#[tonic::async_trait]
+impl Account for AccountHandler {
+  async fn list_consents(&self, request: Request<()>) -> Result<Response<ListConsentsResponse>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    let consents = Consent::for_user(&conn, user.id)?
+      .into_iter()
+      .map(|(consent, client_name)| consent_to_proto(consent, client_name))
+      .collect();
+
+    Ok(Response::new(ListConsentsResponse { consents }))
+  }
+
+  async fn revoke_consent(&self, request: Request<RevokeConsentRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+    let client_id = &request.get_ref().client_id;
+
+    let revoked = conn.transaction(|| {
+      RefreshToken::revoke_for_user_and_client(&conn, user.id, client_id)?;
+      Consent::revoke(&conn, user.id, client_id)
+    })?;
+
+    if revoked {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("consent not found").into())
+    }
+  }
+
+  async fn enroll_totp(&self, request: Request<()>) -> Result<Response<TotpEnrollment>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    if mfa::has_totp(&conn, user.id)? {
+      return Err(ApiError::failed_precondition("an authenticator app is already set up; disable it first").into());
+    }
+
+    let secret     = mfa::generate_secret()?;
+    let credential = TotpCredential::enroll(&conn, user.id, &secret)?;
+    let account    = user.email.as_deref().unwrap_or(&user.username);
+
+    Ok(Response::new(TotpEnrollment {
+      otpauth_uri: mfa::otpauth_uri(&self.totp_issuer(), account, &credential.secret),
+      secret: credential.secret
+    }))
+  }
+
+  async fn confirm_totp(&self, request: Request<TotpCodeRequest>) -> Result<Response<RecoveryCodes>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    let credential = match TotpCredential::find(&conn, user.id)? {
+      Some(credential) if !credential.is_confirmed() => credential,
+      Some(_) => return Err(ApiError::failed_precondition("the authenticator app has already been confirmed").into()),
+      None    => return Err(ApiError::failed_precondition("no authenticator app enrollment was started").into())
+    };
+
+    if !mfa::verify_totp(&conn, &credential, &request.get_ref().code)? {
+      return Err(ApiError::invalid_field("code", "the code is invalid or has expired").into());
+    }
+
+    let codes = conn.transaction(|| {
+      credential.confirm(&conn)?;
+      mfa::new_recovery_codes(&conn, user.id)
+    })?;
+
+    Ok(Response::new(RecoveryCodes { codes }))
+  }
+
+  async fn disable_totp(&self, request: Request<TotpCodeRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    self.verify_second_factor(&conn, &user, &request.get_ref().code)?;
+    mfa::disable_totp(&conn, user.id)?;
+
+    Ok(Response::new(()))
+  }
+
+  async fn regenerate_recovery_codes(&self, request: Request<TotpCodeRequest>) -> Result<Response<RecoveryCodes>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    self.verify_second_factor(&conn, &user, &request.get_ref().code)?;
+    let codes = mfa::new_recovery_codes(&conn, user.id)?;
+
+    Ok(Response::new(RecoveryCodes { codes }))
+  }
+
+  async fn start_passkey_registration(&self, request: Request<()>) -> Result<Response<ProtoWebauthnChallenge>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    WebauthnChallenge::delete_expired(&conn)?;
+
+    let challenge = webauthn::generate_challenge()?;
+    let record = WebauthnChallenge::create(&conn, &NewWebauthnChallenge {
+      challenge: &challenge,
+      ceremony: webauthn::REGISTRATION,
+      user_id: Some(user.id),
+      client_id: None,
+      user_verification: self.passkeys.user_verification().as_str(),
+      expires_at: (Utc::now() + self.passkeys.challenge_ttl()).naive_utc()
+    })?;
+
+    let existing = WebauthnCredential::for_user(&conn, user.id)?;
+
+    Ok(Response::new(ProtoWebauthnChallenge {
+      challenge_id: record.id.to_string(),
+      options_json: self.passkeys.creation_options(&challenge, &user, &existing).to_string()
+    }))
+  }
+
+  async fn finish_passkey_registration(&self, request: Request<FinishPasskeyRegistrationRequest>) -> Result<Response<Passkey>, Status> {
+    let conn    = self.connection()?;
+    let user    = self.authenticate(&conn, request.metadata())?;
+    let request = request.get_ref();
+
+    let challenge = match Uuid::parse_str(&request.challenge_id).ok() {
+      Some(id) => WebauthnChallenge::take(&conn, id, webauthn::REGISTRATION)?,
+      None     => None
+    };
+    let challenge = match challenge {
+      Some(challenge) if !challenge.is_expired() && challenge.user_id == Some(user.id) => challenge,
+      _ => return Err(ApiError::invalid_field("challenge_id", "the registration is unknown or has expired").into())
+    };
+
+    let user_verification = UserVerification::parse(&challenge.user_verification).unwrap_or(UserVerification::Required);
+    let registration = webauthn::parse_response::<RegistrationResponse>(&request.credential)
+      .and_then(|response| self.passkeys.verify_registration(&challenge.challenge, user_verification, &response))
+      .map_err(|err| ApiError::invalid_field("credential", err.to_string()))?;
+
+    let name = match request.name.trim() {
+      ""   => "Passkey",
+      name => name
+    };
+
+    let credential = WebauthnCredential::create(&conn, &NewWebauthnCredential {
+      user_id: user.id,
+      credential_id: &registration.credential_id,
+      public_key: &registration.public_key,
+      sign_count: i64::from(registration.sign_count),
+      attestation_format: &registration.attestation_format,
+      name
+    })?;
+
+    Ok(Response::new(passkey_to_proto(credential)))
+  }
+
+  async fn list_passkeys(&self, request: Request<()>) -> Result<Response<ListPasskeysResponse>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    let passkeys = WebauthnCredential::for_user(&conn, user.id)?.into_iter().map(passkey_to_proto).collect();
+
+    Ok(Response::new(ListPasskeysResponse { passkeys }))
+  }
+
+  async fn delete_passkey(&self, request: Request<DeletePasskeyRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+    let id   = Uuid::parse_str(&request.get_ref().id).map_err(|_| ApiError::invalid_field("id", "id must be a UUID"))?;
+
+    if WebauthnCredential::delete(&conn, user.id, id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("passkey not found").into())
+    }
+  }
+
+  async fn send_email_verification(&self, request: Request<()>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    if user.email.is_none() {
+      return Err(ApiError::failed_precondition("the account has no email address").into());
+    }
+    if user.email_verified {
+      return Err(ApiError::failed_precondition("the email address is already verified").into());
+    }
+
+    super::auth::delivered(self.outbox.send_verification(&conn, &user).map_err(ApiError::from)?)?;
+    Ok(Response::new(()))
+  }
+
+  async fn send_phone_code(&self, request: Request<SendPhoneCodeRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    if user.phone_number.is_none() {
+      return Err(ApiError::failed_precondition("the account has no phone number").into());
+    }
+
+    let (codes, channel) = (self.sms.clone(), super::auth::channel_from_proto(request.get_ref().channel));
+
+    // Codes are hashed before they are stored, so sending stays off the reactor.
+    let delivery = super::blocking(move || Ok(codes.send(&conn, &user, sms::VERIFY_PHONE, channel)?)).await?;
+    super::auth::delivered(delivery)?;
+    Ok(Response::new(()))
+  }
+
+  async fn verify_phone(&self, request: Request<VerifyPhoneRequest>) -> Result<Response<()>, Status> {
+    let conn    = self.connection()?;
+    let user    = self.authenticate(&conn, request.metadata())?;
+    let (codes, request) = (self.sms.clone(), request.into_inner());
+
+    // Codes are checked against their hash, so the check stays off the reactor.
+    super::blocking(move || {
+      verify_phone_code(&codes, &conn, &user, &request.code)?;
+
+      Ok(conn.transaction(|| {
+        if !user.phone_number_verified {
+          User::update(&conn, user.id, &UserChanges { phone_number_verified: Some(true), ..Default::default() })?;
+        }
+        if request.enable_sms_mfa { SmsFactor::enable(&conn, user.id) } else { Ok(()) }
+      })?)
+    }).await?;
+
+    Ok(Response::new(()))
+  }
+
+  async fn disable_sms_mfa(&self, request: Request<DisableSmsMfaRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+    let user = self.authenticate(&conn, request.metadata())?;
+
+    if !SmsFactor::exists(&conn, user.id)? {
+      return Err(ApiError::failed_precondition("codes by SMS are not set up").into());
+    }
+
+    let (codes, code) = (self.sms.clone(), request.into_inner().code);
+
+    // Codes are checked against their hash, so the check stays off the reactor.
+    super::blocking(move || {
+      verify_phone_code(&codes, &conn, &user, &code)?;
+      Ok(SmsFactor::delete(&conn, user.id)?)
+    }).await?;
+
+    Ok(Response::new(()))
+  }
+
+  async fn change_password(&self, request: Request<ChangePasswordRequest>) -> Result<Response<()>, Status> {
+    let conn    = self.connection()?;
+    let user    = self.authenticate(&conn, request.metadata())?;
+    let request = request.into_inner();
+
+    if request.new_password.is_empty() {
+      return Err(ApiError::invalid_field("new_password", "new_password is required").into());
+    }
+
+    // Both passwords are hashed, which is slow on purpose.
+    let (issuer, policy) = (self.issuer.clone(), self.policy.clone());
+    super::blocking(move || {
+      // A stolen access token is not enough to lock the owner out.
+      if !password::verify(&user.password_hash, &request.current_password) {
+        return Err(ApiError::invalid_field("current_password", "current_password is incorrect"));
+      }
+
+      conn.transaction(|| {
+        let changes = password_changes(&conn, &policy, &user, &request.new_password, "new_password")?;
+        User::update(&conn, user.id, &changes).map_err(ApiError::from)
+      })?;
+
+      Ok(logout::end_all_sessions(&conn, &issuer, user.id)?)
+    }).await?;
+
+    Ok(Response::new(()))
+  }
+}
+
+/// Changes to the phone of a user need a code sent to it, so a stolen access token alone cannot
+/// turn its codes on or off.
+fn verify_phone_code(codes: &SmsCodes, conn: &PgConnection, user: &User, code: &str) -> Result<(), ApiError> {
+  if codes.verify(conn, user, sms::VERIFY_PHONE, code)? {
+    Ok(())
+  }
+  else {
+    Err(ApiError::invalid_field("code", "the code is invalid, has expired or has already been used"))
+  }
+}
+
+fn consent_to_proto(consent: Consent, client_name: String) -> ProtoConsent {
+  ProtoConsent {
+    client_id: consent.client_id,
+    client_name,
+    scopes: consent.scopes,
+    created_at: Some(super::timestamp(consent.created_at)),
+    updated_at: Some(super::timestamp(consent.updated_at))
+  }
+}
+
+fn passkey_to_proto(credential: WebauthnCredential) -> Passkey {
+  Passkey {
+    id: credential.id.to_string(),
+    name: credential.name,
+    created_at: Some(super::timestamp(credential.created_at)),
+    last_used_at: credential.last_used_at.map(super::timestamp)
+  }
+}
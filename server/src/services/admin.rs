@@ -0,0 +1,936 @@
+use heimdallr_api::admin::v1::{
+  admin_server::{Admin, AdminServer},
+  self as proto
+};
+use crate::db::{Database, models::*};
+use crate::error::*;
+use crate::jwt::{KeyPair, SharedKeyStore, TokenValidation};
+use crate::lockout;
+use crate::mfa;
+use crate::oidc::GRANT_TYPES;
+use crate::password::{self, Policy};
+use crate::rbac;
+use crate::registration;
+use super::auth::password_changes;
+use super::error::{self, ApiError, ErrorCode, ErrorDetails};
+
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use tonic::{Request, Response, Status};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Scope an access token must carry to use the admin API.
+pub const ADMIN_SCOPE: &str = "admin";
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+
+pub struct AdminHandler {
+  db: Arc<Database>,
+  keys: SharedKeyStore,
+  policy: Policy
+}
+
+impl AdminHandler {
+  pub fn new(db: Database, keys: SharedKeyStore, policy: Policy) -> Self {
+    Self { db: Arc::new(db), keys, policy }
+  }
+
+  /// Wraps the handler in a server that only accepts admin access tokens issued by this server.
+  pub fn service(self, validation: TokenValidation) -> ErrorDetails<AdminServer<Self>> {
+    let keys = self.keys.clone();
+    error::with_details(AdminServer::with_interceptor(self, move |request: Request<()>| super::authorize(&keys, &validation, request, ADMIN_SCOPE)))
+  }
+
+  fn connection(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Status> {
+    Ok(self.db.pool.get().map_err(ApiError::from)?)
+  }
+
+  fn reload_keys(&self, conn: &PgConnection) -> Result<(), Status> {
+    Ok(self.keys.reload(conn)?)
+  }
+
+  fn user_response(&self, conn: &PgConnection, user: User) -> Result<Response<proto::User>, Status> {
+    Ok(Response::new(load_user(conn, user)?))
+  }
+
+  fn role_response(&self, conn: &PgConnection, role: Role) -> Result<Response<proto::Role>, Status> {
+    let permissions = Permission::for_roles(conn, &[role.id])?.into_iter().map(|(_, permission)| permission.name).collect();
+    Ok(Response::new(role_to_proto(role, permissions)))
+  }
+
+  fn group_response(&self, conn: &PgConnection, group: Group) -> Result<Response<proto::Group>, Status> {
+    let roles = Role::for_groups(conn, &[group.id])?.into_iter().map(|(_, role)| role.name).collect();
+    Ok(Response::new(group_to_proto(group, roles)))
+  }
+}
+
+/// Names the record involved in a constraint violation.
+fn conflict(err: HeimdallrError, what: &str) -> ApiError {
+  match ApiError::from(err) {
+    ApiError { code: ErrorCode::AlreadyExists, .. } => ApiError::already_exists(format!("{} already exists", what)),
+    ApiError { code: ErrorCode::NotFound, .. }      => ApiError::not_found(format!("{} references a record that does not exist", what)),
+    other                                           => other
+  }
+}
+
+fn parse_uuid(field: &str, value: &str) -> Result<Uuid, ApiError> {
+  Uuid::parse_str(value).map_err(|_| ApiError::invalid_field(field, format!("{} must be a UUID", field)))
+}
+
+fn page(limit: u32, offset: u32) -> (i64, i64) {
+  let limit = if limit == 0 { DEFAULT_PAGE_SIZE } else { limit.min(1000) };
+  (limit as i64, offset as i64)
+}
+
+fn permission_name(value: &str) -> Result<&str, ApiError> {
+  let name = not_empty(value, "name")?;
+
+  if !rbac::is_valid_permission_name(name) {
+    return Err(ApiError::invalid_field("name", "permission names look like `<resource>:<action>`, without whitespace"));
+  }
+  Ok(name)
+}
+
+fn not_empty<'a>(value: &'a str, field: &str) -> Result<&'a str, ApiError> {
+  if value.trim().is_empty() {
+    Err(ApiError::invalid_field(field, format!("{} is required", field)))
+  }
+  else {
+    Ok(value)
+  }
+}
+
+fn validate_grant_types(grant_types: &[String]) -> Result<(), ApiError> {
+  match grant_types.iter().find(|value| !GRANT_TYPES.contains(&value.as_str())) {
+    Some(unknown) => Err(ApiError::invalid_field("grant_types", format!("unknown grant type `{}`", unknown))),
+    None          => Ok(())
+  }
+}
+
+/// Origins are compared verbatim against the `Origin` header, so only serialized origins are accepted.
+fn validate_origins(origins: &[String]) -> Result<(), ApiError> {
+  let valid = |origin: &String| {
+    url::Url::parse(origin).map(|url| url.origin().ascii_serialization() == *origin).unwrap_or(false)
+  };
+
+  match origins.iter().find(|origin| !valid(origin)) {
+    Some(invalid) => Err(ApiError::invalid_field("allowed_origins", format!("`{}` is not an origin like https://app.example.com", invalid))),
+    None          => Ok(())
+  }
+}
+
+/// Logout URIs are requested by Heimdallr or sent to browsers, so they must be absolute HTTP(S) URLs.
+fn validate_uris(field: &str, uris: &[String]) -> Result<(), ApiError> {
+  let valid = |uri: &String| url::Url::parse(uri).map(|url| url.scheme() == "https" || url.scheme() == "http").unwrap_or(false);
+
+  match uris.iter().find(|uri| !valid(uri)) {
+    Some(invalid) => Err(ApiError::invalid_field(field, format!("`{}` is not an absolute http(s) URL", invalid))),
+    None          => Ok(())
+  }
+}
+
+fn load_user(conn: &PgConnection, user: User) -> Result<proto::User, HeimdallrError> {
+  let roles  = Role::for_user(conn, user.id)?;
+  let groups = Group::for_user(conn, user.id)?;
+  Ok(user_to_proto(user, roles, groups))
+}
+
+fn user_to_proto(user: User, roles: Vec<Role>, groups: Vec<Group>) -> proto::User {
+  proto::User {
+    id: user.id.to_string(),
+    username: user.username,
+    email: user.email.unwrap_or_default(),
+    disabled: user.disabled,
+    roles: roles.into_iter().map(|role| role.name).collect(),
+    groups: groups.into_iter().map(|group| group.name).collect(),
+    created_at: Some(super::timestamp(user.created_at)),
+    updated_at: Some(super::timestamp(user.updated_at)),
+    profile: Some(proto::Profile {
+      name: user.name.unwrap_or_default(),
+      given_name: user.given_name.unwrap_or_default(),
+      family_name: user.family_name.unwrap_or_default(),
+      picture: user.picture.unwrap_or_default(),
+      locale: user.locale.unwrap_or_default(),
+      email_verified: user.email_verified,
+      phone_number: user.phone_number.unwrap_or_default(),
+      phone_number_verified: user.phone_number_verified
+    })
+  }
+}
+
+/// Changes replacing every profile field; empty strings clear the claim.
+fn profile_changes(profile: proto::Profile) -> UserChanges {
+  let optional = |value: String| Some(Some(value).filter(|value| !value.is_empty()));
+
+  UserChanges {
+    name: optional(profile.name),
+    given_name: optional(profile.given_name),
+    family_name: optional(profile.family_name),
+    picture: optional(profile.picture),
+    locale: optional(profile.locale),
+    email_verified: Some(profile.email_verified),
+    phone_number: optional(profile.phone_number),
+    phone_number_verified: Some(profile.phone_number_verified),
+    ..Default::default()
+  }
+}
+
+fn client_to_proto(client: Client) -> proto::Client {
+  proto::Client {
+    confidential: client.is_confidential(),
+    client_id: client.id,
+    name: client.name,
+    redirect_uris: client.redirect_uris,
+    grant_types: client.grant_types,
+    scopes: client.scopes,
+    allowed_origins: client.allowed_origins,
+    post_logout_redirect_uris: client.post_logout_redirect_uris,
+    backchannel_logout_uri: client.backchannel_logout_uri.unwrap_or_default(),
+    created_at: Some(super::timestamp(client.created_at)),
+    updated_at: Some(super::timestamp(client.updated_at))
+  }
+}
+
+fn scope_to_proto(scope: Scope) -> proto::Scope {
+  proto::Scope {
+    name: scope.name,
+    description: scope.description,
+    restricted: scope.restricted
+  }
+}
+
+fn role_to_proto(role: Role, permissions: Vec<String>) -> proto::Role {
+  proto::Role {
+    id: role.id,
+    name: role.name,
+    description: role.description,
+    scopes: role.scopes,
+    permissions
+  }
+}
+
+fn permission_to_proto(permission: Permission) -> proto::Permission {
+  proto::Permission {
+    id: permission.id,
+    name: permission.name,
+    description: permission.description
+  }
+}
+
+fn group_to_proto(group: Group, roles: Vec<String>) -> proto::Group {
+  proto::Group {
+    id: group.id,
+    name: group.name,
+    description: group.description,
+    roles
+  }
+}
+
+/// The names of the records paired with each id, in the order they were loaded.
+fn names_by_id<T>(pairs: &[(i32, T)], id: i32, name: impl Fn(&T) -> &str) -> Vec<String> {
+  pairs.iter().filter(|(owner, _)| *owner == id).map(|(_, record)| name(record).to_owned()).collect()
+}
+
+/// Invitations store role ids; roles deleted since are left out.
+fn invitation_to_proto(invitation: Invitation, roles: &[Role]) -> proto::Invitation {
+  let roles = roles.iter().filter(|role| invitation.role_ids.contains(&role.id)).map(|role| role.name.clone()).collect();
+
+  proto::Invitation {
+    id: invitation.id.to_string(),
+    description: invitation.description,
+    roles,
+    max_uses: invitation.max_uses as u32,
+    uses: invitation.uses as u32,
+    expires_at: invitation.expires_at.map(super::timestamp),
+    created_at: Some(super::timestamp(invitation.created_at))
+  }
+}
+
+fn key_to_proto(key: SigningKey) -> proto::Key {
+  proto::Key {
+    kid: key.kid,
+    algorithm: key.algorithm,
+    status: key.status,
+    public_key: key.public_key,
+    created_at: Some(super::timestamp(key.created_at)),
+    activated_at: key.activated_at.map(super::timestamp),
+    retired_at: key.retired_at.map(super::timestamp)
+  }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminHandler {
+  async fn create_user(&self, request: Request<proto::CreateUserRequest>) -> Result<Response<proto::User>, Status> {
+    let request  = request.into_inner();
+    let username = normalize(not_empty(&request.username, "username")?);
+    let email    = Some(normalize(&request.email)).filter(|email| !email.is_empty());
+    let password = not_empty(&request.password, "password")?.to_owned();
+
+    // Checking the password against the policy & hashing it are slow on purpose.
+    let (policy, identifiers) = (self.policy.clone(), std::iter::once(username.clone()).chain(email.clone()).collect::<Vec<_>>());
+    let hash = super::blocking(move || {
+      let identifiers: Vec<&str> = identifiers.iter().map(String::as_str).collect();
+      let violations = policy.check(&password, &identifiers)?;
+      if !violations.is_empty() {
+        return Err(ApiError::password_rejected("password", &violations));
+      }
+      Ok(password::hash(&password)?)
+    }).await?;
+
+    let conn = self.connection()?;
+    let mut user = User::create(&conn, &NewUser {
+      username: &username,
+      email: email.as_deref(),
+      password_hash: &hash
+    }).map_err(|err| conflict(err, "username or email"))?;
+
+    if let Some(profile) = request.profile {
+      user = User::update(&conn, user.id, &profile_changes(profile))?.unwrap_or(user);
+    }
+
+    Ok(Response::new(user_to_proto(user, vec![], vec![])))
+  }
+
+  async fn get_user(&self, request: Request<proto::GetUserRequest>) -> Result<Response<proto::User>, Status> {
+    let id   = parse_uuid("id", &request.get_ref().id)?;
+    let conn = self.connection()?;
+
+    match User::find(&conn, id)? {
+      Some(user) => self.user_response(&conn, user),
+      None       => Err(ApiError::not_found("user not found").into())
+    }
+  }
+
+  async fn list_users(&self, request: Request<proto::ListUsersRequest>) -> Result<Response<proto::ListUsersResponse>, Status> {
+    let (limit, offset) = page(request.get_ref().limit, request.get_ref().offset);
+    let conn = self.connection()?;
+
+    let users = User::list(&conn, limit, offset)?
+      .into_iter()
+      .map(|user| load_user(&conn, user))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Response::new(proto::ListUsersResponse { users }))
+  }
+
+  async fn update_user(&self, request: Request<proto::UpdateUserRequest>) -> Result<Response<proto::User>, Status> {
+    let request = request.into_inner();
+    let id      = parse_uuid("id", &request.id)?;
+
+    // New passwords go through the policy & history like those users pick themselves, which is
+    // slow on purpose.
+    let password = match request.password.clone() {
+      Some(value) => {
+        let (db, policy) = (self.db.clone(), self.policy.clone());
+        super::blocking(move || {
+          let conn = db.pool.get()?;
+          let user = User::find(&conn, id)?.ok_or_else(|| ApiError::not_found("user not found"))?;
+          password_changes(&conn, &policy, &user, not_empty(&value, "password")?, "password")
+        }).await?
+      },
+      None => UserChanges::default()
+    };
+
+    let conn = self.connection()?;
+
+    let changes = UserChanges {
+      username: match request.username {
+        Some(username) => Some(normalize(not_empty(&username, "username")?)),
+        None           => None
+      },
+      email: request.email.map(|email| Some(normalize(&email)).filter(|email| !email.is_empty())),
+      password_hash: password.password_hash,
+      password_changed_at: password.password_changed_at,
+      disabled: request.disabled,
+      ..request.profile.map(profile_changes).unwrap_or_default()
+    };
+
+    match User::update(&conn, id, &changes).map_err(|err| conflict(err, "username or email"))? {
+      Some(user) => self.user_response(&conn, user),
+      None       => Err(ApiError::not_found("user not found").into())
+    }
+  }
+
+  async fn delete_user(&self, request: Request<proto::DeleteUserRequest>) -> Result<Response<()>, Status> {
+    let id   = parse_uuid("id", &request.get_ref().id)?;
+    let conn = self.connection()?;
+
+    if User::delete(&conn, id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("user not found").into())
+    }
+  }
+
+  async fn reset_mfa(&self, request: Request<proto::ResetMfaRequest>) -> Result<Response<()>, Status> {
+    let id   = parse_uuid("id", &request.get_ref().id)?;
+    let conn = self.connection()?;
+
+    if User::find(&conn, id)?.is_none() {
+      return Err(ApiError::not_found("user not found").into());
+    }
+
+    mfa::reset(&conn, id)?;
+    Ok(Response::new(()))
+  }
+
+  async fn unlock_user(&self, request: Request<proto::UnlockUserRequest>) -> Result<Response<()>, Status> {
+    let id   = parse_uuid("id", &request.get_ref().id)?;
+    let conn = self.connection()?;
+    let user = User::find(&conn, id)?.ok_or_else(|| ApiError::not_found("user not found"))?;
+
+    if lockout::unlock(&conn, &user)? {
+      log::info!("Unlocked logins of user {}", user.id);
+    }
+    Ok(Response::new(()))
+  }
+
+  async fn create_client(&self, request: Request<proto::CreateClientRequest>) -> Result<Response<proto::CreateClientResponse>, Status> {
+    let request = request.into_inner();
+    validate_grant_types(&request.grant_types)?;
+    validate_origins(&request.allowed_origins)?;
+    validate_uris("post_logout_redirect_uris", &request.post_logout_redirect_uris)?;
+
+    let backchannel_logout_uri = Some(request.backchannel_logout_uri.as_str()).filter(|uri| !uri.is_empty());
+    if let Some(uri) = backchannel_logout_uri {
+      validate_uris("backchannel_logout_uri", &[uri.to_owned()])?;
+    }
+
+    let client_id = if request.client_id.is_empty() {
+      Uuid::new_v4().to_string()
+    }
+    else {
+      request.client_id.clone()
+    };
+
+    let (secret, secret_hash) = if request.confidential {
+      let secret = crate::crypto::random_token(32)?;
+      let hash   = password::hash(&secret)?;
+      (secret, Some(hash))
+    }
+    else {
+      (String::new(), None)
+    };
+
+    let conn   = self.connection()?;
+    let client = Client::create(&conn, &NewClient {
+      id: &client_id,
+      name: not_empty(&request.name, "name")?,
+      secret_hash: secret_hash.as_deref(),
+      redirect_uris: &request.redirect_uris,
+      grant_types: &request.grant_types,
+      scopes: &request.scopes,
+      allowed_origins: &request.allowed_origins,
+      post_logout_redirect_uris: &request.post_logout_redirect_uris,
+      backchannel_logout_uri
+    }).map_err(|err| conflict(err, "client"))?;
+
+    Ok(Response::new(proto::CreateClientResponse {
+      client: Some(client_to_proto(client)),
+      client_secret: secret
+    }))
+  }
+
+  async fn get_client(&self, request: Request<proto::GetClientRequest>) -> Result<Response<proto::Client>, Status> {
+    let conn = self.connection()?;
+
+    match Client::find(&conn, &request.get_ref().client_id)? {
+      Some(client) => Ok(Response::new(client_to_proto(client))),
+      None         => Err(ApiError::not_found("client not found").into())
+    }
+  }
+
+  async fn list_clients(&self, request: Request<proto::ListClientsRequest>) -> Result<Response<proto::ListClientsResponse>, Status> {
+    let (limit, offset) = page(request.get_ref().limit, request.get_ref().offset);
+    let conn    = self.connection()?;
+    let clients = Client::list(&conn, limit, offset)?;
+
+    Ok(Response::new(proto::ListClientsResponse {
+      clients: clients.into_iter().map(client_to_proto).collect()
+    }))
+  }
+
+  async fn update_client(&self, request: Request<proto::UpdateClientRequest>) -> Result<Response<proto::Client>, Status> {
+    let request = request.into_inner();
+
+    if let Some(grant_types) = &request.grant_types {
+      validate_grant_types(&grant_types.values)?;
+    }
+    if let Some(origins) = &request.allowed_origins {
+      validate_origins(&origins.values)?;
+    }
+    if let Some(uris) = &request.post_logout_redirect_uris {
+      validate_uris("post_logout_redirect_uris", &uris.values)?;
+    }
+    let backchannel_logout_uri = request.backchannel_logout_uri.map(|uri| Some(uri).filter(|uri| !uri.is_empty()));
+    if let Some(Some(uri)) = &backchannel_logout_uri {
+      validate_uris("backchannel_logout_uri", std::slice::from_ref(uri))?;
+    }
+
+    let changes = ClientChanges {
+      name: request.name,
+      secret_hash: None,
+      redirect_uris: request.redirect_uris.map(|list| list.values),
+      grant_types: request.grant_types.map(|list| list.values),
+      scopes: request.scopes.map(|list| list.values),
+      allowed_origins: request.allowed_origins.map(|list| list.values),
+      post_logout_redirect_uris: request.post_logout_redirect_uris.map(|list| list.values),
+      backchannel_logout_uri
+    };
+
+    let conn = self.connection()?;
+    match Client::update(&conn, &request.client_id, &changes)? {
+      Some(client) => Ok(Response::new(client_to_proto(client))),
+      None         => Err(ApiError::not_found("client not found").into())
+    }
+  }
+
+  async fn delete_client(&self, request: Request<proto::DeleteClientRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+
+    if Client::delete(&conn, &request.get_ref().client_id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("client not found").into())
+    }
+  }
+
+  async fn rotate_client_secret(&self, request: Request<proto::RotateClientSecretRequest>) -> Result<Response<proto::RotateClientSecretResponse>, Status> {
+    let conn   = self.connection()?;
+    let client = Client::find(&conn, &request.get_ref().client_id)
+      ?
+      .ok_or_else(|| ApiError::not_found("client not found"))?;
+
+    if !client.is_confidential() {
+      return Err(ApiError::failed_precondition("public clients do not have a secret").into());
+    }
+
+    let secret  = crate::crypto::random_token(32)?;
+    let changes = ClientChanges {
+      secret_hash: Some(Some(password::hash(&secret)?)),
+      ..Default::default()
+    };
+    Client::update(&conn, &client.id, &changes)?;
+
+    Ok(Response::new(proto::RotateClientSecretResponse { client_secret: secret }))
+  }
+
+  async fn put_scope(&self, request: Request<proto::Scope>) -> Result<Response<proto::Scope>, Status> {
+    let request = request.into_inner();
+    let name    = not_empty(&request.name, "name")?;
+
+    if name.contains(' ') {
+      return Err(ApiError::invalid_field("name", "scope names cannot contain spaces").into());
+    }
+
+    let conn  = self.connection()?;
+    let scope = Scope::upsert(&conn, &NewScope {
+      name,
+      description: &request.description,
+      restricted: request.restricted
+    })?;
+
+    Ok(Response::new(scope_to_proto(scope)))
+  }
+
+  async fn list_scopes(&self, _: Request<()>) -> Result<Response<proto::ListScopesResponse>, Status> {
+    let conn   = self.connection()?;
+    let scopes = Scope::all(&conn)?;
+
+    Ok(Response::new(proto::ListScopesResponse {
+      scopes: scopes.into_iter().map(scope_to_proto).collect()
+    }))
+  }
+
+  async fn delete_scope(&self, request: Request<proto::DeleteScopeRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+
+    if Scope::delete(&conn, &request.get_ref().name)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("scope not found").into())
+    }
+  }
+
+  async fn create_role(&self, request: Request<proto::CreateRoleRequest>) -> Result<Response<proto::Role>, Status> {
+    let request = request.into_inner();
+    let conn    = self.connection()?;
+
+    let role = Role::create(&conn, &NewRole {
+      name: not_empty(&request.name, "name")?,
+      description: &request.description,
+      scopes: &request.scopes
+    }).map_err(|err| conflict(err, "role"))?;
+
+    Ok(Response::new(role_to_proto(role, vec![])))
+  }
+
+  async fn list_roles(&self, _: Request<()>) -> Result<Response<proto::ListRolesResponse>, Status> {
+    let conn   = self.connection()?;
+    let roles  = Role::all(&conn)?;
+    let grants = Permission::for_roles(&conn, &roles.iter().map(|role| role.id).collect::<Vec<_>>())?;
+
+    Ok(Response::new(proto::ListRolesResponse {
+      roles: roles.into_iter().map(|role| {
+        let permissions = names_by_id(&grants, role.id, |permission| permission.name.as_str());
+        role_to_proto(role, permissions)
+      }).collect()
+    }))
+  }
+
+  async fn update_role(&self, request: Request<proto::UpdateRoleRequest>) -> Result<Response<proto::Role>, Status> {
+    let request = request.into_inner();
+    let changes = RoleChanges {
+      name: request.name,
+      description: request.description,
+      scopes: request.scopes.map(|list| list.values)
+    };
+
+    let conn = self.connection()?;
+    match Role::update(&conn, request.id, &changes).map_err(|err| conflict(err, "role"))? {
+      Some(role) => self.role_response(&conn, role),
+      None       => Err(ApiError::not_found("role not found").into())
+    }
+  }
+
+  async fn delete_role(&self, request: Request<proto::DeleteRoleRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+
+    if Role::delete(&conn, request.get_ref().id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("role not found").into())
+    }
+  }
+
+  async fn assign_role(&self, request: Request<proto::RoleAssignment>) -> Result<Response<()>, Status> {
+    let user_id = parse_uuid("user_id", &request.get_ref().user_id)?;
+    let conn    = self.connection()?;
+
+    Role::assign(&conn, user_id, request.get_ref().role_id).map_err(|err| conflict(err, "role assignment"))?;
+    Ok(Response::new(()))
+  }
+
+  async fn unassign_role(&self, request: Request<proto::RoleAssignment>) -> Result<Response<()>, Status> {
+    let user_id = parse_uuid("user_id", &request.get_ref().user_id)?;
+    let conn    = self.connection()?;
+
+    if Role::unassign(&conn, user_id, request.get_ref().role_id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("role is not assigned to the user").into())
+    }
+  }
+
+  async fn create_permission(&self, request: Request<proto::CreatePermissionRequest>) -> Result<Response<proto::Permission>, Status> {
+    let request = request.into_inner();
+    let conn    = self.connection()?;
+
+    let permission = Permission::create(&conn, &NewPermission {
+      name: permission_name(&request.name)?,
+      description: &request.description
+    }).map_err(|err| conflict(err, "permission"))?;
+
+    Ok(Response::new(permission_to_proto(permission)))
+  }
+
+  async fn list_permissions(&self, _: Request<()>) -> Result<Response<proto::ListPermissionsResponse>, Status> {
+    let conn        = self.connection()?;
+    let permissions = Permission::all(&conn)?;
+
+    Ok(Response::new(proto::ListPermissionsResponse {
+      permissions: permissions.into_iter().map(permission_to_proto).collect()
+    }))
+  }
+
+  async fn delete_permission(&self, request: Request<proto::DeletePermissionRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+
+    if Permission::delete(&conn, request.get_ref().id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("permission not found").into())
+    }
+  }
+
+  async fn grant_permission(&self, request: Request<proto::PermissionGrant>) -> Result<Response<()>, Status> {
+    let grant = request.into_inner();
+    let conn  = self.connection()?;
+
+    Permission::grant(&conn, grant.role_id, grant.permission_id).map_err(|err| conflict(err, "permission grant"))?;
+    Ok(Response::new(()))
+  }
+
+  async fn revoke_permission(&self, request: Request<proto::PermissionGrant>) -> Result<Response<()>, Status> {
+    let grant = request.into_inner();
+    let conn  = self.connection()?;
+
+    if Permission::revoke(&conn, grant.role_id, grant.permission_id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("permission is not granted to the role").into())
+    }
+  }
+
+  async fn create_group(&self, request: Request<proto::CreateGroupRequest>) -> Result<Response<proto::Group>, Status> {
+    let request = request.into_inner();
+    let conn    = self.connection()?;
+
+    let group = Group::create(&conn, &NewGroup {
+      name: not_empty(&request.name, "name")?,
+      description: &request.description
+    }).map_err(|err| conflict(err, "group"))?;
+
+    Ok(Response::new(group_to_proto(group, vec![])))
+  }
+
+  async fn list_groups(&self, _: Request<()>) -> Result<Response<proto::ListGroupsResponse>, Status> {
+    let conn   = self.connection()?;
+    let groups = Group::all(&conn)?;
+    let roles  = Role::for_groups(&conn, &groups.iter().map(|group| group.id).collect::<Vec<_>>())?;
+
+    Ok(Response::new(proto::ListGroupsResponse {
+      groups: groups.into_iter().map(|group| {
+        let names = names_by_id(&roles, group.id, |role| role.name.as_str());
+        group_to_proto(group, names)
+      }).collect()
+    }))
+  }
+
+  async fn update_group(&self, request: Request<proto::UpdateGroupRequest>) -> Result<Response<proto::Group>, Status> {
+    let request = request.into_inner();
+    let changes = GroupChanges {
+      name: match request.name {
+        Some(name) => Some(not_empty(&name, "name")?.to_owned()),
+        None       => None
+      },
+      description: request.description
+    };
+
+    let conn = self.connection()?;
+    match Group::update(&conn, request.id, &changes).map_err(|err| conflict(err, "group"))? {
+      Some(group) => self.group_response(&conn, group),
+      None        => Err(ApiError::not_found("group not found").into())
+    }
+  }
+
+  async fn delete_group(&self, request: Request<proto::DeleteGroupRequest>) -> Result<Response<()>, Status> {
+    let conn = self.connection()?;
+
+    if Group::delete(&conn, request.get_ref().id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("group not found").into())
+    }
+  }
+
+  async fn add_group_member(&self, request: Request<proto::GroupMembership>) -> Result<Response<()>, Status> {
+    let user_id = parse_uuid("user_id", &request.get_ref().user_id)?;
+    let conn    = self.connection()?;
+
+    Group::add_member(&conn, request.get_ref().group_id, user_id).map_err(|err| conflict(err, "group membership"))?;
+    Ok(Response::new(()))
+  }
+
+  async fn remove_group_member(&self, request: Request<proto::GroupMembership>) -> Result<Response<()>, Status> {
+    let user_id = parse_uuid("user_id", &request.get_ref().user_id)?;
+    let conn    = self.connection()?;
+
+    if Group::remove_member(&conn, request.get_ref().group_id, user_id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("user does not belong to the group").into())
+    }
+  }
+
+  async fn list_group_members(&self, request: Request<proto::ListGroupMembersRequest>) -> Result<Response<proto::ListUsersResponse>, Status> {
+    let conn     = self.connection()?;
+    let group_id = request.get_ref().group_id;
+
+    if Group::find(&conn, group_id)?.is_none() {
+      return Err(ApiError::not_found("group not found").into());
+    }
+
+    let users = Group::members(&conn, group_id)?
+      .into_iter()
+      .map(|user| load_user(&conn, user))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Response::new(proto::ListUsersResponse { users }))
+  }
+
+  async fn assign_group_role(&self, request: Request<proto::GroupRoleAssignment>) -> Result<Response<()>, Status> {
+    let assignment = request.into_inner();
+    let conn       = self.connection()?;
+
+    Group::assign_role(&conn, assignment.group_id, assignment.role_id).map_err(|err| conflict(err, "group role assignment"))?;
+    Ok(Response::new(()))
+  }
+
+  async fn unassign_group_role(&self, request: Request<proto::GroupRoleAssignment>) -> Result<Response<()>, Status> {
+    let assignment = request.into_inner();
+    let conn       = self.connection()?;
+
+    if Group::unassign_role(&conn, assignment.group_id, assignment.role_id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("role is not assigned to the group").into())
+    }
+  }
+
+  async fn create_invitation(&self, request: Request<proto::CreateInvitationRequest>) -> Result<Response<proto::CreateInvitationResponse>, Status> {
+    let request  = request.into_inner();
+    let max_uses = if request.max_uses == 0 { 1 } else { request.max_uses.min(i32::MAX as u32) as i32 };
+
+    let expires_at = match &request.expires_at {
+      Some(timestamp) => Some(super::naive_datetime(timestamp).ok_or_else(|| ApiError::invalid_field("expires_at", "expires_at is out of range"))?),
+      None            => None
+    };
+
+    let conn  = self.connection()?;
+    let roles = Role::all(&conn)?;
+
+    let role_ids = request.roles.iter()
+      .map(|name| match roles.iter().find(|role| &role.name == name) {
+        Some(role) => Ok(role.id),
+        None       => Err(ApiError::invalid_field("roles", format!("unknown role `{}`", name)))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let code       = registration::generate_invitation_code()?;
+    let invitation = Invitation::create(&conn, &NewInvitation {
+      code_hash: &crate::crypto::hash_token(&registration::normalize_invitation_code(&code))?,
+      description: &request.description,
+      role_ids: &role_ids,
+      max_uses,
+      expires_at
+    })?;
+
+    Ok(Response::new(proto::CreateInvitationResponse {
+      invitation: Some(invitation_to_proto(invitation, &roles)),
+      code
+    }))
+  }
+
+  async fn list_invitations(&self, request: Request<proto::ListInvitationsRequest>) -> Result<Response<proto::ListInvitationsResponse>, Status> {
+    let (limit, offset) = page(request.get_ref().limit, request.get_ref().offset);
+    let conn        = self.connection()?;
+    let roles       = Role::all(&conn)?;
+    let invitations = Invitation::list(&conn, limit, offset)?;
+
+    Ok(Response::new(proto::ListInvitationsResponse {
+      invitations: invitations.into_iter().map(|invitation| invitation_to_proto(invitation, &roles)).collect()
+    }))
+  }
+
+  async fn delete_invitation(&self, request: Request<proto::DeleteInvitationRequest>) -> Result<Response<()>, Status> {
+    let id   = parse_uuid("id", &request.get_ref().id)?;
+    let conn = self.connection()?;
+
+    if Invitation::delete(&conn, id)? {
+      Ok(Response::new(()))
+    }
+    else {
+      Err(ApiError::not_found("invitation not found").into())
+    }
+  }
+
+  async fn generate_key(&self, request: Request<proto::GenerateKeyRequest>) -> Result<Response<proto::Key>, Status> {
+    let request   = request.into_inner();
+    let algorithm = request.algorithm.parse().map_err(|err: HeimdallrError| ApiError::invalid_field("algorithm", err.to_string()))?;
+    let pair      = KeyPair::generate(algorithm)?;
+    self.store_key(pair, request.activate)
+  }
+
+  async fn import_key(&self, request: Request<proto::ImportKeyRequest>) -> Result<Response<proto::Key>, Status> {
+    let request = request.into_inner();
+    let pair    = KeyPair::import(request.private_key_pem.as_bytes()).map_err(|err| ApiError::invalid_field("private_key_pem", err.to_string()))?;
+    self.store_key(pair, request.activate)
+  }
+
+  async fn list_keys(&self, _: Request<()>) -> Result<Response<proto::ListKeysResponse>, Status> {
+    let conn = self.connection()?;
+    let keys = SigningKey::all(&conn)?;
+
+    Ok(Response::new(proto::ListKeysResponse {
+      keys: keys.into_iter().map(key_to_proto).collect()
+    }))
+  }
+
+  async fn activate_key(&self, request: Request<proto::KeyRequest>) -> Result<Response<proto::Key>, Status> {
+    let conn = self.connection()?;
+    let key  = SigningKey::activate(&conn, &request.get_ref().kid).map_err(key_error)?;
+    self.reload_keys(&conn)?;
+    Ok(Response::new(key_to_proto(key)))
+  }
+
+  async fn retire_key(&self, request: Request<proto::KeyRequest>) -> Result<Response<proto::Key>, Status> {
+    let conn = self.connection()?;
+    let kid  = &request.get_ref().kid;
+
+    let key = SigningKey::find_by_kid(&conn, kid)?.ok_or_else(|| ApiError::not_found("key not found"))?;
+    if key.is_active() {
+      return Err(ApiError::failed_precondition("cannot retire the active signing key; activate another key first").into());
+    }
+
+    let key = SigningKey::retire(&conn, kid).map_err(key_error)?;
+    self.reload_keys(&conn)?;
+    Ok(Response::new(key_to_proto(key)))
+  }
+}
+
+impl AdminHandler {
+  fn store_key(&self, pair: KeyPair, activate: bool) -> Result<Response<proto::Key>, Status> {
+    let conn    = self.connection()?;
+    let mut key = SigningKey::insert(&conn, &pair).map_err(|err| conflict(err, "key"))?;
+
+    if activate {
+      key = SigningKey::activate(&conn, &key.kid).map_err(key_error)?;
+    }
+
+    self.reload_keys(&conn)?;
+    Ok(Response::new(key_to_proto(key)))
+  }
+}
+
+fn key_error(err: HeimdallrError) -> ApiError {
+  match err {
+    HeimdallrError::KeyError(reason) => ApiError::failed_precondition(reason),
+    other                            => other.into()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::test_helpers;
+  use diesel::Connection;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_conflict_names_the_record() {
+    let conn = test_helpers::connection();
+    let user = NewUser { username: "takara", email: None, password_hash: "hash" };
+    User::create(&conn, &user).unwrap();
+
+    // Each failing statement runs in a savepoint, so it doesn't abort the test transaction.
+    let duplicate = conn.transaction(|| User::create(&conn, &user)).map_err(|err| conflict(err, "username or email")).unwrap_err();
+    assert_eq!(duplicate.code, ErrorCode::AlreadyExists);
+    assert_eq!(duplicate.description, "username or email already exists");
+
+    let dangling = conn.transaction(|| Role::assign(&conn, Uuid::new_v4(), i32::MAX)).map_err(|err| conflict(err, "role assignment")).unwrap_err();
+    assert_eq!(dangling.code, ErrorCode::NotFound);
+    assert_eq!(dangling.description, "role assignment references a record that does not exist");
+  }
+}
@@ -1,30 +1,1038 @@
 use heimdallr_api::auth::{
   login_server::{Login, LoginServer},
-  LoginRequest, LoginResponse
+  Configuration, GrantType, LoginRequest, LoginResponse, RegisterRequest, RegisterResponse, RequestPasswordResetRequest, ResetPasswordRequest,
+  SendMfaCodeRequest, SmsChannel, StartPasskeyLoginRequest, UserInfoRequest, UserInfoResponse, VerifyEmailRequest,
+  WebauthnChallenge as ProtoWebauthnChallenge
 };
-use crate::db::Database;
+use crate::crypto;
+use crate::db::{Database, models::*};
+use crate::lockout::Lockout;
+use crate::logout;
+use crate::mail::{self, Delivery, Outbox};
+use crate::mfa;
+use crate::oidc::{self, Authentication, Discovery};
+use crate::password::{self, Policy, Verification};
+use crate::rbac::Entitlements;
+use crate::registration::{self, Mode, Registrar};
+use crate::sms::{self, Channel, SmsCodes};
+use crate::tokens::{scopes_of, IdTokenBindings, PendingLogin, TokenIssuer};
+use crate::webauthn::{self, AssertionResponse, RelyingParty, UserVerification};
+use super::error::{self, ApiError, ErrorCode, ErrorDetails};
 
+use chrono::{DateTime, Duration, Utc};
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use serde_json::{Map, Value};
 use tonic::{Request, Response, Status};
+use std::net::IpAddr;
 use std::sync::Arc;
+use uuid::Uuid;
 
-// #[derive(Default)]
+/// Authorization codes only need to survive the redirect back to the client.
+const AUTHORIZATION_CODE_TTL: i64 = 60;
+
+/// Wrong codes in a row after which an `mfa_token` is burned & the password has to be entered again.
+const MFA_TOKEN_ATTEMPTS: i32 = 5;
+
+#[derive(Clone)]
 pub struct AuthHandler {
-  db: Arc<Database>
+  db: Arc<Database>,
+  issuer: TokenIssuer,
+  passkeys: RelyingParty,
+  outbox: Outbox,
+  sms: SmsCodes,
+  registrar: Registrar,
+  policy: Policy,
+  lockout: Lockout
 }
 
 impl AuthHandler {
 
-  pub fn new(db: Database) -> Self {
-    Self { db: Arc::new(db) }
+  pub fn new(db: Database, issuer: TokenIssuer, passkeys: RelyingParty, outbox: Outbox, sms: SmsCodes, registrar: Registrar, policy: Policy) -> Self {
+    Self { db: Arc::new(db), issuer, passkeys, outbox, sms, registrar, policy, lockout: Lockout::default() }
   }
 
-  pub fn service(self) -> LoginServer<Self> {
-    LoginServer::new(self)
+  /// Replaces the default limits on failed password logins.
+  pub fn with_lockout(mut self, lockout: Lockout) -> Self {
+    self.lockout = lockout;
+    self
+  }
+
+  pub fn service(self) -> ErrorDetails<LoginServer<Self>> {
+    error::with_details(LoginServer::new(self))
   }
 
   pub fn database(&self) -> Arc<Database> {
     self.db.clone()
   }
+
+  pub fn issuer(&self) -> &TokenIssuer {
+    &self.issuer
+  }
+
+  pub fn lockout(&self) -> &Lockout {
+    &self.lockout
+  }
+
+  /// Discovery metadata; only unrestricted scopes are advertised.
+  pub fn discovery(&self) -> Result<Discovery, ApiError> {
+    let conn   = self.db.pool.get()?;
+    let scopes = Scope::all(&conn)?.into_iter().filter(|scope| !scope.restricted).map(|scope| scope.name).collect();
+
+    Ok(Discovery::new(&self.issuer.settings().issuer, scopes))
+  }
+
+  /// Runs a token request through the grant it asks for; `client_ip` is where it came from, see
+  /// [`Lockout::client_ip`].
+  pub fn token(&self, request: &LoginRequest, client_ip: Option<IpAddr>) -> Result<LoginResponse, ApiError> {
+    let conn = self.db.pool.get()?;
+
+    if request.client_id.is_empty() {
+      return Err(ApiError::invalid_field("client_id", "client_id is required"));
+    }
+
+    let grant_type = GrantType::from_i32(request.grant_type)
+      .ok_or_else(|| ApiError::unsupported_grant_type("unknown grant type"))?;
+
+    // The second step of a password login is covered by the client being allowed the first.
+    let registered = match grant_type {
+      GrantType::MfaOtp                                    => grant_name(GrantType::Password),
+      GrantType::Webauthn if !request.mfa_token.is_empty() => grant_name(GrantType::Password),
+      _                                                    => grant_name(grant_type)
+    };
+
+    let client = authenticate_client(&conn, &request.client_id, &request.client_secret)?;
+    if !client.allows_grant_type(registered) {
+      return Err(ApiError::unauthorized_client(format!("client is not allowed to use the {} grant", registered)));
+    }
+
+    match grant_type {
+      GrantType::Password          => self.password_grant(&conn, &client, request, client_ip),
+      GrantType::ClientCredentials => self.client_credentials_grant(&conn, &client, request),
+      GrantType::RefreshToken      => self.refresh_token_grant(&conn, &client, request),
+      GrantType::AuthorizationCode => self.authorization_code_grant(&conn, &client, request),
+      GrantType::MfaOtp            => self.mfa_otp_grant(&conn, &client, request),
+      GrantType::Webauthn          => self.webauthn_grant(&conn, &client, request)
+    }
+  }
+
+  /// Starts a passkey login, returning the challenge id & the options for `navigator.credentials.get()`.
+  ///
+  /// With a user the login is limited to their passkeys; `second_factor` marks logins whose
+  /// password was already checked, which only need the configured user verification.
+  pub fn passkey_challenge(&self, conn: &PgConnection, client_id: Option<&str>, user_id: Option<Uuid>, second_factor: bool) -> Result<(Uuid, Value), ApiError> {
+    let user_verification = if second_factor { self.passkeys.user_verification() } else { UserVerification::Required };
+    let allowed = match user_id {
+      Some(user_id) => WebauthnCredential::for_user(conn, user_id)?,
+      None          => Vec::new()
+    };
+
+    WebauthnChallenge::delete_expired(conn)?;
+
+    let challenge = webauthn::generate_challenge()?;
+    let record = WebauthnChallenge::create(conn, &NewWebauthnChallenge {
+      challenge: &challenge,
+      ceremony: webauthn::AUTHENTICATION,
+      user_id,
+      client_id,
+      user_verification: user_verification.as_str(),
+      expires_at: (Utc::now() + self.passkeys.challenge_ttl()).naive_utc()
+    })?;
+
+    Ok((record.id, self.passkeys.request_options(&challenge, &allowed, user_verification)))
+  }
+
+  /// Checks the answer to a passkey login started by the same client, returning the user it
+  /// authenticates.
+  ///
+  /// `second_factor_of` is the user whose password was already checked; passwordless logins always
+  /// require user verification, whatever the challenge asked for.
+  pub fn verify_passkey(&self, conn: &PgConnection, challenge_id: &str, credential: &str, client_id: Option<&str>, second_factor_of: Option<Uuid>) -> Result<User, ApiError> {
+    let challenge = match Uuid::parse_str(challenge_id).ok() {
+      Some(id) => WebauthnChallenge::take(conn, id, webauthn::AUTHENTICATION)?,
+      None     => None
+    };
+    let challenge = match challenge {
+      Some(challenge) if !challenge.is_expired() && challenge.client_id.as_deref() == client_id => challenge,
+      _ => return Err(ApiError::invalid_grant("passkey challenge is invalid or has expired"))
+    };
+
+    let response: AssertionResponse = webauthn::parse_response(credential).map_err(|err| ApiError::invalid_grant(err.to_string()))?;
+    let credential = WebauthnCredential::find_by_credential_id(conn, response.id.trim_end_matches('='))?
+      .ok_or_else(|| ApiError::invalid_grant("passkey is not registered"))?;
+
+    if let Some(user_id) = second_factor_of.or(challenge.user_id) {
+      if credential.user_id != user_id {
+        return Err(ApiError::invalid_grant("passkey belongs to a different user"));
+      }
+    }
+
+    let user_verification = match second_factor_of {
+      Some(_) => UserVerification::parse(&challenge.user_verification).unwrap_or(UserVerification::Required),
+      None    => UserVerification::Required
+    };
+
+    let assertion = self.passkeys.verify_assertion(&challenge.challenge, user_verification, &credential, &response)
+      .map_err(|err| ApiError::invalid_grant(err.to_string()))?;
+
+    if !credential.record_use(conn, i64::from(assertion.sign_count))? {
+      return Err(ApiError::invalid_grant(webauthn::WebauthnError::SignCountRegressed.to_string()));
+    }
+
+    match User::find(conn, credential.user_id)? {
+      Some(user) if !user.disabled => Ok(user),
+      _ => Err(ApiError::invalid_grant("account is disabled"))
+    }
+  }
+
+  /// Refuses passwords older than the policy allows; they can only be reset.
+  pub fn check_password_age(&self, user: &User) -> Result<(), ApiError> {
+    if self.policy.is_expired(user) {
+      return Err(ApiError::password_expired("the password has expired and has to be reset"));
+    }
+
+    Ok(())
+  }
+
+  /// Checks a user's password; failures are deliberately indistinguishable.
+  ///
+  /// Logins as a username or from an address that failed too often lately are turned away before
+  /// the password is looked at, see [`Lockout`]. Hashes made with older argon2 parameters are
+  /// replaced while the password is at hand. Hashing is slow on purpose, so async callers should
+  /// run this on the blocking thread pool.
+  pub fn authenticate_user(&self, conn: &PgConnection, username: &str, password: &str, client_ip: Option<IpAddr>) -> Result<User, ApiError> {
+    if let Some(wait) = self.lockout.retry_after(conn, username, client_ip)? {
+      let seconds = (wait.num_milliseconds() + 999) / 1000;
+      return Err(ApiError::too_many_requests("too many failed logins, try again later", seconds));
+    }
+
+    let user = User::find_by_username(conn, username)?;
+    let verification = match &user {
+      Some(user) => password::check(&user.password_hash, password),
+      None       => {
+        password::check_nothing(password)?;
+        Verification::Invalid
+      }
+    };
+
+    let user = match (user, verification) {
+      (Some(user), Verification::Valid)    => user,
+      (Some(user), Verification::Outdated) => upgrade_hash(conn, user, password),
+      _ => {
+        self.lockout.record_failure(conn, username, client_ip)?;
+        return Err(ApiError::invalid_grant("invalid username or password"));
+      }
+    };
+
+    self.lockout.record_success(conn, username)?;
+
+    if user.disabled {
+      return Err(ApiError::invalid_grant("account is disabled"));
+    }
+
+    Ok(user)
+  }
+
+  /// Validates the `mfa_token` of a password login waiting for its second factor; tokens that were
+  /// redeemed, or burned by too many wrong codes, are rejected like expired ones.
+  pub fn pending_login(&self, conn: &PgConnection, mfa_token: &str, client_id: &str) -> Result<PendingLogin, ApiError> {
+    let invalid = || ApiError::invalid_grant("mfa_token is invalid or has expired");
+    let pending = self.issuer.validate_mfa_token(mfa_token, client_id).map_err(|_| invalid())?;
+
+    if SpentMfaToken::is_spent(conn, pending.jti)? {
+      return Err(invalid());
+    }
+
+    Ok(pending)
+  }
+
+  /// Checks the code of the two-factor step, returning the `amr` value of the factor it came from:
+  /// `otp` for the authenticator app & recovery codes, `sms` for a code sent to the phone.
+  ///
+  /// A matching code redeems the `mfa_token`. Wrong ones are counted per user like failed logins,
+  /// and burn the token once there were [`MFA_TOKEN_ATTEMPTS`] in a row.
+  pub fn verify_code(&self, conn: &PgConnection, pending: &PendingLogin, user: &User, code: &str) -> Result<Option<&'static str>, ApiError> {
+    if let Some(wait) = self.lockout.mfa_retry_after(conn, user.id)? {
+      let seconds = (wait.num_milliseconds() + 999) / 1000;
+      return Err(ApiError::too_many_requests("too many wrong codes, try again later", seconds));
+    }
+
+    let method = if mfa::verify(conn, user.id, code)? {
+      Some("otp")
+    }
+    else if mfa::has_sms(conn, user.id)? && self.sms.verify(conn, user, sms::MFA, code)? {
+      Some("sms")
+    }
+    else {
+      None
+    };
+
+    match method {
+      Some(_) => {
+        self.lockout.record_mfa_success(conn, user.id)?;
+        self.redeem_mfa_token(conn, pending)?;
+      },
+      None => {
+        if self.lockout.record_mfa_failure(conn, user.id)? >= MFA_TOKEN_ATTEMPTS {
+          SpentMfaToken::spend(conn, pending.jti, pending.expires_at.naive_utc())?;
+        }
+      }
+    }
+
+    Ok(method)
+  }
+
+  /// Spends the `mfa_token` of a login whose second factor was accepted, so it completes only one.
+  pub fn redeem_mfa_token(&self, conn: &PgConnection, pending: &PendingLogin) -> Result<(), ApiError> {
+    SpentMfaToken::delete_expired(conn)?;
+
+    if !SpentMfaToken::spend(conn, pending.jti, pending.expires_at.naive_utc())? {
+      return Err(ApiError::invalid_grant("mfa_token is invalid or has expired"));
+    }
+
+    Ok(())
+  }
+
+  /// Sends the code of the two-factor step to the phone of a user whose password was accepted.
+  pub fn send_mfa_code(&self, conn: &PgConnection, user: &User, channel: Channel) -> Result<(), ApiError> {
+    if !mfa::has_sms(conn, user.id)? {
+      return Err(ApiError::failed_precondition("codes by SMS are not set up"));
+    }
+
+    delivered(self.sms.send(conn, user, sms::MFA, channel)?)
+  }
+
+  /// Creates an account through self-service sign-up, as far as the registration mode allows,
+  /// returning it along with whether a verification link was mailed.
+  ///
+  /// Invitation codes are used up in the transaction creating the account, so refused sign-ups do
+  /// not count against them.
+  pub fn register(&self, conn: &PgConnection, request: &RegisterRequest) -> Result<(User, bool), ApiError> {
+    let code = registration::normalize_invitation_code(&request.invitation_code);
+
+    match self.registrar.mode() {
+      Mode::Disabled                          => return Err(ApiError::failed_precondition("registration is disabled")),
+      Mode::InvitationOnly if code.is_empty() => return Err(ApiError::invalid_field("invitation_code", "an invitation is required to register")),
+      _                                       => ()
+    }
+
+    let username = normalize(&request.username);
+    registration::validate_username(&username).map_err(|reason| ApiError::invalid_field("username", reason))?;
+
+    let email = Some(normalize(&request.email)).filter(|email| !email.is_empty());
+    match &email {
+      Some(email)                            => registration::validate_email(email).map_err(|reason| ApiError::invalid_field("email", reason))?,
+      None if self.registrar.require_email() => return Err(ApiError::invalid_field("email", "email is required")),
+      None                                   => ()
+    }
+
+    let identifiers: Vec<&str> = std::iter::once(username.as_str()).chain(email.as_deref()).chain(non_empty(request.name.trim())).collect();
+    let violations = self.policy.check(&request.password, &identifiers)?;
+    if !violations.is_empty() {
+      return Err(ApiError::password_rejected("password", &violations));
+    }
+    let hash = password::hash(&request.password)?;
+
+    let user = conn.transaction::<_, ApiError, _>(|| {
+      let role_ids = if code.is_empty() {
+        Vec::new()
+      }
+      else {
+        Invitation::redeem(conn, &code)?
+          .ok_or_else(|| ApiError::invalid_field("invitation_code", "the invitation is invalid, has expired or was used up"))?
+          .role_ids
+      };
+
+      let user = User::create(conn, &NewUser {
+        username: &username,
+        email: email.as_deref(),
+        password_hash: &hash
+      }).map_err(|err| match ApiError::from(err) {
+        ApiError { code: ErrorCode::AlreadyExists, .. } => ApiError::already_exists("username or email is already taken"),
+        other                                           => other
+      })?;
+
+      // Roles deleted since the invitation was issued are skipped.
+      for role_id in role_ids {
+        if Role::find(conn, role_id)?.is_some() {
+          Role::assign(conn, user.id, role_id)?;
+        }
+      }
+
+      let changes = UserChanges {
+        name: non_empty(request.name.trim()).map(|name| Some(name.to_owned())),
+        locale: non_empty(request.locale.trim()).map(|locale| Some(locale.to_owned())),
+        ..Default::default()
+      };
+      Ok(User::update(conn, user.id, &changes)?.unwrap_or(user))
+    })?;
+
+    log::info!("User {} registered", user.username);
+
+    let verification_sent = match &user.email {
+      Some(_) if self.registrar.verify_email() => self.outbox.send_verification(conn, &user)? == Delivery::Accepted,
+      _                                        => false
+    };
+
+    Ok((user, verification_sent))
+  }
+
+  /// Mails a password reset link, unless the address is not known; callers cannot tell either way.
+  pub fn request_password_reset(&self, conn: &PgConnection, email: &str) -> Result<(), ApiError> {
+    if email.trim().is_empty() {
+      return Err(ApiError::invalid_field("email", "email is required"));
+    }
+
+    delivered(self.outbox.send_password_reset(conn, email)?)
+  }
+
+  /// Sets a new password with the token of a reset link.
+  ///
+  /// Any other reset & sign-in links are invalidated, and every session & refresh token of the user
+  /// ends: whoever knew the old password is logged out. Passwords the policy refuses leave the link
+  /// usable, so another one can be picked.
+  pub fn reset_password(&self, conn: &PgConnection, token: &str, new_password: &str) -> Result<User, ApiError> {
+    if new_password.is_empty() {
+      return Err(ApiError::invalid_field("new_password", "new_password is required"));
+    }
+
+    let user = conn.transaction(|| {
+      let user    = self.redeem_email_token(conn, token, mail::PASSWORD_RESET)?;
+      let changes = UserChanges {
+        // The link could only be followed by the owner of the address.
+        email_verified: Some(true),
+        ..password_changes(conn, &self.policy, &user, new_password, "new_password")?
+      };
+
+      User::update(conn, user.id, &changes)?.ok_or_else(|| ApiError::invalid_grant("account is disabled"))
+    })?;
+
+    EmailToken::revoke_all(conn, user.id, mail::PASSWORD_RESET)?;
+    EmailToken::revoke_all(conn, user.id, mail::MAGIC_LINK)?;
+    logout::end_all_sessions(conn, &self.issuer, user.id)?;
+
+    Ok(user)
+  }
+
+  /// Marks the email address of a user as verified with the token of a verification link.
+  pub fn verify_email(&self, conn: &PgConnection, token: &str) -> Result<User, ApiError> {
+    let user = self.redeem_email_token(conn, token, mail::VERIFY_EMAIL)?;
+
+    if user.email_verified {
+      return Ok(user);
+    }
+
+    let changes = UserChanges { email_verified: Some(true), ..Default::default() };
+    Ok(User::update(conn, user.id, &changes)?.unwrap_or(user))
+  }
+
+  /// Mails a sign-in link resuming the authorization request `request`, unless the address is not
+  /// known; callers cannot tell either way.
+  pub fn send_magic_link(&self, conn: &PgConnection, email: &str, request: &str) -> Result<(), ApiError> {
+    if email.trim().is_empty() {
+      return Err(ApiError::invalid_field("email", "email is required"));
+    }
+
+    delivered(self.outbox.send_magic_link(conn, email, request)?)
+  }
+
+  /// Signs a user in with the token of a magic link, which also proves they own their address.
+  pub fn redeem_magic_link(&self, conn: &PgConnection, token: &str) -> Result<User, ApiError> {
+    let user = self.redeem_email_token(conn, token, mail::MAGIC_LINK)?;
+
+    if user.email_verified {
+      return Ok(user);
+    }
+
+    let changes = UserChanges { email_verified: Some(true), ..Default::default() };
+    Ok(User::update(conn, user.id, &changes)?.unwrap_or(user))
+  }
+
+  /// Uses up a mailed token, returning its user as long as they are enabled & still have the
+  /// address the token was sent to.
+  fn redeem_email_token(&self, conn: &PgConnection, token: &str, purpose: &str) -> Result<User, ApiError> {
+    if token.is_empty() {
+      return Err(ApiError::invalid_field("token", "token is required"));
+    }
+
+    let token = EmailToken::redeem(conn, token, purpose)?
+      .ok_or_else(|| ApiError::invalid_grant("the link is invalid, has expired or was already used"))?;
+
+    match User::find(conn, token.user_id)? {
+      Some(user) if !user.disabled && user.email.as_deref() == Some(token.email.as_str()) => Ok(user),
+      _ => Err(ApiError::invalid_grant("the link is invalid, has expired or was already used"))
+    }
+  }
+
+  /// Revokes a refresh token held by the calling client (RFC 7009).
+  ///
+  /// Unknown tokens & access tokens, which are self-contained JWTs, are silently accepted.
+  pub fn revoke(&self, client_id: &str, client_secret: &str, token: &str) -> Result<(), ApiError> {
+    let conn   = self.db.pool.get()?;
+    let client = authenticate_client(&conn, client_id, client_secret)?;
+
+    if let Some(refresh_token) = RefreshToken::find_by_token(&conn, token)? {
+      if refresh_token.client_id == client.id {
+        refresh_token.revoke(&conn)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Describes a token to a confidential client (RFC 7662).
+  pub fn introspect(&self, client_id: &str, client_secret: &str, token: &str) -> Result<Value, ApiError> {
+    let conn   = self.db.pool.get()?;
+    let client = authenticate_client(&conn, client_id, client_secret)?;
+
+    if !client.is_confidential() {
+      return Err(ApiError::unauthorized_client("only confidential clients may introspect tokens"));
+    }
+
+    // ID tokens are signed by the same keys, but only access tokens carry a `client_id`.
+    if let Ok(claims) = self.issuer.validate(token) {
+      if claims.get("client_id").is_some() {
+        return Ok(serde_json::json!({
+          "active": true,
+          "token_type": "Bearer",
+          "scope": claims["scope"],
+          "client_id": claims["client_id"],
+          "sub": claims["sub"],
+          "iss": claims["iss"],
+          "exp": claims["exp"],
+          "iat": claims["iat"],
+          "jti": claims["jti"]
+        }));
+      }
+    }
+
+    if let Some(refresh_token) = RefreshToken::find_by_token(&conn, token)? {
+      if refresh_token.is_usable() {
+        return Ok(serde_json::json!({
+          "active": true,
+          "token_type": "refresh_token",
+          "scope": refresh_token.scopes.join(" "),
+          "client_id": refresh_token.client_id,
+          "sub": refresh_token.user_id.map(|id| id.to_string()).unwrap_or_else(|| refresh_token.client_id.clone()),
+          "iss": self.issuer.settings().issuer,
+          "exp": refresh_token.expires_at.timestamp(),
+          "iat": refresh_token.created_at.timestamp()
+        }));
+      }
+    }
+
+    Ok(serde_json::json!({ "active": false }))
+  }
+
+  /// Looks up the claims an access token entitles its bearer to see about the user it was issued for.
+  pub fn userinfo(&self, access_token: &str) -> Result<UserInfo, ApiError> {
+    let claims = self.issuer.validate(access_token)
+      .map_err(|err| ApiError::invalid_token(format!("invalid access token: {}", err)))?;
+
+    let scopes: Vec<String> = scopes_of(&claims).into_iter().map(str::to_owned).collect();
+    if !oidc::is_openid(&scopes) {
+      return Err(ApiError::insufficient_scope("the `openid` scope is required"));
+    }
+
+    // Client credentials tokens carry the client id as their subject.
+    let user_id = claims["sub"].as_str()
+      .and_then(|subject| Uuid::parse_str(subject).ok())
+      .ok_or_else(|| ApiError::invalid_token("access token was not issued for a user"))?;
+
+    let conn = self.db.pool.get()?;
+    let user = match User::find(&conn, user_id)? {
+      Some(user) if !user.disabled => user,
+      _ => return Err(ApiError::invalid_token("the user no longer exists or is disabled"))
+    };
+
+    Ok(UserInfo {
+      client_id: claims["client_id"].as_str().unwrap_or_default().to_owned(),
+      claims: oidc::standard_claims(&user, &scopes)
+    })
+  }
+
+  /// Signs UserInfo claims as a JWT addressed to the client.
+  pub fn sign_userinfo(&self, info: &UserInfo) -> Result<String, ApiError> {
+    Ok(self.issuer.userinfo_token(&info.client_id, &info.claims)?)
+  }
+
+  /// Issues a single-use authorization code once the user approved an authorization request.
+  pub fn authorization_code(&self, conn: &PgConnection, approval: &Approval) -> Result<String, ApiError> {
+    let code = crypto::random_token(32)?;
+
+    AuthorizationCode::create(conn, &NewAuthorizationCode {
+      code_hash: &crypto::hash_token(&code)?,
+      client_id: &approval.client.id,
+      user_id: approval.user.id,
+      redirect_uri: approval.redirect_uri,
+      scopes: &approval.scopes,
+      nonce: approval.nonce,
+      code_challenge: approval.code_challenge,
+      auth_time: approval.auth.auth_time.naive_utc(),
+      amr: &approval.auth.amr,
+      expires_at: (Utc::now() + Duration::seconds(AUTHORIZATION_CODE_TTL)).naive_utc(),
+      session_id: approval.auth.session_id
+    })?;
+
+    Ok(code)
+  }
+
+  /// Mints the access token, plus an ID token for OpenID requests & a refresh token when the client may use one
+  /// on behalf of a user; client credentials never get one (RFC 6749 section 4.4.3).
+  fn issue(&self, conn: &PgConnection, grant: Grant) -> Result<LoginResponse, ApiError> {
+    let subject = match grant.user {
+      Some(user) => user.id.to_string(),
+      None       => grant.client.id.clone()
+    };
+
+    let entitlements = match grant.user {
+      Some(user) if self.issuer.settings().carries_entitlements() => Some(Entitlements::load(conn, user.id)?),
+      _ => None
+    };
+
+    let access = self.issuer.access_token(&subject, &grant.client.id, &grant.scopes, entitlements.as_ref())?;
+
+    let id_token = match grant.user {
+      Some(user) if oidc::is_openid(&grant.scopes) => {
+        let bindings = IdTokenBindings { nonce: grant.nonce, access_token: Some(&access.token), code: grant.code };
+        self.issuer.id_token(user, &grant.client.id, &grant.scopes, &grant.auth, bindings)?
+      },
+      _ => String::new()
+    };
+
+    let refresh_token = if grant.user.is_some() && grant.client.allows_grant_type("refresh_token") {
+      let token = crypto::random_token(32)?;
+      RefreshToken::create(conn, &NewRefreshToken {
+        token_hash: &crypto::hash_token(&token)?,
+        client_id: &grant.client.id,
+        user_id: grant.user.map(|user| user.id),
+        scopes: &grant.scopes,
+        auth_time: grant.auth.auth_time.naive_utc(),
+        amr: &grant.auth.amr,
+        expires_at: (Utc::now() + self.issuer.settings().refresh_token_ttl()).naive_utc(),
+        session_id: grant.auth.session_id
+      })?;
+      token
+    }
+    else {
+      String::new()
+    };
+
+    Ok(LoginResponse {
+      access_token: access.token,
+      expires_in: Some(prost_types::Timestamp {
+        seconds: access.expires_at.timestamp(),
+        nanos: 0
+      }),
+      id_token,
+      refresh_token,
+      token_type: "Bearer".to_owned(),
+      scope: grant.scopes,
+      data: None
+    })
+  }
+
+  fn password_grant(&self, conn: &PgConnection, client: &Client, request: &LoginRequest, client_ip: Option<IpAddr>) -> Result<LoginResponse, ApiError> {
+    if request.username.is_empty() {
+      return Err(ApiError::invalid_field("username", "username is required"));
+    }
+    if request.password.is_empty() {
+      return Err(ApiError::invalid_field("password", "password is required"));
+    }
+
+    let user = self.authenticate_user(conn, &request.username, &request.password, client_ip)?;
+    self.check_password_age(&user)?;
+
+    let scopes = resolve_scopes(conn, &request.scope, client, Some(&user))?;
+    let auth   = Authentication::new(&["pwd"]);
+
+    let methods = mfa::methods(conn, user.id)?;
+    if !methods.is_empty() {
+      return Err(ApiError::mfa_required(self.issuer.mfa_token(&user, &client.id, &auth)?).with_metadata("mfa_methods", methods.join(" ")));
+    }
+
+    self.issue(conn, Grant {
+      client,
+      user: Some(&user),
+      scopes,
+      auth,
+      nonce: non_empty(&request.nonce),
+      code: None
+    })
+  }
+
+  /// Completes a password login answered with `mfa_required` by checking the second factor.
+  fn mfa_otp_grant(&self, conn: &PgConnection, client: &Client, request: &LoginRequest) -> Result<LoginResponse, ApiError> {
+    if request.mfa_token.is_empty() {
+      return Err(ApiError::invalid_field("mfa_token", "mfa_token is required"));
+    }
+    if request.otp.is_empty() {
+      return Err(ApiError::invalid_field("otp", "otp is required"));
+    }
+
+    let pending  = self.pending_login(conn, &request.mfa_token, &client.id)?;
+    let mut auth = pending.auth.clone();
+
+    let user = match User::find(conn, pending.user_id)? {
+      Some(user) if !user.disabled => user,
+      _ => return Err(ApiError::invalid_grant("account is disabled"))
+    };
+
+    match self.verify_code(conn, &pending, &user, &request.otp)? {
+      Some(method) => auth.amr.push(method.to_owned()),
+      None         => return Err(ApiError::invalid_grant("the code is invalid or has already been used"))
+    }
+
+    let scopes = resolve_scopes(conn, &request.scope, client, Some(&user))?;
+    self.issue(conn, Grant {
+      client,
+      user: Some(&user),
+      scopes,
+      auth,
+      nonce: non_empty(&request.nonce),
+      code: None
+    })
+  }
+
+  /// Logs in with a passkey, or completes a password login answered with `mfa_required` when an
+  /// `mfa_token` is given.
+  fn webauthn_grant(&self, conn: &PgConnection, client: &Client, request: &LoginRequest) -> Result<LoginResponse, ApiError> {
+    if request.webauthn_challenge_id.is_empty() {
+      return Err(ApiError::invalid_field("webauthn_challenge_id", "webauthn_challenge_id is required"));
+    }
+    if request.webauthn_credential.is_empty() {
+      return Err(ApiError::invalid_field("webauthn_credential", "webauthn_credential is required"));
+    }
+
+    let (user, auth) = if request.mfa_token.is_empty() {
+      let user = self.verify_passkey(conn, &request.webauthn_challenge_id, &request.webauthn_credential, Some(&client.id), None)?;
+      // A passkey that verified its user is something they have plus something they know or are.
+      (user, Authentication::new(&["hwk", "mfa"]))
+    }
+    else {
+      let pending = self.pending_login(conn, &request.mfa_token, &client.id)?;
+      let user    = self.verify_passkey(conn, &request.webauthn_challenge_id, &request.webauthn_credential, Some(&client.id), Some(pending.user_id))?;
+      self.redeem_mfa_token(conn, &pending)?;
+
+      let mut auth = pending.auth;
+      auth.amr.push("hwk".to_owned());
+      (user, auth)
+    };
+
+    let scopes = resolve_scopes(conn, &request.scope, client, Some(&user))?;
+    self.issue(conn, Grant {
+      client,
+      user: Some(&user),
+      scopes,
+      auth,
+      nonce: non_empty(&request.nonce),
+      code: None
+    })
+  }
+
+  fn client_credentials_grant(&self, conn: &PgConnection, client: &Client, request: &LoginRequest) -> Result<LoginResponse, ApiError> {
+    if !client.is_confidential() {
+      return Err(ApiError::unauthorized_client("public clients cannot use the client_credentials grant"));
+    }
+
+    let scopes = resolve_scopes(conn, &request.scope, client, None)?;
+    self.issue(conn, Grant {
+      client,
+      user: None,
+      scopes,
+      auth: Authentication::new(&[]),
+      nonce: None,
+      code: None
+    })
+  }
+
+  /// Exchanges a refresh token for new tokens; the presented refresh token is rotated out.
+  fn refresh_token_grant(&self, conn: &PgConnection, client: &Client, request: &LoginRequest) -> Result<LoginResponse, ApiError> {
+    if request.refresh_token.is_empty() {
+      return Err(ApiError::invalid_field("refresh_token", "refresh_token is required"));
+    }
+
+    let token = match RefreshToken::find_by_token(conn, &request.refresh_token)? {
+      Some(token) if token.client_id == client.id => token,
+      _ => return Err(ApiError::invalid_grant("refresh token is invalid"))
+    };
+
+    // A rotated token being replayed means it leaked; cut off the whole family.
+    let replayed = |token: &RefreshToken| -> Result<LoginResponse, ApiError> {
+      if let Some(user_id) = token.user_id {
+        RefreshToken::revoke_for_user_and_client(conn, user_id, &client.id)?;
+      }
+      Err(ApiError::invalid_grant("refresh token has been revoked"))
+    };
+
+    if token.revoked_at.is_some() {
+      return replayed(&token);
+    }
+
+    if !token.is_usable() {
+      return Err(ApiError::invalid_grant("refresh token has expired"));
+    }
+
+    if let Some(scope) = request.scope.iter().find(|scope| !token.scopes.contains(scope)) {
+      return Err(ApiError::invalid_scope(format!("scope `{}` was not part of the original grant", scope)));
+    }
+    let requested = if request.scope.is_empty() { token.scopes.clone() } else { request.scope.clone() };
+
+    let user = match token.user_id {
+      Some(user_id) => match User::find(conn, user_id)? {
+        Some(user) if !user.disabled => Some(user),
+        _ => return Err(ApiError::invalid_grant("account is disabled"))
+      },
+      None => None
+    };
+
+    let scopes = narrow_scopes(conn, requested, client, user.as_ref())?;
+
+    // Two requests racing with the same token both get this far; only one of them revokes it.
+    if !token.revoke(conn)? {
+      return replayed(&token);
+    }
+
+    self.issue(conn, Grant {
+      client,
+      user: user.as_ref(),
+      scopes,
+      auth: Authentication {
+        auth_time: DateTime::<Utc>::from_utc(token.auth_time, Utc),
+        amr: token.amr.clone(),
+        session_id: token.session_id
+      },
+      nonce: None,
+      code: None
+    })
+  }
+
+  /// Exchanges an authorization code issued by `/authorize` for tokens.
+  fn authorization_code_grant(&self, conn: &PgConnection, client: &Client, request: &LoginRequest) -> Result<LoginResponse, ApiError> {
+    if request.code.is_empty() {
+      return Err(ApiError::invalid_field("code", "code is required"));
+    }
+
+    let code = match AuthorizationCode::find_by_code(conn, &request.code)? {
+      Some(code) if code.client_id == client.id => code,
+      _ => return Err(ApiError::invalid_grant("authorization code is invalid"))
+    };
+
+    if !code.redeem(conn)? {
+      // RFC 6749 §4.1.2: a replayed code revokes what was issued with it.
+      RefreshToken::revoke_for_user_and_client(conn, code.user_id, &client.id)?;
+      return Err(ApiError::invalid_grant("authorization code has already been used"));
+    }
+
+    if code.is_expired() {
+      return Err(ApiError::invalid_grant("authorization code has expired"));
+    }
+
+    if code.redirect_uri != request.redirect_uri {
+      return Err(ApiError::invalid_grant("redirect_uri does not match the authorization request"));
+    }
+
+    if let Some(challenge) = &code.code_challenge {
+      if request.code_verifier.is_empty() {
+        return Err(ApiError::invalid_field("code_verifier", "code_verifier is required"));
+      }
+      if !crypto::constant_time_eq(challenge.as_bytes(), crypto::pkce_challenge(&request.code_verifier)?.as_bytes()) {
+        return Err(ApiError::invalid_grant("code_verifier does not match the code challenge"));
+      }
+    }
+
+    let user = match User::find(conn, code.user_id)? {
+      Some(user) if !user.disabled => user,
+      _ => return Err(ApiError::invalid_grant("account is disabled"))
+    };
+
+    self.issue(conn, Grant {
+      client,
+      user: Some(&user),
+      scopes: code.scopes.clone(),
+      auth: Authentication {
+        auth_time: DateTime::<Utc>::from_utc(code.auth_time, Utc),
+        amr: code.amr.clone(),
+        session_id: code.session_id
+      },
+      nonce: code.nonce.as_deref(),
+      code: None
+    })
+  }
+}
+
+/// An authorization request the user has approved.
+pub struct Approval<'a> {
+  pub client: &'a Client,
+  pub user: &'a User,
+  pub redirect_uri: &'a str,
+  /// The scopes the user granted, already narrowed down by [`resolve_scopes`].
+  pub scopes: Vec<String>,
+  pub nonce: Option<&'a str>,
+  pub code_challenge: Option<&'a str>,
+  pub auth: Authentication
+}
+
+/// Claims returned by the UserInfo endpoint.
+#[derive(Debug, Clone)]
+pub struct UserInfo {
+  /// Client the access token was issued to.
+  pub client_id: String,
+  pub claims: Map<String, Value>
+}
+
+/// The outcome of a grant, before any tokens are minted.
+struct Grant<'a> {
+  client: &'a Client,
+  user: Option<&'a User>,
+  scopes: Vec<String>,
+  auth: Authentication,
+  nonce: Option<&'a str>,
+  code: Option<&'a str>
+}
+
+/// The channel a code is sent over; unknown values fall back to SMS.
+pub fn channel_from_proto(channel: i32) -> Channel {
+  match SmsChannel::from_i32(channel) {
+    Some(SmsChannel::Voice) => Channel::Voice,
+    _                       => Channel::Sms
+  }
+}
+
+/// Turns a throttled message into a `too_many_requests` error.
+pub fn delivered(delivery: Delivery) -> Result<(), ApiError> {
+  match delivery {
+    Delivery::Accepted               => Ok(()),
+    Delivery::Throttled(retry_after) => Err(ApiError::too_many_requests("too many messages were sent to this address, try again later", retry_after.num_seconds()))
+  }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+  Some(value).filter(|value| !value.is_empty())
+}
+
+/// The registered name of a grant type, as used in client registrations.
+pub fn grant_name(grant_type: GrantType) -> &'static str {
+  match grant_type {
+    GrantType::Password          => "password",
+    GrantType::AuthorizationCode => "authorization_code",
+    GrantType::ClientCredentials => "client_credentials",
+    GrantType::RefreshToken      => "refresh_token",
+    GrantType::MfaOtp            => "mfa_otp",
+    GrantType::Webauthn          => "webauthn"
+  }
+}
+
+
+/// Re-hashes the password of a user with the current parameters; a failure only delays the upgrade
+/// to the next login.
+fn upgrade_hash(conn: &PgConnection, user: User, password: &str) -> User {
+  let upgraded = password::hash(password)
+    .and_then(|hash| User::update(conn, user.id, &UserChanges { password_hash: Some(hash), ..Default::default() }));
+
+  match upgraded {
+    Ok(Some(upgraded)) => upgraded,
+    Ok(None)           => user,
+    Err(err) => {
+      log::warn!("Unable to upgrade the password hash of user {}: {}", user.id, err);
+      user
+    }
+  }
+}
+
+/// Checks a new password of a user against the policy & keeps their current one in their history,
+/// returning the changes that set it; refused passwords are reported as violations of `field`.
+pub fn password_changes(conn: &PgConnection, policy: &Policy, user: &User, password: &str, field: &str) -> Result<UserChanges, ApiError> {
+  let violations = policy.check_change(conn, user, password)?;
+  if !violations.is_empty() {
+    return Err(ApiError::password_rejected(field, &violations));
+  }
+
+  policy.remember(conn, user)?;
+  Ok(UserChanges::password(password::hash(password)?))
+}
+
+/// Looks up a client and checks its secret if it is a confidential client.
+pub fn authenticate_client(conn: &PgConnection, client_id: &str, client_secret: &str) -> Result<Client, ApiError> {
+  let client = Client::find(conn, client_id)?.ok_or_else(|| ApiError::invalid_client("client authentication failed"))?;
+
+  if client.is_confidential() && !client.verify_secret(client_secret) {
+    return Err(ApiError::invalid_client("client authentication failed"));
+  }
+
+  Ok(client)
+}
+
+/// Works out which scopes a token may carry.
+///
+/// Requested scopes must all be allowed for the client. Restricted scopes additionally require the
+/// user to hold a role listing them, directly or through a group; when no scopes are requested,
+/// every allowed scope the user is entitled to is granted.
+pub fn resolve_scopes(conn: &PgConnection, requested: &[String], client: &Client, user: Option<&User>) -> Result<Vec<String>, ApiError> {
+  if let Some(unknown) = requested.iter().find(|scope| !client.scopes.contains(scope)) {
+    return Err(ApiError::invalid_scope(format!("scope `{}` is not allowed for this client", unknown)));
+  }
+
+  let candidates = if requested.is_empty() { client.scopes.clone() } else { requested.to_vec() };
+
+  let user = match user {
+    Some(user) => user,
+    None       => return Ok(candidates)
+  };
+
+  let restricted: Vec<String> = Scope::find_all(conn, &candidates)?
+    .into_iter()
+    .filter(|scope| scope.restricted)
+    .map(|scope| scope.name)
+    .collect();
+
+  if restricted.is_empty() {
+    return Ok(candidates);
+  }
+
+  let entitled: Vec<String> = Role::effective_for_user(conn, user.id)?.into_iter().flat_map(|role| role.scopes).collect();
+  let denied = |scope: &String| restricted.contains(scope) && !entitled.contains(scope);
+
+  if !requested.is_empty() {
+    if let Some(scope) = requested.iter().find(|scope| denied(scope)) {
+      return Err(ApiError::invalid_scope(format!("scope `{}` has not been granted to this user", scope)));
+    }
+  }
+
+  Ok(candidates.into_iter().filter(|scope| !denied(scope)).collect())
+}
+
+/// Drops the scopes of an earlier grant that the client or the user has lost since, e.g. when a
+/// role was taken away, so refreshed tokens never carry more than a new grant would.
+fn narrow_scopes(conn: &PgConnection, granted: Vec<String>, client: &Client, user: Option<&User>) -> Result<Vec<String>, ApiError> {
+  let allowed = resolve_scopes(conn, &[], client, user)?;
+  Ok(granted.into_iter().filter(|scope| allowed.contains(scope)).collect())
+}
+
+fn configuration_to_proto(discovery: Discovery) -> Configuration {
+  Configuration {
+    issuer: discovery.issuer,
+    authorization_endpoint: discovery.authorization_endpoint,
+    token_endpoint: discovery.token_endpoint,
+    userinfo_endpoint: discovery.userinfo_endpoint,
+    jwks_uri: discovery.jwks_uri,
+    scopes_supported: discovery.scopes_supported,
+    response_types_supported: discovery.response_types_supported,
+    response_modes_supported: discovery.response_modes_supported,
+    grant_types_supported: discovery.grant_types_supported,
+    subject_types_supported: discovery.subject_types_supported,
+    id_token_signing_alg_values_supported: discovery.id_token_signing_alg_values_supported,
+    userinfo_signing_alg_values_supported: discovery.userinfo_signing_alg_values_supported,
+    token_endpoint_auth_methods_supported: discovery.token_endpoint_auth_methods_supported,
+    claims_supported: discovery.claims_supported,
+    acr_values_supported: discovery.acr_values_supported,
+    revocation_endpoint: discovery.revocation_endpoint,
+    introspection_endpoint: discovery.introspection_endpoint,
+    code_challenge_methods_supported: discovery.code_challenge_methods_supported,
+    end_session_endpoint: discovery.end_session_endpoint,
+    backchannel_logout_supported: discovery.backchannel_logout_supported,
+    backchannel_logout_session_supported: discovery.backchannel_logout_session_supported
+  }
 }
 
 #[tonic::async_trait]
@@ -34,8 +1042,192 @@ impl Login for AuthHandler {
   }
 
   async fn login(&self, request: Request<LoginRequest>) -> Result<Response<LoginResponse>, Status> {
-    println!("Login = {:?}", request);
+    let forwarded_for = request.metadata().get("x-forwarded-for").and_then(|value| value.to_str().ok());
+    let client_ip     = self.lockout.client_ip(super::peer_addr(&request).map(|addr| addr.ip()), forwarded_for);
+
+    let handler = self.clone();
+    let request = request.into_inner();
+
+    Ok(Response::new(super::blocking(move || handler.token(&request, client_ip)).await?))
+  }
+
+  async fn user_info(&self, request: Request<UserInfoRequest>) -> Result<Response<UserInfoResponse>, Status> {
+    let token = super::bearer_token(request.metadata())
+      .ok_or_else(|| ApiError::invalid_token("missing bearer token"))?;
+    let info = self.userinfo(token)?;
+
+    let response = if request.get_ref().signed {
+      UserInfoResponse { claims: None, jwt: self.sign_userinfo(&info)? }
+    }
+    else {
+      UserInfoResponse { claims: Some(super::json_to_struct(info.claims)), jwt: String::new() }
+    };
+
+    Ok(Response::new(response))
+  }
+
+  async fn get_configuration(&self, _: Request<()>) -> Result<Response<Configuration>, Status> {
+    Ok(Response::new(configuration_to_proto(self.discovery()?)))
+  }
+
+  async fn start_passkey_login(&self, request: Request<StartPasskeyLoginRequest>) -> Result<Response<ProtoWebauthnChallenge>, Status> {
+    let conn    = self.db.pool.get().map_err(ApiError::from)?;
+    let request = request.get_ref();
+
+    if request.client_id.is_empty() {
+      return Err(ApiError::invalid_field("client_id", "client_id is required").into());
+    }
+    if Client::find(&conn, &request.client_id)?.is_none() {
+      return Err(ApiError::invalid_client("unknown client").into());
+    }
+
+    let (user_id, second_factor) = if !request.mfa_token.is_empty() {
+      (Some(self.pending_login(&conn, &request.mfa_token, &request.client_id)?.user_id), true)
+    }
+    else if !request.username.is_empty() {
+      // Unknown users get a challenge all the same, offering whatever discoverable passkeys there are.
+      (User::find_by_username(&conn, &request.username)?.map(|user| user.id), false)
+    }
+    else {
+      (None, false)
+    };
+
+    let (challenge_id, options) = self.passkey_challenge(&conn, Some(&request.client_id), user_id, second_factor)?;
+
+    Ok(Response::new(ProtoWebauthnChallenge {
+      challenge_id: challenge_id.to_string(),
+      options_json: options.to_string()
+    }))
+  }
+
+  async fn send_mfa_code(&self, request: Request<SendMfaCodeRequest>) -> Result<Response<()>, Status> {
+    let conn    = self.db.pool.get().map_err(ApiError::from)?;
+    let request = request.get_ref();
+
+    if request.mfa_token.is_empty() {
+      return Err(ApiError::invalid_field("mfa_token", "mfa_token is required").into());
+    }
+
+    let pending = self.pending_login(&conn, &request.mfa_token, &request.client_id)?;
+
+    let user = match User::find(&conn, pending.user_id)? {
+      Some(user) if !user.disabled => user,
+      _ => return Err(ApiError::invalid_grant("account is disabled").into())
+    };
+
+    let (handler, channel) = (self.clone(), channel_from_proto(request.channel));
+
+    // Codes are hashed before they are stored, so sending stays off the reactor.
+    super::blocking(move || handler.send_mfa_code(&conn, &user, channel)).await?;
+    Ok(Response::new(()))
+  }
+
+  async fn register(&self, request: Request<RegisterRequest>) -> Result<Response<RegisterResponse>, Status> {
+    let (handler, request) = (self.clone(), request.into_inner());
+
+    // Checking the new password against the policy & hashing it are slow on purpose.
+    let (user, verification_sent) = super::blocking(move || {
+      let conn = handler.db.pool.get()?;
+      handler.register(&conn, &request)
+    }).await?;
+
+    Ok(Response::new(RegisterResponse {
+      user_id: user.id.to_string(),
+      verification_sent
+    }))
+  }
+
+  async fn request_password_reset(&self, request: Request<RequestPasswordResetRequest>) -> Result<Response<()>, Status> {
+    let conn = self.db.pool.get().map_err(ApiError::from)?;
+    self.request_password_reset(&conn, &request.get_ref().email)?;
+    Ok(Response::new(()))
+  }
+
+  async fn reset_password(&self, request: Request<ResetPasswordRequest>) -> Result<Response<()>, Status> {
+    let (handler, request) = (self.clone(), request.into_inner());
+
+    // Hashing the new password is slow on purpose.
+    super::blocking(move || {
+      let conn = handler.db.pool.get()?;
+      handler.reset_password(&conn, &request.token, &request.new_password)
+    }).await?;
+
+    Ok(Response::new(()))
+  }
+
+  async fn verify_email(&self, request: Request<VerifyEmailRequest>) -> Result<Response<()>, Status> {
+    let conn = self.db.pool.get().map_err(ApiError::from)?;
+    self.verify_email(&conn, &request.get_ref().token)?;
+    Ok(Response::new(()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::test_helpers;
+  use pretty_assertions::assert_eq;
+
+  fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| (*value).to_owned()).collect()
+  }
+
+  fn create_client(conn: &PgConnection, secret_hash: Option<&str>, scopes: &[String]) -> Client {
+    Client::create(conn, &NewClient {
+      id: "app",
+      name: "App",
+      secret_hash,
+      redirect_uris: &[],
+      grant_types: &strings(&["client_credentials"]),
+      scopes,
+      allowed_origins: &[],
+      post_logout_redirect_uris: &[],
+      backchannel_logout_uri: None
+    }).unwrap()
+  }
+
+  #[test]
+  fn test_authenticate_client() {
+    let conn = test_helpers::connection();
+    let hash = password::hash("s3cret").unwrap();
+    create_client(&conn, Some(&hash), &[]);
+
+    assert_eq!(authenticate_client(&conn, "app", "s3cret").unwrap().id, "app");
+    assert_eq!(authenticate_client(&conn, "app", "wrong").unwrap_err().code, ErrorCode::InvalidClient);
+    assert_eq!(authenticate_client(&conn, "nobody", "s3cret").unwrap_err().code, ErrorCode::InvalidClient);
+  }
+
+  #[test]
+  fn test_resolve_scopes_needs_a_role_for_restricted_scopes() {
+    let conn   = test_helpers::connection();
+    let client = create_client(&conn, None, &strings(&["openid", "reports"]));
+    let user   = User::create(&conn, &NewUser { username: "takara", email: None, password_hash: "" }).unwrap();
+    Scope::upsert(&conn, &NewScope { name: "reports", description: "Reports", restricted: true }).unwrap();
+
+    assert_eq!(resolve_scopes(&conn, &[], &client, Some(&user)).unwrap(), strings(&["openid"]));
+    assert_eq!(resolve_scopes(&conn, &strings(&["reports"]), &client, Some(&user)).unwrap_err().code, ErrorCode::InvalidScope);
+    assert_eq!(resolve_scopes(&conn, &strings(&["admin"]), &client, None).unwrap_err().code, ErrorCode::InvalidScope);
+    assert_eq!(resolve_scopes(&conn, &strings(&["reports"]), &client, None).unwrap(), strings(&["reports"]));
+
+    let role = Role::create(&conn, &NewRole { name: "analyst", description: "", scopes: &strings(&["reports"]) }).unwrap();
+    Role::assign(&conn, user.id, role.id).unwrap();
+
+    assert_eq!(resolve_scopes(&conn, &[], &client, Some(&user)).unwrap(), strings(&["openid", "reports"]));
+  }
+
+  #[test]
+  fn test_refreshed_scopes_follow_the_current_roles() {
+    let conn    = test_helpers::connection();
+    let client  = create_client(&conn, None, &strings(&["openid", "reports"]));
+    let user    = User::create(&conn, &NewUser { username: "takara", email: None, password_hash: "" }).unwrap();
+    let granted = strings(&["openid", "reports", "retired"]);
+    Scope::upsert(&conn, &NewScope { name: "reports", description: "Reports", restricted: true }).unwrap();
+
+    let role = Role::create(&conn, &NewRole { name: "analyst", description: "", scopes: &strings(&["reports"]) }).unwrap();
+    Role::assign(&conn, user.id, role.id).unwrap();
+    assert_eq!(narrow_scopes(&conn, granted.clone(), &client, Some(&user)).unwrap(), strings(&["openid", "reports"]));
 
-    Ok(Response::new(LoginResponse::default()))
+    Role::unassign(&conn, user.id, role.id).unwrap();
+    assert_eq!(narrow_scopes(&conn, granted, &client, Some(&user)).unwrap(), strings(&["openid"]));
   }
 }
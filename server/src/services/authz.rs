@@ -0,0 +1,115 @@
+use heimdallr_api::authz::v1::{
+  authz_server::{Authz, AuthzServer},
+  self as proto
+};
+use crate::authz::{Authorizer, Decision, Snapshot};
+use crate::db::Database;
+use crate::jwt::{SharedKeyStore, TokenValidation};
+use crate::metrics;
+use super::error::{self, ApiError, ErrorDetails};
+
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+/// Scope an access token must carry to ask for authorization decisions.
+pub const AUTHZ_SCOPE: &str = "authz";
+
+/// Most checks a single `BatchCheck` may ask for.
+const MAX_BATCH_SIZE: usize = 100;
+
+pub struct AuthzHandler {
+  db: Arc<Database>,
+  keys: SharedKeyStore,
+  authorizer: Authorizer
+}
+
+impl AuthzHandler {
+  pub fn new(db: Database, keys: SharedKeyStore, authorizer: Authorizer) -> Self {
+    Self { db: Arc::new(db), keys, authorizer }
+  }
+
+  /// Wraps the handler in a server that only accepts access tokens with the `authz` scope.
+  pub fn service(self, validation: TokenValidation) -> ErrorDetails<AuthzServer<Self>> {
+    let keys = self.keys.clone();
+    error::with_details(AuthzServer::with_interceptor(self, move |request: Request<()>| super::authorize(&keys, &validation, request, AUTHZ_SCOPE)))
+  }
+
+  fn connection(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Status> {
+    Ok(self.db.pool.get().map_err(ApiError::from)?)
+  }
+
+  /// Decides a single check; `fresh` holds the snapshots read for fully consistent checks, so a
+  /// batch reads the grants of a subject only once.
+  fn decide(&self, conn: &PgConnection, check: &proto::CheckRequest, field: &str, fresh: &mut HashMap<Uuid, Arc<Snapshot>>) -> Result<proto::CheckResponse, ApiError> {
+    let invalid = |name: &str, description: &str| ApiError::invalid_field(format!("{}{}", field, name), format!("{}{} {}", field, name, description));
+
+    let subject = Uuid::parse_str(&check.subject).map_err(|_| invalid("subject", "must be a user id"))?;
+    if check.action.is_empty() || check.action.contains(':') {
+      return Err(invalid("action", "is required and cannot contain `:`"));
+    }
+    if check.resource.is_empty() {
+      return Err(invalid("resource", "is required"));
+    }
+
+    let snapshot = match fresh.get(&subject) {
+      Some(snapshot) => snapshot.clone(),
+      None           => {
+        let snapshot = self.authorizer.snapshot(conn, subject, check.fully_consistent)?;
+        if check.fully_consistent {
+          fresh.insert(subject, snapshot.clone());
+        }
+        snapshot
+      }
+    };
+
+    let decision = snapshot.decide(&check.action, &check.resource);
+    metrics::increment(metrics::AUTHZ_DECISIONS, &[("allowed", if decision.allowed { "true" } else { "false" })]);
+
+    Ok(decision_to_proto(decision))
+  }
+}
+
+fn decision_to_proto(decision: Decision) -> proto::CheckResponse {
+  let (permission, role, group) = match decision.grant {
+    Some(grant) => (grant.permission, grant.role, grant.group.unwrap_or_default()),
+    None        => Default::default()
+  };
+
+  proto::CheckResponse {
+    allowed: decision.allowed,
+    explanation: Some(proto::Explanation { permission, role, group, reason: decision.reason }),
+    evaluated_at: Some(super::timestamp(decision.evaluated_at.naive_utc()))
+  }
+}
+
+#[tonic::async_trait]
+impl Authz for AuthzHandler {
+  async fn check(&self, request: Request<proto::CheckRequest>) -> Result<Response<proto::CheckResponse>, Status> {
+    let conn     = self.connection()?;
+    let response = self.decide(&conn, request.get_ref(), "", &mut HashMap::new())?;
+
+    Ok(Response::new(response))
+  }
+
+  async fn batch_check(&self, request: Request<proto::BatchCheckRequest>) -> Result<Response<proto::BatchCheckResponse>, Status> {
+    let checks = &request.get_ref().checks;
+    if checks.len() > MAX_BATCH_SIZE {
+      return Err(ApiError::invalid_field("checks", format!("at most {} checks can be made at once", MAX_BATCH_SIZE)).into());
+    }
+
+    let conn      = self.connection()?;
+    let mut fresh = HashMap::new();
+
+    let results = checks
+      .iter()
+      .enumerate()
+      .map(|(index, check)| self.decide(&conn, check, &format!("checks[{}].", index), &mut fresh))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Response::new(proto::BatchCheckResponse { results }))
+  }
+}
@@ -0,0 +1,70 @@
+use heimdallr_api::admin::v1::{
+  bootstrap_server::{Bootstrap, BootstrapServer},
+  BootstrapRequest, BootstrapResponse, User as ProtoUser
+};
+use crate::bootstrap;
+use crate::db::Database;
+use crate::error::*;
+use crate::password::Policy;
+use super::error::{self, ApiError, ErrorDetails};
+
+use tonic::{Request, Response, Status};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct BootstrapHandler {
+  db: Arc<Database>,
+  policy: Policy,
+  token_file: PathBuf
+}
+
+impl BootstrapHandler {
+  pub fn new(db: Database, policy: Policy, token_file: PathBuf) -> Self {
+    Self { db: Arc::new(db), policy, token_file }
+  }
+
+  pub fn service(self) -> ErrorDetails<BootstrapServer<Self>> {
+    error::with_details(BootstrapServer::new(self))
+  }
+}
+
+#[tonic::async_trait]
+impl Bootstrap for BootstrapHandler {
+  async fn bootstrap(&self, request: Request<BootstrapRequest>) -> Result<Response<BootstrapResponse>, Status> {
+    let (handler, request) = (self.clone(), request.into_inner());
+
+    // The password & the secret of the admin client are hashed, which is slow on purpose.
+    let account = super::blocking(move || {
+      let conn  = handler.db.pool.get()?;
+      let email = Some(request.email.as_str()).filter(|email| !email.is_empty());
+
+      bootstrap::redeem(&conn, &handler.policy, &request.bootstrap_token, &request.username, email, &request.password)
+        .map_err(|err| match err {
+          HeimdallrError::BootstrapError(reason) => ApiError::access_denied(reason),
+          other                                  => other.into()
+        })
+    }).await?;
+
+    log::warn!("Bootstrapped administrator `{}`; bootstrapping is now disabled", account.user.username);
+    if let Err(err) = bootstrap::remove_token_file(&self.token_file) {
+      log::warn!("Unable to remove bootstrap token file {}: {}", self.token_file.display(), err);
+    }
+
+    Ok(Response::new(BootstrapResponse {
+      user: Some(ProtoUser {
+        id: account.user.id.to_string(),
+        username: account.user.username,
+        email: account.user.email.unwrap_or_default(),
+        disabled: account.user.disabled,
+        roles: vec![bootstrap::ADMIN_ROLE.to_owned()],
+        created_at: Some(super::timestamp(account.user.created_at)),
+        updated_at: Some(super::timestamp(account.user.updated_at)),
+        profile: None,
+        groups: vec![]
+      }),
+      client_id: account.client_id,
+      client_secret: account.client_secret
+    }))
+  }
+}
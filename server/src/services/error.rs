@@ -0,0 +1,491 @@
+use heimdallr_api::google::rpc::{bad_request::FieldViolation, BadRequest, ErrorInfo, RetryInfo, Status as RpcStatus};
+use crate::error::*;
+use crate::password::Violation;
+
+use futures::future::BoxFuture;
+use hyper::{Request, Response};
+use prost::Message;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::Service;
+use tonic::transport::NamedService;
+use tonic::{Code, Status};
+
+/// Domain reported in `google.rpc.ErrorInfo`.
+const ERROR_DOMAIN: &str = "heimdallr";
+
+tokio::task_local! {
+  /// Details of the last `ApiError` a call turned into a `Status`; tonic 0.1 statuses cannot carry
+  /// them, so [`ErrorDetails`] writes them into the response instead.
+  static DETAILS: RefCell<Vec<u8>>;
+}
+
+/// Error vocabulary of every RPC, modelled on the OAuth 2.0 error codes (RFC 6749 §5.2, RFC 6750 §3.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+  InvalidRequest,
+  InvalidClient,
+  InvalidGrant,
+  UnauthorizedClient,
+  UnsupportedGrantType,
+  UnsupportedResponseType,
+  InvalidScope,
+  AccessDenied,
+  InvalidToken,
+  InsufficientScope,
+  LoginRequired,
+  ConsentRequired,
+  MfaRequired,
+  PasswordExpired,
+  NotFound,
+  AlreadyExists,
+  FailedPrecondition,
+  TooManyRequests,
+  TemporarilyUnavailable,
+  ServerError
+}
+
+impl ErrorCode {
+  /// The OAuth 2.0 `error` value.
+  pub fn as_str(&self) -> &'static str {
+    use ErrorCode::*;
+
+    match self {
+      InvalidRequest          => "invalid_request",
+      InvalidClient           => "invalid_client",
+      InvalidGrant            => "invalid_grant",
+      UnauthorizedClient      => "unauthorized_client",
+      UnsupportedGrantType    => "unsupported_grant_type",
+      UnsupportedResponseType => "unsupported_response_type",
+      InvalidScope            => "invalid_scope",
+      AccessDenied            => "access_denied",
+      InvalidToken            => "invalid_token",
+      InsufficientScope       => "insufficient_scope",
+      LoginRequired           => "login_required",
+      ConsentRequired         => "consent_required",
+      MfaRequired             => "mfa_required",
+      PasswordExpired         => "password_expired",
+      NotFound                => "not_found",
+      AlreadyExists           => "already_exists",
+      FailedPrecondition      => "failed_precondition",
+      TooManyRequests         => "too_many_requests",
+      TemporarilyUnavailable  => "temporarily_unavailable",
+      ServerError             => "server_error"
+    }
+  }
+
+  /// The gRPC status code the error is reported with.
+  pub fn grpc_code(&self) -> Code {
+    use ErrorCode::*;
+
+    match self {
+      InvalidRequest | InvalidGrant | UnsupportedGrantType | InvalidScope => Code::InvalidArgument,
+      UnsupportedResponseType                                            => Code::InvalidArgument,
+      InvalidClient | InvalidToken | LoginRequired                       => Code::Unauthenticated,
+      MfaRequired                                                        => Code::Unauthenticated,
+      UnauthorizedClient | AccessDenied | InsufficientScope              => Code::PermissionDenied,
+      ConsentRequired                                                    => Code::PermissionDenied,
+      NotFound                                                           => Code::NotFound,
+      AlreadyExists                                                      => Code::AlreadyExists,
+      FailedPrecondition | PasswordExpired                               => Code::FailedPrecondition,
+      TooManyRequests                                                    => Code::ResourceExhausted,
+      TemporarilyUnavailable                                             => Code::Unavailable,
+      ServerError                                                        => Code::Internal
+    }
+  }
+
+  /// The HTTP status code used by the HTTP front end.
+  pub fn http_status(&self) -> u16 {
+    use ErrorCode::*;
+
+    match self {
+      InvalidClient | InvalidToken                 => 401,
+      AccessDenied | InsufficientScope             => 403,
+      MfaRequired | PasswordExpired                => 403,
+      NotFound                                     => 404,
+      AlreadyExists | FailedPrecondition           => 409,
+      TooManyRequests                              => 429,
+      TemporarilyUnavailable                       => 503,
+      ServerError                                  => 500,
+      _                                            => 400
+    }
+  }
+}
+
+impl fmt::Display for ErrorCode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// An error that is safe to return to callers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+  pub code: ErrorCode,
+  pub description: String,
+  pub field_violations: Vec<(String, String)>,
+  pub metadata: HashMap<String, String>
+}
+
+impl ApiError {
+  pub fn new<D: Into<String>>(code: ErrorCode, description: D) -> Self {
+    ApiError {
+      code,
+      description: description.into(),
+      field_violations: vec![],
+      metadata: HashMap::new()
+    }
+  }
+
+  pub fn invalid_request<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::InvalidRequest, description)
+  }
+
+  /// An `invalid_request` caused by a single bad field.
+  pub fn invalid_field<F: Into<String>, D: Into<String>>(field: F, description: D) -> Self {
+    let description = description.into();
+    Self::invalid_request(description.clone()).with_violation(field, description)
+  }
+
+  pub fn invalid_client<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::InvalidClient, description)
+  }
+
+  pub fn invalid_grant<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::InvalidGrant, description)
+  }
+
+  pub fn unauthorized_client<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::UnauthorizedClient, description)
+  }
+
+  pub fn unsupported_grant_type<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::UnsupportedGrantType, description)
+  }
+
+  pub fn unsupported_response_type<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::UnsupportedResponseType, description)
+  }
+
+  pub fn invalid_scope<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::InvalidScope, description)
+  }
+
+  pub fn access_denied<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::AccessDenied, description)
+  }
+
+  pub fn invalid_token<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::InvalidToken, description)
+  }
+
+  pub fn insufficient_scope<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::InsufficientScope, description)
+  }
+
+  pub fn login_required<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::LoginRequired, description)
+  }
+
+  pub fn consent_required<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::ConsentRequired, description)
+  }
+
+  /// The password was right but a second factor is needed; `mfa_token` continues the login.
+  pub fn mfa_required<T: Into<String>>(mfa_token: T) -> Self {
+    Self::new(ErrorCode::MfaRequired, "multi-factor authentication is required").with_metadata("mfa_token", mfa_token)
+  }
+
+  pub fn not_found<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::NotFound, description)
+  }
+
+  pub fn already_exists<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::AlreadyExists, description)
+  }
+
+  pub fn failed_precondition<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::FailedPrecondition, description)
+  }
+
+  /// The password of the user is older than the policy allows; they have to reset it.
+  pub fn password_expired<D: Into<String>>(description: D) -> Self {
+    Self::new(ErrorCode::PasswordExpired, description)
+  }
+
+  /// A new password breaks the password policy; every rule it breaks is reported as a violation of
+  /// `field`, and their names in the `password_violations` metadata.
+  pub fn password_rejected<F: Into<String>>(field: F, violations: &[Violation]) -> Self {
+    let field = field.into();
+    let names: Vec<&str> = violations.iter().map(Violation::as_str).collect();
+
+    violations.iter()
+      .fold(Self::invalid_request("password does not meet the password policy"), |err, violation| err.with_violation(field.clone(), violation.to_string()))
+      .with_metadata("password_violations", names.join(" "))
+  }
+
+  /// The caller has to slow down; `retry_after` is in seconds, as in the HTTP `Retry-After` header.
+  pub fn too_many_requests<D: Into<String>>(description: D, retry_after: i64) -> Self {
+    Self::new(ErrorCode::TooManyRequests, description).with_metadata("retry_after", retry_after.max(1).to_string())
+  }
+
+  /// A generic server error; the details of what went wrong are only ever logged.
+  pub fn server_error() -> Self {
+    Self::new(ErrorCode::ServerError, "internal server error")
+  }
+
+  pub fn with_violation<F: Into<String>, D: Into<String>>(mut self, field: F, description: D) -> Self {
+    self.field_violations.push((field.into(), description.into()));
+    self
+  }
+
+  pub fn with_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+    self.metadata.insert(key.into(), value.into());
+    self
+  }
+
+  /// Encodes the `google.rpc.Status` carried in the `grpc-status-details-bin` trailer.
+  pub(crate) fn details(&self) -> Vec<u8> {
+    let mut metadata = self.metadata.clone();
+    metadata.insert("error".to_owned(), self.code.as_str().to_owned());
+    metadata.insert("error_description".to_owned(), self.description.clone());
+
+    let mut details = vec![any("type.googleapis.com/google.rpc.ErrorInfo", &ErrorInfo {
+      reason: self.code.as_str().to_uppercase(),
+      domain: ERROR_DOMAIN.to_owned(),
+      metadata
+    })];
+
+    if !self.field_violations.is_empty() {
+      details.push(any("type.googleapis.com/google.rpc.BadRequest", &BadRequest {
+        field_violations: self.field_violations
+          .iter()
+          .map(|(field, description)| FieldViolation { field: field.clone(), description: description.clone() })
+          .collect()
+      }));
+    }
+
+    if let Some(seconds) = self.metadata.get("retry_after").and_then(|seconds| seconds.parse().ok()) {
+      details.push(any("type.googleapis.com/google.rpc.RetryInfo", &RetryInfo {
+        retry_delay: Some(prost_types::Duration { seconds, nanos: 0 })
+      }));
+    }
+
+    encode(&RpcStatus {
+      code: self.code.grpc_code() as i32,
+      message: self.description.clone(),
+      details
+    })
+  }
+}
+
+impl std::error::Error for ApiError {}
+
+impl fmt::Display for ApiError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}: {}", self.code, self.description)
+  }
+}
+
+impl From<ApiError> for Status {
+  fn from(err: ApiError) -> Status {
+    // Outside of a call served through `ErrorDetails` there is nowhere to put the details.
+    let _ = DETAILS.try_with(|details| details.replace(err.details()));
+    Status::new(err.code.grpc_code(), err.description)
+  }
+}
+
+/// Wraps a tonic service so the details of the `ApiError` a call failed with reach the caller in
+/// the `grpc-status-details-bin` trailer.
+pub fn with_details<S>(inner: S) -> ErrorDetails<S> {
+  ErrorDetails { inner }
+}
+
+/// A tonic service whose error responses carry `google.rpc.Status` details.
+#[derive(Clone)]
+pub struct ErrorDetails<S> {
+  inner: S
+}
+
+impl<S: NamedService> NamedService for ErrorDetails<S> {
+  const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> Service<Request<B>> for ErrorDetails<S>
+  where S: Service<Request<B>, Response = Response<BoxBody>>,
+        S::Future: Send + 'static
+{
+  type Response = Response<BoxBody>;
+  type Error = S::Error;
+  type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, request: Request<B>) -> Self::Future {
+    let call = self.inner.call(request);
+
+    Box::pin(DETAILS.scope(RefCell::new(Vec::new()), async move {
+      let mut response = call.await?;
+
+      // Errors are trailers-only responses, so the details go next to `grpc-status`.
+      let details = DETAILS.with(|details| details.replace(Vec::new()));
+      let failed  = response.headers().get("grpc-status").map(|code| code != "0").unwrap_or(false);
+      if failed && !details.is_empty() {
+        super::insert_details(response.headers_mut(), &details);
+      }
+
+      Ok(response)
+    }))
+  }
+}
+
+impl From<HeimdallrError> for ApiError {
+  fn from(err: HeimdallrError) -> ApiError {
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    match err {
+      HeimdallrError::DatabaseError(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+        ApiError::already_exists("a record with the same identifier already exists")
+      },
+      HeimdallrError::DatabaseError(Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => {
+        ApiError::not_found("a referenced record does not exist")
+      },
+      HeimdallrError::DatabaseError(Error::NotFound) => ApiError::not_found("record not found"),
+      HeimdallrError::TokenError(err) => ApiError::invalid_token(err.to_string()),
+      HeimdallrError::PasswordRejected(violations) => ApiError::password_rejected("password", &violations),
+      HeimdallrError::R2D2Error(err) => {
+        log::error!("Database pool exhausted: {}", err);
+        ApiError::new(ErrorCode::TemporarilyUnavailable, "service temporarily unavailable")
+      },
+      other => {
+        log::error!("Request failed: {}", other);
+        ApiError::server_error()
+      }
+    }
+  }
+}
+
+impl From<HeimdallrError> for Status {
+  fn from(err: HeimdallrError) -> Status {
+    ApiError::from(err).into()
+  }
+}
+
+impl From<diesel::result::Error> for ApiError {
+  fn from(err: diesel::result::Error) -> ApiError {
+    HeimdallrError::from(err).into()
+  }
+}
+
+impl From<r2d2::Error> for ApiError {
+  fn from(err: r2d2::Error) -> ApiError {
+    HeimdallrError::from(err).into()
+  }
+}
+
+fn any<M: Message>(type_url: &str, message: &M) -> prost_types::Any {
+  prost_types::Any {
+    type_url: type_url.to_owned(),
+    value: encode(message)
+  }
+}
+
+fn encode<M: Message>(message: &M) -> Vec<u8> {
+  let mut buf = Vec::with_capacity(message.encoded_len());
+  // Encoding into a Vec can only fail when running out of capacity, which Vec grows on demand.
+  message.encode(&mut buf).expect("encoding into a Vec cannot fail");
+  buf
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_oauth_codes_map_to_grpc_codes() {
+    assert_eq!(Status::from(ApiError::invalid_client("nope")).code(), Code::Unauthenticated);
+    assert_eq!(Status::from(ApiError::invalid_grant("nope")).code(), Code::InvalidArgument);
+    assert_eq!(Status::from(ApiError::access_denied("nope")).code(), Code::PermissionDenied);
+    assert_eq!(Status::from(ApiError::too_many_requests("slow down", 30)).code(), Code::ResourceExhausted);
+  }
+
+  #[test]
+  fn test_details_carry_error_info_and_bad_request() {
+    let decoded = RpcStatus::decode(&ApiError::invalid_field("username", "username is required").details()[..]).unwrap();
+
+    assert_eq!(decoded.code, Code::InvalidArgument as i32);
+    assert_eq!(decoded.details.len(), 2);
+
+    let info = ErrorInfo::decode(&decoded.details[0].value[..]).unwrap();
+    assert_eq!(info.reason, "INVALID_REQUEST");
+    assert_eq!(info.metadata["error"], "invalid_request");
+
+    let bad_request = BadRequest::decode(&decoded.details[1].value[..]).unwrap();
+    assert_eq!(bad_request.field_violations[0].field, "username");
+  }
+
+  #[test]
+  fn test_too_many_requests_carries_retry_info() {
+    let decoded = RpcStatus::decode(&ApiError::too_many_requests("slow down", 30).details()[..]).unwrap();
+
+    let retry = RetryInfo::decode(&decoded.details[1].value[..]).unwrap();
+    assert_eq!(retry.retry_delay.map(|delay| delay.seconds), Some(30));
+  }
+
+  /// Fails every call the way a tonic service does when a handler returns an `ApiError`.
+  struct Failing;
+
+  impl Service<Request<()>> for Failing {
+    type Response = Response<BoxBody>;
+    type Error = Status;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+      Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _request: Request<()>) -> Self::Future {
+      Box::pin(async {
+        let status = Status::from(ApiError::invalid_field("username", "username is required"));
+
+        let mut response = Response::new(BoxBody::empty());
+        crate::services::insert_status(response.headers_mut(), &status);
+        Ok(response)
+      })
+    }
+  }
+
+  #[tokio::test]
+  async fn test_error_details_reach_the_response() {
+    let response = with_details(Failing).call(Request::new(())).await.unwrap();
+    assert_eq!(response.headers()["grpc-status"], "3");
+
+    let details = base64::decode_config(&response.headers()["grpc-status-details-bin"], base64::STANDARD_NO_PAD).unwrap();
+    assert_eq!(RpcStatus::decode(&details[..]).unwrap().message, "username is required");
+  }
+
+  #[test]
+  fn test_internal_errors_do_not_leak() {
+    let status = Status::from(HeimdallrError::KeyError("secret internals".to_owned()));
+    assert_eq!(status.code(), Code::Internal);
+    assert!(!status.message().contains("secret"));
+  }
+
+  #[test]
+  fn test_password_rejected_lists_every_violation() {
+    let err = ApiError::password_rejected("password", &[Violation::TooShort { min_length: 8 }, Violation::Breached { count: 3 }]);
+
+    assert_eq!(err.field_violations, vec![
+      ("password".to_owned(), "password must be at least 8 characters long".to_owned()),
+      ("password".to_owned(), "password has appeared in data breaches 3 times".to_owned())
+    ]);
+    assert_eq!(err.metadata["password_violations"], "too_short breached");
+  }
+}
@@ -1,2 +1,159 @@
+pub mod account;
+pub mod admin;
+pub mod authz;
+pub mod bootstrap;
+pub mod error;
 pub mod health_check;
 pub mod auth;
+
+use hyper::header::{HeaderMap, HeaderValue};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use prost_types::{value::Kind, ListValue, Struct};
+use std::net::SocketAddr;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Status};
+
+use crate::jwt::{SharedKeyStore, TokenValidation};
+use crate::tokens::scopes_of;
+
+/// Characters percent-encoded in `grpc-message`.
+const GRPC_MESSAGE: &AsciiSet = &CONTROLS.add(b'%');
+
+tokio::task_local! {
+  /// Address of the peer of the call being served, set by `RateLimited`: reading it there takes it
+  /// away from the `tonic::Request` the handler gets.
+  pub(crate) static PEER: Option<SocketAddr>;
+}
+
+/// Address of the peer of a call.
+pub(crate) fn peer_addr<T>(request: &Request<T>) -> Option<SocketAddr> {
+  PEER.try_with(|peer| *peer).ok().flatten().or_else(|| request.remote_addr())
+}
+
+/// Converts a database timestamp into a protobuf timestamp.
+pub(crate) fn timestamp(time: chrono::NaiveDateTime) -> prost_types::Timestamp {
+  prost_types::Timestamp {
+    seconds: time.timestamp(),
+    nanos: time.timestamp_subsec_nanos() as i32
+  }
+}
+
+/// Converts a protobuf timestamp into a database timestamp; out of range values give `None`.
+pub(crate) fn naive_datetime(timestamp: &prost_types::Timestamp) -> Option<chrono::NaiveDateTime> {
+  if timestamp.nanos < 0 {
+    return None;
+  }
+
+  chrono::NaiveDateTime::from_timestamp_opt(timestamp.seconds, timestamp.nanos as u32)
+}
+
+/// Runs work that blocks, such as checking passwords, on the blocking thread pool so it doesn't
+/// stall the reactor.
+pub(crate) async fn blocking<F, T>(work: F) -> Result<T, error::ApiError>
+  where F: FnOnce() -> Result<T, error::ApiError> + Send + 'static, T: Send + 'static {
+  tokio::task::spawn_blocking(work).await.map_err(|err| {
+    log::error!("Blocking task failed: {}", err);
+    error::ApiError::server_error()
+  })?
+}
+
+/// Extracts the bearer token from the `authorization` metadata, if present.
+pub(crate) fn bearer_token(metadata: &MetadataMap) -> Option<&str> {
+  parse_bearer(metadata.get("authorization")?.to_str().ok()?)
+}
+
+/// Extracts the token from an `Authorization: Bearer ...` header value.
+pub(crate) fn parse_bearer(value: &str) -> Option<&str> {
+  if value.len() > 7 && value[..7].eq_ignore_ascii_case("bearer ") {
+    Some(value[7..].trim())
+  }
+  else {
+    None
+  }
+}
+
+/// Checks that a request carries an access token issued by this server holding `scope`.
+pub(crate) fn authorize(keys: &SharedKeyStore, validation: &TokenValidation, request: Request<()>, scope: &str) -> Result<Request<()>, Status> {
+  let token = bearer_token(request.metadata())
+    .ok_or_else(|| error::ApiError::invalid_token("missing bearer token"))?;
+
+  let claims = keys.read().verify(token, validation)
+    .map_err(|err| error::ApiError::invalid_token(format!("invalid access token: {}", err)))?;
+
+  if !scopes_of(&claims).contains(&scope) {
+    return Err(error::ApiError::insufficient_scope(format!("the `{}` scope is required", scope)).into());
+  }
+
+  Ok(request)
+}
+
+/// Sets the `grpc-status` & `grpc-message` headers of a trailers-only response reporting an error.
+pub(crate) fn insert_status(headers: &mut HeaderMap, status: &Status) {
+  headers.insert("grpc-status", HeaderValue::from(status.code() as i32));
+
+  let message = utf8_percent_encode(status.message(), GRPC_MESSAGE).to_string();
+  if let Ok(value) = HeaderValue::from_str(&message) {
+    headers.insert("grpc-message", value);
+  }
+}
+
+/// Sets the `grpc-status-details-bin` header carrying an encoded `google.rpc.Status`.
+pub(crate) fn insert_details(headers: &mut HeaderMap, details: &[u8]) {
+  let details = base64::encode_config(details, base64::STANDARD_NO_PAD);
+  if let Ok(value) = HeaderValue::from_str(&details) {
+    headers.insert("grpc-status-details-bin", value);
+  }
+}
+
+/// Sets every header of a trailers-only response reporting `err`.
+pub(crate) fn insert_error(headers: &mut HeaderMap, err: error::ApiError) {
+  let details = err.details();
+  insert_status(headers, &Status::from(err));
+  insert_details(headers, &details);
+}
+
+/// Converts JSON claims into a protobuf struct.
+pub(crate) fn json_to_struct(object: serde_json::Map<String, serde_json::Value>) -> Struct {
+  Struct {
+    fields: object.into_iter().map(|(key, value)| (key, json_to_value(value))).collect()
+  }
+}
+
+fn json_to_value(value: serde_json::Value) -> prost_types::Value {
+  use serde_json::Value as Json;
+
+  let kind = match value {
+    Json::Null          => Kind::NullValue(0),
+    Json::Bool(value)   => Kind::BoolValue(value),
+    Json::Number(value) => Kind::NumberValue(value.as_f64().unwrap_or_default()),
+    Json::String(value) => Kind::StringValue(value),
+    Json::Array(values) => Kind::ListValue(ListValue { values: values.into_iter().map(json_to_value).collect() }),
+    Json::Object(value) => Kind::StructValue(json_to_struct(value))
+  };
+
+  prost_types::Value { kind: Some(kind) }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_parse_bearer() {
+    assert_eq!(parse_bearer("Bearer abc.def"), Some("abc.def"));
+    assert_eq!(parse_bearer("bearer abc"), Some("abc"));
+    assert_eq!(parse_bearer("Basic abc"), None);
+  }
+
+  #[test]
+  fn test_json_to_struct() {
+    let claims = serde_json::json!({ "sub": "abc", "email_verified": true, "updated_at": 10 });
+    let object = json_to_struct(claims.as_object().unwrap().clone());
+
+    assert_eq!(object.fields["sub"].kind, Some(Kind::StringValue("abc".to_owned())));
+    assert_eq!(object.fields["email_verified"].kind, Some(Kind::BoolValue(true)));
+    assert_eq!(object.fields["updated_at"].kind, Some(Kind::NumberValue(10.0)));
+  }
+}
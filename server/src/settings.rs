@@ -6,7 +6,29 @@ use crate::error::*;
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
   pub grpc_listener: Listener,
-  pub database: Database
+  /// HTTP/1.1 OAuth 2.0 & OpenID Connect endpoints (`/token`, `/authorize`, ...); disabled when omitted.
+  pub http_listener: Option<Listener>,
+  pub database: Database,
+  pub jwt: Jwt,
+  pub bootstrap: Option<Bootstrap>,
+  pub ui: Option<Ui>,
+  pub webauthn: Option<Webauthn>,
+  /// Outgoing email; messages are only logged when omitted.
+  pub mail: Option<Mail>,
+  /// One-time codes sent to phones; codes are only logged when omitted.
+  pub sms: Option<Sms>,
+  /// Self-service sign-up through the `Register` RPC; disabled when omitted.
+  pub registration: Option<Registration>,
+  /// Rules for passwords picked by users & administrators.
+  pub password_policy: Option<PasswordPolicy>,
+  /// Cost of password hashing; see `heimdallr argon2 calibrate`.
+  pub argon2: Option<Argon2>,
+  /// Brute-force protection of password logins; on with the defaults when omitted.
+  pub lockout: Option<Lockout>,
+  /// Token-bucket limits on RPCs; nothing is limited when omitted.
+  pub rate_limits: Option<RateLimits>,
+  /// Caching of the `heimdallr.authz.v1.Authz` decisions.
+  pub authz: Option<Authz>
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -29,7 +51,490 @@ pub struct Database {
   pub pool: Option<usize>
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct Jwt {
+  /// Value of the `iss` claim; should be the public URL of the HTTP listener, which serves discovery.
+  pub issuer: String,
+
+  /// Lifetime of access tokens in seconds.
+  pub access_token_ttl: Option<i64>,
+
+  /// Lifetime of ID tokens in seconds.
+  pub id_token_ttl: Option<i64>,
+
+  /// Lifetime of refresh tokens in seconds.
+  pub refresh_token_ttl: Option<i64>,
+
+  /// Allowed clock skew in seconds when validating `exp` & `nbf`.
+  pub leeway: Option<u64>,
+
+  /// Claim of access tokens listing the roles of the user, e.g. `roles`; left out when not set.
+  pub roles_claim: Option<String>,
+
+  /// Claim of access tokens listing the permissions of the user, e.g. `permissions`; left out when
+  /// not set.
+  pub permissions_claim: Option<String>
+}
+
+impl Jwt {
+  pub fn access_token_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.access_token_ttl.unwrap_or(3600))
+  }
+
+  pub fn id_token_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.id_token_ttl.unwrap_or(3600))
+  }
+
+  pub fn refresh_token_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.refresh_token_ttl.unwrap_or(30 * 24 * 3600))
+  }
+
+  /// Whether access tokens of users carry their roles or permissions.
+  pub fn carries_entitlements(&self) -> bool {
+    self.roles_claim.is_some() || self.permissions_claim.is_some()
+  }
+
+  /// Refuses entitlement claims that would overwrite a claim every access token carries, or each
+  /// other.
+  pub fn check_claims(&self) -> Result<(), HeimdallrError> {
+    let invalid = |message: String| HeimdallrError::ConfigError(config::ConfigError::Message(format!("jwt.{}", message)));
+
+    for (option, claim) in &[("roles_claim", &self.roles_claim), ("permissions_claim", &self.permissions_claim)] {
+      if let Some(claim) = claim {
+        if claim.is_empty() || crate::tokens::RESERVED_CLAIMS.contains(&claim.as_str()) {
+          return Err(invalid(format!("{} `{}` is empty or a claim every access token carries", option, claim)));
+        }
+      }
+    }
+
+    if self.roles_claim.is_some() && self.roles_claim == self.permissions_claim {
+      return Err(invalid("roles_claim & permissions_claim must differ".to_owned()));
+    }
+
+    Ok(())
+  }
+
+  /// Validation rules for tokens issued by this server.
+  pub fn validation(&self) -> crate::jwt::TokenValidation {
+    crate::jwt::TokenValidation {
+      issuer: Some(self.issuer.clone()),
+      audience: None,
+      leeway: self.leeway.unwrap_or(30),
+      allow_expired: false
+    }
+  }
+}
+
+#[cfg(test)]
+impl Jwt {
+  /// The defaults for an issuer, as if nothing else was configured.
+  pub(crate) fn for_issuer(issuer: &str) -> Self {
+    Jwt {
+      issuer: issuer.to_owned(),
+      access_token_ttl: None,
+      id_token_ttl: None,
+      refresh_token_ttl: None,
+      leeway: None,
+      roles_claim: None,
+      permissions_claim: None
+    }
+  }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Bootstrap {
+  /// Where the one-time bootstrap token is written on first boot.
+  pub token_file: Option<String>
+}
+
+/// Login, consent & logout pages served by the HTTP listener.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Ui {
+  /// Directory of templates replacing the built-in ones (`login.hbs`, `consent.hbs`, `error.hbs`, `logout.hbs`, ...).
+  pub templates_dir: Option<String>,
+
+  /// Directory of assets served under `/static/`, taking precedence over the built-in stylesheet.
+  pub static_dir: Option<String>,
+
+  /// Secret session cookies are signed with; a random one is generated on boot when omitted,
+  /// which logs everyone out on restart and does not work with several instances.
+  pub session_secret: Option<String>,
+
+  /// Lifetime of a login session in seconds.
+  pub session_ttl: Option<i64>,
+
+  /// Only send cookies over HTTPS; can be turned off for local development.
+  pub secure_cookies: Option<bool>
+}
+
+impl Ui {
+  pub fn session_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.session_ttl.unwrap_or(8 * 3600))
+  }
+
+  pub fn secure_cookies(&self) -> bool {
+    self.secure_cookies.unwrap_or(true)
+  }
+}
+
+/// Passkeys (WebAuthn); the relying party defaults to the host & origin of the issuer.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Webauthn {
+  /// Relying party id, the domain passkeys are bound to; must be the host of the pages running the
+  /// ceremonies or a registrable suffix of it.
+  pub rp_id: Option<String>,
+
+  /// Name authenticators show for the relying party.
+  pub rp_name: Option<String>,
+
+  /// Origins allowed to run ceremonies, e.g. `https://app.example.com`.
+  pub origins: Option<Vec<String>>,
+
+  /// `required`, `preferred` or `discouraged`; passwordless logins always require user verification.
+  pub user_verification: Option<String>,
+
+  /// Seconds the user has to complete a ceremony.
+  pub challenge_ttl: Option<i64>
+}
+
+impl Webauthn {
+  pub fn challenge_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.challenge_ttl.unwrap_or(300))
+  }
+}
+
+/// Email verification, password reset & magic-link messages.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Mail {
+  /// `From` header of every message, e.g. `Heimdallr <no-reply@example.com>`.
+  pub from: Option<String>,
+
+  /// `smtp`, `file` or `log` (the default).
+  pub transport: Option<String>,
+
+  pub smtp: Option<Smtp>,
+
+  /// Directory the `file` transport writes one `.eml` file per message to.
+  pub directory: Option<String>,
+
+  /// Directory of message templates replacing or adding to the built-in ones, laid out as
+  /// `<locale>/<name>.hbs` (`verify_email`, `password_reset`, `magic_link`).
+  pub templates_dir: Option<String>,
+
+  /// Locale of users who have none.
+  pub default_locale: Option<String>,
+
+  /// Messages a single recipient can be sent per hour.
+  pub max_per_hour: Option<i64>,
+
+  /// Lifetime of email verification links in seconds.
+  pub verification_ttl: Option<i64>,
+
+  /// Lifetime of password reset links in seconds.
+  pub password_reset_ttl: Option<i64>,
+
+  /// Lifetime of magic sign-in links in seconds.
+  pub magic_link_ttl: Option<i64>
+}
+
+impl Mail {
+  pub fn from(&self) -> String {
+    self.from.clone().unwrap_or_else(|| "Heimdallr <no-reply@localhost>".to_owned())
+  }
+
+  pub fn default_locale(&self) -> String {
+    self.default_locale.clone().unwrap_or_else(|| "en".to_owned())
+  }
+
+  pub fn max_per_hour(&self) -> i64 {
+    self.max_per_hour.unwrap_or(5)
+  }
+
+  pub fn verification_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.verification_ttl.unwrap_or(24 * 3600))
+  }
+
+  pub fn password_reset_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.password_reset_ttl.unwrap_or(3600))
+  }
+
+  pub fn magic_link_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.magic_link_ttl.unwrap_or(900))
+  }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Smtp {
+  pub host: String,
+
+  /// Defaults to 465 for `tls`, 587 for `starttls` and 25 for `none`.
+  pub port: Option<u16>,
+
+  /// `starttls` (the default), `tls` or `none`.
+  pub security: Option<String>,
+
+  pub username: Option<String>,
+  pub password: Option<String>,
+
+  /// Seconds to wait for the server before giving up.
+  pub timeout: Option<u64>
+}
+
+/// Phone number verification & second factor codes, sent by SMS or voice call.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Sms {
+  /// `webhook` or `log` (the default).
+  pub transport: Option<String>,
+
+  pub webhook: Option<SmsWebhook>,
+
+  /// Lifetime of a code in seconds.
+  pub code_ttl: Option<i64>,
+
+  /// Wrong guesses after which a code stops working.
+  pub max_attempts: Option<i32>,
+
+  /// Codes a single phone number can be sent per hour.
+  pub max_per_hour: Option<i64>
+}
+
+impl Sms {
+  pub fn code_ttl(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.code_ttl.unwrap_or(300))
+  }
+
+  pub fn max_attempts(&self) -> i32 {
+    self.max_attempts.unwrap_or(5)
+  }
+
+  pub fn max_per_hour(&self) -> i64 {
+    self.max_per_hour.unwrap_or(5)
+  }
+}
+
+/// A gateway that takes messages as `{"to", "body", "channel"}` JSON POSTs, e.g. a small adapter in
+/// front of the API of an SMS provider.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SmsWebhook {
+  pub url: String,
+
+  /// Value of the `Authorization` header, e.g. `Bearer <token>`.
+  pub authorization: Option<String>,
+
+  /// Seconds to wait for the gateway before giving up.
+  pub timeout: Option<u64>
+}
+
+/// Who may create an account through the `Register` RPC.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Registration {
+  /// `open`, `invitation_only` or `disabled` (the default).
+  pub mode: Option<String>,
+
+  /// Refuse sign-ups without an email address.
+  pub require_email: Option<bool>,
+
+  /// Mail a verification link to the address of new accounts; on by default.
+  pub verify_email: Option<bool>
+}
+
+impl Registration {
+  pub fn require_email(&self) -> bool {
+    self.require_email.unwrap_or(false)
+  }
+
+  pub fn verify_email(&self) -> bool {
+    self.verify_email.unwrap_or(true)
+  }
+}
+
+/// Rules for new passwords, checked on registration, resets, changes & when administrators set one.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct PasswordPolicy {
+  /// Minimum number of characters; 8 by default.
+  pub min_length: Option<usize>,
+
+  /// Minimum strength score, from 0 (guessed within a thousand tries) to 4 (more than ten billion
+  /// tries); 2 by default.
+  pub min_strength: Option<u8>,
+
+  /// Number of recent passwords of a user, including the current one, that cannot be picked again;
+  /// 0 (the default) allows reusing any.
+  pub history: Option<usize>,
+
+  /// Days after which a password stops working & has to be reset; passwords never expire when omitted.
+  pub max_age_days: Option<i64>,
+
+  /// Pwned Passwords dump (SHA-1 version) to refuse breached passwords with: a directory of range
+  /// files named after 5 character hash prefixes (`5BAA6.txt`), or one file ordered by hash.
+  pub breached_passwords: Option<String>
+}
+
+impl PasswordPolicy {
+  pub fn min_length(&self) -> usize {
+    self.min_length.unwrap_or(8)
+  }
+
+  pub fn min_strength(&self) -> u8 {
+    self.min_strength.unwrap_or(2)
+  }
+
+  pub fn history(&self) -> usize {
+    self.history.unwrap_or(0)
+  }
+
+  pub fn max_age(&self) -> Option<chrono::Duration> {
+    self.max_age_days.map(chrono::Duration::days)
+  }
+}
+
+/// Parameters of new password hashes; hashes made with other ones are upgraded when users log in.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Argon2 {
+  /// `argon2id` (the default), `argon2i` or `argon2d`.
+  pub variant: Option<String>,
+
+  /// Memory used per hash in KiB; 19456 (19 MiB) by default.
+  pub memory_cost: Option<u32>,
+
+  /// Passes over the memory; 2 by default.
+  pub time_cost: Option<u32>,
+
+  /// Lanes computed in parallel; 1 by default.
+  pub parallelism: Option<u32>,
+
+  /// Secret mixed into every hash, so a leaked database alone is not enough to guess passwords;
+  /// best set through `HEIMDALLR_ARGON2_PEPPER`. Hashes made without it keep working & are
+  /// upgraded on login, but changing it invalidates every password.
+  pub pepper: Option<String>
+}
+
+impl Argon2 {
+  pub fn variant(&self) -> &str {
+    self.variant.as_deref().unwrap_or("argon2id")
+  }
+
+  pub fn memory_cost(&self) -> u32 {
+    self.memory_cost.unwrap_or(19456)
+  }
+
+  pub fn time_cost(&self) -> u32 {
+    self.time_cost.unwrap_or(2)
+  }
+
+  pub fn parallelism(&self) -> u32 {
+    self.parallelism.unwrap_or(1)
+  }
+}
+
+/// Limits on failed password logins, counted per username & per client address.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Lockout {
+  /// Failed logins for a username before further attempts have to wait; 5 by default.
+  pub user_free_attempts: Option<u32>,
+
+  /// Failed logins for a username that lock the account for `lockout_minutes`, unless an
+  /// administrator unlocks it sooner; 20 by default.
+  pub user_lockout_attempts: Option<u32>,
+
+  /// 60 by default.
+  pub lockout_minutes: Option<i64>,
+
+  /// Failed logins from an address (or IPv6 /64 network) before further attempts have to wait;
+  /// 20 by default.
+  pub ip_free_attempts: Option<u32>,
+
+  /// Wait after the first failure past the free attempts, doubling with every further one; 1 second
+  /// by default.
+  pub base_delay_seconds: Option<i64>,
+
+  /// Longest wait short of a lockout; 900 seconds (15 minutes) by default.
+  pub max_delay_seconds: Option<i64>,
+
+  /// Failures are forgotten after this long without one; 24 hours by default.
+  pub reset_after_hours: Option<i64>,
+
+  /// Addresses or CIDR ranges of the reverse proxies whose `X-Forwarded-For` header is trusted;
+  /// without any, clients are told apart by the address they connect from.
+  #[serde(default)]
+  pub trusted_proxies: Vec<String>
+}
+
+impl Lockout {
+  pub fn user_free_attempts(&self) -> u32 {
+    self.user_free_attempts.unwrap_or(5)
+  }
+
+  pub fn user_lockout_attempts(&self) -> u32 {
+    self.user_lockout_attempts.unwrap_or(20)
+  }
+
+  pub fn lockout_duration(&self) -> chrono::Duration {
+    chrono::Duration::minutes(self.lockout_minutes.unwrap_or(60))
+  }
+
+  pub fn ip_free_attempts(&self) -> u32 {
+    self.ip_free_attempts.unwrap_or(20)
+  }
+
+  pub fn base_delay(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.base_delay_seconds.unwrap_or(1))
+  }
+
+  pub fn max_delay(&self) -> chrono::Duration {
+    chrono::Duration::seconds(self.max_delay_seconds.unwrap_or(900))
+  }
+
+  pub fn reset_after(&self) -> chrono::Duration {
+    chrono::Duration::hours(self.reset_after_hours.unwrap_or(24))
+  }
+}
+
+/// Token buckets the RPCs of every service go through, e.g. at most 10 logins per minute from an
+/// address in bursts of up to 5.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RateLimits {
+  /// Where buckets are kept: `memory` (the default), so every replica limits on its own, or
+  /// `postgres`, shared by all replicas at the cost of a query per limited call.
+  pub backend: Option<String>,
+
+  #[serde(default)]
+  pub rules: Vec<RateLimitRule>
+}
+
+impl RateLimits {
+  pub fn backend(&self) -> &str {
+    self.backend.as_deref().unwrap_or("memory")
+  }
+}
+
+/// A bucket per method, client or address; a call has to get past every rule matching its method.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RateLimitRule {
+  /// Full method name (`/heimdallr.auth.Login/Login`), every method of a service
+  /// (`/heimdallr.auth.Login/*`) or `*` for all of them.
+  pub method: String,
+
+  /// What calls are counted by: `ip`, the client address (IPv6 by /64); `client_id`, the client of
+  /// the bearer access token, verified; or `method`, every caller of each method together.
+  pub per: String,
+
+  /// Calls let through in a burst.
+  pub burst: u32,
+
+  /// Calls the bucket refills with per minute.
+  pub per_minute: u32
+}
+
 impl Settings {
+  /// Path of the one-time bootstrap token file.
+  pub fn bootstrap_token_file(&self) -> std::path::PathBuf {
+    self.bootstrap.as_ref()
+      .and_then(|bootstrap| bootstrap.token_file.clone())
+      .unwrap_or_else(|| "./bootstrap-token".to_owned())
+      .into()
+  }
+
   pub fn new<S>(config_file: S) -> Result<Self, HeimdallrError>
     where S: Into<String> {
     let mut cfg = Config::new();
@@ -38,6 +543,30 @@ impl Settings {
     cfg.merge(Environment::with_prefix("heimdallr").separator("_"))?;
 
     // Deserialize and freeze the entire configuration
-    cfg.try_into().map_err(|err| { err.into() })
+    let settings: Settings = cfg.try_into()?;
+    settings.jwt.check_claims()?;
+
+    Ok(settings)
+  }
+}
+
+/// How fresh the grants authorization decisions are made on have to be.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Authz {
+  /// Longest a decision may lag behind changes to roles, permissions & groups; 10 seconds by
+  /// default, 0 reads the grants of the subject on every check.
+  pub cache_ttl_seconds: Option<u64>,
+
+  /// Most subjects whose grants are cached at once; 10000 by default.
+  pub cache_capacity: Option<usize>
+}
+
+impl Authz {
+  pub fn cache_ttl(&self) -> std::time::Duration {
+    std::time::Duration::from_secs(self.cache_ttl_seconds.unwrap_or(10))
+  }
+
+  pub fn cache_capacity(&self) -> usize {
+    self.cache_capacity.unwrap_or(10_000)
   }
 }
@@ -0,0 +1,160 @@
+use chrono::Utc;
+use diesel::pg::PgConnection;
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::crypto;
+use crate::db::models::{MailDelivery, NewSmsCode, SmsCode, User};
+use crate::error::*;
+use crate::mail::Delivery;
+use crate::settings::Sms as SmsSettings;
+use super::{Channel, Message, SmsSender};
+
+/// `purpose` of codes confirming a phone number.
+pub const VERIFY_PHONE: &str = "verify_phone";
+
+/// `purpose` of codes used as the second factor of a login.
+pub const MFA: &str = "mfa";
+
+/// Digits of a code.
+const CODE_DIGITS: u32 = 6;
+
+/// Sends short-lived numeric codes to the phone number of a user & checks them.
+///
+/// Codes are stored hashed, expire after `sms.code_ttl` and stop working after `sms.max_attempts`
+/// guesses; sending a new code replaces the previous one.
+#[derive(Clone)]
+pub struct SmsCodes {
+  sender: Arc<dyn SmsSender>,
+  settings: SmsSettings,
+  /// How messages refer to the server: the host of the issuer.
+  name: String
+}
+
+impl SmsCodes {
+  pub fn new(settings: &SmsSettings, issuer: &str) -> Result<Self, HeimdallrError> {
+    Ok(Self::with_sender(super::from_settings(settings)?, settings, issuer))
+  }
+
+  pub fn with_sender(sender: Arc<dyn SmsSender>, settings: &SmsSettings, issuer: &str) -> Self {
+    let name = url::Url::parse(issuer).ok().and_then(|url| url.host_str().map(str::to_owned)).unwrap_or_else(|| issuer.to_owned());
+    SmsCodes { sender, settings: settings.clone(), name }
+  }
+
+  /// Sends a new code to the phone number of a user; messages are delivered in the background.
+  pub fn send(&self, conn: &PgConnection, user: &User, purpose: &str, channel: Channel) -> Result<Delivery, HeimdallrError> {
+    let phone_number = user.phone_number.as_deref().ok_or_else(|| HeimdallrError::SmsError("the user has no phone number".to_owned()))?;
+
+    if let Some(retry_after) = MailDelivery::throttle(conn, MailDelivery::PHONE, phone_number, purpose, self.settings.max_per_hour())? {
+      return Ok(Delivery::Throttled(retry_after));
+    }
+
+    let code = generate_code()?;
+
+    SmsCode::delete_expired(conn)?;
+    SmsCode::replace(conn, &NewSmsCode {
+      user_id: user.id,
+      purpose,
+      phone_number,
+      code_hash: &hash_code(user.id, purpose, phone_number, &code)?,
+      expires_at: (Utc::now() + self.settings.code_ttl()).naive_utc()
+    })?;
+
+    let message = Message { to: phone_number.to_owned(), body: self.body(&code, channel), channel };
+    let sender  = self.sender.clone();
+
+    tokio::spawn(async move {
+      if let Err(err) = sender.send(&message).await {
+        log::error!("Unable to send a code to {}: {}", super::mask(&message.to), err);
+      }
+    });
+
+    Ok(Delivery::Accepted)
+  }
+
+  /// Checks a code sent to the current phone number of a user; a right code is used up.
+  pub fn verify(&self, conn: &PgConnection, user: &User, purpose: &str, code: &str) -> Result<bool, HeimdallrError> {
+    let sent = match SmsCode::outstanding(conn, user.id, purpose)? {
+      Some(sent) if user.phone_number.as_deref() == Some(sent.phone_number.as_str()) => sent,
+      _ => return Ok(false)
+    };
+
+    // Counted before checking, so concurrent guesses cannot get past the limit.
+    if !sent.record_attempt(conn, self.settings.max_attempts())? {
+      return Ok(false);
+    }
+
+    let hash = hash_code(user.id, purpose, &sent.phone_number, code.trim())?;
+    if !crypto::constant_time_eq(sent.code_hash.as_bytes(), hash.as_bytes()) {
+      return Ok(false);
+    }
+
+    sent.redeem(conn)
+  }
+
+  fn body(&self, code: &str, channel: Channel) -> String {
+    match channel {
+      Channel::Sms   => format!("{} is your {} code. It expires in {} minutes.", code, self.name, self.settings.code_ttl().num_minutes()),
+      Channel::Voice => {
+        // Spaced out so text-to-speech reads the digits one by one.
+        let spoken = code.chars().map(String::from).collect::<Vec<_>>().join(" ");
+        format!("Your {} code is {}. Once more, your code is {}.", self.name, spoken, spoken)
+      }
+    }
+  }
+}
+
+/// Hashes a code for storage. A slow hash would not help a code this short; the attempt limit keeps
+/// it from being guessed, the hash only keeps it out of sight.
+fn hash_code(user_id: Uuid, purpose: &str, phone_number: &str, code: &str) -> Result<String, HeimdallrError> {
+  crypto::hash_token(&format!("{}:{}:{}:{}", user_id, purpose, phone_number, code))
+}
+
+/// A random code of `CODE_DIGITS` digits, leading zeros included.
+fn generate_code() -> Result<String, HeimdallrError> {
+  let mut bytes = [0u8; 8];
+  openssl::rand::rand_bytes(&mut bytes)?;
+
+  let modulus = 10u64.pow(CODE_DIGITS);
+  Ok(format!("{:0width$}", u64::from_be_bytes(bytes) % modulus, width = CODE_DIGITS as usize))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::RecordingSender;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_generate_code() -> Result<(), HeimdallrError> {
+    let code = generate_code()?;
+    assert_eq!(code.len(), CODE_DIGITS as usize);
+    assert!(code.bytes().all(|byte| byte.is_ascii_digit()));
+    Ok(())
+  }
+
+  #[test]
+  fn test_hash_code_is_bound_to_its_use() -> Result<(), HeimdallrError> {
+    let user_id = Uuid::new_v4();
+    let hash    = hash_code(user_id, MFA, "+15550100", "012345")?;
+
+    assert_eq!(hash_code(user_id, MFA, "+15550100", "012345")?, hash);
+    assert_ne!(hash_code(user_id, VERIFY_PHONE, "+15550100", "012345")?, hash);
+    assert_ne!(hash_code(user_id, MFA, "+15550199", "012345")?, hash);
+    assert_ne!(hash_code(Uuid::new_v4(), MFA, "+15550100", "012345")?, hash);
+    Ok(())
+  }
+
+  #[test]
+  fn test_body() {
+    let codes = SmsCodes::with_sender(Arc::new(RecordingSender::new()), &SmsSettings::default(), "https://auth.example.com");
+
+    assert_eq!(codes.body("012345", Channel::Sms), "012345 is your auth.example.com code. It expires in 5 minutes.");
+    assert_eq!(
+      codes.body("012345", Channel::Voice),
+      "Your auth.example.com code is 0 1 2 3 4 5. Once more, your code is 0 1 2 3 4 5."
+    );
+  }
+}
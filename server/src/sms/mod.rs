@@ -0,0 +1,93 @@
+//! One-time codes sent to phones, by SMS or voice call. An [`SmsSender`] delivers messages, which
+//! [`SmsCodes`] uses to verify phone numbers & as a second factor.
+//!
+//! `sms.transport` picks the sender: `webhook`, which POSTs every message to a gateway, or `log`.
+
+mod codes;
+mod recorder;
+mod webhook;
+
+pub use codes::{SmsCodes, MFA, VERIFY_PHONE};
+pub use recorder::RecordingSender;
+pub use webhook::WebhookSender;
+
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::settings::Sms as SmsSettings;
+
+/// How a message reaches the phone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+  Sms,
+  /// Read out by a voice call, for phones that cannot receive texts.
+  Voice
+}
+
+impl Channel {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Channel::Sms   => "sms",
+      Channel::Voice => "voice"
+    }
+  }
+}
+
+/// A message for a single phone number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+  /// E.164, e.g. `+15555550123`.
+  pub to: String,
+  pub body: String,
+  pub channel: Channel
+}
+
+#[tonic::async_trait]
+pub trait SmsSender: Send + Sync {
+  /// Hands a message over for delivery.
+  async fn send(&self, message: &Message) -> Result<(), HeimdallrError>;
+}
+
+/// The sender configured by `sms.transport`.
+pub fn from_settings(settings: &SmsSettings) -> Result<Arc<dyn SmsSender>, HeimdallrError> {
+  match settings.transport.as_deref().unwrap_or("log") {
+    "webhook" => {
+      let webhook = settings.webhook.as_ref()
+        .ok_or_else(|| HeimdallrError::ConfigError(config::ConfigError::Message("sms.webhook is required for the webhook transport".to_owned())))?;
+      Ok(Arc::new(WebhookSender::new(webhook)?))
+    },
+    "log" => Ok(Arc::new(LogSender)),
+    other => Err(HeimdallrError::ConfigError(config::ConfigError::Message(format!("sms.transport `{}` must be webhook or log", other))))
+  }
+}
+
+/// Logs every message; the codes in them are live, so this is only fit for development.
+pub struct LogSender;
+
+#[tonic::async_trait]
+impl SmsSender for LogSender {
+  async fn send(&self, message: &Message) -> Result<(), HeimdallrError> {
+    log::info!("{} to {}: {}", message.channel.as_str(), message.to, message.body);
+    Ok(())
+  }
+}
+
+/// Hides all but the last digits of a phone number, e.g. `••• 0123`.
+pub fn mask(phone_number: &str) -> String {
+  let digits: Vec<char> = phone_number.chars().filter(|c| c.is_ascii_digit()).collect();
+  let shown: String = digits[digits.len().saturating_sub(4)..].iter().collect();
+  format!("••• {}", shown)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_mask() {
+    assert_eq!(mask("+1 (555) 555-0123"), "••• 0123");
+    assert_eq!(mask("12"), "••• 12");
+  }
+}
@@ -0,0 +1,36 @@
+use std::sync::Mutex;
+
+use crate::error::*;
+use super::{Message, SmsSender};
+
+/// Keeps every message instead of sending it, for tests.
+#[derive(Default)]
+pub struct RecordingSender {
+  messages: Mutex<Vec<Message>>
+}
+
+impl RecordingSender {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The messages sent so far, oldest first.
+  pub fn messages(&self) -> Vec<Message> {
+    self.messages.lock().map(|messages| messages.clone()).unwrap_or_default()
+  }
+
+  /// The last message sent, if any.
+  pub fn last(&self) -> Option<Message> {
+    self.messages().pop()
+  }
+}
+
+#[tonic::async_trait]
+impl SmsSender for RecordingSender {
+  async fn send(&self, message: &Message) -> Result<(), HeimdallrError> {
+    self.messages.lock()
+      .map_err(|_| HeimdallrError::SmsError("the recorder was poisoned".to_owned()))?
+      .push(message.clone());
+    Ok(())
+  }
+}
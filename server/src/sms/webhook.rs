@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use crate::error::*;
+use crate::settings::SmsWebhook;
+use super::{Message, SmsSender};
+
+/// POSTs every message as JSON to a gateway, which is expected to answer with a 2xx status:
+///
+/// ```json
+/// { "to": "+15555550123", "body": "123456 is your code", "channel": "sms" }
+/// ```
+pub struct WebhookSender {
+  client: reqwest::Client,
+  url: String,
+  authorization: Option<String>
+}
+
+impl WebhookSender {
+  pub fn new(settings: &SmsWebhook) -> Result<Self, HeimdallrError> {
+    let client = reqwest::Client::builder()
+      .timeout(Duration::from_secs(settings.timeout.unwrap_or(10)))
+      .redirect(reqwest::redirect::Policy::none())
+      .build()?;
+
+    Ok(WebhookSender { client, url: settings.url.clone(), authorization: settings.authorization.clone() })
+  }
+}
+
+#[tonic::async_trait]
+impl SmsSender for WebhookSender {
+  async fn send(&self, message: &Message) -> Result<(), HeimdallrError> {
+    let body = serde_json::json!({
+      "to": message.to,
+      "body": message.body,
+      "channel": message.channel.as_str()
+    });
+
+    let mut request = self.client.post(&self.url)
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(body.to_string());
+
+    if let Some(authorization) = &self.authorization {
+      request = request.header(reqwest::header::AUTHORIZATION, authorization);
+    }
+
+    request.send().await?.error_for_status()?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::Channel;
+
+  use hyper::service::{make_service_fn, service_fn};
+  use hyper::{Body, Request, Response, Server};
+  use pretty_assertions::assert_eq;
+  use std::convert::Infallible;
+  use std::sync::{Arc, Mutex};
+
+  #[tokio::test]
+  async fn test_posts_messages_as_json() {
+    let received = Arc::new(Mutex::new(None));
+    let recorder = received.clone();
+
+    // Stands in for the gateway.
+    let make_service = make_service_fn(move |_| {
+      let recorder = recorder.clone();
+      async move {
+        Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+          let recorder = recorder.clone();
+          async move {
+            let authorization = request.headers()[hyper::header::AUTHORIZATION].to_str().unwrap().to_owned();
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            *recorder.lock().unwrap() = Some((authorization, serde_json::from_slice::<serde_json::Value>(&body).unwrap()));
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+          }
+        }))
+      }
+    });
+
+    let server  = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let address = server.local_addr();
+    tokio::spawn(server);
+
+    let sender = WebhookSender::new(&SmsWebhook {
+      url: format!("http://{}/messages", address),
+      authorization: Some("Bearer secret".to_owned()),
+      timeout: Some(5)
+    }).unwrap();
+
+    sender.send(&Message { to: "+15555550123".to_owned(), body: "123456".to_owned(), channel: Channel::Voice }).await.unwrap();
+
+    let (authorization, body) = received.lock().unwrap().take().unwrap();
+    assert_eq!(authorization, "Bearer secret");
+    assert_eq!(body, serde_json::json!({ "to": "+15555550123", "body": "123456", "channel": "voice" }));
+  }
+}
@@ -0,0 +1,352 @@
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{Map, Value};
+
+use crate::db::models::User;
+use crate::error::*;
+use crate::jwt::{JwtClaimsBuilder, SharedKeyStore, TokenError, TokenValidation};
+use crate::oidc::{self, Authentication};
+use crate::rbac::Entitlements;
+use crate::settings::Jwt as JwtSettings;
+
+/// How long a user has to enter their second factor after the password was checked.
+const MFA_TOKEN_TTL: i64 = 300;
+
+/// `typ` header of MFA tokens, so they can never pass for any other token.
+const MFA_TOKEN_TYPE: &str = "mfa+jwt";
+
+/// Claims set by the issuer on access tokens, which `jwt.roles_claim` & `jwt.permissions_claim`
+/// may not be named after.
+pub const RESERVED_CLAIMS: &[&str] = &["iss", "sub", "aud", "exp", "nbf", "iat", "jti", "client_id", "scope", "sid"];
+
+/// A signed token together with its expiration.
+#[derive(Debug, Clone)]
+pub struct IssuedToken {
+  pub token: String,
+  pub expires_at: DateTime<Utc>
+}
+
+/// What an ID token is bound to: the `nonce` of the authorization request, and the access token &
+/// authorization code issued along with it, through `at_hash` / `c_hash`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IdTokenBindings<'a> {
+  pub nonce: Option<&'a str>,
+  pub access_token: Option<&'a str>,
+  pub code: Option<&'a str>
+}
+
+/// A password login waiting for its second factor, as carried by an MFA token.
+#[derive(Debug, Clone)]
+pub struct PendingLogin {
+  pub user_id: uuid::Uuid,
+  /// How the user authenticated so far.
+  pub auth: Authentication,
+  /// Identifies the MFA token, which is spent once redeemed.
+  pub jti: uuid::Uuid,
+  pub expires_at: DateTime<Utc>
+}
+
+/// Issues & validates the tokens handed out by this server.
+#[derive(Clone)]
+pub struct TokenIssuer {
+  keys: SharedKeyStore,
+  settings: JwtSettings
+}
+
+impl TokenIssuer {
+  pub fn new(keys: SharedKeyStore, settings: JwtSettings) -> Self {
+    Self { keys, settings }
+  }
+
+  pub fn keys(&self) -> &SharedKeyStore {
+    &self.keys
+  }
+
+  pub fn settings(&self) -> &JwtSettings {
+    &self.settings
+  }
+
+  /// Issues an access token for a subject (a user id, or the client id for client credentials).
+  ///
+  /// The entitlements of a user are added under the claims named by `jwt.roles_claim` &
+  /// `jwt.permissions_claim`.
+  pub fn access_token(&self, subject: &str, client_id: &str, scopes: &[String], entitlements: Option<&Entitlements>) -> Result<IssuedToken, HeimdallrError> {
+    let expires_at = Utc::now() + self.settings.access_token_ttl();
+
+    let mut builder = JwtClaimsBuilder::new();
+    builder
+      .issuer(self.settings.issuer.as_str())
+      .subject(subject)
+      .jwt_id(uuid::Uuid::new_v4().to_string())
+      .expires(expires_at.timestamp())
+      .add_claim("client_id", serde_json::Value::String(client_id.to_owned()))
+      .add_claim("scope", serde_json::Value::String(scopes.join(" ")));
+
+    if let Some(entitlements) = entitlements {
+      if let Some(claim) = &self.settings.roles_claim {
+        builder.add_claim(claim, serde_json::json!(entitlements.roles));
+      }
+      if let Some(claim) = &self.settings.permissions_claim {
+        builder.add_claim(claim, serde_json::json!(entitlements.permissions));
+      }
+    }
+
+    Ok(IssuedToken {
+      token: self.keys.read().sign_with_type(&builder.build()?, "at+jwt")?,
+      expires_at
+    })
+  }
+
+  /// Issues an OpenID Connect ID token for a user.
+  pub fn id_token(&self, user: &User, client_id: &str, scopes: &[String], auth: &Authentication, bindings: IdTokenBindings) -> Result<String, HeimdallrError> {
+    let keys      = self.keys.read();
+    let algorithm = keys.signing_algorithm().ok_or(HeimdallrError::JwtError("No active signing key"))?;
+
+    let subject = user.id.to_string();
+
+    let mut builder = JwtClaimsBuilder::new();
+    builder
+      .issuer(self.settings.issuer.as_str())
+      .subject(subject.as_str())
+      .audience(client_id)
+      .expires((Utc::now() + self.settings.id_token_ttl()).timestamp())
+      .add_claim("auth_time", serde_json::Value::from(auth.auth_time.timestamp()))
+      .add_claim("acr", serde_json::Value::String(auth.acr().to_owned()))
+      .add_claim("amr", serde_json::json!(auth.amr))
+      .add_claim("azp", serde_json::Value::String(client_id.to_owned()));
+
+    if let Some(session_id) = auth.session_id {
+      builder.add_claim("sid", serde_json::Value::String(session_id.to_string()));
+    }
+
+    for (name, value) in oidc::standard_claims(user, scopes).into_iter().filter(|(name, _)| name != "sub") {
+      builder.add_claim(name, value);
+    }
+
+    if let Some(nonce) = bindings.nonce {
+      builder.add_claim("nonce", serde_json::Value::String(nonce.to_owned()));
+    }
+    if let Some(access_token) = bindings.access_token {
+      builder.add_claim("at_hash", serde_json::Value::String(oidc::left_half_hash(algorithm, access_token)?));
+    }
+    if let Some(code) = bindings.code {
+      builder.add_claim("c_hash", serde_json::Value::String(oidc::left_half_hash(algorithm, code)?));
+    }
+
+    keys.sign(&builder.build()?)
+  }
+
+  /// Signs a UserInfo response for the client the access token was issued to (OpenID Connect Core §5.3.2).
+  pub fn userinfo_token(&self, client_id: &str, claims: &Map<String, Value>) -> Result<String, HeimdallrError> {
+    let mut builder = JwtClaimsBuilder::new();
+    builder
+      .issuer(self.settings.issuer.as_str())
+      .audience(client_id)
+      .expires((Utc::now() + self.settings.id_token_ttl()).timestamp());
+
+    for (name, value) in claims {
+      match (name.as_str(), value) {
+        ("sub", Value::String(subject)) => builder.subject(subject.as_str()),
+        _                               => builder.add_claim(name.as_str(), value.clone())
+      };
+    }
+
+    self.keys.read().sign(&builder.build()?)
+  }
+
+  /// Issues a logout token telling a client that a session ended (OpenID Connect Back-Channel Logout §2.4).
+  pub fn logout_token(&self, client_id: &str, subject: &str, session_id: &str) -> Result<String, HeimdallrError> {
+    let claims = JwtClaimsBuilder::new()
+      .issuer(self.settings.issuer.as_str())
+      .subject(subject)
+      .audience(client_id)
+      .jwt_id(uuid::Uuid::new_v4().to_string())
+      .expires((Utc::now() + self.settings.id_token_ttl()).timestamp())
+      .add_claim("sid", serde_json::Value::String(session_id.to_owned()))
+      .add_claim("events", serde_json::json!({ (oidc::BACKCHANNEL_LOGOUT_EVENT): {} }))
+      .build()?;
+
+    self.keys.read().sign_with_type(&claims, "logout+jwt")
+  }
+
+  /// Issues the token continuing a login that still needs a second factor; it remembers the
+  /// password check so the next step only has to present the code.
+  pub fn mfa_token(&self, user: &User, client_id: &str, auth: &Authentication) -> Result<String, HeimdallrError> {
+    let claims = JwtClaimsBuilder::new()
+      .issuer(self.settings.issuer.as_str())
+      .subject(user.id.to_string())
+      .audience(client_id)
+      .jwt_id(uuid::Uuid::new_v4().to_string())
+      .expires((Utc::now() + Duration::seconds(MFA_TOKEN_TTL)).timestamp())
+      .add_claim("auth_time", serde_json::Value::from(auth.auth_time.timestamp()))
+      .add_claim("amr", serde_json::json!(auth.amr))
+      .build()?;
+
+    self.keys.read().sign_with_type(&claims, MFA_TOKEN_TYPE)
+  }
+
+  /// Validates an MFA token issued to a client, returning the login it continues.
+  ///
+  /// Only the signature & claims are checked; whether the token was spent is up to the caller.
+  pub fn validate_mfa_token(&self, token: &str, client_id: &str) -> Result<PendingLogin, TokenError> {
+    if jsonwebtoken::decode_header(token)?.typ.as_deref() != Some(MFA_TOKEN_TYPE) {
+      return Err(TokenError::Malformed("not an MFA token".to_owned()));
+    }
+
+    let validation = TokenValidation { audience: Some(client_id.to_owned()), ..self.settings.validation() };
+    let claims     = self.keys.read().verify(token, &validation)?;
+    let uuid_claim = |name: &str| {
+      claims[name].as_str().and_then(|value| uuid::Uuid::parse_str(value).ok()).ok_or_else(|| TokenError::MissingClaim(name.to_owned()))
+    };
+    let timestamp_claim = |name: &str| {
+      claims[name].as_i64()
+        .map(|timestamp| DateTime::<Utc>::from_utc(chrono::NaiveDateTime::from_timestamp(timestamp, 0), Utc))
+        .ok_or_else(|| TokenError::MissingClaim(name.to_owned()))
+    };
+
+    let amr = claims["amr"].as_array()
+      .map(|methods| methods.iter().filter_map(|method| method.as_str().map(str::to_owned)).collect())
+      .unwrap_or_default();
+
+    Ok(PendingLogin {
+      user_id: uuid_claim("sub")?,
+      auth: Authentication {
+        auth_time: timestamp_claim("auth_time")?,
+        amr,
+        session_id: None
+      },
+      jti: uuid_claim("jti")?,
+      expires_at: timestamp_claim("exp")?
+    })
+  }
+
+  /// Validates an ID token this server issued that is passed back as a hint; expired ones are fine.
+  pub fn validate_id_token_hint(&self, token: &str) -> Result<serde_json::Value, TokenError> {
+    let validation = TokenValidation { allow_expired: true, ..self.settings.validation() };
+    self.keys.read().verify(token, &validation)
+  }
+
+  /// Validates a token issued by this server, returning its claims.
+  pub fn validate(&self, token: &str) -> Result<serde_json::Value, TokenError> {
+    self.keys.read().verify(token, &self.settings.validation())
+  }
+}
+
+/// Splits the space delimited `scope` claim of a validated token.
+pub fn scopes_of(claims: &serde_json::Value) -> Vec<&str> {
+  claims["scope"].as_str().map(|scope| scope.split(' ').filter(|s| !s.is_empty()).collect()).unwrap_or_default()
+}
+
+/// An issuer signing with a fresh key pair, without a database.
+#[cfg(test)]
+impl TokenIssuer {
+  pub(crate) fn ephemeral(settings: JwtSettings) -> Self {
+    let pair = crate::jwt::KeyPair::generate(crate::jwt::Algorithm::ES256).expect("an ES256 key pair is generated");
+    TokenIssuer::new(SharedKeyStore::new(crate::jwt::KeyStore::for_pair(&pair)), settings)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::jwt::decode_unverified;
+  use pretty_assertions::assert_eq;
+
+  fn issuer() -> TokenIssuer {
+    TokenIssuer::ephemeral(JwtSettings::for_issuer("https://heimdallr.test"))
+  }
+
+  #[test]
+  fn test_access_token_round_trip() -> Result<(), HeimdallrError> {
+    let issuer = issuer();
+    let issued = issuer.access_token("alice", "app", &["openid".to_owned(), "profile".to_owned()], None)?;
+
+    let (header, _) = decode_unverified(&issued.token).unwrap();
+    assert_eq!(header["typ"], "at+jwt");
+
+    let claims = issuer.validate(&issued.token).unwrap();
+    assert_eq!(claims["iss"], "https://heimdallr.test");
+    assert_eq!(claims["sub"], "alice");
+    assert_eq!(claims["client_id"], "app");
+    assert_eq!(claims["exp"].as_i64(), Some(issued.expires_at.timestamp()));
+    assert_eq!(scopes_of(&claims), vec!["openid", "profile"]);
+    Ok(())
+  }
+
+  #[test]
+  fn test_access_token_entitlements() -> Result<(), HeimdallrError> {
+    let issuer = TokenIssuer::ephemeral(JwtSettings {
+      roles_claim: Some("roles".to_owned()),
+      permissions_claim: Some("permissions".to_owned()),
+      ..JwtSettings::for_issuer("https://heimdallr.test")
+    });
+    let entitlements = Entitlements { roles: vec!["editor".to_owned()], permissions: vec!["posts:read".to_owned(), "posts:write".to_owned()] };
+
+    let issued = issuer.access_token("alice", "app", &[], Some(&entitlements))?;
+    let claims = issuer.validate(&issued.token).unwrap();
+    assert_eq!(claims["sub"], "alice");
+    assert_eq!(claims["roles"], serde_json::json!(["editor"]));
+    assert_eq!(claims["permissions"], serde_json::json!(["posts:read", "posts:write"]));
+
+    let issued = issuer.access_token("app", "app", &[], None)?;
+    let claims = issuer.validate(&issued.token).unwrap();
+    assert_eq!(claims.get("roles"), None);
+    assert_eq!(claims.get("permissions"), None);
+    Ok(())
+  }
+
+  #[test]
+  fn test_reserved_claims_are_refused() {
+    let settings = |roles: &str, permissions: &str| JwtSettings {
+      roles_claim: Some(roles.to_owned()),
+      permissions_claim: Some(permissions.to_owned()),
+      ..JwtSettings::for_issuer("https://heimdallr.test")
+    };
+
+    assert!(settings("roles", "permissions").check_claims().is_ok());
+    assert!(settings("sub", "permissions").check_claims().is_err());
+    assert!(settings("roles", "scope").check_claims().is_err());
+    assert!(settings("", "permissions").check_claims().is_err());
+    assert!(settings("roles", "roles").check_claims().is_err());
+  }
+
+  #[test]
+  fn test_mfa_token_round_trip() -> Result<(), HeimdallrError> {
+    let issuer = issuer();
+    let now    = Utc::now().naive_utc();
+    let user   = User {
+      id: uuid::Uuid::new_v4(),
+      username: "takara".to_owned(),
+      email: None,
+      password_hash: String::new(),
+      disabled: false,
+      created_at: now,
+      updated_at: now,
+      name: None,
+      given_name: None,
+      family_name: None,
+      picture: None,
+      locale: None,
+      email_verified: false,
+      phone_number: None,
+      phone_number_verified: false,
+      password_changed_at: now
+    };
+
+    let token   = issuer.mfa_token(&user, "app", &Authentication::new(&["pwd"]))?;
+    let pending = issuer.validate_mfa_token(&token, "app").unwrap();
+    assert_eq!(pending.user_id, user.id);
+    assert_eq!(pending.auth.amr, vec!["pwd"]);
+    assert!(pending.expires_at > Utc::now());
+
+    assert!(issuer.validate_mfa_token(&token, "other").is_err());
+    assert!(issuer.validate_mfa_token(&issuer.access_token("takara", "app", &[], None)?.token, "app").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_scopes_of() {
+    assert_eq!(scopes_of(&serde_json::json!({ "scope": " admin  openid " })), vec!["admin", "openid"]);
+    assert!(scopes_of(&serde_json::json!({ "scope": 42 })).is_empty());
+    assert!(scopes_of(&serde_json::json!({})).is_empty());
+  }
+}
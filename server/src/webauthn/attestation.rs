@@ -0,0 +1,72 @@
+//! Attestation statement verification (Web Authentication §8) for the `none` & `packed` formats.
+//!
+//! Attestation certificates are checked for well-formedness but not chained to a trust anchor;
+//! which authenticator models to accept is a policy question this server leaves open.
+
+use openssl::nid::Nid;
+use openssl::x509::X509;
+use serde_cbor::Value;
+
+use super::cose::{self, CoseKey};
+use super::{cbor_get, WebauthnError};
+
+/// Verifies an attestation statement over `authenticator data || SHA-256(client data)`.
+pub fn verify(format: &str, statement: &Value, auth_data: &[u8], client_data_hash: &[u8], credential_key: &CoseKey) -> Result<(), WebauthnError> {
+  match format {
+    "none"   => verify_none(statement),
+    "packed" => verify_packed(statement, &[auth_data, client_data_hash].concat(), credential_key),
+    other    => Err(WebauthnError::UnsupportedAttestation(other.to_owned()))
+  }
+}
+
+fn verify_none(statement: &Value) -> Result<(), WebauthnError> {
+  match statement {
+    Value::Map(map) if map.is_empty() => Ok(()),
+    _ => Err(WebauthnError::InvalidAttestation("`none` attestation statements must be empty"))
+  }
+}
+
+/// §8.2: either a certificate based attestation (`x5c`) or self attestation.
+fn verify_packed(statement: &Value, signed: &[u8], credential_key: &CoseKey) -> Result<(), WebauthnError> {
+  let alg = match cbor_get(statement, Value::Text("alg".to_owned())) {
+    Some(Value::Integer(alg)) => *alg as i64,
+    _ => return Err(WebauthnError::InvalidAttestation("packed attestation has no `alg`"))
+  };
+  let signature = match cbor_get(statement, Value::Text("sig".to_owned())) {
+    Some(Value::Bytes(signature)) => signature,
+    _ => return Err(WebauthnError::InvalidAttestation("packed attestation has no `sig`"))
+  };
+
+  match cbor_get(statement, Value::Text("x5c".to_owned())) {
+    Some(Value::Array(chain)) => {
+      let leaf = match chain.first() {
+        Some(Value::Bytes(der)) => X509::from_der(der)?,
+        _ => return Err(WebauthnError::InvalidAttestation("`x5c` has no attestation certificate"))
+      };
+
+      // §8.2.1: the subject OU of attestation certificates is fixed.
+      let unit = leaf.subject_name().entries_by_nid(Nid::ORGANIZATIONALUNITNAME).next().map(|entry| entry.data().as_slice());
+      if unit != Some(&b"Authenticator Attestation"[..]) {
+        return Err(WebauthnError::InvalidAttestation("attestation certificate has the wrong subject OU"));
+      }
+
+      let certificate_key = leaf.public_key()?;
+      if !cose::verify_signature(alg, &certificate_key, signed, signature)? {
+        return Err(WebauthnError::InvalidAttestation("attestation signature does not match the certificate"));
+      }
+
+      Ok(())
+    },
+    Some(_) => Err(WebauthnError::InvalidAttestation("`x5c` must be an array of certificates")),
+    None => {
+      if alg != credential_key.alg {
+        return Err(WebauthnError::InvalidAttestation("self attestation must use the algorithm of the credential"));
+      }
+      if !credential_key.verify(signed, signature)? {
+        return Err(WebauthnError::InvalidAttestation("self attestation signature does not match the credential"));
+      }
+
+      Ok(())
+    }
+  }
+}
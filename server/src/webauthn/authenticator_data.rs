@@ -0,0 +1,83 @@
+//! Authenticator data (Web Authentication §6.1).
+
+use serde::Deserialize;
+use serde_cbor::Value;
+
+use super::WebauthnError;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// The parts of authenticator data a relying party checks.
+#[derive(Debug)]
+pub struct AuthenticatorData {
+  pub rp_id_hash: Vec<u8>,
+  pub flags: u8,
+  pub sign_count: u32,
+  /// Only present when registering a credential.
+  pub attested_credential: Option<AttestedCredential>
+}
+
+#[derive(Debug)]
+pub struct AttestedCredential {
+  pub aaguid: Vec<u8>,
+  pub credential_id: Vec<u8>,
+  /// The COSE key, exactly as encoded by the authenticator.
+  pub public_key: Vec<u8>
+}
+
+impl AuthenticatorData {
+  pub fn parse(data: &[u8]) -> Result<Self, WebauthnError> {
+    let malformed = || WebauthnError::Malformed("authenticator data is truncated".to_owned());
+
+    if data.len() < 37 {
+      return Err(malformed());
+    }
+
+    let flags      = data[32];
+    let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+    let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+      let rest = &data[37..];
+      if rest.len() < 18 {
+        return Err(malformed());
+      }
+
+      let id_length = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+      let key_start = 18 + id_length;
+      if rest.len() <= key_start {
+        return Err(malformed());
+      }
+
+      // The COSE key is followed by extensions, so its length is only known after decoding it.
+      let mut deserializer = serde_cbor::Deserializer::from_slice(&rest[key_start..]);
+      Value::deserialize(&mut deserializer).map_err(|_| WebauthnError::Malformed("credential public key is not valid CBOR".to_owned()))?;
+      let key_end = key_start + deserializer.byte_offset();
+
+      Some(AttestedCredential {
+        aaguid: rest[..16].to_vec(),
+        credential_id: rest[18..key_start].to_vec(),
+        public_key: rest[key_start..key_end].to_vec()
+      })
+    }
+    else {
+      None
+    };
+
+    Ok(AuthenticatorData {
+      rp_id_hash: data[..32].to_vec(),
+      flags,
+      sign_count,
+      attested_credential
+    })
+  }
+
+  pub fn user_present(&self) -> bool {
+    self.flags & FLAG_USER_PRESENT != 0
+  }
+
+  pub fn user_verified(&self) -> bool {
+    self.flags & FLAG_USER_VERIFIED != 0
+  }
+}
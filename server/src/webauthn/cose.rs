@@ -0,0 +1,97 @@
+//! COSE public keys (RFC 8152 §13) as found in attested credential data.
+
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, PKeyRef, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+use serde_cbor::Value;
+
+use super::{cbor_get, WebauthnError};
+
+/// ECDSA with P-256 & SHA-256.
+pub const ES256: i64 = -7;
+/// Ed25519.
+pub const EDDSA: i64 = -8;
+/// RSASSA-PKCS1-v1_5 with SHA-256.
+pub const RS256: i64 = -257;
+
+/// Algorithms credentials may use, in order of preference.
+pub const SUPPORTED_ALGORITHMS: [i64; 3] = [ES256, EDDSA, RS256];
+
+const KTY_OKP: i128 = 1;
+const KTY_EC2: i128 = 2;
+const KTY_RSA: i128 = 3;
+
+const CRV_P256: i128 = 1;
+const CRV_ED25519: i128 = 6;
+
+/// DER `SubjectPublicKeyInfo` prefix of a raw Ed25519 key (RFC 8410 §4).
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// A credential public key together with the algorithm it signs with.
+pub struct CoseKey {
+  pub alg: i64,
+  key: PKey<Public>
+}
+
+impl CoseKey {
+  pub fn parse(bytes: &[u8]) -> Result<Self, WebauthnError> {
+    let map: Value = serde_cbor::from_slice(bytes).map_err(|_| WebauthnError::Malformed("credential public key is not valid CBOR".to_owned()))?;
+
+    let int   = |label: i128| match cbor_get(&map, Value::Integer(label)) { Some(Value::Integer(value)) => Some(*value), _ => None };
+    let bytes = |label: i128| match cbor_get(&map, Value::Integer(label)) { Some(Value::Bytes(value)) => Some(value.as_slice()), _ => None };
+    let missing = |what: &str| WebauthnError::Malformed(format!("credential public key has no {}", what));
+
+    let alg = int(3).ok_or_else(|| missing("alg"))?;
+    let kty = int(1).ok_or_else(|| missing("kty"))?;
+
+    let key = match (alg as i64, kty) {
+      (ES256, KTY_EC2) => {
+        if int(-1) != Some(CRV_P256) {
+          return Err(WebauthnError::Malformed("ES256 keys must use the P-256 curve".to_owned()));
+        }
+        let x = BigNum::from_slice(bytes(-2).ok_or_else(|| missing("x coordinate"))?)?;
+        let y = BigNum::from_slice(bytes(-3).ok_or_else(|| missing("y coordinate"))?)?;
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        PKey::from_ec_key(EcKey::from_public_key_affine_coordinates(&group, &x, &y)?)?
+      },
+      (EDDSA, KTY_OKP) => {
+        if int(-1) != Some(CRV_ED25519) {
+          return Err(WebauthnError::Malformed("EdDSA keys must use the Ed25519 curve".to_owned()));
+        }
+        let x = bytes(-2).ok_or_else(|| missing("public key"))?;
+        PKey::public_key_from_der(&[&ED25519_SPKI_PREFIX[..], x].concat())?
+      },
+      (RS256, KTY_RSA) => {
+        let n = BigNum::from_slice(bytes(-1).ok_or_else(|| missing("modulus"))?)?;
+        let e = BigNum::from_slice(bytes(-2).ok_or_else(|| missing("exponent"))?)?;
+        PKey::from_rsa(Rsa::from_public_components(n, e)?)?
+      },
+      _ => return Err(WebauthnError::UnsupportedAlgorithm(alg))
+    };
+
+    Ok(CoseKey { alg: alg as i64, key })
+  }
+
+  pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<bool, WebauthnError> {
+    verify_signature(self.alg, &self.key, data, signature)
+  }
+}
+
+/// Checks a signature made with a COSE algorithm, e.g. by an attestation certificate's key.
+pub fn verify_signature(alg: i64, key: &PKeyRef<Public>, data: &[u8], signature: &[u8]) -> Result<bool, WebauthnError> {
+  match alg {
+    ES256 | RS256 => {
+      let mut verifier = Verifier::new(MessageDigest::sha256(), key)?;
+      verifier.update(data)?;
+      // Malformed signatures surface as errors rather than `false`.
+      Ok(verifier.verify(signature).unwrap_or(false))
+    },
+    EDDSA => Ok(Verifier::new_without_digest(key)?.verify_oneshot(signature, data).unwrap_or(false)),
+    _ => Err(WebauthnError::UnsupportedAlgorithm(alg as i128))
+  }
+}
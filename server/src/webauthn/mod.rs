@@ -0,0 +1,519 @@
+//! WebAuthn relying party: the registration & authentication ceremonies of passkeys and security
+//! keys (Web Authentication Level 2 §7).
+//!
+//! Binary values are exchanged base64url encoded, matching the JSON serialization of
+//! `PublicKeyCredential` that browsers & client libraries produce.
+
+mod attestation;
+mod authenticator_data;
+mod cose;
+#[cfg(test)]
+pub(crate) mod software;
+
+use chrono::Duration;
+use openssl::hash::{hash, MessageDigest};
+use serde::Deserialize;
+use serde_cbor::Value;
+use serde_json::json;
+use std::fmt;
+
+use crate::crypto;
+use crate::db::models::{User, WebauthnCredential};
+use crate::error::*;
+use crate::settings::Webauthn as WebauthnSettings;
+use authenticator_data::AuthenticatorData;
+use cose::CoseKey;
+
+/// `ceremony` of challenges for registering a credential.
+pub const REGISTRATION: &str = "registration";
+
+/// `ceremony` of challenges for logging in with a credential.
+pub const AUTHENTICATION: &str = "authentication";
+
+/// Why a ceremony was rejected; safe to show to the user.
+#[derive(Debug)]
+pub enum WebauthnError {
+  Malformed(String),
+  WrongCeremony,
+  ChallengeMismatch,
+  OriginNotAllowed(String),
+  RpIdMismatch,
+  UserNotPresent,
+  UserNotVerified,
+  UnsupportedAlgorithm(i128),
+  UnsupportedAttestation(String),
+  InvalidAttestation(&'static str),
+  InvalidSignature,
+  /// The signature counter went backwards, which indicates a cloned authenticator.
+  SignCountRegressed,
+  Crypto(openssl::error::ErrorStack)
+}
+
+impl std::error::Error for WebauthnError {}
+
+impl fmt::Display for WebauthnError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    use WebauthnError::*;
+
+    match self {
+      Malformed(reason)              => write!(f, "credential is malformed ({})", reason),
+      WrongCeremony                  => write!(f, "client data belongs to a different ceremony"),
+      ChallengeMismatch              => write!(f, "client data does not echo the challenge"),
+      OriginNotAllowed(origin)       => write!(f, "origin `{}` is not allowed", origin),
+      RpIdMismatch                   => write!(f, "credential is scoped to a different relying party"),
+      UserNotPresent                 => write!(f, "the authenticator did not confirm user presence"),
+      UserNotVerified                => write!(f, "the authenticator did not verify the user"),
+      UnsupportedAlgorithm(alg)      => write!(f, "COSE algorithm {} is not supported", alg),
+      UnsupportedAttestation(format) => write!(f, "attestation format `{}` is not supported", format),
+      InvalidAttestation(reason)     => write!(f, "attestation is invalid ({})", reason),
+      InvalidSignature               => write!(f, "signature does not match"),
+      SignCountRegressed             => write!(f, "signature counter went backwards; the authenticator may have been cloned"),
+      Crypto(_)                      => write!(f, "credential could not be verified")
+    }
+  }
+}
+
+impl From<openssl::error::ErrorStack> for WebauthnError {
+  fn from(err: openssl::error::ErrorStack) -> Self {
+    WebauthnError::Crypto(err)
+  }
+}
+
+/// Whether the authenticator has to verify the user (PIN, biometrics), not just their presence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserVerification {
+  Required,
+  Preferred,
+  Discouraged
+}
+
+impl UserVerification {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      UserVerification::Required    => "required",
+      UserVerification::Preferred   => "preferred",
+      UserVerification::Discouraged => "discouraged"
+    }
+  }
+
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "required"    => Some(UserVerification::Required),
+      "preferred"   => Some(UserVerification::Preferred),
+      "discouraged" => Some(UserVerification::Discouraged),
+      _             => None
+    }
+  }
+}
+
+/// Response of `navigator.credentials.create()`.
+#[derive(Debug, Deserialize)]
+pub struct RegistrationResponse {
+  pub id: String,
+  pub response: AttestationResponse
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttestationResponse {
+  #[serde(rename = "clientDataJSON")]
+  pub client_data_json: String,
+  #[serde(rename = "attestationObject")]
+  pub attestation_object: String
+}
+
+/// Response of `navigator.credentials.get()`.
+#[derive(Debug, Deserialize)]
+pub struct AssertionResponse {
+  pub id: String,
+  pub response: AuthenticatorAssertion
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthenticatorAssertion {
+  #[serde(rename = "clientDataJSON")]
+  pub client_data_json: String,
+  #[serde(rename = "authenticatorData")]
+  pub authenticator_data: String,
+  pub signature: String,
+  #[serde(rename = "userHandle", default)]
+  pub user_handle: Option<String>
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientData {
+  #[serde(rename = "type")]
+  ceremony: String,
+  challenge: String,
+  origin: String,
+  #[serde(rename = "crossOrigin", default)]
+  cross_origin: bool
+}
+
+/// A verified new credential, ready to be stored.
+#[derive(Debug)]
+pub struct Registration {
+  /// Base64url encoded.
+  pub credential_id: String,
+  pub public_key: Vec<u8>,
+  pub sign_count: u32,
+  pub attestation_format: String,
+  pub user_verified: bool
+}
+
+/// A verified assertion.
+#[derive(Debug)]
+pub struct Assertion {
+  pub sign_count: u32,
+  pub user_verified: bool
+}
+
+/// This server in its role as a WebAuthn relying party.
+#[derive(Debug, Clone)]
+pub struct RelyingParty {
+  id: String,
+  name: String,
+  origins: Vec<String>,
+  user_verification: UserVerification,
+  challenge_ttl: Duration
+}
+
+impl RelyingParty {
+  /// The relying party described by the settings, defaulting to the host & origin of the issuer.
+  pub fn new(settings: &WebauthnSettings, issuer: &str) -> Result<Self, HeimdallrError> {
+    let issuer_url = url::Url::parse(issuer).map_err(|_| config_error(format!("jwt.issuer `{}` is not a URL", issuer)))?;
+
+    let id = match (&settings.rp_id, issuer_url.host_str()) {
+      (Some(id), _)      => id.clone(),
+      (None, Some(host)) => host.to_owned(),
+      (None, None)       => return Err(config_error("webauthn.rp_id is required when the issuer has no host".to_owned()))
+    };
+
+    let user_verification = match &settings.user_verification {
+      Some(value) => UserVerification::parse(value)
+        .ok_or_else(|| config_error(format!("webauthn.user_verification `{}` must be required, preferred or discouraged", value)))?,
+      None => UserVerification::Preferred
+    };
+
+    Ok(RelyingParty {
+      id,
+      name: settings.rp_name.clone().unwrap_or_else(|| "Heimdallr".to_owned()),
+      origins: settings.origins.clone().unwrap_or_else(|| vec![issuer_url.origin().ascii_serialization()]),
+      user_verification,
+      challenge_ttl: settings.challenge_ttl()
+    })
+  }
+
+  pub fn id(&self) -> &str {
+    &self.id
+  }
+
+  pub fn challenge_ttl(&self) -> Duration {
+    self.challenge_ttl
+  }
+
+  /// The configured user verification requirement for registrations & second factor logins.
+  pub fn user_verification(&self) -> UserVerification {
+    self.user_verification
+  }
+
+  /// Options for `navigator.credentials.create()`; existing credentials are excluded so the same
+  /// authenticator is not registered twice.
+  pub fn creation_options(&self, challenge: &str, user: &User, existing: &[WebauthnCredential]) -> serde_json::Value {
+    json!({
+      "rp": { "id": self.id, "name": self.name },
+      "user": {
+        "id": encode(user.id.as_bytes()),
+        "name": user.username,
+        "displayName": user.name.as_deref().unwrap_or(&user.username)
+      },
+      "challenge": challenge,
+      "pubKeyCredParams": cose::SUPPORTED_ALGORITHMS.iter().map(|alg| json!({ "type": "public-key", "alg": alg })).collect::<Vec<_>>(),
+      "timeout": self.challenge_ttl.num_milliseconds(),
+      "excludeCredentials": descriptors(existing),
+      "authenticatorSelection": {
+        "residentKey": "preferred",
+        "userVerification": self.user_verification.as_str()
+      },
+      "attestation": "none"
+    })
+  }
+
+  /// Options for `navigator.credentials.get()`; without allowed credentials the authenticator
+  /// offers its discoverable credentials (passkeys).
+  pub fn request_options(&self, challenge: &str, allowed: &[WebauthnCredential], user_verification: UserVerification) -> serde_json::Value {
+    json!({
+      "challenge": challenge,
+      "timeout": self.challenge_ttl.num_milliseconds(),
+      "rpId": self.id,
+      "allowCredentials": descriptors(allowed),
+      "userVerification": user_verification.as_str()
+    })
+  }
+
+  /// Verifies a new credential (§7.1).
+  pub fn verify_registration(&self, challenge: &str, user_verification: UserVerification, response: &RegistrationResponse) -> Result<Registration, WebauthnError> {
+    let client_data_json = decode("clientDataJSON", &response.response.client_data_json)?;
+    self.check_client_data(&client_data_json, "webauthn.create", challenge)?;
+
+    let attestation_object = decode("attestationObject", &response.response.attestation_object)?;
+    let attestation_object: Value = serde_cbor::from_slice(&attestation_object)
+      .map_err(|_| WebauthnError::Malformed("attestationObject is not valid CBOR".to_owned()))?;
+
+    let format = match cbor_get(&attestation_object, Value::Text("fmt".to_owned())) {
+      Some(Value::Text(format)) => format.clone(),
+      _ => return Err(WebauthnError::Malformed("attestationObject has no fmt".to_owned()))
+    };
+    let raw_auth_data = match cbor_get(&attestation_object, Value::Text("authData".to_owned())) {
+      Some(Value::Bytes(auth_data)) => auth_data,
+      _ => return Err(WebauthnError::Malformed("attestationObject has no authData".to_owned()))
+    };
+    let statement = cbor_get(&attestation_object, Value::Text("attStmt".to_owned()))
+      .ok_or_else(|| WebauthnError::Malformed("attestationObject has no attStmt".to_owned()))?;
+
+    let auth_data = AuthenticatorData::parse(raw_auth_data)?;
+    self.check_authenticator_data(&auth_data, user_verification)?;
+
+    let credential = auth_data.attested_credential.as_ref()
+      .ok_or_else(|| WebauthnError::Malformed("authenticator data has no attested credential".to_owned()))?;
+    if decode("id", &response.id)? != credential.credential_id {
+      return Err(WebauthnError::Malformed("id does not match the attested credential".to_owned()));
+    }
+
+    let key = CoseKey::parse(&credential.public_key)?;
+    attestation::verify(&format, statement, raw_auth_data, &sha256(&client_data_json)?, &key)?;
+
+    Ok(Registration {
+      credential_id: encode(&credential.credential_id),
+      public_key: credential.public_key.clone(),
+      sign_count: auth_data.sign_count,
+      attestation_format: format,
+      user_verified: auth_data.user_verified()
+    })
+  }
+
+  /// Verifies an assertion made with a stored credential (§7.2).
+  pub fn verify_assertion(&self, challenge: &str, user_verification: UserVerification, credential: &WebauthnCredential, response: &AssertionResponse) -> Result<Assertion, WebauthnError> {
+    if decode("id", &response.id)? != decode("credential id", &credential.credential_id)? {
+      return Err(WebauthnError::Malformed("id does not match the credential".to_owned()));
+    }
+
+    // Discoverable credentials return the user id they were registered with.
+    if let Some(user_handle) = response.response.user_handle.as_deref().filter(|handle| !handle.is_empty()) {
+      if decode("userHandle", user_handle)? != credential.user_id.as_bytes() {
+        return Err(WebauthnError::Malformed("userHandle does not match the credential".to_owned()));
+      }
+    }
+
+    let client_data_json = decode("clientDataJSON", &response.response.client_data_json)?;
+    self.check_client_data(&client_data_json, "webauthn.get", challenge)?;
+
+    let raw_auth_data = decode("authenticatorData", &response.response.authenticator_data)?;
+    let auth_data = AuthenticatorData::parse(&raw_auth_data)?;
+    self.check_authenticator_data(&auth_data, user_verification)?;
+
+    let signed = [raw_auth_data.as_slice(), &sha256(&client_data_json)?].concat();
+    if !CoseKey::parse(&credential.public_key)?.verify(&signed, &decode("signature", &response.response.signature)?)? {
+      return Err(WebauthnError::InvalidSignature);
+    }
+
+    // Counters of 0 on both sides mean the authenticator does not implement one.
+    let stored = credential.sign_count as u32;
+    if (auth_data.sign_count != 0 || stored != 0) && auth_data.sign_count <= stored {
+      return Err(WebauthnError::SignCountRegressed);
+    }
+
+    Ok(Assertion {
+      sign_count: auth_data.sign_count,
+      user_verified: auth_data.user_verified()
+    })
+  }
+
+  fn check_client_data(&self, client_data_json: &[u8], ceremony: &str, challenge: &str) -> Result<(), WebauthnError> {
+    let client_data: ClientData = serde_json::from_slice(client_data_json)
+      .map_err(|_| WebauthnError::Malformed("clientDataJSON is not valid".to_owned()))?;
+
+    if client_data.ceremony != ceremony {
+      return Err(WebauthnError::WrongCeremony);
+    }
+    if !crypto::constant_time_eq(client_data.challenge.as_bytes(), challenge.as_bytes()) {
+      return Err(WebauthnError::ChallengeMismatch);
+    }
+    if client_data.cross_origin || !self.origins.contains(&client_data.origin) {
+      return Err(WebauthnError::OriginNotAllowed(client_data.origin));
+    }
+
+    Ok(())
+  }
+
+  fn check_authenticator_data(&self, auth_data: &AuthenticatorData, user_verification: UserVerification) -> Result<(), WebauthnError> {
+    if auth_data.rp_id_hash != sha256(self.id.as_bytes())? {
+      return Err(WebauthnError::RpIdMismatch);
+    }
+    if !auth_data.user_present() {
+      return Err(WebauthnError::UserNotPresent);
+    }
+    if user_verification == UserVerification::Required && !auth_data.user_verified() {
+      return Err(WebauthnError::UserNotVerified);
+    }
+
+    Ok(())
+  }
+}
+
+fn config_error(message: String) -> HeimdallrError {
+  HeimdallrError::ConfigError(config::ConfigError::Message(message))
+}
+
+/// A new random challenge, base64url encoded.
+pub fn generate_challenge() -> Result<String, HeimdallrError> {
+  crypto::random_token(32)
+}
+
+/// Parses a `PublicKeyCredential` sent by a client as JSON.
+pub fn parse_response<'a, T: Deserialize<'a>>(json: &'a str) -> Result<T, WebauthnError> {
+  serde_json::from_str(json).map_err(|err| WebauthnError::Malformed(err.to_string()))
+}
+
+fn descriptors(credentials: &[WebauthnCredential]) -> Vec<serde_json::Value> {
+  credentials.iter().map(|credential| json!({ "type": "public-key", "id": credential.credential_id })).collect()
+}
+
+fn sha256(data: &[u8]) -> Result<Vec<u8>, WebauthnError> {
+  Ok(hash(MessageDigest::sha256(), data)?.to_vec())
+}
+
+fn encode(data: &[u8]) -> String {
+  base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+/// Decodes base64url, with or without padding.
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, WebauthnError> {
+  base64::decode_config(value.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+    .map_err(|_| WebauthnError::Malformed(format!("{} is not base64url", field)))
+}
+
+/// Looks up an entry of a CBOR map.
+fn cbor_get(map: &Value, key: Value) -> Option<&Value> {
+  match map {
+    Value::Map(map) => map.get(&key),
+    _               => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::software::{AttestationMode, SoftwareAuthenticator};
+
+  use chrono::Utc;
+  use pretty_assertions::assert_eq;
+
+  const ORIGIN: &str = "https://auth.example.com";
+
+  fn relying_party(user_verification: &str) -> RelyingParty {
+    let settings = WebauthnSettings { user_verification: Some(user_verification.to_owned()), ..Default::default() };
+    RelyingParty::new(&settings, ORIGIN).unwrap()
+  }
+
+  fn stored(registration: &Registration) -> WebauthnCredential {
+    WebauthnCredential {
+      id: uuid::Uuid::new_v4(),
+      user_id: uuid::Uuid::new_v4(),
+      credential_id: registration.credential_id.clone(),
+      public_key: registration.public_key.clone(),
+      sign_count: registration.sign_count as i64,
+      attestation_format: registration.attestation_format.clone(),
+      name: "Test key".to_owned(),
+      last_used_at: None,
+      created_at: Utc::now().naive_utc()
+    }
+  }
+
+  #[test]
+  fn test_relying_party_defaults_to_issuer() {
+    let rp = RelyingParty::new(&WebauthnSettings::default(), "https://auth.example.com:8443/realm").unwrap();
+
+    assert_eq!(rp.id(), "auth.example.com");
+    assert_eq!(rp.origins, vec!["https://auth.example.com:8443".to_owned()]);
+    assert_eq!(rp.user_verification(), UserVerification::Preferred);
+  }
+
+  #[test]
+  fn test_register_and_assert() {
+    let rp = relying_party("required");
+
+    for mode in &[AttestationMode::None, AttestationMode::SelfAttestation, AttestationMode::Certificate] {
+      let mut authenticator = SoftwareAuthenticator::new("auth.example.com", *mode);
+
+      let response     = authenticator.register("register-challenge", ORIGIN);
+      let registration = rp.verify_registration("register-challenge", UserVerification::Required, &response).unwrap();
+      assert_eq!(registration.attestation_format, mode.format());
+      assert!(registration.user_verified);
+
+      let credential = stored(&registration);
+      authenticator.user_handle = Some(credential.user_id.as_bytes().to_vec());
+
+      let response   = authenticator.assert("login-challenge", ORIGIN);
+      let assertion  = rp.verify_assertion("login-challenge", UserVerification::Required, &credential, &response).unwrap();
+      assert_eq!(assertion.sign_count, registration.sign_count + 1);
+    }
+  }
+
+  #[test]
+  fn test_client_data_must_match_ceremony() {
+    let rp = relying_party("preferred");
+    let mut authenticator = SoftwareAuthenticator::new("auth.example.com", AttestationMode::None);
+
+    let response = authenticator.register("challenge", ORIGIN);
+    assert!(matches!(rp.verify_registration("other", UserVerification::Preferred, &response), Err(WebauthnError::ChallengeMismatch)));
+
+    let response = authenticator.register("challenge", "https://evil.example.com");
+    assert!(matches!(rp.verify_registration("challenge", UserVerification::Preferred, &response), Err(WebauthnError::OriginNotAllowed(_))));
+
+    let mut phished = SoftwareAuthenticator::new("evil.example.com", AttestationMode::None);
+    let response = phished.register("challenge", ORIGIN);
+    assert!(matches!(rp.verify_registration("challenge", UserVerification::Preferred, &response), Err(WebauthnError::RpIdMismatch)));
+  }
+
+  #[test]
+  fn test_user_verification_is_enforced() {
+    let rp = relying_party("preferred");
+    let mut authenticator = SoftwareAuthenticator::new("auth.example.com", AttestationMode::None);
+    authenticator.user_verified = false;
+
+    let response     = authenticator.register("challenge", ORIGIN);
+    let registration = rp.verify_registration("challenge", UserVerification::Preferred, &response).unwrap();
+    assert!(!registration.user_verified);
+
+    let response = authenticator.assert("login", ORIGIN);
+    assert!(matches!(rp.verify_assertion("login", UserVerification::Required, &stored(&registration), &response), Err(WebauthnError::UserNotVerified)));
+  }
+
+  #[test]
+  fn test_cloned_authenticator_is_detected() {
+    let rp = relying_party("preferred");
+    let mut authenticator = SoftwareAuthenticator::new("auth.example.com", AttestationMode::None);
+
+    let registration = rp.verify_registration("challenge", UserVerification::Preferred, &authenticator.register("challenge", ORIGIN)).unwrap();
+    let mut credential = stored(&registration);
+
+    let assertion = rp.verify_assertion("first", UserVerification::Preferred, &credential, &authenticator.assert("first", ORIGIN)).unwrap();
+    credential.sign_count = assertion.sign_count as i64 + 10;
+
+    let response = authenticator.assert("second", ORIGIN);
+    assert!(matches!(rp.verify_assertion("second", UserVerification::Preferred, &credential, &response), Err(WebauthnError::SignCountRegressed)));
+  }
+
+  #[test]
+  fn test_forged_signature_is_rejected() {
+    let rp = relying_party("preferred");
+    let mut authenticator = SoftwareAuthenticator::new("auth.example.com", AttestationMode::None);
+    let registration = rp.verify_registration("challenge", UserVerification::Preferred, &authenticator.register("challenge", ORIGIN)).unwrap();
+
+    let mut impostor = SoftwareAuthenticator::new("auth.example.com", AttestationMode::None);
+    impostor.credential_id = authenticator.credential_id.clone();
+
+    let response = impostor.assert("login", ORIGIN);
+    assert!(matches!(rp.verify_assertion("login", UserVerification::Preferred, &stored(&registration), &response), Err(WebauthnError::InvalidSignature)));
+  }
+}
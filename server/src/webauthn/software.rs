@@ -0,0 +1,216 @@
+//! A software authenticator that drives the WebAuthn ceremonies in tests, without hardware.
+
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+use openssl::x509::{X509, X509Builder, X509NameBuilder};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+use super::{AssertionResponse, AttestationResponse, AuthenticatorAssertion, RegistrationResponse};
+
+/// How the software authenticator attests new credentials.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttestationMode {
+  None,
+  /// `packed`, signed with the credential key itself.
+  SelfAttestation,
+  /// `packed`, signed with a (self-signed) attestation certificate.
+  Certificate
+}
+
+impl AttestationMode {
+  pub fn format(&self) -> &'static str {
+    match self {
+      AttestationMode::None => "none",
+      _                     => "packed"
+    }
+  }
+}
+
+/// An ES256 authenticator holding a single credential.
+pub struct SoftwareAuthenticator {
+  rp_id: String,
+  mode: AttestationMode,
+  key: PKey<Private>,
+  pub credential_id: Vec<u8>,
+  pub sign_count: u32,
+  pub user_verified: bool,
+  pub user_handle: Option<Vec<u8>>
+}
+
+impl SoftwareAuthenticator {
+  pub fn new(rp_id: &str, mode: AttestationMode) -> Self {
+    let mut credential_id = vec![0u8; 16];
+    openssl::rand::rand_bytes(&mut credential_id).unwrap();
+
+    SoftwareAuthenticator {
+      rp_id: rp_id.to_owned(),
+      mode,
+      key: p256_key(),
+      credential_id,
+      sign_count: 0,
+      user_verified: true,
+      user_handle: None
+    }
+  }
+
+  /// Answers `navigator.credentials.create()`.
+  pub fn register(&mut self, challenge: &str, origin: &str) -> RegistrationResponse {
+    let client_data = client_data("webauthn.create", challenge, origin);
+    let auth_data   = self.authenticator_data(true);
+    let signed      = [auth_data.as_slice(), &sha256(client_data.as_bytes())].concat();
+
+    let statement = match self.mode {
+      AttestationMode::None => Value::Map(BTreeMap::new()),
+      AttestationMode::SelfAttestation => cbor_map(vec![
+        (text("alg"), Value::Integer(-7)),
+        (text("sig"), Value::Bytes(sign(&self.key, &signed)))
+      ]),
+      AttestationMode::Certificate => {
+        let (certificate, key) = attestation_certificate();
+        cbor_map(vec![
+          (text("alg"), Value::Integer(-7)),
+          (text("sig"), Value::Bytes(sign(&key, &signed))),
+          (text("x5c"), Value::Array(vec![Value::Bytes(certificate.to_der().unwrap())]))
+        ])
+      }
+    };
+
+    let attestation_object = cbor_map(vec![
+      (text("fmt"), text(self.mode.format())),
+      (text("attStmt"), statement),
+      (text("authData"), Value::Bytes(auth_data))
+    ]);
+
+    RegistrationResponse {
+      id: encode(&self.credential_id),
+      response: AttestationResponse {
+        client_data_json: encode(client_data.as_bytes()),
+        attestation_object: encode(&serde_cbor::to_vec(&attestation_object).unwrap())
+      }
+    }
+  }
+
+  /// Answers `navigator.credentials.get()`.
+  pub fn assert(&mut self, challenge: &str, origin: &str) -> AssertionResponse {
+    self.sign_count += 1;
+
+    let client_data = client_data("webauthn.get", challenge, origin);
+    let auth_data   = self.authenticator_data(false);
+    let signed      = [auth_data.as_slice(), &sha256(client_data.as_bytes())].concat();
+
+    AssertionResponse {
+      id: encode(&self.credential_id),
+      response: AuthenticatorAssertion {
+        client_data_json: encode(client_data.as_bytes()),
+        authenticator_data: encode(&auth_data),
+        signature: encode(&sign(&self.key, &signed)),
+        user_handle: self.user_handle.as_ref().map(|handle| encode(handle))
+      }
+    }
+  }
+
+  fn authenticator_data(&self, attested: bool) -> Vec<u8> {
+    let mut flags = 0x01;
+    if self.user_verified {
+      flags |= 0x04;
+    }
+    if attested {
+      flags |= 0x40;
+    }
+
+    let mut data = sha256(self.rp_id.as_bytes());
+    data.push(flags);
+    data.extend_from_slice(&self.sign_count.to_be_bytes());
+
+    if attested {
+      data.extend_from_slice(&[0u8; 16]);
+      data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+      data.extend_from_slice(&self.credential_id);
+      data.extend_from_slice(&self.cose_key());
+    }
+
+    data
+  }
+
+  fn cose_key(&self) -> Vec<u8> {
+    let ec = self.key.ec_key().unwrap();
+    let mut context = BigNumContext::new().unwrap();
+    let (mut x, mut y) = (BigNum::new().unwrap(), BigNum::new().unwrap());
+    ec.public_key().affine_coordinates_gfp(ec.group(), &mut x, &mut y, &mut context).unwrap();
+
+    let key = cbor_map(vec![
+      (Value::Integer(1), Value::Integer(2)),
+      (Value::Integer(3), Value::Integer(-7)),
+      (Value::Integer(-1), Value::Integer(1)),
+      (Value::Integer(-2), Value::Bytes(pad(x.to_vec()))),
+      (Value::Integer(-3), Value::Bytes(pad(y.to_vec())))
+    ]);
+
+    serde_cbor::to_vec(&key).unwrap()
+  }
+}
+
+fn p256_key() -> PKey<Private> {
+  let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+  PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+fn attestation_certificate() -> (X509, PKey<Private>) {
+  let key = p256_key();
+
+  let mut name = X509NameBuilder::new().unwrap();
+  name.append_entry_by_text("C", "SE").unwrap();
+  name.append_entry_by_text("O", "Heimdallr Test Authenticators").unwrap();
+  name.append_entry_by_text("OU", "Authenticator Attestation").unwrap();
+  name.append_entry_by_text("CN", "Software Authenticator").unwrap();
+  let name = name.build();
+
+  let mut builder = X509Builder::new().unwrap();
+  builder.set_version(2).unwrap();
+  builder.set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap()).unwrap();
+  builder.set_subject_name(&name).unwrap();
+  builder.set_issuer_name(&name).unwrap();
+  builder.set_pubkey(&key).unwrap();
+  builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+  builder.set_not_after(&Asn1Time::days_from_now(365).unwrap()).unwrap();
+  builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+  (builder.build(), key)
+}
+
+fn client_data(ceremony: &str, challenge: &str, origin: &str) -> String {
+  serde_json::json!({ "type": ceremony, "challenge": challenge, "origin": origin, "crossOrigin": false }).to_string()
+}
+
+fn sign(key: &PKey<Private>, data: &[u8]) -> Vec<u8> {
+  let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+  signer.update(data).unwrap();
+  signer.sign_to_vec().unwrap()
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+  hash(MessageDigest::sha256(), data).unwrap().to_vec()
+}
+
+/// Left-pads a big-endian coordinate to the 32 bytes of a P-256 field element.
+fn pad(bytes: Vec<u8>) -> Vec<u8> {
+  [vec![0u8; 32 - bytes.len()], bytes].concat()
+}
+
+fn cbor_map(entries: Vec<(Value, Value)>) -> Value {
+  Value::Map(entries.into_iter().collect())
+}
+
+fn text(value: &str) -> Value {
+  Value::Text(value.to_owned())
+}
+
+fn encode(data: &[u8]) -> String {
+  base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
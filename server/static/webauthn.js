@@ -0,0 +1,76 @@
+// Passkey sign in for the login & two-factor pages: forms marked `data-passkey` are shown when the
+// browser supports WebAuthn, and submit the assertion for a challenge fetched from the server.
+(function () {
+  'use strict';
+
+  if (!window.PublicKeyCredential || !window.fetch) {
+    return;
+  }
+
+  function decode(value) {
+    var base64 = value.replace(/-/g, '+').replace(/_/g, '/');
+    return Uint8Array.from(atob(base64), function (c) { return c.charCodeAt(0); });
+  }
+
+  function encode(buffer) {
+    var binary = String.fromCharCode.apply(null, new Uint8Array(buffer));
+    return btoa(binary).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+  }
+
+  function start(form) {
+    var body = new URLSearchParams();
+    body.set('csrf_token', form.elements.csrf_token.value);
+    body.set('mfa_token', form.elements.mfa_token ? form.elements.mfa_token.value : '');
+
+    return fetch('/login/passkey/options', { method: 'POST', body: body, credentials: 'same-origin' })
+      .then(function (response) {
+        if (!response.ok) {
+          throw new Error('challenge request failed');
+        }
+        return response.json();
+      });
+  }
+
+  function assert(challenge) {
+    var options = challenge.options;
+    options.challenge = decode(options.challenge);
+    options.allowCredentials = options.allowCredentials.map(function (credential) {
+      return { type: credential.type, id: decode(credential.id) };
+    });
+
+    return navigator.credentials.get({ publicKey: options }).then(function (credential) {
+      return {
+        id: credential.id,
+        type: credential.type,
+        response: {
+          clientDataJSON: encode(credential.response.clientDataJSON),
+          authenticatorData: encode(credential.response.authenticatorData),
+          signature: encode(credential.response.signature),
+          userHandle: credential.response.userHandle ? encode(credential.response.userHandle) : null
+        }
+      };
+    });
+  }
+
+  document.querySelectorAll('form[data-passkey]').forEach(function (form) {
+    var error = form.querySelector('[data-passkey-error]');
+    form.hidden = false;
+
+    form.addEventListener('submit', function (event) {
+      event.preventDefault();
+      error.hidden = true;
+
+      start(form)
+        .then(function (challenge) {
+          return assert(challenge).then(function (credential) {
+            form.elements.challenge_id.value = challenge.challenge_id;
+            form.elements.credential.value = JSON.stringify(credential);
+            HTMLFormElement.prototype.submit.call(form);
+          });
+        })
+        .catch(function () {
+          error.hidden = false;
+        });
+    });
+  });
+})();
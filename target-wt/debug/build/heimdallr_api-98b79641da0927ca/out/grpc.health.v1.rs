@@ -0,0 +1,157 @@
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HealthCheckRequest {
+  #[prost(string, tag = "1")]
+  pub service: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HealthCheckResponse {
+  #[prost(enumeration = "health_check_response::ServingStatus", tag = "1")]
+  pub status: i32,
+}
+pub mod health_check_response {
+  #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+  #[repr(i32)]
+  pub enum ServingStatus {
+    Unknown = 0,
+    Serving = 1,
+    NotServing = 2,
+  }
+}
+#[doc = r" Generated client implementations."]
+pub mod health_client {
+  #![allow(unused_variables, dead_code, missing_docs)]
+  use tonic::codegen::*;
+  pub struct HealthClient<T> {
+    inner: tonic::client::Grpc<T>,
+  }
+  impl HealthClient<tonic::transport::Channel> {
+    #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+    pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+    where
+      D: std::convert::TryInto<tonic::transport::Endpoint>,
+      D::Error: Into<StdError>,
+    {
+      let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+      Ok(Self::new(conn))
+    }
+  }
+  impl<T> HealthClient<T>
+  where
+    T: tonic::client::GrpcService<tonic::body::BoxBody>,
+    T::ResponseBody: Body + HttpBody + Send + 'static,
+    T::Error: Into<StdError>,
+    <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+  {
+    pub fn new(inner: T) -> Self {
+      let inner = tonic::client::Grpc::new(inner);
+      Self { inner }
+    }
+    pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+      let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
+      Self { inner }
+    }
+    pub async fn check(&mut self, request: impl tonic::IntoRequest<super::HealthCheckRequest>) -> Result<tonic::Response<super::HealthCheckResponse>, tonic::Status> {
+      self
+        .inner
+        .ready()
+        .await
+        .map_err(|e| tonic::Status::new(tonic::Code::Unknown, format!("Service was not ready: {}", e.into())))?;
+      let codec = tonic::codec::ProstCodec::default();
+      let path = http::uri::PathAndQuery::from_static("/grpc.health.v1.Health/Check");
+      self.inner.unary(request.into_request(), path, codec).await
+    }
+  }
+  impl<T: Clone> Clone for HealthClient<T> {
+    fn clone(&self) -> Self {
+      Self { inner: self.inner.clone() }
+    }
+  }
+}
+#[doc = r" Generated server implementations."]
+pub mod health_server {
+  #![allow(unused_variables, dead_code, missing_docs)]
+  use tonic::codegen::*;
+  #[doc = "Generated trait containing gRPC methods that should be implemented for use with HealthServer."]
+  #[async_trait]
+  pub trait Health: Send + Sync + 'static {
+    async fn check(&self, request: tonic::Request<super::HealthCheckRequest>) -> Result<tonic::Response<super::HealthCheckResponse>, tonic::Status>;
+  }
+  #[derive(Debug)]
+  #[doc(hidden)]
+  pub struct HealthServer<T: Health> {
+    inner: _Inner<T>,
+  }
+  struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+  impl<T: Health> HealthServer<T> {
+    pub fn new(inner: T) -> Self {
+      let inner = Arc::new(inner);
+      let inner = _Inner(inner, None);
+      Self { inner }
+    }
+    pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+      let inner = Arc::new(inner);
+      let inner = _Inner(inner, Some(interceptor.into()));
+      Self { inner }
+    }
+  }
+  impl<T: Health> Service<http::Request<HyperBody>> for HealthServer<T> {
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Never;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+      Poll::Ready(Ok(()))
+    }
+    fn call(&mut self, req: http::Request<HyperBody>) -> Self::Future {
+      let inner = self.inner.clone();
+      match req.uri().path() {
+        "/grpc.health.v1.Health/Check" => {
+          struct CheckSvc<T: Health>(pub Arc<T>);
+          impl<T: Health> tonic::server::UnaryService<super::HealthCheckRequest> for CheckSvc<T> {
+            type Response = super::HealthCheckResponse;
+            type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+            fn call(&mut self, request: tonic::Request<super::HealthCheckRequest>) -> Self::Future {
+              let inner = self.0.clone();
+              let fut = async move { inner.check(request).await };
+              Box::pin(fut)
+            }
+          }
+          let inner = self.inner.clone();
+          let fut = async move {
+            let interceptor = inner.1.clone();
+            let inner = inner.0;
+            let method = CheckSvc(inner);
+            let codec = tonic::codec::ProstCodec::default();
+            let mut grpc = if let Some(interceptor) = interceptor {
+              tonic::server::Grpc::with_interceptor(codec, interceptor)
+            } else {
+              tonic::server::Grpc::new(codec)
+            };
+            let res = grpc.unary(method, req).await;
+            Ok(res)
+          };
+          Box::pin(fut)
+        }
+        _ => Box::pin(async move { Ok(http::Response::builder().status(200).header("grpc-status", "12").body(tonic::body::BoxBody::empty()).unwrap()) }),
+      }
+    }
+  }
+  impl<T: Health> Clone for HealthServer<T> {
+    fn clone(&self) -> Self {
+      let inner = self.inner.clone();
+      Self { inner }
+    }
+  }
+  impl<T: Health> Clone for _Inner<T> {
+    fn clone(&self) -> Self {
+      Self(self.0.clone(), self.1.clone())
+    }
+  }
+  impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "{:?}", self.0)
+    }
+  }
+  impl<T: Health> tonic::transport::NamedService for HealthServer<T> {
+    const NAME: &'static str = "grpc.health.v1.Health";
+  }
+}
@@ -0,0 +1,223 @@
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LoginRequest {
+  /// Required
+  #[prost(string, tag = "1")]
+  pub client_id: std::string::String,
+  /// Optional
+  #[prost(string, tag = "2")]
+  pub client_secret: std::string::String,
+  /// Required
+  #[prost(enumeration = "GrantType", tag = "3")]
+  pub grant_type: i32,
+  /// One or more registered scopes. If omitted, a token for all explicitly allowed scopes will be issued.
+  #[prost(string, repeated, tag = "4")]
+  pub scope: ::std::vec::Vec<std::string::String>,
+  /// Required for the `authorization_code` grant type.
+  #[prost(string, tag = "5")]
+  pub redirect_uri: std::string::String,
+  /// Authorization code - Required for `authorization_code` grant type.
+  #[prost(string, tag = "6")]
+  pub code: std::string::String,
+  /// Resource owner username - Required for `password` grant type.
+  #[prost(string, tag = "7")]
+  pub username: std::string::String,
+  /// Resource owner password - Required for `password` grant type.
+  #[prost(string, tag = "8")]
+  pub password: std::string::String,
+  /// Refresh token - Required for `refresh_token` grant type.
+  #[prost(string, tag = "9")]
+  pub refresh_token: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LoginResponse {
+  #[prost(string, tag = "1")]
+  pub access_token: std::string::String,
+  #[prost(message, optional, tag = "2")]
+  pub expires_in: ::std::option::Option<::prost_types::Timestamp>,
+  #[prost(message, optional, tag = "20")]
+  pub data: ::std::option::Option<::prost_types::Struct>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum GrantType {
+  Password = 0,
+  AuthorizationCode = 1,
+  ClientCredentials = 2,
+  RefreshToken = 3,
+}
+#[doc = r" Generated client implementations."]
+pub mod login_client {
+  #![allow(unused_variables, dead_code, missing_docs)]
+  use tonic::codegen::*;
+  pub struct LoginClient<T> {
+    inner: tonic::client::Grpc<T>,
+  }
+  impl LoginClient<tonic::transport::Channel> {
+    #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+    pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+    where
+      D: std::convert::TryInto<tonic::transport::Endpoint>,
+      D::Error: Into<StdError>,
+    {
+      let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+      Ok(Self::new(conn))
+    }
+  }
+  impl<T> LoginClient<T>
+  where
+    T: tonic::client::GrpcService<tonic::body::BoxBody>,
+    T::ResponseBody: Body + HttpBody + Send + 'static,
+    T::Error: Into<StdError>,
+    <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+  {
+    pub fn new(inner: T) -> Self {
+      let inner = tonic::client::Grpc::new(inner);
+      Self { inner }
+    }
+    pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+      let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
+      Self { inner }
+    }
+    pub async fn ping(&mut self, request: impl tonic::IntoRequest<()>) -> Result<tonic::Response<::std::string::String>, tonic::Status> {
+      self
+        .inner
+        .ready()
+        .await
+        .map_err(|e| tonic::Status::new(tonic::Code::Unknown, format!("Service was not ready: {}", e.into())))?;
+      let codec = tonic::codec::ProstCodec::default();
+      let path = http::uri::PathAndQuery::from_static("/heimdallr.auth.Login/Ping");
+      self.inner.unary(request.into_request(), path, codec).await
+    }
+    pub async fn login(&mut self, request: impl tonic::IntoRequest<super::LoginRequest>) -> Result<tonic::Response<super::LoginResponse>, tonic::Status> {
+      self
+        .inner
+        .ready()
+        .await
+        .map_err(|e| tonic::Status::new(tonic::Code::Unknown, format!("Service was not ready: {}", e.into())))?;
+      let codec = tonic::codec::ProstCodec::default();
+      let path = http::uri::PathAndQuery::from_static("/heimdallr.auth.Login/Login");
+      self.inner.unary(request.into_request(), path, codec).await
+    }
+  }
+  impl<T: Clone> Clone for LoginClient<T> {
+    fn clone(&self) -> Self {
+      Self { inner: self.inner.clone() }
+    }
+  }
+}
+#[doc = r" Generated server implementations."]
+pub mod login_server {
+  #![allow(unused_variables, dead_code, missing_docs)]
+  use tonic::codegen::*;
+  #[doc = "Generated trait containing gRPC methods that should be implemented for use with LoginServer."]
+  #[async_trait]
+  pub trait Login: Send + Sync + 'static {
+    async fn ping(&self, request: tonic::Request<()>) -> Result<tonic::Response<::std::string::String>, tonic::Status>;
+    async fn login(&self, request: tonic::Request<super::LoginRequest>) -> Result<tonic::Response<super::LoginResponse>, tonic::Status>;
+  }
+  #[derive(Debug)]
+  #[doc(hidden)]
+  pub struct LoginServer<T: Login> {
+    inner: _Inner<T>,
+  }
+  struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+  impl<T: Login> LoginServer<T> {
+    pub fn new(inner: T) -> Self {
+      let inner = Arc::new(inner);
+      let inner = _Inner(inner, None);
+      Self { inner }
+    }
+    pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+      let inner = Arc::new(inner);
+      let inner = _Inner(inner, Some(interceptor.into()));
+      Self { inner }
+    }
+  }
+  impl<T: Login> Service<http::Request<HyperBody>> for LoginServer<T> {
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Never;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+      Poll::Ready(Ok(()))
+    }
+    fn call(&mut self, req: http::Request<HyperBody>) -> Self::Future {
+      let inner = self.inner.clone();
+      match req.uri().path() {
+        "/heimdallr.auth.Login/Ping" => {
+          struct PingSvc<T: Login>(pub Arc<T>);
+          impl<T: Login> tonic::server::UnaryService<()> for PingSvc<T> {
+            type Response = ::std::string::String;
+            type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+            fn call(&mut self, request: tonic::Request<()>) -> Self::Future {
+              let inner = self.0.clone();
+              let fut = async move { inner.ping(request).await };
+              Box::pin(fut)
+            }
+          }
+          let inner = self.inner.clone();
+          let fut = async move {
+            let interceptor = inner.1.clone();
+            let inner = inner.0;
+            let method = PingSvc(inner);
+            let codec = tonic::codec::ProstCodec::default();
+            let mut grpc = if let Some(interceptor) = interceptor {
+              tonic::server::Grpc::with_interceptor(codec, interceptor)
+            } else {
+              tonic::server::Grpc::new(codec)
+            };
+            let res = grpc.unary(method, req).await;
+            Ok(res)
+          };
+          Box::pin(fut)
+        }
+        "/heimdallr.auth.Login/Login" => {
+          struct LoginSvc<T: Login>(pub Arc<T>);
+          impl<T: Login> tonic::server::UnaryService<super::LoginRequest> for LoginSvc<T> {
+            type Response = super::LoginResponse;
+            type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+            fn call(&mut self, request: tonic::Request<super::LoginRequest>) -> Self::Future {
+              let inner = self.0.clone();
+              let fut = async move { inner.login(request).await };
+              Box::pin(fut)
+            }
+          }
+          let inner = self.inner.clone();
+          let fut = async move {
+            let interceptor = inner.1.clone();
+            let inner = inner.0;
+            let method = LoginSvc(inner);
+            let codec = tonic::codec::ProstCodec::default();
+            let mut grpc = if let Some(interceptor) = interceptor {
+              tonic::server::Grpc::with_interceptor(codec, interceptor)
+            } else {
+              tonic::server::Grpc::new(codec)
+            };
+            let res = grpc.unary(method, req).await;
+            Ok(res)
+          };
+          Box::pin(fut)
+        }
+        _ => Box::pin(async move { Ok(http::Response::builder().status(200).header("grpc-status", "12").body(tonic::body::BoxBody::empty()).unwrap()) }),
+      }
+    }
+  }
+  impl<T: Login> Clone for LoginServer<T> {
+    fn clone(&self) -> Self {
+      let inner = self.inner.clone();
+      Self { inner }
+    }
+  }
+  impl<T: Login> Clone for _Inner<T> {
+    fn clone(&self) -> Self {
+      Self(self.0.clone(), self.1.clone())
+    }
+  }
+  impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "{:?}", self.0)
+    }
+  }
+  impl<T: Login> tonic::transport::NamedService for LoginServer<T> {
+    const NAME: &'static str = "heimdallr.auth.Login";
+  }
+}